@@ -1,7 +1,10 @@
-use crate::{DeCollectionHandle, DeZSetHandle, SerOutputBatchHandle};
+use crate::{
+    schema::SchemaHandle, CatalogSchema, DeCollectionHandle, DeZSetHandle, RelationSchema,
+    SerMaterializedHandle, SerOutputBatchHandle,
+};
 use dbsp::{algebra::ZRingValue, CollectionHandle, DBData, DBWeight};
 use serde::Deserialize;
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, sync::Arc};
 
 /// A catalog of input and output stream handles of a circuit.
 ///
@@ -11,10 +14,24 @@ use std::collections::BTreeMap;
 /// to DBSP streams
 /// (See [`InputFormat::new_parser()`](`crate::InputFormat::new_parser`)
 /// method).
+///
+/// Streams can optionally be registered with a [`RelationSchema`] that
+/// describes their columns.  Parsers use input schemas to coerce input
+/// values to column types.  Output streams registered without a schema are
+/// described by the schema derived from the Rust type of their records (see
+/// [`RelationSchema::of`]), if the type has one.  Input and output schemas
+/// are reported by the `/metadata` endpoint of the server.
+///
+/// Output streams registered with a handle to their integral (see
+/// [`Catalog::register_materialized_output_batch_handle`]) are materialized:
+/// their current contents can be queried at runtime using
+/// [`Controller::query_output`](`crate::Controller::query_output`).
 #[derive(Default)]
 pub struct Catalog {
     input_collection_handles: BTreeMap<String, Box<dyn DeCollectionHandle>>,
     output_batch_handles: BTreeMap<String, Box<dyn SerOutputBatchHandle>>,
+    materialized_handles: BTreeMap<String, Box<dyn SerMaterializedHandle>>,
+    output_schemas: BTreeMap<String, RelationSchema>,
 }
 
 impl Catalog {
@@ -26,11 +43,24 @@ impl Catalog {
     pub fn register_input_zset_handle<K, R>(&mut self, name: &str, handle: CollectionHandle<K, R>)
     where
         K: DBData + for<'de> Deserialize<'de>,
-        R: DBWeight + ZRingValue,
+        R: DBWeight + ZRingValue + TryFrom<i64>,
     {
         self.register_input_collection_handle(name, DeZSetHandle::new(handle));
     }
 
+    /// Add a named input Z-set handle with schema `schema` to the catalog.
+    pub fn register_input_zset_handle_with_schema<K, R>(
+        &mut self,
+        name: &str,
+        handle: CollectionHandle<K, R>,
+        schema: RelationSchema,
+    ) where
+        K: DBData + for<'de> Deserialize<'de>,
+        R: DBWeight + ZRingValue + TryFrom<i64>,
+    {
+        self.register_input_collection_handle_with_schema(name, DeZSetHandle::new(handle), schema);
+    }
+
     /// Add a named input stream handle to the catalog.
     pub fn register_input_collection_handle<H>(&mut self, name: &str, handle: H)
     where
@@ -40,15 +70,71 @@ impl Catalog {
             .insert(name.to_owned(), Box::new(handle));
     }
 
+    /// Add a named input stream handle with schema `schema` to the catalog.
+    pub fn register_input_collection_handle_with_schema<H>(
+        &mut self,
+        name: &str,
+        handle: H,
+        schema: RelationSchema,
+    ) where
+        H: DeCollectionHandle + 'static,
+    {
+        self.register_input_collection_handle(
+            name,
+            SchemaHandle::new(Box::new(handle), Arc::new(schema)),
+        );
+    }
+
     /// Add a named output stream handle to the catalog.
+    ///
+    /// The schema of the stream is derived from the type of its records,
+    /// unless that type is not a struct of primitive values.
     pub fn register_output_batch_handle<H>(&mut self, name: &str, handle: H)
     where
         H: SerOutputBatchHandle + 'static,
     {
+        if let Ok(schema) = handle.relation_schema() {
+            self.output_schemas.insert(name.to_owned(), schema);
+        }
         self.output_batch_handles
             .insert(name.to_owned(), Box::new(handle));
     }
 
+    /// Add a named output stream handle with schema `schema` to the catalog.
+    ///
+    /// `schema` overrides the schema derived from the type of the records.
+    pub fn register_output_batch_handle_with_schema<H>(
+        &mut self,
+        name: &str,
+        handle: H,
+        schema: RelationSchema,
+    ) where
+        H: SerOutputBatchHandle + 'static,
+    {
+        self.register_output_batch_handle(name, handle);
+        self.output_schemas.insert(name.to_owned(), schema);
+    }
+
+    /// Add a named output stream handle to the catalog along with a handle
+    /// to the integral of the stream, which makes the stream materialized.
+    ///
+    /// `integral_handle` must be attached to the integral of the stream
+    /// (e.g., `stream.integrate().output()`).  Note that each worker keeps
+    /// its partition of the integral in memory.
+    pub fn register_materialized_output_batch_handle<H, I>(
+        &mut self,
+        name: &str,
+        handle: H,
+        integral_handle: I,
+    ) where
+        H: SerOutputBatchHandle + 'static,
+        I: SerMaterializedHandle + 'static,
+    {
+        self.register_output_batch_handle(name, handle);
+        self.materialized_handles
+            .insert(name.to_owned(), Box::new(integral_handle));
+    }
+
     /// Look up an input stream handle by name.
     pub fn input_collection_handle(&self, name: &str) -> Option<&dyn DeCollectionHandle> {
         self.input_collection_handles.get(name).map(|b| &**b)
@@ -58,4 +144,36 @@ impl Catalog {
     pub fn output_batch_handle(&self, name: &str) -> Option<&dyn SerOutputBatchHandle> {
         self.output_batch_handles.get(name).map(|b| &**b)
     }
+
+    /// Handles to the integrals of all materialized output streams.
+    pub fn materialized_handles(&self) -> impl Iterator<Item = (&str, &dyn SerMaterializedHandle)> {
+        self.materialized_handles
+            .iter()
+            .map(|(name, handle)| (name.as_str(), &**handle))
+    }
+
+    /// Look up the schema of an input stream by name.
+    pub fn input_schema(&self, name: &str) -> Option<&RelationSchema> {
+        self.input_collection_handles
+            .get(name)
+            .and_then(|handle| handle.schema())
+    }
+
+    /// Look up the schema of an output stream by name.
+    pub fn output_schema(&self, name: &str) -> Option<&RelationSchema> {
+        self.output_schemas.get(name)
+    }
+
+    /// Schemas of all input streams registered with a schema and all output
+    /// streams that have a schema.
+    pub fn schema(&self) -> CatalogSchema {
+        CatalogSchema {
+            inputs: self
+                .input_collection_handles
+                .iter()
+                .filter_map(|(name, handle)| Some((name.clone(), handle.schema()?.clone())))
+                .collect(),
+            outputs: self.output_schemas.clone(),
+        }
+    }
 }
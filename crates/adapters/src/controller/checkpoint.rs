@@ -0,0 +1,147 @@
+//! Pipeline checkpoints.
+//!
+//! A checkpoint captures the state of a running pipeline between two circuit
+//! steps, so that the pipeline can be restarted from that point after a
+//! failure without reprocessing its inputs from the beginning.
+//!
+//! The checkpoint consists of:
+//!
+//! * The state of all operators in the circuit, e.g., traces, `Z1` delays
+//!   and aggregates (see [`DBSPHandle::checkpoint`](`dbsp::DBSPHandle::checkpoint`)).
+//!
+//! * The state of the parsers of each input endpoint, e.g., a partially
+//!   received record (see [`Parser::checkpoint`](`crate::Parser::checkpoint`)),
+//!   including parsers of consumers forked by the endpoint.
+//!
+//! * The position of each input endpoint in its input stream: the number of
+//!   bytes covered by the state of its parser and, for endpoints that track
+//!   their position in the data source, e.g., Kafka offsets, an
+//!   endpoint-specific position (see
+//!   [`InputEndpoint::position`](`crate::InputEndpoint::position`)).
+//!
+//! The checkpoint directory contains two kinds of files:
+//!
+//! * `circuit-<steps>` - the state of the circuit after `steps` steps.
+//!
+//! * `checkpoint` - bincode-encoded [`CheckpointMetadata`], which contains the
+//!   number of steps completed at the time of the last checkpoint and the
+//!   state of input endpoints.  The file is replaced atomically, so that a
+//!   failure while writing a checkpoint leaves the previous checkpoint
+//!   intact.
+
+use crate::Step;
+use anyhow::{Error as AnyError, Result as AnyResult};
+use bincode::{Decode, Encode};
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, read, read_dir, remove_file, rename, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+const CHECKPOINT_FILE: &str = "checkpoint";
+const CHECKPOINT_TMP_FILE: &str = "checkpoint.tmp";
+const CIRCUIT_FILE_PREFIX: &str = "circuit-";
+
+/// Checkpointed state of an input probe.
+#[derive(Encode, Decode)]
+pub(super) struct ProbeCheckpoint {
+    /// Number of bytes received by the probe that are covered by the state
+    /// of the parser, i.e., excluding bytes the parser needs to receive again
+    /// (see [`Parser::replay_bytes`](`crate::Parser::replay_bytes`)).
+    pub(super) offset: u64,
+
+    /// Serialized state of the probe's parser.
+    pub(super) parser: Vec<u8>,
+}
+
+/// Checkpointed state of an input endpoint.
+#[derive(Encode, Decode)]
+pub(super) struct InputCheckpoint {
+    /// State of the probe created along with the endpoint.
+    pub(super) probe: ProbeCheckpoint,
+
+    /// States of probes forked by the endpoint, in the order of creation.
+    pub(super) forks: Vec<ProbeCheckpoint>,
+
+    /// Endpoint-specific position in the input stream.
+    pub(super) position: Option<Vec<u8>>,
+}
+
+/// Contents of the `checkpoint` file.
+#[derive(Encode, Decode)]
+pub(super) struct CheckpointMetadata {
+    /// Number of steps completed by the circuit.
+    pub(super) steps: Step,
+
+    /// State of input endpoints, indexed by endpoint name.
+    pub(super) inputs: BTreeMap<String, InputCheckpoint>,
+}
+
+/// Directory that stores pipeline checkpoints.
+pub(super) struct CheckpointDir {
+    dir: PathBuf,
+}
+
+impl CheckpointDir {
+    /// Open checkpoint directory `dir`, creating it if it doesn't exist.
+    pub(super) fn open(dir: &Path) -> AnyResult<Self> {
+        create_dir_all(dir).map_err(|e| {
+            AnyError::msg(format!(
+                "failed to create checkpoint directory '{}': {e}",
+                dir.display()
+            ))
+        })?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// File that stores the state of the circuit after `steps` steps.
+    pub(super) fn circuit_path(&self, steps: Step) -> PathBuf {
+        self.dir.join(format!("{CIRCUIT_FILE_PREFIX}{steps}"))
+    }
+
+    /// Read the last checkpoint; returns `None` if the directory doesn't
+    /// contain a checkpoint.
+    pub(super) fn read(&self) -> AnyResult<Option<CheckpointMetadata>> {
+        let path = self.dir.join(CHECKPOINT_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let (metadata, _) = bincode::decode_from_slice(&read(&path)?, bincode::config::standard())?;
+        Ok(Some(metadata))
+    }
+
+    /// Make `metadata` the last checkpoint.
+    ///
+    /// The state of the circuit must already be stored in
+    /// [`Self::circuit_path`]`(metadata.steps)`.  Deletes the state of the
+    /// circuit written by previous checkpoints.
+    pub(super) fn write(&self, metadata: &CheckpointMetadata) -> AnyResult<()> {
+        let tmp_path = self.dir.join(CHECKPOINT_TMP_FILE);
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&bincode::encode_to_vec(
+            metadata,
+            bincode::config::standard(),
+        )?)?;
+        file.sync_data()?;
+        rename(&tmp_path, self.dir.join(CHECKPOINT_FILE))?;
+
+        let current = self.circuit_path(metadata.steps);
+        for entry in read_dir(&self.dir)? {
+            let path = entry?.path();
+            let is_circuit_file = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| name.starts_with(CIRCUIT_FILE_PREFIX));
+            if is_circuit_file && path != current {
+                remove_file(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -5,6 +5,7 @@
 //! endpoint configs.  We represent these configs as opaque yaml values, so
 //! that the entire configuration tree can be deserialized from a yaml file.
 
+use crate::transport::Compression;
 use serde::{Deserialize, Serialize};
 use serde_yaml::Value as YamlValue;
 use std::{borrow::Cow, collections::BTreeMap};
@@ -17,11 +18,21 @@ const fn default_max_buffered_records() -> u64 {
     1_000_000
 }
 
+/// Default value of `DeadLetterConfig::max_buffered_records`.
+const fn default_max_dead_letters() -> usize {
+    100
+}
+
 /// Default number of DBSP worker threads.
 const fn default_workers() -> u16 {
     1
 }
 
+/// Default value of `GlobalPipelineConfig::step_period_usecs`.
+const fn default_step_period_usecs() -> u64 {
+    1_000_000
+}
+
 /// Pipeline configuration specified by the user when creating
 /// a new pipeline instance.
 #[derive(Clone, Serialize, Deserialize, ToSchema)]
@@ -50,13 +61,19 @@ pub struct GlobalPipelineConfig {
     #[serde(default)]
     pub cpu_profiler: bool,
 
+    /// Policy that determines when the controller triggers a circuit step.
+    ///
+    /// Defaults to `on_input`.
+    #[serde(default)]
+    pub step_trigger: StepTrigger,
+
     /// Minimal input batch size.
     ///
-    /// The controller delays pushing input records to the circuit until at
-    /// least `min_batch_size_records` records have been received (total
-    /// across all endpoints) or `max_buffering_delay_usecs` microseconds
-    /// have passed since at least one input records has been buffered.
-    /// Defaults to 0.
+    /// In the `on_input` step trigger mode, the controller delays pushing
+    /// input records to the circuit until at least `min_batch_size_records`
+    /// records have been received (total across all endpoints) or
+    /// `max_buffering_delay_usecs` microseconds have passed since at least
+    /// one input records has been buffered.  Defaults to 0.
     #[serde(default)]
     pub min_batch_size_records: u64,
 
@@ -64,6 +81,56 @@ pub struct GlobalPipelineConfig {
     /// get buffered by the controller, defaults to 0.
     #[serde(default)]
     pub max_buffering_delay_usecs: u64,
+
+    /// Interval in microseconds between steps in the `periodic` step trigger
+    /// mode.  Must be greater than 0.
+    ///
+    /// The default is 1 second.
+    #[serde(default = "default_step_period_usecs")]
+    pub step_period_usecs: u64,
+
+    /// Maximal number of input records consumed by a single step.
+    ///
+    /// When the number of records buffered across all input endpoints reaches
+    /// this limit, the controller pauses the input endpoints until the next
+    /// step, and, in the `on_input` mode, triggers the step without waiting
+    /// for `max_buffering_delay_usecs`.  The limit is approximate: records
+    /// parsed from input chunks received before the endpoints were paused
+    /// are still consumed by the step.  No limit by default.
+    #[serde(default)]
+    pub max_step_size_records: Option<u64>,
+
+    /// Directory used to store pipeline checkpoints.
+    ///
+    /// When specified, checkpoints taken using
+    /// [`Controller::checkpoint`](`crate::Controller::checkpoint`) are
+    /// written to this directory.  If the
+    /// directory already contains a checkpoint, the pipeline resumes from
+    /// that checkpoint.
+    #[serde(default)]
+    pub checkpoint_dir: Option<String>,
+}
+
+/// Policy that determines when the controller triggers a circuit step.
+///
+/// Regardless of the policy, a step can be triggered explicitly using
+/// [`Controller::step`](`crate::Controller::step`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StepTrigger {
+    /// Trigger a step when input records are available (see
+    /// `min_batch_size_records` and `max_buffering_delay_usecs`).
+    #[default]
+    OnInput,
+
+    /// Trigger a step every `step_period_usecs` microseconds, whether or not
+    /// any inputs have been received since the previous step.
+    ///
+    /// Inputs received between ticks are consumed by the next step.
+    Periodic,
+
+    /// Only trigger steps on explicit request.
+    Manual,
 }
 
 #[derive(Clone, Serialize, Deserialize, ToSchema)]
@@ -89,6 +156,38 @@ pub struct InputEndpointConfig {
     /// The default is 1 million.
     #[serde(default = "default_max_buffered_records")]
     pub max_buffered_records: u64,
+
+    /// Dead-letter queue configuration.
+    ///
+    /// When specified, records rejected by the parser are routed to the
+    /// dead-letter queue instead of being discarded.
+    #[serde(default)]
+    pub dead_letter: Option<DeadLetterConfig>,
+}
+
+/// Dead-letter queue configuration of an input endpoint.
+///
+/// The dead-letter queue retains the most recent records rejected by the
+/// parser in memory and optionally forwards all rejected records to an
+/// output transport endpoint.
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct DeadLetterConfig {
+    /// Output transport to write rejected records to.
+    ///
+    /// Each rejected record is written as a separate buffer containing a
+    /// JSON object with the base64-encoded raw contents of the record,
+    /// endpoint name, error message, and location of the record in the input
+    /// stream.  Records rejected during a circuit step are written as one
+    /// batch after the step.  The `compression` setting of the transport is
+    /// applied to each buffer, as for output endpoints.
+    #[serde(default)]
+    pub transport: Option<TransportConfig>,
+
+    /// Maximal number of most recent rejected records retained in memory.
+    ///
+    /// The default is 100.
+    #[serde(default = "default_max_dead_letters")]
+    pub max_buffered_records: usize,
 }
 
 #[derive(Clone, Serialize, Deserialize, ToSchema)]
@@ -108,6 +207,50 @@ pub struct OutputEndpointConfig {
     /// The default is 1 million.
     #[serde(default = "default_max_buffered_records")]
     pub max_buffered_records: u64,
+
+    /// Send steps that produced no output for this endpoint to the endpoint.
+    ///
+    /// When `true`, the endpoint receives a `batch_start`/`batch_end` pair
+    /// for every circuit step, allowing consumers to distinguish "no changes
+    /// yet" from "the pipeline is stalled".  When `false` (the default),
+    /// empty steps are skipped.
+    #[serde(default)]
+    pub heartbeats: bool,
+
+    /// Representation of changes to the output stream sent to the endpoint.
+    #[serde(default)]
+    pub mode: OutputMode,
+}
+
+/// Representation of changes to an output stream sent to an endpoint.
+///
+/// The `upsert` and `multimap` modes are designed for indexed Z-sets, whose
+/// records are key/value pairs, and convert the changes produced by each
+/// step into per-key events that can be applied directly to a key-value
+/// store.  Each event is a record with three fields: `op`
+/// (the type of the event), `key`, and `value`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    /// Send inserted and deleted records along with their weights.
+    #[default]
+    Changes,
+
+    /// Send one event per modified key: `upsert` sets the key to a new value
+    /// (a deletion and an insertion for the same key are collapsed into a
+    /// single `upsert`); `delete` removes the key, whose last value is
+    /// carried by the event.
+    ///
+    /// Keys associated with more than one value are reported as errors; the
+    /// output of the step that produced them is dropped.
+    Upsert,
+
+    /// Send one event per inserted or deleted key/value pair, for streams
+    /// where a key can be associated with multiple values: `insert` adds a
+    /// value to the key, `delete` removes it.  Multiple copies of the same
+    /// key/value pair are sent as one event whose weight is the number of
+    /// copies.
+    Multimap,
 }
 
 /// Transport endpoint configuration.
@@ -123,6 +266,21 @@ pub struct TransportConfig {
     #[serde(default)]
     #[schema(value_type = Object)]
     pub config: YamlValue,
+
+    /// Compression of the data received or sent by the endpoint.
+    ///
+    /// Input endpoints decompress data before passing it to the parser;
+    /// `auto` detects the compression algorithm from the first bytes of
+    /// each chunk of input data (e.g., a file or an HTTP request).  Output
+    /// endpoints compress each output buffer as a separate gzip member or
+    /// Zstandard frame; `auto` is not supported for output endpoints.
+    /// Output compression is not supported by the `http` transport.
+    ///
+    /// Prefer the `compression` setting of the `file` output transport, which
+    /// compresses each output file as a single stream; the two settings
+    /// cannot be combined.
+    #[serde(default)]
+    pub compression: Compression,
 }
 
 /// Data format specification used to parse raw data received from the
@@ -0,0 +1,219 @@
+//! Dead-letter queue for records rejected by input parsers.
+
+use super::{ControllerError, DeadLetterConfig};
+use crate::{transport::CompressEndpoint, OutputEndpoint, OutputTransport, ParseError, Step};
+use anyhow::{Error as AnyError, Result as AnyResult};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Serialize, Serializer};
+use std::{collections::VecDeque, mem::take, sync::Mutex};
+
+/// A record rejected by the parser of an input endpoint.
+#[derive(Clone, Debug, Serialize)]
+pub struct DeadLetter {
+    /// Name of the input endpoint that received the record.
+    pub endpoint_name: String,
+
+    /// Parser error message.
+    pub error: String,
+
+    /// Raw contents of the record, if available.  Serialized as a base64
+    /// string, since the record need not be valid UTF-8.
+    #[serde(serialize_with = "serialize_base64")]
+    pub record: Option<Vec<u8>>,
+
+    /// Offset in bytes of the record from the start of the stream received
+    /// by the parser, if known.  For compressed input, this is the offset
+    /// in the decompressed stream.
+    pub offset: Option<u64>,
+
+    /// Line number of the record within the input stream, if known.
+    pub line: Option<u64>,
+
+    /// The step that consumed the valid records of the input buffer that
+    /// contained the record.
+    pub step: Step,
+}
+
+impl DeadLetter {
+    pub fn new(endpoint_name: &str, error: &ParseError, step: Step) -> Self {
+        Self {
+            endpoint_name: endpoint_name.to_string(),
+            error: error.description().to_string(),
+            record: error.invalid_bytes().map(<[u8]>::to_vec),
+            offset: error.offset(),
+            line: error.line(),
+            step,
+        }
+    }
+}
+
+fn serialize_base64<S>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match bytes {
+        Some(bytes) => serializer.serialize_some(&BASE64.encode(bytes)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Dead-letter queue of an input endpoint.
+///
+/// Retains up to `max_buffered_records` most recent rejected records in
+/// memory and forwards all rejected records to an optional output transport
+/// endpoint.
+///
+/// Input endpoints only add records to in-memory queues.  Records are
+/// written to the output endpoint by the circuit thread, one batch per
+/// circuit step (see [`Self::write`]), so that input endpoints are never
+/// blocked by transport I/O.
+pub(crate) struct DeadLetterQueue {
+    /// Name of the output endpoint used to report transport errors.
+    endpoint_name: String,
+    max_buffered_records: usize,
+    records: Mutex<VecDeque<DeadLetter>>,
+    /// Records not yet written to `endpoint`.
+    pending: Mutex<Vec<DeadLetter>>,
+    endpoint: Option<Mutex<Box<dyn OutputEndpoint>>>,
+}
+
+impl DeadLetterQueue {
+    /// Create a dead-letter queue for input endpoint `input_endpoint_name`.
+    ///
+    /// `async_error_callback` is passed to the output transport endpoint.
+    pub(crate) fn new(
+        input_endpoint_name: &str,
+        config: &DeadLetterConfig,
+        async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+    ) -> AnyResult<Self> {
+        let endpoint_name = format!("{input_endpoint_name}.dead_letter");
+
+        let endpoint = match &config.transport {
+            None => None,
+            Some(transport_config) => {
+                let transport = <dyn OutputTransport>::get_transport(&transport_config.name)
+                    .ok_or_else(|| {
+                        ControllerError::unknown_output_transport(&transport_config.name)
+                    })?;
+                let endpoint = transport.new_endpoint(
+                    &endpoint_name,
+                    &transport_config.config,
+                    async_error_callback,
+                )?;
+                Some(Mutex::new(CompressEndpoint::wrap(
+                    transport_config.compression,
+                    endpoint,
+                )?))
+            }
+        };
+
+        Ok(Self {
+            endpoint_name,
+            max_buffered_records: config.max_buffered_records,
+            records: Mutex::new(VecDeque::with_capacity(config.max_buffered_records)),
+            pending: Mutex::new(Vec::new()),
+            endpoint,
+        })
+    }
+
+    /// Name of the output endpoint used to report transport errors.
+    pub(crate) fn endpoint_name(&self) -> &str {
+        &self.endpoint_name
+    }
+
+    /// Add rejected records to the queue.
+    ///
+    /// Records are written to the output transport endpoint by the next
+    /// [`write`](`Self::write`) call.
+    pub(crate) fn push(&self, records: Vec<DeadLetter>) {
+        if records.is_empty() {
+            return;
+        }
+
+        if self.max_buffered_records > 0 {
+            let mut buffered = self.records.lock().unwrap();
+            for record in records.iter() {
+                if buffered.len() == self.max_buffered_records {
+                    buffered.pop_front();
+                }
+                buffered.push_back(record.clone());
+            }
+        }
+
+        if self.endpoint.is_some() {
+            self.pending.lock().unwrap().extend(records);
+        }
+    }
+
+    /// Write all records added since the previous call to the output
+    /// transport endpoint as a single batch labeled with step `step`, one
+    /// buffer per record.
+    ///
+    /// Invoked by the circuit thread after evaluating step `step`.  Does
+    /// nothing if there are no new records.
+    pub(crate) fn write(&self, step: Step) -> AnyResult<()> {
+        let endpoint = match &self.endpoint {
+            None => return Ok(()),
+            Some(endpoint) => endpoint,
+        };
+
+        let records = take(&mut *self.pending.lock().unwrap());
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let mut endpoint = endpoint.lock().unwrap();
+        endpoint.batch_start(step)?;
+        for record in records.iter() {
+            let mut buffer = serde_json::to_vec(record)?;
+            buffer.push(b'\n');
+            endpoint.push_buffer(&buffer)?;
+        }
+        endpoint.batch_end()
+    }
+
+    /// Most recent rejected records, oldest first.
+    pub(crate) fn records(&self) -> Vec<DeadLetter> {
+        self.records.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DeadLetter, DeadLetterQueue};
+    use crate::{controller::DeadLetterConfig, ParseError};
+
+    #[test]
+    fn test_ring_buffer() {
+        let config: DeadLetterConfig = serde_yaml::from_str("max_buffered_records: 2").unwrap();
+        let queue = DeadLetterQueue::new("test_input", &config, Box::new(|_, _| {})).unwrap();
+
+        for i in 0..3 {
+            let error = ParseError::new(format!("error {i}"), Some(i + 1), Some(b"xxx".to_vec()))
+                .with_offset(i * 4);
+            queue.push(vec![DeadLetter::new("test_input", &error, 0)]);
+        }
+
+        let records = queue.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].error, "error 1");
+        assert_eq!(records[0].line, Some(2));
+        assert_eq!(records[0].offset, Some(4));
+        assert_eq!(records[1].error, "error 2");
+        assert_eq!(records[1].record.as_deref(), Some(&b"xxx"[..]));
+
+        // Records are serialized with base64-encoded contents.
+        let error = ParseError::new("invalid".to_string(), None, Some(vec![0xff, b'x']));
+        assert_eq!(
+            serde_json::to_value(DeadLetter::new("test_input", &error, 5)).unwrap(),
+            serde_json::json!({
+                "endpoint_name": "test_input",
+                "error": "invalid",
+                "record": "/3g=",
+                "offset": null,
+                "line": null,
+                "step": 5
+            })
+        );
+    }
+}
@@ -14,6 +14,12 @@ pub enum ConfigError {
     /// Output endpoint with this name already exists.
     DuplicateOutputEndpoint { endpoint_name: String },
 
+    /// Input endpoint with this name does not exist.
+    UnknownInputEndpoint { endpoint_name: String },
+
+    /// Output endpoint with this name does not exist.
+    UnknownOutputEndpoint { endpoint_name: String },
+
     /// Endpoint configuration specifies unknown input format name.
     UnknownInputFormat { format_name: String },
 
@@ -29,6 +35,9 @@ pub enum ConfigError {
     /// Controller configuration specifies output stream name
     /// that is not found in the circuit catalog.
     UnknownOutputStream { stream_name: String },
+
+    /// Invalid step trigger policy configuration.
+    InvalidStepTrigger { reason: String },
 }
 
 impl Display for ConfigError {
@@ -37,6 +46,12 @@ impl Display for ConfigError {
             Self::DuplicateInputEndpoint { endpoint_name } => {
                 write!(f, "input endpoint '{endpoint_name}' already exists")
             }
+            Self::UnknownInputEndpoint { endpoint_name } => {
+                write!(f, "unknown input endpoint '{endpoint_name}'")
+            }
+            Self::UnknownOutputEndpoint { endpoint_name } => {
+                write!(f, "unknown output endpoint '{endpoint_name}'")
+            }
             Self::UnknownInputFormat { format_name } => {
                 write!(f, "unknown input format '{format_name}'")
             }
@@ -55,6 +70,9 @@ impl Display for ConfigError {
             Self::UnknownOutputStream { stream_name } => {
                 write!(f, "unknown output stream '{stream_name}'")
             }
+            Self::InvalidStepTrigger { reason } => {
+                write!(f, "invalid step trigger configuration: {reason}")
+            }
         }
     }
 }
@@ -66,6 +84,18 @@ impl ConfigError {
         }
     }
 
+    pub fn unknown_input_endpoint(endpoint_name: &str) -> Self {
+        Self::UnknownInputEndpoint {
+            endpoint_name: endpoint_name.to_owned(),
+        }
+    }
+
+    pub fn unknown_output_endpoint(endpoint_name: &str) -> Self {
+        Self::UnknownOutputEndpoint {
+            endpoint_name: endpoint_name.to_owned(),
+        }
+    }
+
     pub fn unknown_input_format(format_name: &str) -> Self {
         Self::UnknownInputFormat {
             format_name: format_name.to_owned(),
@@ -101,6 +131,12 @@ impl ConfigError {
             stream_name: stream_name.to_owned(),
         }
     }
+
+    pub fn invalid_step_trigger(reason: &str) -> Self {
+        Self::InvalidStepTrigger {
+            reason: reason.to_owned(),
+        }
+    }
 }
 
 /// Controller error.
@@ -145,6 +181,9 @@ pub enum ControllerError {
 
     /// Error evaluating the DBSP circuit.
     DbspError { error: DBSPError },
+
+    /// Error writing or reading a checkpoint.
+    CheckpointError { error: AnyError },
 }
 
 impl StdError for ControllerError {}
@@ -198,6 +237,9 @@ impl Display for ControllerError {
             Self::DbspError { error } => {
                 write!(f, "DBSP error: '{error}'")
             }
+            Self::CheckpointError { error } => {
+                write!(f, "checkpoint error: '{error}'")
+            }
         }
     }
 }
@@ -209,6 +251,18 @@ impl ControllerError {
         }
     }
 
+    pub fn unknown_input_endpoint(endpoint_name: &str) -> Self {
+        Self::Config {
+            config_error: ConfigError::unknown_input_endpoint(endpoint_name),
+        }
+    }
+
+    pub fn unknown_output_endpoint(endpoint_name: &str) -> Self {
+        Self::Config {
+            config_error: ConfigError::unknown_output_endpoint(endpoint_name),
+        }
+    }
+
     pub fn unknown_input_format(format_name: &str) -> Self {
         Self::Config {
             config_error: ConfigError::unknown_input_format(format_name),
@@ -245,6 +299,12 @@ impl ControllerError {
         }
     }
 
+    pub fn invalid_step_trigger(reason: &str) -> Self {
+        Self::Config {
+            config_error: ConfigError::invalid_step_trigger(reason),
+        }
+    }
+
     pub fn input_transport_error(endpoint_name: &str, fatal: bool, error: AnyError) -> Self {
         Self::InputTransportError {
             endpoint_name: endpoint_name.to_owned(),
@@ -278,4 +338,8 @@ impl ControllerError {
     pub fn dbsp_error(error: DBSPError) -> Self {
         Self::DbspError { error }
     }
+
+    pub fn checkpoint_error(error: AnyError) -> Self {
+        Self::CheckpointError { error }
+    }
 }
@@ -4,10 +4,13 @@
 //! # Design
 //!
 //! The controller logic is split into two tasks that run in separate threads.
-//! The circuit thread owns the `DBSPHandle` and calls `step()` on it whenever
-//! there is some input data available for the circuit.  It can be configured
-//! to improve batching by slightly delaying the `step()` call if the number of
-//! available input records is below some used-defined threshold.
+//! The circuit thread owns the `DBSPHandle` and calls `step()` on it according
+//! to the configured [`StepTrigger`] policy: by default, whenever there is some
+//! input data available for the circuit, but also on a fixed clock tick or
+//! only on explicit request ([`Controller::step`]).  In the default mode, it
+//! can be configured to improve batching by slightly delaying the `step()`
+//! call if the number of available input records is below some used-defined
+//! threshold.
 //!
 //! The backpressure thread controls the flow of data through transport
 //! endpoints, pausing the endpoints either when the amount of data buffered by
@@ -31,12 +34,15 @@
 //!
 //! The probe passes the data through to the parser, while counting the number
 //! of transmitted bytes and records and updating respective performance
-//! counters in the controller.
+//! counters in the controller.  The parser is shared with the controller,
+//! which serializes its state when writing a checkpoint (see [`checkpoint`]).
 
 use crate::{
+    transport::{CompressEndpoint, Compression, DecompressParser},
     Catalog, Encoder, InputConsumer, InputEndpoint, InputFormat, InputTransport, OutputConsumer,
-    OutputEndpoint, OutputFormat, OutputTransport, Parser, PipelineState, SerBatch,
-    SerOutputBatchHandle,
+    OutputEndpoint, OutputFormat, OutputTransport, ParseError, Parser, PipelineState,
+    RelationSchema, SerBatch, SerMaterializedHandle, SerOutputBatchHandle, SerSnapshot,
+    SnapshotSink, Step,
 };
 use anyhow::{Error as AnyError, Result as AnyResult};
 use crossbeam::{
@@ -46,30 +52,45 @@ use crossbeam::{
 use dbsp::DBSPHandle;
 use log::{debug, error, info};
 use num_traits::FromPrimitive;
+use serde_json::Value as JsonValue;
+use serde_yaml::Value as YamlValue;
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, BTreeSet, HashSet},
+    collections::{BTreeMap, BTreeSet, HashSet, VecDeque},
+    iter::once,
+    mem::take,
+    path::Path,
     sync::{
-        atomic::{AtomicBool, AtomicU32, Ordering},
-        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        mpsc::{channel, Sender},
+        Arc, Mutex, Weak,
     },
     thread::{spawn, JoinHandle},
     time::{Duration, Instant},
 };
 
+mod checkpoint;
 mod config;
+mod dead_letter;
 mod error;
 mod stats;
 
+use checkpoint::{CheckpointDir, CheckpointMetadata, InputCheckpoint, ProbeCheckpoint};
 pub use config::{
-    FormatConfig, GlobalPipelineConfig, InputEndpointConfig, OutputEndpointConfig, PipelineConfig,
-    TransportConfig,
+    DeadLetterConfig, FormatConfig, GlobalPipelineConfig, InputEndpointConfig,
+    OutputEndpointConfig, OutputMode, PipelineConfig, StepTrigger, TransportConfig,
 };
+pub use dead_letter::DeadLetter;
+use dead_letter::DeadLetterQueue;
 pub use error::ControllerError;
 pub use stats::{ControllerStatus, InputEndpointStatus, OutputEndpointStatus};
 
 pub(crate) type EndpointId = u64;
 
+/// Checkpointed states of the probes of input endpoints: the probe created
+/// along with the endpoint and probes forked from it.
+type ProbeCheckpoints = BTreeMap<EndpointId, (ProbeCheckpoint, Vec<ProbeCheckpoint>)>;
+
 /// Controller that coordinates the creation, reconfiguration, teardown of
 /// input/output adapters, and implements runtime flow control.
 ///
@@ -86,6 +107,33 @@ pub struct Controller {
     backpressure_thread_handle: JoinHandle<()>,
 }
 
+/// A cloneable handle to a [`Controller`].
+///
+/// The handle exposes controller operations that can take a long time to
+/// complete, so that they can run without exclusive access to the
+/// controller.
+#[derive(Clone)]
+pub struct ControllerHandle {
+    inner: Arc<ControllerInner>,
+}
+
+impl ControllerHandle {
+    /// See [`Controller::query_output`].
+    pub fn query_output(&self, stream: &str, query: &OutputQuery) -> AnyResult<Vec<u8>> {
+        self.inner.query_output(stream, query)
+    }
+
+    /// See [`Controller::checkpoint`].
+    pub fn checkpoint(&self) -> AnyResult<()> {
+        self.inner.request_checkpoint()
+    }
+
+    /// See [`Controller::step`].
+    pub fn step(&self) -> AnyResult<Step> {
+        self.inner.request_step()
+    }
+}
+
 impl Controller {
     /// Create a new I/O controller for a circuit.
     ///
@@ -108,6 +156,13 @@ impl Controller {
     ///   error handling policy, but simply forwards most errors to this
     ///   callback.
     ///
+    /// If [`GlobalPipelineConfig::checkpoint_dir`] contains a checkpoint
+    /// (see [`Self::checkpoint`]), the controller restores the state of the
+    /// circuit from the checkpoint and input endpoints resume from their
+    /// positions at the time of the checkpoint.  The circuit and the
+    /// configuration must be the same as the ones used to create the
+    /// checkpoint.
+    ///
     /// # Errors
     ///
     /// The method may fail for the following reasons:
@@ -116,21 +171,39 @@ impl Controller {
     ///   transport or data format.
     ///
     /// * One or more of the endpoints fails to initialize.
+    ///
+    /// * Failure to read the checkpoint, or an input endpoint that consumed
+    ///   data before the checkpoint does not support resuming from a
+    ///   checkpoint.
     pub fn with_config(
         mut circuit: DBSPHandle,
         catalog: Catalog,
         config: &PipelineConfig,
         error_cb: Box<dyn Fn(ControllerError) + Send + Sync>,
     ) -> AnyResult<Self> {
+        if config.global.step_trigger == StepTrigger::Periodic
+            && config.global.step_period_usecs == 0
+        {
+            Err(ControllerError::invalid_step_trigger(
+                "'step_period_usecs' must be greater than 0",
+            ))?;
+        }
+
         let circuit_thread_parker = Parker::new();
         let circuit_thread_unparker = circuit_thread_parker.unparker().clone();
 
         let backpressure_thread_parker = Parker::new();
         let backpressure_thread_unparker = backpressure_thread_parker.unparker().clone();
 
+        let checkpoint_dir = match &config.global.checkpoint_dir {
+            None => None,
+            Some(checkpoint_dir) => Some(CheckpointDir::open(Path::new(checkpoint_dir))?),
+        };
+
         let inner = Arc::new(ControllerInner::new(
             catalog,
             &config.global,
+            checkpoint_dir,
             circuit_thread_unparker,
             backpressure_thread_unparker,
             error_cb,
@@ -142,6 +215,9 @@ impl Controller {
                 .map_err(|e| AnyError::msg(format!("error enabling CPU profiler: {e}")))?;
         }
 
+        // Restore the state of the circuit from the last checkpoint, if any.
+        let mut restored_inputs = inner.restore(&mut circuit)?;
+
         let backpressure_thread_handle = {
             let inner = inner.clone();
             spawn(move || Self::backpressure_thread(inner, backpressure_thread_parker))
@@ -153,7 +229,11 @@ impl Controller {
         };
 
         for (input_name, input_config) in config.inputs.iter() {
-            inner.connect_input(input_name, input_config)?;
+            inner.connect_input_restored(
+                input_name,
+                input_config,
+                restored_inputs.remove(input_name.as_ref()),
+            )?;
         }
 
         for (output_name, output_config) in config.outputs.iter() {
@@ -190,6 +270,83 @@ impl Controller {
         self.inner.connect_input(endpoint_name, config)
     }
 
+    /// Connect a new output endpoint with specified name and configuration.
+    ///
+    /// Creates an endpoint with data transport and format specified by
+    /// `config`.  The endpoint receives outputs produced by the circuit
+    /// starting from the next step.
+    ///
+    /// # Errors
+    ///
+    /// The method may fail for the following reasons:
+    ///
+    /// * The endpoint configuration is invalid, e.g., specifies an unknown
+    ///   transport or data format.
+    ///
+    /// * The endpoint fails to initialize.
+    pub fn connect_output(
+        &self,
+        endpoint_name: &str,
+        config: &OutputEndpointConfig,
+    ) -> AnyResult<()> {
+        self.inner.connect_output(endpoint_name, config)
+    }
+
+    /// Disconnect and destroy input endpoint `endpoint_name`.
+    ///
+    /// Records already received from the endpoint are still fed to the
+    /// circuit.  The endpoint is removed from [`ControllerStatus`] and no
+    /// longer prevents the pipeline from completing.
+    pub fn disconnect_input(&self, endpoint_name: &str) -> Result<(), ControllerError> {
+        self.inner.disconnect_input(endpoint_name)
+    }
+
+    /// Disconnect and destroy output endpoint `endpoint_name`.
+    ///
+    /// Output batches queued for the endpoint but not yet sent to the
+    /// transport are discarded.
+    pub fn disconnect_output(&self, endpoint_name: &str) -> Result<(), ControllerError> {
+        self.inner.disconnect_output(endpoint_name)
+    }
+
+    /// Pause input endpoint `endpoint_name`.
+    ///
+    /// The endpoint stays paused until [`Self::start_input_endpoint`] is
+    /// called, regardless of the state of the pipeline.
+    pub fn pause_input_endpoint(&self, endpoint_name: &str) -> Result<(), ControllerError> {
+        self.inner.set_input_paused(endpoint_name, true)
+    }
+
+    /// Resume input endpoint `endpoint_name` previously paused with
+    /// [`Self::pause_input_endpoint`].
+    ///
+    /// The endpoint starts receiving data once the pipeline is running.
+    pub fn start_input_endpoint(&self, endpoint_name: &str) -> Result<(), ControllerError> {
+        self.inner.set_input_paused(endpoint_name, false)
+    }
+
+    /// Change the `max_buffered_records` setting of input endpoint
+    /// `endpoint_name`.
+    pub fn set_input_max_buffered_records(
+        &self,
+        endpoint_name: &str,
+        max_buffered_records: u64,
+    ) -> Result<(), ControllerError> {
+        self.inner
+            .set_input_max_buffered_records(endpoint_name, max_buffered_records)
+    }
+
+    /// Change the `max_buffered_records` setting of output endpoint
+    /// `endpoint_name`.
+    pub fn set_output_max_buffered_records(
+        &self,
+        endpoint_name: &str,
+        max_buffered_records: u64,
+    ) -> Result<(), ControllerError> {
+        self.inner
+            .set_output_max_buffered_records(endpoint_name, max_buffered_records)
+    }
+
     /// Change the state of all input endpoints to running.
     ///
     /// Start streaming data through all connected input endpoints.
@@ -218,6 +375,61 @@ impl Controller {
         self.inner.dump_profile();
     }
 
+    /// Returns the most recent records rejected by the parser of input
+    /// endpoint `endpoint_name`, oldest first.
+    ///
+    /// Returns `None` if the endpoint does not exist or does not have a
+    /// dead-letter queue.
+    pub fn input_endpoint_dead_letters(&self, endpoint_name: &str) -> Option<Vec<DeadLetter>> {
+        self.inner.input_endpoint_dead_letters(endpoint_name)
+    }
+
+    /// Returns a cloneable handle to the controller.
+    pub fn handle(&self) -> ControllerHandle {
+        ControllerHandle {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Returns the current contents of a materialized output stream (see
+    /// [`Catalog::register_materialized_output_batch_handle`]) encoded using
+    /// the output format specified in `query`.
+    ///
+    /// The result reflects the outputs of all steps completed by the circuit
+    /// so far.
+    pub fn query_output(&self, stream: &str, query: &OutputQuery) -> AnyResult<Vec<u8>> {
+        self.inner.query_output(stream, query)
+    }
+
+    /// Write a checkpoint to [`GlobalPipelineConfig::checkpoint_dir`].
+    ///
+    /// The checkpoint is taken by the circuit thread, which performs a step
+    /// that consumes all input records buffered by the controller and writes
+    /// the checkpoint after the step.  Input endpoints are blocked until the
+    /// state of their parsers has been saved.  The method blocks until the
+    /// checkpoint has been written.  A pipeline created with the same
+    /// configuration resumes from the last checkpoint (see
+    /// [`Self::with_config`]).
+    ///
+    /// Fails if checkpointing is not enabled in the pipeline configuration.
+    pub fn checkpoint(&self) -> AnyResult<()> {
+        self.inner.request_checkpoint()
+    }
+
+    /// Trigger a circuit step and wait for it to complete.
+    ///
+    /// The step consumes all input records buffered by the controller and
+    /// pushes its outputs to output endpoints.  This is the only way to
+    /// trigger a step in the [`StepTrigger::Manual`] mode, but it can be used
+    /// with any step trigger policy.  Like other steps, the requested step
+    /// waits for space in output buffers to become available.
+    ///
+    /// Returns the number of the completed step.  Fails if the circuit
+    /// reports an error while evaluating the step.
+    pub fn step(&self) -> AnyResult<Step> {
+        self.inner.request_step()
+    }
+
     /// Terminate the controller, stop all input endpoints and destroy the
     /// circuit.
     pub fn stop(self) -> AnyResult<()> {
@@ -261,6 +473,12 @@ impl Controller {
         let max_buffering_delay =
             Duration::from_micros(controller.status.global_config.max_buffering_delay_usecs);
         let min_batch_size_records = controller.status.global_config.min_batch_size_records;
+        let max_step_size_records = controller.status.global_config.max_step_size_records;
+        let step_trigger = controller.status.global_config.step_trigger;
+        let step_period = Duration::from_micros(controller.status.global_config.step_period_usecs);
+
+        // Time of the last step triggered by the clock in the `Periodic` mode.
+        let mut last_tick = Instant::now();
 
         loop {
             let dump_profile = controller
@@ -281,6 +499,7 @@ impl Controller {
                     }
                 }
             }
+
             match controller.state() {
                 PipelineState::Running | PipelineState::Paused => {
                     // Backpressure in the output pipeline: wait for room in output buffers to
@@ -293,42 +512,72 @@ impl Controller {
                     }
 
                     let buffered_records = controller.status.num_buffered_input_records();
+                    let step_requests = take(&mut *controller.step_requests.lock().unwrap());
+                    let checkpoint_requests =
+                        take(&mut *controller.checkpoint_requests.lock().unwrap());
+
+                    let triggered = match step_trigger {
+                        // We have sufficient buffered inputs or the buffering delay has expired --
+                        // kick the circuit to consume buffered data.  Use strict inequality in case
+                        // `min_batch_size_records` is 0.
+                        StepTrigger::OnInput => {
+                            buffered_records > min_batch_size_records
+                                || max_step_size_records
+                                    .map_or(false, |max| buffered_records >= max)
+                                || start
+                                    .map(|start| start.elapsed() >= max_buffering_delay)
+                                    .unwrap_or(false)
+                        }
+                        StepTrigger::Periodic => last_tick.elapsed() >= step_period,
+                        StepTrigger::Manual => false,
+                    };
 
-                    // We have sufficient buffered inputs or the buffering delay has expired --
-                    // kick the circuit to consume buffered data.  Use strict inequality in case
-                    // `min_batch_size_records` is 0.
-                    if buffered_records > min_batch_size_records
-                        || start
-                            .map(|start| start.elapsed() >= max_buffering_delay)
-                            .unwrap_or(false)
-                    {
+                    if triggered || !step_requests.is_empty() || !checkpoint_requests.is_empty() {
                         start = None;
-                        // Reset all counters of buffered records and bytes to 0.
-                        controller.status.consume_buffered_inputs();
+                        if triggered && step_trigger == StepTrigger::Periodic {
+                            last_tick = Instant::now();
+                        }
 
-                        // All input records accumulated so far (and possibly some more) will
-                        // be fully processed after the `step()` call returns.
-                        let processed_records = controller.status.num_total_input_records();
+                        // A checkpoint is written after the step.  Snapshot the state
+                        // of input probes when starting the step.
+                        let (step, processed_records, probes) =
+                            controller.start_step(!checkpoint_requests.is_empty());
 
-                        // Wake up the backpressure thread to unpause endpoints blocked due to
-                        // backpressure.
-                        controller.unpark_backpressure();
                         debug!("circuit thread: calling 'circuit.step'");
-                        circuit
-                            .step()
-                            .unwrap_or_else(|e| controller.error(ControllerError::dbsp_error(e)));
-                        debug!("circuit thread: 'circuit.step' returned");
+                        let step_result = match circuit.step() {
+                            Ok(()) => {
+                                debug!("circuit thread: 'circuit.step' returned");
+
+                                // Notify input endpoints that all inputs labeled with `step` or
+                                // earlier have been fully processed.
+                                for ep in controller.inputs.lock().unwrap().values() {
+                                    ep.endpoint.completed_step(step);
+                                }
+                                controller
+                                    .status
+                                    .set_num_total_processed_records(processed_records);
+                                controller.status.set_num_completed_steps(step + 1);
+                                Ok(step)
+                            }
+                            Err(e) => {
+                                let message = format!("step {step} failed: {e}");
+                                controller.error(ControllerError::dbsp_error(e));
+                                Err(message)
+                            }
+                        };
+                        let step_completed = step_result.is_ok();
 
-                        controller
-                            .status
-                            .set_num_total_processed_records(processed_records);
+                        controller.write_dead_letters(step);
+
+                        controller.update_materialized_outputs();
 
                         // Push output batches to output pipelines.
                         let outputs = controller.outputs.read().unwrap();
-                        for (_stream, (output_handle, endpoints)) in outputs.iter_by_stream() {
+                        for (stream, (output_handle, endpoints)) in outputs.iter_by_stream() {
                             // TODO: add an endpoint config option to consolidate output batches.
                             let batch = output_handle.take_from_all();
                             let num_records = batch.iter().map(|b| b.len()).sum();
+                            let contents = controller.materialized_contents(stream);
 
                             for endpoint_id in endpoints.iter() {
                                 let endpoint = outputs.lookup_by_id(endpoint_id).unwrap();
@@ -339,7 +588,12 @@ impl Controller {
                                 // Associate the input frontier with the batch.  Once the batch has
                                 // been sent to the output endpoint, the endpoint will get labeled
                                 // with this frontier.
-                                endpoint.queue.push((batch.clone(), processed_records));
+                                endpoint.queue.push((
+                                    step,
+                                    batch.clone(),
+                                    processed_records,
+                                    contents.clone().filter(|_| endpoint.snapshots),
+                                ));
 
                                 // Wake up the output thread.  We're not trying to be smart here and
                                 // wake up the thread conditionally if it was previously idle, as I
@@ -347,7 +601,34 @@ impl Controller {
                                 endpoint.unparker.unpark();
                             }
                         }
-                    } else if buffered_records > 0 {
+
+                        // Notify `Controller::step` callers.
+                        for request in step_requests {
+                            let _ = request.send(step_result.clone().map_err(AnyError::msg));
+                        }
+
+                        // Write the checkpoint.
+                        if !checkpoint_requests.is_empty() {
+                            let result = if step_completed {
+                                probes.and_then(|probes| {
+                                    controller.checkpoint(&mut circuit, step, probes)
+                                })
+                            } else {
+                                Err(AnyError::msg("the step preceding the checkpoint failed"))
+                            };
+                            for request in checkpoint_requests {
+                                let _ = request.send(
+                                    result
+                                        .as_ref()
+                                        .map(|_| ())
+                                        .map_err(|e| AnyError::msg(e.to_string())),
+                                );
+                            }
+                        }
+                    } else if step_trigger == StepTrigger::Periodic {
+                        // Wait for the next clock tick.
+                        parker.park_timeout(step_period.saturating_sub(last_tick.elapsed()));
+                    } else if step_trigger == StepTrigger::OnInput && buffered_records > 0 {
                         // We have some buffered data, but less than `min_batch_size_records` --
                         // wait up to `max_buffering_delay` for more data to
                         // arrive.
@@ -356,12 +637,13 @@ impl Controller {
                         }
                         parker.park_timeout(Duration::from_millis(1));
                     } else {
-                        debug!("circuit thread: park: input buffers empty");
+                        debug!("circuit thread: park: waiting for step trigger");
                         parker.park();
                         debug!("circuit thread: unparked");
                     }
                 }
                 PipelineState::Terminated => {
+                    controller.write_dead_letters(*controller.step.read().unwrap());
                     circuit
                         .kill()
                         .map_err(|_| AnyError::msg("dbsp thead panicked"))?;
@@ -373,73 +655,41 @@ impl Controller {
 
     /// Backpressure thread function.
     fn backpressure_thread(controller: Arc<ControllerInner>, parker: Parker) {
-        // `global_pause` flag is `true` when the entire controller is paused
-        // (the controller starts in a paused state and switches between
-        // paused and running states in responsed to `Controller::start()` and
-        // `Controller::pause()` methods).
-        let mut global_pause = true;
-
-        // Endpoints paused due to backpressure.
-        let mut paused_endpoints = HashSet::new();
+        // Input endpoints that are currently running.  All endpoints are
+        // created in a paused state.  An endpoint should be running when the
+        // controller is running, the endpoint hasn't been paused by the user,
+        // its buffer is not full, and the next step has not reached its maximal
+        // size.
+        let mut running_endpoints = HashSet::new();
 
         loop {
             let inputs = controller.inputs.lock().unwrap();
 
-            match controller.state() {
-                PipelineState::Paused => {
-                    // Pause circuit if not yet paused.
-                    if !global_pause {
-                        for (epid, ep) in inputs.iter() {
-                            // Pause the endpoint unless it's already paused due to backpressure.
-                            if !paused_endpoints.contains(epid) {
-                                ep.endpoint.pause().unwrap_or_else(|e| {
-                                    controller.input_transport_error(
-                                        *epid,
-                                        &ep.endpoint_name,
-                                        true,
-                                        e,
-                                    )
-                                });
-                            }
-                        }
-                    }
-                    global_pause = true;
-                }
-                PipelineState::Running => {
-                    // Resume endpoints that have buffer space, pause endpoints with full buffers.
-                    for (epid, ep) in inputs.iter() {
-                        if controller.status.input_endpoint_full(epid) {
-                            // The endpoint is full and is not yet in the paused state -- pause it
-                            // now.
-                            if !global_pause && !paused_endpoints.contains(epid) {
-                                ep.endpoint.pause().unwrap_or_else(|e| {
-                                    controller.input_transport_error(
-                                        *epid,
-                                        &ep.endpoint_name,
-                                        true,
-                                        e,
-                                    )
-                                });
-                            }
-                            paused_endpoints.insert(*epid);
-                        } else {
-                            // The endpoint is paused when it should be running -- unpause it.
-                            if global_pause || paused_endpoints.contains(epid) {
-                                ep.endpoint.start().unwrap_or_else(|e| {
-                                    controller.input_transport_error(
-                                        *epid,
-                                        &ep.endpoint_name,
-                                        true,
-                                        e,
-                                    )
-                                });
-                            }
-                            paused_endpoints.remove(epid);
-                        }
-                    }
-                    global_pause = false;
+            let state = controller.state();
+            if state == PipelineState::Terminated {
+                return;
+            }
+
+            // Forget disconnected endpoints.
+            running_endpoints.retain(|epid| inputs.contains_key(epid));
+
+            for (epid, ep) in inputs.iter() {
+                let should_run = state == PipelineState::Running
+                    && !controller.status.input_paused(epid)
+                    && !controller.status.input_endpoint_full(epid)
+                    && !controller.status.step_full();
+
+                if should_run && !running_endpoints.contains(epid) {
+                    ep.endpoint.start().unwrap_or_else(|e| {
+                        controller.input_transport_error(*epid, &ep.endpoint_name, true, e)
+                    });
+                    running_endpoints.insert(*epid);
+                } else if !should_run && running_endpoints.contains(epid) {
+                    ep.endpoint.pause().unwrap_or_else(|e| {
+                        controller.input_transport_error(*epid, &ep.endpoint_name, true, e)
+                    });
+                    running_endpoints.remove(epid);
                 }
-                PipelineState::Terminated => return,
             }
 
             drop(inputs);
@@ -453,23 +703,40 @@ impl Controller {
 struct InputEndpointDescr {
     endpoint_name: String,
     endpoint: Box<dyn InputEndpoint>,
+    /// Input probes of the endpoint.
+    probes: Arc<EndpointProbes>,
+    dead_letters: Option<Arc<DeadLetterQueue>>,
 }
 
 impl InputEndpointDescr {
-    pub fn new(endpoint_name: &str, endpoint: Box<dyn InputEndpoint>) -> Self {
+    pub fn new(
+        endpoint_name: &str,
+        endpoint: Box<dyn InputEndpoint>,
+        probes: Arc<EndpointProbes>,
+        dead_letters: Option<Arc<DeadLetterQueue>>,
+    ) -> Self {
         Self {
             endpoint_name: endpoint_name.to_owned(),
             endpoint,
+            probes,
+            dead_letters,
         }
     }
 }
 
 /// A lock-free queue used to send output batches from the circuit thread
-/// to output endpoint threads.  Each entry is annotated with a progress label
-/// that is equal to the number of input records fully processed by
-/// DBSP before emitting this batch of outputs.  The label increases
-/// monotonically over time.
-type BatchQueue = SegQueue<(Vec<Arc<dyn SerBatch>>, u64)>;
+/// to output endpoint threads.  Each entry is annotated with the number of
+/// the step that produced the batch and with a progress label that is equal
+/// to the number of input records fully processed by DBSP before emitting
+/// this batch of outputs.  Both labels increase monotonically over time.
+/// Entries sent to endpoints that support snapshots also carry the contents
+/// of the materialized output stream after the step.
+type BatchQueue = SegQueue<(
+    Step,
+    Vec<Arc<dyn SerBatch>>,
+    u64,
+    Option<Arc<dyn SerSnapshot>>,
+)>;
 
 /// State tracked by the controller for each output endpoint.
 struct OutputEndpointDescr {
@@ -481,15 +748,180 @@ struct OutputEndpointDescr {
 
     /// Unparker for the endpoint thread.
     unparker: Unparker,
+
+    /// Send steps that produced no output to the endpoint (see
+    /// [`OutputEndpointConfig::heartbeats`]).
+    heartbeats: bool,
+
+    /// Representation of changes sent to the endpoint.
+    mode: OutputMode,
+
+    /// The endpoint supports snapshots (see
+    /// [`OutputEndpoint::snapshot_sink`]).
+    snapshots: bool,
 }
 
 impl OutputEndpointDescr {
-    pub fn new(endpoint_name: &str, unparker: Unparker) -> Self {
+    pub fn new(
+        endpoint_name: &str,
+        unparker: Unparker,
+        config: &OutputEndpointConfig,
+        snapshots: bool,
+    ) -> Self {
         Self {
             endpoint_name: endpoint_name.to_string(),
             queue: Arc::new(SegQueue::new()),
             unparker,
+            heartbeats: config.heartbeats,
+            mode: config.mode,
+            snapshots,
+        }
+    }
+}
+
+/// Convert the outputs of a step to the representation expected by an
+/// endpoint in `mode`.
+fn convert_output(
+    batches: &[Arc<dyn SerBatch>],
+    mode: OutputMode,
+) -> AnyResult<Vec<Arc<dyn SerBatch>>> {
+    let multimap = match mode {
+        OutputMode::Changes => return Ok(batches.to_vec()),
+        OutputMode::Upsert => false,
+        OutputMode::Multimap => true,
+    };
+
+    // All updates to a key must be in the same batch in order to collapse
+    // them into a single event.
+    match batches.split_first() {
+        None => Ok(Vec::new()),
+        Some((first, rest)) => Ok(vec![first
+            .clone()
+            .merge(rest.to_vec())
+            .upserts(multimap)?]),
+    }
+}
+
+/// A materialized output stream.
+struct MaterializedOutput {
+    /// Handle to the integral of the stream.
+    handle: Box<dyn SerMaterializedHandle>,
+
+    /// Contents of the stream after the last step; `None` until the circuit
+    /// completes its first step.
+    snapshot: Option<Arc<dyn SerSnapshot>>,
+}
+
+/// Query over the contents of a materialized output stream (see
+/// [`Controller::query_output`]).
+pub struct OutputQuery {
+    /// Format used to encode query results.
+    pub format: FormatConfig,
+
+    /// Smallest key to include in the result.
+    ///
+    /// Deserialized from JSON into the key type of the stream.
+    pub from: Option<JsonValue>,
+
+    /// Largest key to include in the result.
+    ///
+    /// Deserialized from JSON into the key type of the stream.
+    pub to: Option<JsonValue>,
+
+    /// Maximal number of records to return.
+    pub limit: Option<usize>,
+}
+
+/// Output consumer that accumulates all buffers produced by the encoder in
+/// memory.
+struct QueryOutputConsumer(Arc<Mutex<Vec<u8>>>);
+
+impl OutputConsumer for QueryOutputConsumer {
+    fn batch_start(&mut self, _step: Step) {}
+
+    fn push_buffer(&mut self, buffer: &[u8]) {
+        self.0.lock().unwrap().extend_from_slice(buffer);
+    }
+
+    /// The consumer receives a single step.
+    fn separates_steps(&self) -> bool {
+        true
+    }
+
+    fn batch_end(&mut self) {}
+}
+
+/// Snapshot state maintained by the output thread of an endpoint that
+/// supports snapshots (see [`OutputEndpoint::snapshot_sink`]).
+///
+/// Snapshots are served from the materialized output stream (see
+/// [`Catalog::register_materialized_output_batch_handle`]), so the endpoint
+/// does not maintain an integral of its own.
+struct OutputSnapshot {
+    sink: Box<dyn SnapshotSink>,
+
+    /// Format used to encode snapshots.
+    format: &'static dyn OutputFormat,
+    format_config: YamlValue,
+    schema: Option<RelationSchema>,
+
+    /// Contents of the materialized stream after the last step whose output
+    /// has been sent to the endpoint.
+    contents: Option<Arc<dyn SerSnapshot>>,
+
+    /// Number of the last step whose output has been sent to the endpoint.
+    step: Step,
+}
+
+impl OutputSnapshot {
+    fn new(
+        sink: Box<dyn SnapshotSink>,
+        format: &'static dyn OutputFormat,
+        format_config: &YamlValue,
+        schema: Option<RelationSchema>,
+    ) -> Self {
+        Self {
+            sink,
+            format,
+            format_config: format_config.clone(),
+            schema,
+            contents: None,
+            step: 0,
+        }
+    }
+
+    /// Record the contents of the stream after step `step`, whose output has
+    /// just been sent to the endpoint.
+    fn update(&mut self, step: Step, contents: Option<Arc<dyn SerSnapshot>>) {
+        if contents.is_some() {
+            self.contents = contents;
+        }
+        self.step = step;
+    }
+
+    /// Encode the contents of the stream and send it to the endpoint if the
+    /// endpoint requested a snapshot.
+    fn send_if_requested(&mut self) -> AnyResult<()> {
+        if !self.sink.snapshot_requested() {
+            return Ok(());
         }
+
+        // Use a new encoder for each snapshot, so that every snapshot starts
+        // with a fresh encoder state, e.g., includes CSV headers.
+        let mut encoder = self.format.new_encoder(
+            &self.format_config,
+            self.schema.as_ref(),
+            self.sink.snapshot_consumer(),
+        )?;
+
+        let batches = match &self.contents {
+            None => Vec::new(),
+            Some(contents) => vec![contents.key_range(None, None, None)?],
+        };
+
+        // The snapshot is labeled with the last step whose output it
+        // includes.
+        encoder.encode_step(self.step, &batches)
     }
 }
 
@@ -499,6 +931,9 @@ type StreamEndpointMap =
 struct OutputEndpoints {
     by_id: BTreeMap<EndpointId, OutputEndpointDescr>,
     by_stream: StreamEndpointMap,
+    /// Id to assign to the next endpoint.  Ids are never reused, so that a
+    /// disconnected endpoint cannot be confused with a new one.
+    next_endpoint_id: EndpointId,
 }
 
 impl OutputEndpoints {
@@ -506,6 +941,7 @@ impl OutputEndpoints {
         Self {
             by_id: BTreeMap::new(),
             by_stream: BTreeMap::new(),
+            next_endpoint_id: 0,
         }
     }
 
@@ -530,8 +966,17 @@ impl OutputEndpoints {
             .find(|ep| ep.endpoint_name == endpoint_name)
     }
 
-    fn alloc_endpoint_id(&self) -> EndpointId {
-        self.by_id.keys().rev().next().map(|k| k + 1).unwrap_or(0)
+    fn lookup_id_by_name(&self, endpoint_name: &str) -> Option<EndpointId> {
+        self.by_id
+            .iter()
+            .find(|(_, ep)| ep.endpoint_name == endpoint_name)
+            .map(|(endpoint_id, _)| *endpoint_id)
+    }
+
+    fn alloc_endpoint_id(&mut self) -> EndpointId {
+        let endpoint_id = self.next_endpoint_id;
+        self.next_endpoint_id += 1;
+        endpoint_id
     }
 
     fn insert(
@@ -548,6 +993,16 @@ impl OutputEndpoints {
             .1
             .insert(endpoint_id);
     }
+
+    /// Remove endpoint from the map.  The stream handle remains in `by_stream`
+    /// even if no other endpoints are connected to it.
+    fn remove(&mut self, endpoint_id: &EndpointId) -> Option<OutputEndpointDescr> {
+        let endpoint_descr = self.by_id.remove(endpoint_id)?;
+        for (_, endpoints) in self.by_stream.values_mut() {
+            endpoints.remove(endpoint_id);
+        }
+        Some(endpoint_descr)
+    }
 }
 
 /// Controller state sharable across threads.
@@ -560,7 +1015,24 @@ struct ControllerInner {
     dump_profile_request: AtomicBool,
     catalog: Arc<Mutex<Catalog>>,
     inputs: Mutex<BTreeMap<EndpointId, InputEndpointDescr>>,
+    /// Id to assign to the next input endpoint.  Ids are never reused.
+    next_input_id: AtomicU64,
     outputs: ShardedLock<OutputEndpoints>,
+    /// Materialized output streams (see
+    /// [`Catalog::register_materialized_output_batch_handle`]).
+    materialized_outputs: Mutex<BTreeMap<String, MaterializedOutput>>,
+    /// Number of the next step to be performed by the circuit thread.
+    ///
+    /// Input probes hold a read lock while flushing parsed data to input
+    /// handles, so that the data is labeled with the step that will consume
+    /// it.
+    step: ShardedLock<Step>,
+    /// Checkpoint directory; `None` unless checkpointing is enabled.
+    checkpoint_dir: Option<CheckpointDir>,
+    /// Pending `Controller::checkpoint` calls waiting for the circuit thread.
+    checkpoint_requests: Mutex<Vec<Sender<AnyResult<()>>>>,
+    /// Pending `Controller::step` calls waiting for the circuit thread.
+    step_requests: Mutex<Vec<Sender<AnyResult<Step>>>>,
     circuit_thread_unparker: Unparker,
     backpressure_thread_unparker: Unparker,
     error_cb: Box<dyn Fn(ControllerError) + Send + Sync>,
@@ -570,6 +1042,7 @@ impl ControllerInner {
     fn new(
         catalog: Catalog,
         global_config: &GlobalPipelineConfig,
+        checkpoint_dir: Option<CheckpointDir>,
         circuit_thread_unparker: Unparker,
         backpressure_thread_unparker: Unparker,
         error_cb: Box<dyn Fn(ControllerError) + Send + Sync>,
@@ -577,6 +1050,18 @@ impl ControllerInner {
         let status = ControllerStatus::new(global_config);
         let state = AtomicU32::new(PipelineState::Paused as u32);
         let dump_profile_request = AtomicBool::new(false);
+        let materialized_outputs = catalog
+            .materialized_handles()
+            .map(|(stream, handle)| {
+                (
+                    stream.to_string(),
+                    MaterializedOutput {
+                        handle: handle.fork(),
+                        snapshot: None,
+                    },
+                )
+            })
+            .collect();
 
         Self {
             status,
@@ -584,17 +1069,119 @@ impl ControllerInner {
             dump_profile_request,
             catalog: Arc::new(Mutex::new(catalog)),
             inputs: Mutex::new(BTreeMap::new()),
+            next_input_id: AtomicU64::new(0),
             outputs: ShardedLock::new(OutputEndpoints::new()),
+            materialized_outputs: Mutex::new(materialized_outputs),
+            step: ShardedLock::new(0),
+            checkpoint_dir,
+            checkpoint_requests: Mutex::new(Vec::new()),
+            step_requests: Mutex::new(Vec::new()),
             circuit_thread_unparker,
             backpressure_thread_unparker,
             error_cb,
         }
     }
 
+    /// Increment the step counter; returns the number of the step about to
+    /// be performed.
+    fn advance_step(&self) -> Step {
+        let mut step = self.step.write().unwrap();
+        let current = *step;
+        *step += 1;
+        current
+    }
+
+    /// Start a new step; invoked by the circuit thread.
+    ///
+    /// Returns the number of the step, the number of input records that
+    /// will be fully processed once the step completes and, if `checkpoint`
+    /// is `true`, the states of all input probes at the start of the step.
+    /// Probes are only locked while the step counter is advanced and their
+    /// states are serialized, so that the states cover exactly the data
+    /// consumed by the step.
+    fn start_step(&self, checkpoint: bool) -> (Step, u64, AnyResult<ProbeCheckpoints>) {
+        let probes = if checkpoint {
+            self.inputs
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(endpoint_id, descr)| (*endpoint_id, descr.probes.clone()))
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
+
+        // Prevent endpoints from forking new probes, then lock all probes to
+        // prevent them from pushing more data.
+        let forks = probes
+            .iter()
+            .map(|(_, probes)| probes.forks.lock().unwrap())
+            .collect::<Vec<_>>();
+        let states = probes
+            .iter()
+            .zip(forks.iter())
+            .map(|((_, probes), forks)| {
+                once(probes.state.clone())
+                    .chain(forks.iter().filter_map(Weak::upgrade))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let guards = states
+            .iter()
+            .map(|states| {
+                states
+                    .iter()
+                    .map(|state| state.lock().unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // Reset all counters of buffered records and bytes to 0.
+        self.status.consume_buffered_inputs();
+
+        // All input records accumulated so far (and possibly some more) will
+        // be fully processed after the `step()` call returns.
+        let processed_records = self.status.num_total_input_records();
+
+        // Wake up the backpressure thread to unpause endpoints blocked due to
+        // backpressure.
+        self.unpark_backpressure();
+        // Advance the step counter.  All data pushed to input handles
+        // before this point is labeled with the current step number and
+        // will be consumed by the `step()` call.
+        let step = self.advance_step();
+
+        let checkpoints = probes
+            .iter()
+            .zip(guards.iter())
+            .map(|((endpoint_id, _), guards)| {
+                let mut guards = guards.iter().map(|state| state.checkpoint());
+                let probe = guards.next().unwrap()?;
+                Ok((
+                    *endpoint_id,
+                    (probe, guards.collect::<AnyResult<Vec<_>>>()?),
+                ))
+            })
+            .collect();
+
+        (step, processed_records, checkpoints)
+    }
+
     fn connect_input(
         self: &Arc<Self>,
         endpoint_name: &str,
         endpoint_config: &InputEndpointConfig,
+    ) -> AnyResult<()> {
+        self.connect_input_restored(endpoint_name, endpoint_config, None)
+    }
+
+    /// Connect input endpoint, resuming from its state at the time of a
+    /// checkpoint, if `restored` is specified.
+    fn connect_input_restored(
+        self: &Arc<Self>,
+        endpoint_name: &str,
+        endpoint_config: &InputEndpointConfig,
+        restored: Option<InputCheckpoint>,
     ) -> AnyResult<()> {
         let mut inputs = self.inputs.lock().unwrap();
 
@@ -609,27 +1196,63 @@ impl ControllerInner {
         // │endpoint├──►│InputProbe├──►│parser├──►
         // └────────┘   └──────────┘   └──────┘
 
-        // Create parser.
-        let format = <dyn InputFormat>::get_format(&endpoint_config.format.name)
-            .ok_or_else(|| ControllerError::unknown_input_format(&endpoint_config.format.name))?;
-
-        let catalog = self.catalog.lock().unwrap();
-        let input_stream = catalog
-            .input_collection_handle(&endpoint_config.stream)
-            .ok_or_else(|| AnyError::msg(format!("unknown stream '{}'", endpoint_config.stream)))?;
+        let parser_config = ParserConfig {
+            stream: endpoint_config.stream.to_string(),
+            format: endpoint_config.format.clone(),
+            compression: endpoint_config.transport.compression,
+        };
 
-        let parser = format.new_parser(input_stream, &endpoint_config.format.config)?;
+        // Create parser and restore its state, which may contain partially
+        // received records, from the checkpoint, along with the states of
+        // forked parsers.
+        let parser = self.new_parser(&parser_config)?;
+        let mut restored_forks = VecDeque::new();
+        let probe_state = match &restored {
+            None => ProbeState::new(parser),
+            Some(restored) => {
+                for fork in restored.forks.iter() {
+                    restored_forks.push_back(ProbeState::restore(parser.fork(), fork)?);
+                }
+                ProbeState::restore(parser, &restored.probe)?
+            }
+        };
+        let probes = Arc::new(EndpointProbes::new(probe_state));
+
+        let endpoint_id = self.next_input_id.fetch_add(1, Ordering::AcqRel);
+
+        // Create dead-letter queue.
+        let dead_letters = match &endpoint_config.dead_letter {
+            None => None,
+            Some(dead_letter_config) => {
+                let self_weak = Arc::downgrade(self);
+                let dead_letter_endpoint_name = format!("{endpoint_name}.dead_letter");
+                let queue = DeadLetterQueue::new(
+                    endpoint_name,
+                    dead_letter_config,
+                    Box::new(move |fatal: bool, e: AnyError| {
+                        if let Some(controller) = self_weak.upgrade() {
+                            controller.error(ControllerError::output_transport_error(
+                                &dead_letter_endpoint_name,
+                                fatal,
+                                e,
+                            ));
+                        }
+                    }),
+                )?;
+                Some(Arc::new(queue))
+            }
+        };
 
         // Create probe.
-        let endpoint_id = inputs.keys().rev().next().map(|k| k + 1).unwrap_or(0);
-        let probe = Box::new(InputProbe::new(
+        let probe = InputProbe::new(
             endpoint_id,
             endpoint_name,
-            parser,
+            parser_config,
+            probes.state.clone(),
+            probes.clone(),
+            dead_letters.clone(),
             self.clone(),
-            self.circuit_thread_unparker.clone(),
-            self.backpressure_thread_unparker.clone(),
-        ));
+        );
 
         // Create transport endpoint.
         let transport = <dyn InputTransport>::get_transport(&endpoint_config.transport.name)
@@ -637,12 +1260,23 @@ impl ControllerInner {
                 ControllerError::unknown_input_transport(&endpoint_config.transport.name)
             })?;
 
-        let endpoint =
-            transport.new_endpoint(endpoint_name, &endpoint_config.transport.config, probe)?;
+        let endpoint = transport.new_endpoint(
+            endpoint_name,
+            &endpoint_config.transport.config,
+            Box::new(probe),
+        )?;
+
+        // Skip data covered by the checkpoint.
+        if let Some(restored) = &restored {
+            endpoint.seek(restored.probe.offset, restored.position.as_deref())?;
+            if endpoint.resume_forks() {
+                *probes.restored_forks.lock().unwrap() = restored_forks;
+            }
+        }
 
         inputs.insert(
             endpoint_id,
-            InputEndpointDescr::new(endpoint_name, endpoint),
+            InputEndpointDescr::new(endpoint_name, endpoint, probes, dead_letters),
         );
 
         drop(inputs);
@@ -655,6 +1289,133 @@ impl ControllerInner {
         Ok(())
     }
 
+    /// Create a parser for input stream `config.stream`.
+    ///
+    /// The parser decompresses its input if `config.compression` is not
+    /// `None`.
+    fn new_parser(&self, config: &ParserConfig) -> AnyResult<Box<dyn Parser>> {
+        let ParserConfig {
+            stream,
+            format,
+            compression,
+        } = config;
+
+        let input_format = <dyn InputFormat>::get_format(&format.name)
+            .ok_or_else(|| ControllerError::unknown_input_format(&format.name))?;
+
+        let catalog = self.catalog.lock().unwrap();
+        let input_stream = catalog
+            .input_collection_handle(stream)
+            .ok_or_else(|| AnyError::msg(format!("unknown stream '{stream}'")))?;
+
+        let parser = input_format.new_parser(input_stream, &format.config)?;
+        match compression {
+            Compression::None => Ok(parser),
+            _ => Ok(Box::new(DecompressParser::new(*compression, parser)?)),
+        }
+    }
+
+    /// Restore the state of the circuit from the last checkpoint in the
+    /// checkpoint directory, if any.
+    ///
+    /// Returns the state of input endpoints that were connected at the time
+    /// of the checkpoint, indexed by endpoint name.
+    fn restore(&self, circuit: &mut DBSPHandle) -> AnyResult<BTreeMap<String, InputCheckpoint>> {
+        let checkpoint_dir = match &self.checkpoint_dir {
+            None => return Ok(BTreeMap::new()),
+            Some(checkpoint_dir) => checkpoint_dir,
+        };
+
+        let metadata = match checkpoint_dir.read()? {
+            None => return Ok(BTreeMap::new()),
+            Some(metadata) => metadata,
+        };
+
+        circuit
+            .restore(checkpoint_dir.circuit_path(metadata.steps))
+            .map_err(ControllerError::dbsp_error)?;
+        *self.step.write().unwrap() = metadata.steps;
+        self.status.set_num_completed_steps(metadata.steps);
+
+        info!(
+            "restored pipeline state after {} steps from checkpoint",
+            metadata.steps
+        );
+
+        Ok(metadata.inputs)
+    }
+
+    /// Ask the circuit thread to write a checkpoint and wait for it to
+    /// complete (see [`Controller::checkpoint`]).
+    fn request_checkpoint(&self) -> AnyResult<()> {
+        if self.checkpoint_dir.is_none() {
+            return Err(AnyError::msg(
+                "checkpointing is not enabled: 'checkpoint_dir' is not specified in the pipeline configuration",
+            ));
+        }
+
+        let (sender, receiver) = channel();
+        self.checkpoint_requests.lock().unwrap().push(sender);
+        self.unpark_circuit();
+
+        receiver.recv().map_err(|_| {
+            AnyError::msg("the pipeline terminated before completing the checkpoint")
+        })?
+    }
+
+    /// Ask the circuit thread to perform a step and wait for it to complete
+    /// (see [`Controller::step`]).
+    fn request_step(&self) -> AnyResult<Step> {
+        let (sender, receiver) = channel();
+        self.step_requests.lock().unwrap().push(sender);
+        self.unpark_circuit();
+
+        receiver
+            .recv()
+            .map_err(|_| AnyError::msg("the pipeline terminated before completing the step"))?
+    }
+
+    /// Write a checkpoint after step `step`; invoked by the circuit thread.
+    ///
+    /// `probes` contains the states of input probes at the start of the step
+    /// (see [`Self::start_step`]).
+    fn checkpoint(
+        &self,
+        circuit: &mut DBSPHandle,
+        step: Step,
+        probes: ProbeCheckpoints,
+    ) -> AnyResult<()> {
+        // `Controller::checkpoint` only accepts requests when checkpointing
+        // is enabled.
+        let checkpoint_dir = self.checkpoint_dir.as_ref().unwrap();
+        let steps = step + 1;
+
+        circuit
+            .checkpoint(checkpoint_dir.circuit_path(steps))
+            .map_err(ControllerError::dbsp_error)?;
+
+        // Query endpoint positions after the probes have been released: an
+        // endpoint may need to finish pushing a buffer to its probe to report
+        // its position.  Data received since then is consumed by later steps.
+        let endpoints = self.inputs.lock().unwrap();
+        let mut inputs = BTreeMap::new();
+        for (endpoint_id, (probe, forks)) in probes {
+            if let Some(descr) = endpoints.get(&endpoint_id) {
+                inputs.insert(
+                    descr.endpoint_name.clone(),
+                    InputCheckpoint {
+                        probe,
+                        forks,
+                        position: descr.endpoint.position(step)?,
+                    },
+                );
+            }
+        }
+        drop(endpoints);
+
+        checkpoint_dir.write(&CheckpointMetadata { steps, inputs })
+    }
+
     /// Unpark the circuit thread.
     fn unpark_circuit(&self) {
         self.circuit_thread_unparker.unpark();
@@ -665,6 +1426,57 @@ impl ControllerInner {
         self.backpressure_thread_unparker.unpark();
     }
 
+    /// Take the contents of materialized output streams computed by the
+    /// last step; invoked by the circuit thread after each step.
+    fn update_materialized_outputs(&self) {
+        for output in self.materialized_outputs.lock().unwrap().values_mut() {
+            if let Some(snapshot) = output.handle.take_snapshot() {
+                output.snapshot = Some(snapshot);
+            }
+        }
+    }
+
+    /// Contents of materialized output stream `stream` after the last step.
+    fn materialized_contents(&self, stream: &str) -> Option<Arc<dyn SerSnapshot>> {
+        self.materialized_outputs
+            .lock()
+            .unwrap()
+            .get(stream)
+            .and_then(|output| output.snapshot.clone())
+    }
+
+    fn query_output(&self, stream: &str, query: &OutputQuery) -> AnyResult<Vec<u8>> {
+        let schema = self.catalog.lock().unwrap().output_schema(stream).cloned();
+
+        // Don't hold the lock while encoding the output.
+        let contents = self
+            .materialized_outputs
+            .lock()
+            .unwrap()
+            .get(stream)
+            .ok_or_else(|| AnyError::msg(format!("output stream '{stream}' is not materialized")))?
+            .snapshot
+            .clone();
+
+        let format = <dyn OutputFormat>::get_format(&query.format.name)
+            .ok_or_else(|| ControllerError::unknown_output_format(&query.format.name))?;
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let mut encoder = format.new_encoder(
+            &query.format.config,
+            schema.as_ref(),
+            Box::new(QueryOutputConsumer(buffer.clone())),
+        )?;
+
+        if let Some(contents) = contents {
+            let result = contents.key_range(query.from.as_ref(), query.to.as_ref(), query.limit)?;
+            encoder.encode(&[result])?;
+        }
+        drop(encoder);
+
+        let result = take(&mut *buffer.lock().unwrap());
+        Ok(result)
+    }
+
     fn connect_output(
         self: &Arc<Self>,
         endpoint_name: &str,
@@ -683,14 +1495,14 @@ impl ControllerInner {
         // │encoder├──►│OutputProbe├──►│endpoint├──►
         // └───────┘   └───────────┘   └────────┘
 
-        // Lookup output handle in catalog.
-        let collection_handle = self
-            .catalog
-            .lock()
-            .unwrap()
+        // Lookup output handle and schema in catalog.
+        let catalog = self.catalog.lock().unwrap();
+        let collection_handle = catalog
             .output_batch_handle(&endpoint_config.stream)
             .ok_or_else(|| ControllerError::unknown_output_stream(&endpoint_config.stream))?
             .fork();
+        let schema = catalog.output_schema(&endpoint_config.stream).cloned();
+        drop(catalog);
 
         // Create transport endpoint.
         let transport = <dyn OutputTransport>::get_transport(&endpoint_config.transport.name)
@@ -712,21 +1524,53 @@ impl ControllerInner {
             }),
         )?;
 
-        // Create probe.
-        let probe = Box::new(OutputProbe::new(
-            endpoint_id,
-            endpoint_name,
-            endpoint,
+        // Compress output buffers.
+        let endpoint = CompressEndpoint::wrap(endpoint_config.transport.compression, endpoint)
+            .map_err(|e| ControllerError::output_transport_error(endpoint_name, true, e))?;
+
+        // The endpoint wakes up the output thread when it requests a
+        // snapshot.
+        let parker = Parker::new();
+        let unparker = parker.unparker().clone();
+        let snapshot_sink = endpoint.snapshot_sink(Box::new(move || unparker.unpark()));
+        if snapshot_sink.is_some()
+            && !self
+                .materialized_outputs
+                .lock()
+                .unwrap()
+                .contains_key(&*endpoint_config.stream)
+        {
+            Err(ControllerError::output_transport_error(
+                endpoint_name,
+                true,
+                AnyError::msg(format!(
+                    "snapshots require output stream '{}' to be materialized",
+                    endpoint_config.stream
+                )),
+            ))?;
+        }
+
+        // Create probe.
+        let probe = Box::new(OutputProbe::new(
+            endpoint_id,
+            endpoint_name,
+            endpoint,
             self.clone(),
         ));
 
         // Create encoder.
         let format = <dyn OutputFormat>::get_format(&endpoint_config.format.name)
             .ok_or_else(|| ControllerError::unknown_output_format(&endpoint_config.format.name))?;
-        let encoder = format.new_encoder(&endpoint_config.format.config, probe)?;
+        let encoder = format.new_encoder(&endpoint_config.format.config, schema.as_ref(), probe)?;
+        let snapshot = snapshot_sink
+            .map(|sink| OutputSnapshot::new(sink, format, &endpoint_config.format.config, schema));
 
-        let parker = Parker::new();
-        let endpoint_state = OutputEndpointDescr::new(endpoint_name, parker.unparker().clone());
+        let endpoint_state = OutputEndpointDescr::new(
+            endpoint_name,
+            parker.unparker().clone(),
+            endpoint_config,
+            snapshot.is_some(),
+        );
         let queue = endpoint_state.queue.clone();
         let controller = self.clone();
 
@@ -744,6 +1588,7 @@ impl ControllerInner {
                 endpoint_id,
                 endpoint_name_string,
                 encoder,
+                snapshot,
                 parker,
                 queue,
                 controller,
@@ -763,6 +1608,7 @@ impl ControllerInner {
         endpoint_id: EndpointId,
         endpoint_name: String,
         mut encoder: Box<dyn Encoder>,
+        mut snapshot: Option<OutputSnapshot>,
         parker: Parker,
         queue: Arc<BatchQueue>,
         controller: Arc<ControllerInner>,
@@ -772,13 +1618,44 @@ impl ControllerInner {
                 return;
             }
 
+            let (heartbeats, mode) = match controller
+                .outputs
+                .read()
+                .unwrap()
+                .lookup_by_id(&endpoint_id)
+            {
+                Some(endpoint) => (endpoint.heartbeats, endpoint.mode),
+                // The endpoint has been disconnected.
+                None => return,
+            };
+
+            // Send a snapshot of all outputs encoded so far, if requested by the
+            // endpoint.
+            if let Some(snapshot) = &mut snapshot {
+                snapshot
+                    .send_if_requested()
+                    .unwrap_or_else(|e| controller.encode_error(endpoint_id, &endpoint_name, e));
+            }
+
             // Dequeue the next output batch and push it to the encoder.
-            if let Some((data, processed_records)) = queue.pop() {
+            if let Some((step, data, processed_records, contents)) = queue.pop() {
                 let num_records = data.iter().map(|b| b.len()).sum();
 
-                encoder
-                    .encode(data.as_slice())
-                    .unwrap_or_else(|e| controller.encode_error(endpoint_id, &endpoint_name, e));
+                // All buffers produced by the encoder for this step form a single
+                // batch.  Steps without outputs are only sent to endpoints that
+                // requested heartbeats, but are still accounted for in the stats
+                // below.
+                if num_records > 0 || heartbeats {
+                    convert_output(&data, mode)
+                        .and_then(|output| encoder.encode_step(step, output.as_slice()))
+                        .unwrap_or_else(|e| {
+                            controller.encode_error(endpoint_id, &endpoint_name, e)
+                        });
+                }
+
+                if let Some(snapshot) = &mut snapshot {
+                    snapshot.update(step, contents);
+                }
 
                 // `num_records` output records have been transmitted --
                 // update output stats, wake up the circuit thread if the
@@ -791,12 +1668,102 @@ impl ControllerInner {
                 );
             } else {
                 // Queue is empty -- wait for the circuit thread to wake us up when
-                // more data is available.
+                // more data is available, or for the endpoint to request a
+                // snapshot.
                 parker.park();
             }
         }
     }
 
+    fn input_endpoint_id(
+        inputs: &BTreeMap<EndpointId, InputEndpointDescr>,
+        endpoint_name: &str,
+    ) -> Result<EndpointId, ControllerError> {
+        inputs
+            .iter()
+            .find(|(_, ep)| ep.endpoint_name == endpoint_name)
+            .map(|(endpoint_id, _)| *endpoint_id)
+            .ok_or_else(|| ControllerError::unknown_input_endpoint(endpoint_name))
+    }
+
+    fn disconnect_input(&self, endpoint_name: &str) -> Result<(), ControllerError> {
+        let mut inputs = self.inputs.lock().unwrap();
+        let endpoint_id = Self::input_endpoint_id(&inputs, endpoint_name)?;
+
+        let ep = inputs.remove(&endpoint_id).unwrap();
+        ep.endpoint.disconnect();
+        drop(inputs);
+
+        // Records already received from the endpoint remain buffered and will
+        // be processed by the circuit; the endpoint no longer contributes to
+        // `pipeline_complete`.
+        self.status.remove_input(&endpoint_id);
+        self.unpark_backpressure();
+        Ok(())
+    }
+
+    fn disconnect_output(&self, endpoint_name: &str) -> Result<(), ControllerError> {
+        let mut outputs = self.outputs.write().unwrap();
+        let endpoint_id = outputs
+            .lookup_id_by_name(endpoint_name)
+            .ok_or_else(|| ControllerError::unknown_output_endpoint(endpoint_name))?;
+
+        let ep = outputs.remove(&endpoint_id).unwrap();
+        drop(outputs);
+
+        self.status.remove_output(&endpoint_id);
+
+        // Wake up the output thread so that it can exit, and the circuit thread
+        // in case it's waiting for the endpoint's buffer to drain.
+        ep.unparker.unpark();
+        self.unpark_circuit();
+        Ok(())
+    }
+
+    fn set_input_paused(&self, endpoint_name: &str, paused: bool) -> Result<(), ControllerError> {
+        let inputs = self.inputs.lock().unwrap();
+        let endpoint_id = Self::input_endpoint_id(&inputs, endpoint_name)?;
+        self.status.set_input_paused(&endpoint_id, paused);
+        drop(inputs);
+
+        self.unpark_backpressure();
+        Ok(())
+    }
+
+    fn set_input_max_buffered_records(
+        &self,
+        endpoint_name: &str,
+        max_buffered_records: u64,
+    ) -> Result<(), ControllerError> {
+        let inputs = self.inputs.lock().unwrap();
+        let endpoint_id = Self::input_endpoint_id(&inputs, endpoint_name)?;
+        self.status
+            .set_input_max_buffered_records(&endpoint_id, max_buffered_records);
+        drop(inputs);
+
+        // The endpoint may need to be paused or resumed.
+        self.unpark_backpressure();
+        Ok(())
+    }
+
+    fn set_output_max_buffered_records(
+        &self,
+        endpoint_name: &str,
+        max_buffered_records: u64,
+    ) -> Result<(), ControllerError> {
+        let outputs = self.outputs.read().unwrap();
+        let endpoint_id = outputs
+            .lookup_id_by_name(endpoint_name)
+            .ok_or_else(|| ControllerError::unknown_output_endpoint(endpoint_name))?;
+        self.status
+            .set_output_max_buffered_records(&endpoint_id, max_buffered_records);
+        drop(outputs);
+
+        // The circuit thread may be waiting for output buffers to drain.
+        self.unpark_circuit();
+        Ok(())
+    }
+
     fn state(self: &Arc<Self>) -> PipelineState {
         PipelineState::from_u32(self.state.load(Ordering::Acquire)).unwrap()
     }
@@ -828,6 +1795,37 @@ impl ControllerInner {
         self.unpark_backpressure();
     }
 
+    /// Write records rejected by input endpoints since the previous call to
+    /// their dead-letter queues' output endpoints, labeled with step `step`.
+    fn write_dead_letters(&self, step: Step) {
+        let dead_letters = self
+            .inputs
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|ep| ep.dead_letters.clone())
+            .collect::<Vec<_>>();
+
+        for queue in dead_letters {
+            if let Err(e) = queue.write(step) {
+                self.error(ControllerError::output_transport_error(
+                    queue.endpoint_name(),
+                    false,
+                    e,
+                ));
+            }
+        }
+    }
+
+    fn input_endpoint_dead_letters(&self, endpoint_name: &str) -> Option<Vec<DeadLetter>> {
+        let inputs = self.inputs.lock().unwrap();
+        let endpoint = inputs
+            .values()
+            .find(|ep| ep.endpoint_name == endpoint_name)?;
+        let records = endpoint.dead_letters.as_ref()?.records();
+        Some(records)
+    }
+
     fn dump_profile(&self) {
         self.dump_profile_request.store(true, Ordering::Release);
         self.unpark_circuit();
@@ -890,61 +1888,204 @@ impl ControllerInner {
     }
 }
 
+/// State of an input probe.
+struct ProbeState {
+    parser: Box<dyn Parser>,
+    /// Number of bytes received by the probe.
+    num_bytes: u64,
+}
+
+impl ProbeState {
+    fn new(parser: Box<dyn Parser>) -> Self {
+        Self {
+            parser,
+            num_bytes: 0,
+        }
+    }
+
+    /// Create the state of a probe resuming from `checkpoint`.
+    fn restore(mut parser: Box<dyn Parser>, checkpoint: &ProbeCheckpoint) -> AnyResult<Self> {
+        parser.restore(&checkpoint.parser)?;
+        Ok(Self {
+            parser,
+            num_bytes: checkpoint.offset,
+        })
+    }
+
+    /// Serialize the state of the probe.
+    ///
+    /// The offset of the probe excludes the bytes that the parser needs to
+    /// receive again after it has been restored.
+    fn checkpoint(&self) -> AnyResult<ProbeCheckpoint> {
+        Ok(ProbeCheckpoint {
+            offset: self.num_bytes - self.parser.replay_bytes(),
+            parser: self.parser.checkpoint()?,
+        })
+    }
+}
+
+/// Input probes of an endpoint.
+///
+/// Shared by the probes and the controller, which serializes their states
+/// when writing a checkpoint.
+struct EndpointProbes {
+    /// State of the probe created along with the endpoint.
+    state: Arc<Mutex<ProbeState>>,
+    /// States of probes forked by the endpoint with
+    /// [`InputConsumer::fork`], in the order of creation.  Probes dropped by
+    /// the endpoint are skipped.
+    forks: Mutex<Vec<Weak<Mutex<ProbeState>>>>,
+    /// Checkpointed states assigned to the next probes forked by an endpoint
+    /// that resumes its forks (see [`InputEndpoint::resume_forks`]).
+    restored_forks: Mutex<VecDeque<ProbeState>>,
+}
+
+impl EndpointProbes {
+    fn new(state: ProbeState) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(state)),
+            forks: Mutex::new(Vec::new()),
+            restored_forks: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+/// Configuration of the parser of an input endpoint.
+#[derive(Clone)]
+struct ParserConfig {
+    /// Name of the input stream the endpoint is connected to.
+    stream: String,
+    /// Data format.
+    format: FormatConfig,
+    /// Compression of the input data.
+    compression: Compression,
+}
+
 /// An input probe inserted between the transport endpoint and the parser to
 /// track stats and errors.
 struct InputProbe {
     endpoint_id: EndpointId,
     endpoint_name: String,
-    parser: Box<dyn Parser>,
+    parser_config: ParserConfig,
+    state: Arc<Mutex<ProbeState>>,
+    probes: Arc<EndpointProbes>,
+    dead_letters: Option<Arc<DeadLetterQueue>>,
     controller: Arc<ControllerInner>,
-    circuit_thread_unparker: Unparker,
-    backpressure_thread_unparker: Unparker,
 }
 
 impl InputProbe {
     fn new(
         endpoint_id: EndpointId,
         endpoint_name: &str,
-        parser: Box<dyn Parser>,
+        parser_config: ParserConfig,
+        state: Arc<Mutex<ProbeState>>,
+        probes: Arc<EndpointProbes>,
+        dead_letters: Option<Arc<DeadLetterQueue>>,
         controller: Arc<ControllerInner>,
-        circuit_thread_unparker: Unparker,
-        backpressure_thread_unparker: Unparker,
     ) -> Self {
         Self {
             endpoint_id,
             endpoint_name: endpoint_name.to_owned(),
-            parser,
+            parser_config,
+            state,
+            probes,
+            dead_letters,
             controller,
-            circuit_thread_unparker,
-            backpressure_thread_unparker,
+        }
+    }
+
+    /// Create a probe with the same configuration as `self` and a new
+    /// state.
+    fn fork_with_state(&self, parser_config: ParserConfig, state: Arc<Mutex<ProbeState>>) -> Self {
+        Self::new(
+            self.endpoint_id,
+            &self.endpoint_name,
+            parser_config,
+            state,
+            self.probes.clone(),
+            self.dead_letters.clone(),
+            self.controller.clone(),
+        )
+    }
+
+    /// Flush records parsed so far to the input handle.
+    ///
+    /// Returns the number of the step that will consume the records.
+    fn flush(&self, state: &mut ProbeState) -> Step {
+        // Flush the parser while holding the step lock to make sure that the
+        // data is consumed by the step whose number we return.
+        let step = self.controller.step.read().unwrap();
+        state.parser.flush();
+        *step
+    }
+
+    /// Report parse errors returned by the parser.
+    ///
+    /// `step` is the step that consumes the valid records parsed together
+    /// with the invalid ones.
+    fn parse_errors(&self, errors: Vec<ParseError>, step: Step) {
+        if let Some(dead_letters) = &self.dead_letters {
+            dead_letters.push(
+                errors
+                    .iter()
+                    .map(|error| DeadLetter::new(&self.endpoint_name, error, step))
+                    .collect(),
+            );
+        }
+
+        for error in errors {
+            self.controller.parse_error(
+                self.endpoint_id,
+                &self.endpoint_name,
+                AnyError::new(error),
+            );
+        }
+    }
+
+    /// Flush parsed records, report errors and update stats.
+    fn parsed(
+        &self,
+        state: &mut ProbeState,
+        num_bytes: usize,
+        num_records: usize,
+        errors: Vec<ParseError>,
+    ) -> Option<Step> {
+        let step = self.flush(state);
+
+        self.parse_errors(errors, step);
+        state.num_bytes += num_bytes as u64;
+        self.controller.status.input_batch(
+            self.endpoint_id,
+            num_bytes,
+            num_records,
+            &self.controller.status.global_config,
+            &self.controller.circuit_thread_unparker,
+            &self.controller.backpressure_thread_unparker,
+        );
+
+        if num_records > 0 {
+            Some(step)
+        } else {
+            None
         }
     }
 }
 
 /// `InputConsumer` interface exposed to the transport endpoint.
 impl InputConsumer for InputProbe {
-    fn input(&mut self, data: &[u8]) {
-        // println!("input consumer {} bytes", data.len());
-        // Pass input buffer to the parser.
-        match self.parser.input(data) {
-            Ok(num_records) => {
-                // Success: push data to the input handle, update stats.
-                self.parser.flush();
-                self.controller.status.input_batch(
-                    self.endpoint_id,
-                    data.len(),
-                    num_records,
-                    &self.controller.status.global_config,
-                    &self.circuit_thread_unparker,
-                    &self.backpressure_thread_unparker,
-                );
-            }
-            Err(error) => {
-                self.parser.clear();
-                self.controller
-                    .parse_error(self.endpoint_id, &self.endpoint_name, error);
-            }
-        }
+    fn input(&mut self, data: &[u8]) -> Option<Step> {
+        // Pass input buffer to the parser.  Invalid records are skipped by the
+        // parser; valid records are pushed to the input handle.
+        let mut state = self.state.lock().unwrap();
+        let (num_records, errors) = state.parser.input(data);
+        self.parsed(&mut state, data.len(), num_records, errors)
+    }
+
+    fn end_of_chunk(&mut self) -> Option<Step> {
+        // Let the parser complete any partially parsed records.
+        let mut state = self.state.lock().unwrap();
+        let (num_records, errors) = state.parser.eoi();
+        self.parsed(&mut state, 0, num_records, errors)
     }
 
     fn eoi(&mut self) {
@@ -952,37 +2093,54 @@ impl InputConsumer for InputProbe {
         // no new data has been received, the parser may contain some partially
         // parsed data and may be waiting for, e.g., and end-of-line or
         // end-of-file to finish parsing it).
-        match self.parser.eoi() {
-            Ok(num_records) => {
-                self.parser.flush();
-                self.controller.status.eoi(
-                    self.endpoint_id,
-                    num_records,
-                    &self.circuit_thread_unparker,
-                );
-            }
-            Err(error) => {
-                self.parser.clear();
-                self.controller
-                    .error(ControllerError::parse_error(&self.endpoint_name, error));
-            }
-        }
+        let mut state = self.state.lock().unwrap();
+        let (num_records, errors) = state.parser.eoi();
+        let step = self.flush(&mut state);
+        self.parse_errors(errors, step);
+        self.controller.status.eoi(
+            self.endpoint_id,
+            num_records,
+            &self.controller.circuit_thread_unparker,
+        );
     }
 
     fn error(&mut self, fatal: bool, error: AnyError) {
-        self.controller
-            .input_transport_error(self.endpoint_id, &self.endpoint_name, fatal, error);
+        if error.is::<ParseError>() {
+            self.controller
+                .parse_error(self.endpoint_id, &self.endpoint_name, error);
+        } else {
+            self.controller.input_transport_error(
+                self.endpoint_id,
+                &self.endpoint_name,
+                fatal,
+                error,
+            );
+        }
     }
 
     fn fork(&self) -> Box<dyn InputConsumer> {
-        Box::new(Self::new(
-            self.endpoint_id,
-            &self.endpoint_name,
-            self.parser.fork(),
-            self.controller.clone(),
-            self.circuit_thread_unparker.clone(),
-            self.backpressure_thread_unparker.clone(),
-        ))
+        let restored = self.probes.restored_forks.lock().unwrap().pop_front();
+        let state = Arc::new(Mutex::new(restored.unwrap_or_else(|| {
+            ProbeState::new(self.state.lock().unwrap().parser.fork())
+        })));
+
+        // Register the fork, so that its state is included in checkpoints.
+        let mut forks = self.probes.forks.lock().unwrap();
+        forks.retain(|fork| fork.strong_count() > 0);
+        forks.push(Arc::downgrade(&state));
+        drop(forks);
+
+        Box::new(self.fork_with_state(self.parser_config.clone(), state))
+    }
+
+    fn fork_with_format(&self, format: &FormatConfig) -> AnyResult<Box<dyn InputConsumer>> {
+        let parser_config = ParserConfig {
+            format: format.clone(),
+            ..self.parser_config.clone()
+        };
+        let parser = self.controller.new_parser(&parser_config)?;
+        let state = Arc::new(Mutex::new(ProbeState::new(parser)));
+        Ok(Box::new(self.fork_with_state(parser_config, state)))
     }
 }
 
@@ -1011,7 +2169,38 @@ impl OutputProbe {
     }
 }
 
+impl OutputProbe {
+    fn transport_error(&self, error: AnyError) {
+        self.controller
+            .output_transport_error(self.endpoint_id, &self.endpoint_name, false, error);
+    }
+}
+
 impl OutputConsumer for OutputProbe {
+    fn batch_start(&mut self, step: Step) {
+        if let Err(error) = self.endpoint.batch_start(step) {
+            self.transport_error(error);
+        }
+    }
+
+    fn batch_records(&mut self, num_records: usize) {
+        if let Err(error) = self.endpoint.batch_records(num_records) {
+            self.transport_error(error);
+        }
+    }
+
+    fn batch_end(&mut self) {
+        if let Err(error) = self.endpoint.batch_end() {
+            self.transport_error(error);
+        }
+    }
+
+    fn batch_abort(&mut self) {
+        if let Err(error) = self.endpoint.batch_abort() {
+            self.transport_error(error);
+        }
+    }
+
     fn push_buffer(&mut self, buffer: &[u8]) {
         let num_bytes = buffer.len();
 
@@ -1021,29 +2210,32 @@ impl OutputConsumer for OutputProbe {
                     .status
                     .output_buffer(self.endpoint_id, num_bytes);
             }
-            Err(error) => {
-                self.controller.output_transport_error(
-                    self.endpoint_id,
-                    &self.endpoint_name,
-                    false,
-                    error,
-                );
-            }
+            Err(error) => self.transport_error(error),
         }
     }
+
+    fn at_file_start(&self) -> bool {
+        self.endpoint.at_file_start()
+    }
+
+    fn separates_steps(&self) -> bool {
+        self.endpoint.separates_steps()
+    }
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
         test::{generate_test_batch, test_circuit, wait, TestStruct},
-        Controller, PipelineConfig,
+        Controller, FormatConfig, GlobalPipelineConfig, InputEndpointConfig, OutputEndpointConfig,
+        OutputQuery, PipelineConfig, StepTrigger,
     };
     use csv::{ReaderBuilder as CsvReaderBuilder, WriterBuilder as CsvWriterBuilder};
-    use std::fs::remove_file;
-    use tempfile::NamedTempFile;
+    use serde_yaml::Value as YamlValue;
+    use std::{borrow::Cow, fs::remove_file, thread::sleep, time::Duration};
+    use tempfile::{tempdir, NamedTempFile};
 
-    use proptest::prelude::*;
+    use proptest::{prelude::*, strategy::ValueTree, test_runner::TestRunner};
 
     // TODO: Parameterize this with config string, so we can test different
     // input/output formats and transports when we support more than one.
@@ -1095,8 +2287,6 @@ outputs:
             output_path,
             );
 
-            println!("input file: {}", temp_input_file.path().to_str().unwrap());
-            println!("output file: {output_path}");
             let config: PipelineConfig = serde_yaml::from_str(&config_str).unwrap();
 
             let controller = Controller::with_config(
@@ -1146,4 +2336,591 @@ outputs:
             assert_eq!(actual, expected);
         }
     }
+
+    fn query_csv(controller: &Controller, query: OutputQuery) -> Vec<TestStruct> {
+        let result = controller.query_output("test_output1", &query).unwrap();
+
+        CsvReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(result.as_slice())
+            .deserialize::<(TestStruct, i32)>()
+            .map(|res| {
+                let (val, weight) = res.unwrap();
+                assert_eq!(weight, 1);
+                val
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_query_output() {
+        let (circuit, catalog) = test_circuit(4);
+        let temp_input_file = NamedTempFile::new().unwrap();
+
+        let config_str = format!(
+            r#"
+inputs:
+    test_input1:
+        stream: test_input1
+        transport:
+            name: file
+            config:
+                path: {:?}
+                follow: false
+        format:
+            name: csv
+"#,
+            temp_input_file.path().to_str().unwrap(),
+        );
+        let config: PipelineConfig = serde_yaml::from_str(&config_str).unwrap();
+
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+
+        let mut expected = generate_test_batch(1000)
+            .new_tree(&mut TestRunner::default())
+            .unwrap()
+            .current();
+
+        let mut writer = CsvWriterBuilder::new()
+            .has_headers(false)
+            .from_writer(temp_input_file.as_file());
+        for val in expected.iter().cloned() {
+            writer.serialize(val).unwrap();
+        }
+        writer.flush().unwrap();
+        controller.start();
+
+        wait(|| controller.pipeline_complete(), None);
+        expected.sort();
+
+        let csv = || FormatConfig {
+            name: Cow::Borrowed("csv"),
+            config: YamlValue::Null,
+        };
+
+        let actual = query_csv(
+            &controller,
+            OutputQuery {
+                format: csv(),
+                from: None,
+                to: None,
+                limit: None,
+            },
+        );
+        assert_eq!(actual, expected);
+
+        let actual = query_csv(
+            &controller,
+            OutputQuery {
+                format: csv(),
+                from: None,
+                to: None,
+                limit: Some(10),
+            },
+        );
+        assert_eq!(actual, expected[0..expected.len().min(10)]);
+
+        if expected.len() >= 3 {
+            let actual = query_csv(
+                &controller,
+                OutputQuery {
+                    format: csv(),
+                    from: Some(serde_json::to_value(&expected[1]).unwrap()),
+                    to: Some(serde_json::to_value(&expected[2]).unwrap()),
+                    limit: None,
+                },
+            );
+            assert_eq!(actual, expected[1..3]);
+        }
+
+        assert!(controller
+            .query_output(
+                "test_output2",
+                &OutputQuery {
+                    format: csv(),
+                    from: None,
+                    to: None,
+                    limit: None,
+                },
+            )
+            .is_err());
+
+        controller.stop().unwrap();
+    }
+
+    fn write_csv(file: &NamedTempFile, data: &[TestStruct]) {
+        let mut writer = CsvWriterBuilder::new()
+            .has_headers(false)
+            .from_writer(file.as_file());
+        for val in data.iter().cloned() {
+            writer.serialize(val).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    #[test]
+    fn test_endpoint_lifecycle() {
+        let (circuit, catalog) = test_circuit(4);
+        let temp_input_file1 = NamedTempFile::new().unwrap();
+        let temp_input_file2 = NamedTempFile::new().unwrap();
+
+        // An input endpoint that never reaches end-of-input.
+        let config_str = format!(
+            r#"
+inputs:
+    test_input1:
+        stream: test_input1
+        transport:
+            name: file
+            config:
+                path: {:?}
+                follow: true
+        format:
+            name: csv
+"#,
+            temp_input_file1.path().to_str().unwrap(),
+        );
+        let config: PipelineConfig = serde_yaml::from_str(&config_str).unwrap();
+
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+
+        let mut runner = TestRunner::default();
+        let data1 = generate_test_batch(1000)
+            .new_tree(&mut runner)
+            .unwrap()
+            .current();
+        let data2 = generate_test_batch(1000)
+            .new_tree(&mut runner)
+            .unwrap()
+            .current();
+
+        write_csv(&temp_input_file1, &data1);
+        controller.start();
+
+        wait(
+            || controller.status().num_total_processed_records() == data1.len() as u64,
+            None,
+        );
+        assert!(!controller.pipeline_complete());
+
+        // Once disconnected, the endpoint no longer prevents the pipeline from
+        // completing.
+        controller.disconnect_input("test_input1").unwrap();
+        assert!(controller.disconnect_input("test_input1").is_err());
+        wait(|| controller.pipeline_complete(), None);
+
+        // Reuse the name of the disconnected endpoint.  The new endpoint starts
+        // in the paused state.
+        write_csv(&temp_input_file2, &data2);
+        let input_config: InputEndpointConfig = serde_yaml::from_str(&format!(
+            r#"
+stream: test_input1
+transport:
+    name: file
+    config:
+        path: {:?}
+        follow: false
+format:
+    name: csv
+"#,
+            temp_input_file2.path().to_str().unwrap(),
+        ))
+        .unwrap();
+
+        controller.pause();
+        controller
+            .connect_input("test_input1", &input_config)
+            .unwrap();
+        controller.pause_input_endpoint("test_input1").unwrap();
+        controller
+            .set_input_max_buffered_records("test_input1", 100)
+            .unwrap();
+        controller.start();
+
+        sleep(Duration::from_millis(200));
+        assert!(!controller.pipeline_complete());
+        assert_eq!(
+            controller.status().num_total_processed_records(),
+            data1.len() as u64
+        );
+
+        controller.start_input_endpoint("test_input1").unwrap();
+        wait(|| controller.pipeline_complete(), None);
+        assert_eq!(
+            controller.status().num_total_processed_records(),
+            (data1.len() + data2.len()) as u64
+        );
+
+        // Output endpoints.
+        let temp_output_path = NamedTempFile::new().unwrap().into_temp_path();
+        let output_config: OutputEndpointConfig = serde_yaml::from_str(&format!(
+            r#"
+stream: test_output1
+transport:
+    name: file
+    config:
+        path: {:?}
+format:
+    name: csv
+"#,
+            temp_output_path.to_str().unwrap(),
+        ))
+        .unwrap();
+
+        controller
+            .connect_output("test_output1", &output_config)
+            .unwrap();
+        assert!(controller
+            .connect_output("test_output1", &output_config)
+            .is_err());
+        controller
+            .set_output_max_buffered_records("test_output1", 10)
+            .unwrap();
+        controller.disconnect_output("test_output1").unwrap();
+        assert!(controller.status().output_status().is_empty());
+        assert!(controller.disconnect_output("test_output1").is_err());
+        assert!(controller.pause_input_endpoint("test_input2").is_err());
+
+        controller.stop().unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let temp_input_file = NamedTempFile::new().unwrap();
+        let temp_output_path = NamedTempFile::new().unwrap().into_temp_path();
+        let checkpoint_dir = tempdir().unwrap();
+
+        let config_str = format!(
+            r#"
+checkpoint_dir: {:?}
+inputs:
+    test_input1:
+        stream: test_input1
+        transport:
+            name: file
+            config:
+                path: {:?}
+                follow: false
+        format:
+            name: csv
+outputs:
+    test_output1:
+        stream: test_output1
+        transport:
+            name: file
+            config:
+                path: {:?}
+        format:
+            name: csv
+"#,
+            checkpoint_dir.path().to_str().unwrap(),
+            temp_input_file.path().to_str().unwrap(),
+            temp_output_path.to_str().unwrap(),
+        );
+        let config: PipelineConfig = serde_yaml::from_str(&config_str).unwrap();
+
+        let mut runner = TestRunner::default();
+        let data1 = generate_test_batch(1000)
+            .new_tree(&mut runner)
+            .unwrap()
+            .current();
+        let mut data2 = generate_test_batch(1000)
+            .new_tree(&mut runner)
+            .unwrap()
+            .current();
+        data2.retain(|val| !data1.contains(val));
+
+        let csv = || OutputQuery {
+            format: FormatConfig {
+                name: Cow::Borrowed("csv"),
+                config: YamlValue::Null,
+            },
+            from: None,
+            to: None,
+            limit: None,
+        };
+
+        // Process `data1` and checkpoint the pipeline.
+        let (circuit, catalog) = test_circuit(4);
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+
+        write_csv(&temp_input_file, &data1);
+        controller.start();
+        wait(|| controller.pipeline_complete(), None);
+        controller.checkpoint().unwrap();
+        controller.stop().unwrap();
+
+        // Restart the pipeline from the checkpoint and feed it `data2`.
+        write_csv(&temp_input_file, &data2);
+
+        let (circuit, catalog) = test_circuit(4);
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+
+        controller.start();
+        wait(|| controller.pipeline_complete(), None);
+
+        // The input endpoint resumes from where it stopped before the
+        // checkpoint.
+        assert_eq!(
+            controller.status().num_total_input_records(),
+            data2.len() as u64
+        );
+
+        // The restored circuit contains the state computed before the
+        // checkpoint.
+        let mut expected = data1.clone();
+        expected.extend(data2.iter().cloned());
+        expected.sort();
+        assert_eq!(query_csv(&controller, csv()), expected);
+
+        controller.stop().unwrap();
+
+        // The output endpoint only received outputs produced after the restore.
+        let mut actual: Vec<_> = CsvReaderBuilder::new()
+            .has_headers(false)
+            .from_path(&temp_output_path)
+            .unwrap()
+            .deserialize::<(TestStruct, i32)>()
+            .map(|res| res.unwrap().0)
+            .collect();
+        actual.sort();
+        data2.sort();
+        assert_eq!(actual, data2);
+
+        // Checkpointing must be enabled in the configuration.
+        let (circuit, catalog) = test_circuit(4);
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &PipelineConfig {
+                global: GlobalPipelineConfig {
+                    checkpoint_dir: None,
+                    ..config.global.clone()
+                },
+                ..config.clone()
+            },
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+        assert!(controller.checkpoint().is_err());
+        controller.stop().unwrap();
+    }
+
+    /// Checkpoint a pipeline in the middle of a compressed input file.
+    #[cfg(feature = "with-compression")]
+    #[test]
+    fn test_checkpoint_compressed() {
+        use flate2::{write::GzEncoder, Compression as GzLevel};
+        use std::io::Write;
+
+        let temp_input_file = NamedTempFile::new().unwrap();
+        let checkpoint_dir = tempdir().unwrap();
+
+        let config_str = format!(
+            r#"
+checkpoint_dir: {:?}
+inputs:
+    test_input1:
+        stream: test_input1
+        transport:
+            name: file
+            config:
+                path: {:?}
+                follow: true
+            compression: gzip
+        format:
+            name: csv
+"#,
+            checkpoint_dir.path().to_str().unwrap(),
+            temp_input_file.path().to_str().unwrap(),
+        );
+        let config: PipelineConfig = serde_yaml::from_str(&config_str).unwrap();
+
+        // Append `data` to the input file as a gzip member.
+        let write_gzip = |data: &[TestStruct]| {
+            let mut writer = CsvWriterBuilder::new()
+                .has_headers(false)
+                .from_writer(Vec::new());
+            for val in data.iter().cloned() {
+                writer.serialize(val).unwrap();
+            }
+            let mut encoder = GzEncoder::new(temp_input_file.as_file(), GzLevel::default());
+            encoder.write_all(&writer.into_inner().unwrap()).unwrap();
+            encoder.finish().unwrap().flush().unwrap();
+        };
+
+        let mut runner = TestRunner::default();
+        let data1 = generate_test_batch(1000)
+            .new_tree(&mut runner)
+            .unwrap()
+            .current();
+        let mut data2 = generate_test_batch(1000)
+            .new_tree(&mut runner)
+            .unwrap()
+            .current();
+        data2.retain(|val| !data1.contains(val));
+
+        let csv = || OutputQuery {
+            format: FormatConfig {
+                name: Cow::Borrowed("csv"),
+                config: YamlValue::Null,
+            },
+            from: None,
+            to: None,
+            limit: None,
+        };
+
+        // Process `data1` and checkpoint the pipeline while the endpoint
+        // waits for more data in the same gzip stream.
+        let (circuit, catalog) = test_circuit(4);
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+
+        write_gzip(&data1);
+        controller.start();
+        wait(
+            || controller.status().num_total_input_records() == data1.len() as u64,
+            None,
+        );
+        controller.checkpoint().unwrap();
+        controller.stop().unwrap();
+
+        // Restart the pipeline from the checkpoint and feed it `data2`.  The
+        // endpoint replays the stream from its start to restore the state of
+        // the decompressor.
+        write_gzip(&data2);
+
+        let (circuit, catalog) = test_circuit(4);
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+
+        controller.start();
+        wait(
+            || controller.status().num_total_input_records() == data2.len() as u64,
+            None,
+        );
+        controller.step().unwrap();
+
+        let mut expected = data1.clone();
+        expected.extend(data2.iter().cloned());
+        expected.sort();
+        assert_eq!(query_csv(&controller, csv()), expected);
+        assert_eq!(
+            controller.status().num_total_input_records(),
+            data2.len() as u64
+        );
+
+        controller.stop().unwrap();
+    }
+
+    #[test]
+    fn test_manual_step() {
+        let temp_input_file = NamedTempFile::new().unwrap();
+        let config_str = format!(
+            r#"
+step_trigger: manual
+inputs:
+    test_input1:
+        stream: test_input1
+        transport:
+            name: file
+            config:
+                path: {:?}
+        format:
+            name: csv
+"#,
+            temp_input_file.path().to_str().unwrap(),
+        );
+        let config: PipelineConfig = serde_yaml::from_str(&config_str).unwrap();
+
+        let mut runner = TestRunner::default();
+        let data = generate_test_batch(1000)
+            .new_tree(&mut runner)
+            .unwrap()
+            .current();
+        write_csv(&temp_input_file, &data);
+
+        let (circuit, catalog) = test_circuit(4);
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+        controller.start();
+
+        // The circuit doesn't consume buffered inputs until a step is
+        // requested.
+        wait(
+            || controller.status().num_total_input_records() == data.len() as u64,
+            None,
+        );
+        assert_eq!(controller.status().num_completed_steps(), 0);
+        assert_eq!(controller.status().num_total_processed_records(), 0);
+
+        assert_eq!(controller.step().unwrap(), 0);
+        assert_eq!(controller.status().num_completed_steps(), 1);
+        assert_eq!(controller.step().unwrap(), 1);
+        assert_eq!(controller.status().num_completed_steps(), 2);
+        wait(
+            || {
+                controller.step().unwrap();
+                controller.pipeline_complete()
+            },
+            None,
+        );
+        assert_eq!(
+            controller.status().num_total_processed_records(),
+            data.len() as u64
+        );
+        controller.stop().unwrap();
+
+        // Periodic steps require a non-zero period.
+        let (circuit, catalog) = test_circuit(4);
+        let mut config = config;
+        config.global.step_trigger = StepTrigger::Periodic;
+        config.global.step_period_usecs = 0;
+        assert!(Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .is_err());
+    }
 }
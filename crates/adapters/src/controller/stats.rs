@@ -57,6 +57,9 @@ pub struct GlobalControllerMetrics {
     /// for end-to-end progress tracking.
     pub total_processed_records: AtomicU64,
 
+    /// Number of steps completed by the circuit.
+    pub completed_steps: AtomicU64,
+
     /// True if the pipeline has processed all input data to completion.
     /// This means that the following conditions hold:
     ///
@@ -97,6 +100,15 @@ impl GlobalControllerMetrics {
         self.total_processed_records
             .store(total_processed_records, Ordering::Release);
     }
+
+    fn num_completed_steps(&self) -> u64 {
+        self.completed_steps.load(Ordering::Acquire)
+    }
+
+    fn set_num_completed_steps(&self, completed_steps: u64) {
+        self.completed_steps
+            .store(completed_steps, Ordering::Release);
+    }
 }
 
 type InputsStatus = ShardedLock<BTreeMap<EndpointId, InputEndpointStatus>>;
@@ -169,6 +181,37 @@ impl ControllerStatus {
         );
     }
 
+    /// Remove the stats of a disconnected input endpoint.
+    pub fn remove_input(&self, endpoint_id: &EndpointId) {
+        self.inputs.write().unwrap().remove(endpoint_id);
+    }
+
+    /// Set the `max_buffered_records` parameter of an input endpoint.
+    pub fn set_input_max_buffered_records(
+        &self,
+        endpoint_id: &EndpointId,
+        max_buffered_records: u64,
+    ) {
+        if let Some(endpoint_stats) = self.inputs.write().unwrap().get_mut(endpoint_id) {
+            endpoint_stats.config.max_buffered_records = max_buffered_records;
+        }
+    }
+
+    /// Mark an input endpoint as paused or resumed by the user.
+    pub fn set_input_paused(&self, endpoint_id: &EndpointId, paused: bool) {
+        if let Some(endpoint_stats) = self.inputs.read().unwrap().get(endpoint_id) {
+            endpoint_stats.paused.store(paused, Ordering::Release);
+        }
+    }
+
+    /// True if the input endpoint has been paused by the user.
+    pub fn input_paused(&self, endpoint_id: &EndpointId) -> bool {
+        match self.inputs.read().unwrap().get(endpoint_id) {
+            None => false,
+            Some(endpoint_stats) => endpoint_stats.paused.load(Ordering::Acquire),
+        }
+    }
+
     /// Initialize stats for a new output endpoint.
     pub fn add_output(
         &self,
@@ -182,6 +225,22 @@ impl ControllerStatus {
         );
     }
 
+    /// Remove the stats of a disconnected output endpoint.
+    pub fn remove_output(&self, endpoint_id: &EndpointId) {
+        self.outputs.write().unwrap().remove(endpoint_id);
+    }
+
+    /// Set the `max_buffered_records` parameter of an output endpoint.
+    pub fn set_output_max_buffered_records(
+        &self,
+        endpoint_id: &EndpointId,
+        max_buffered_records: u64,
+    ) {
+        if let Some(endpoint_stats) = self.outputs.write().unwrap().get_mut(endpoint_id) {
+            endpoint_stats.config.max_buffered_records = max_buffered_records;
+        }
+    }
+
     /// Total number of records currently buffered by all input endpoints.
     pub fn num_buffered_input_records(&self) -> u64 {
         self.global_metrics.num_buffered_input_records()
@@ -201,6 +260,15 @@ impl ControllerStatus {
             .set_num_total_processed_records(total_processed_records);
     }
 
+    /// Number of steps completed by the circuit.
+    pub fn num_completed_steps(&self) -> u64 {
+        self.global_metrics.num_completed_steps()
+    }
+
+    pub fn set_num_completed_steps(&self, completed_steps: u64) {
+        self.global_metrics.set_num_completed_steps(completed_steps);
+    }
+
     /// Input endpoint stats.
     pub fn input_status(&self) -> ShardedLockReadGuard<BTreeMap<EndpointId, InputEndpointStatus>> {
         self.inputs.read().unwrap()
@@ -238,6 +306,16 @@ impl ControllerStatus {
         }
     }
 
+    /// True if the number of records buffered across all input endpoints has
+    /// reached the `max_step_size_records` config parameter.
+    pub fn step_full(&self) -> bool {
+        self.global_config
+            .max_step_size_records
+            .map_or(false, |max_step_size_records| {
+                self.num_buffered_input_records() >= max_step_size_records
+            })
+    }
+
     /// True if the number of records buffered by the endpoint exceeds
     /// its `max_buffered_records` config parameter.
     pub fn input_endpoint_full(&self, endpoint_id: &EndpointId) -> bool {
@@ -276,25 +354,17 @@ impl ControllerStatus {
         let num_records = num_records as u64;
         let num_bytes = num_bytes as u64;
 
-        // Increment buffered_records; unpark circuit thread once
-        // `min_batch_size_records` is exceeded.
-        let old = self.global_metrics.input_batch(num_records);
-
-        if old == 0
-            || (old <= global_config.min_batch_size_records
-                && old + num_records > global_config.min_batch_size_records)
-        {
-            circuit_thread_unparker.unpark();
-        }
-
-        let inputs = self.inputs.read().unwrap();
-
         // Update endpoint counters; unpark backpressure thread if endpoint's
         // `max_buffered_records` exceeded.
         //
+        // This must happen before updating global counters: the circuit thread
+        // resets all counters before each step, and records counted by the
+        // endpoint but not by the global counter would keep the endpoint paused
+        // without ever triggering the step that unpauses it.
+        //
         // There is a potential race condition if the endpoint is currently being
         // removed. In this case, it's safe to ignore this operation.
-        if let Some(endpoint_stats) = inputs.get(&endpoint_id) {
+        if let Some(endpoint_stats) = self.inputs.read().unwrap().get(&endpoint_id) {
             let old = endpoint_stats.add_buffered(num_bytes, num_records);
 
             if old < endpoint_stats.config.max_buffered_records
@@ -303,6 +373,26 @@ impl ControllerStatus {
                 backpressure_thread_unparker.unpark();
             }
         };
+
+        // Increment buffered_records; unpark circuit thread once
+        // `min_batch_size_records` is exceeded.
+        let old = self.global_metrics.input_batch(num_records);
+
+        if old == 0
+            || (old <= global_config.min_batch_size_records
+                && old + num_records > global_config.min_batch_size_records)
+        {
+            circuit_thread_unparker.unpark();
+        }
+
+        // Wake up the circuit thread and pause input endpoints once
+        // `max_step_size_records` is reached.
+        if let Some(max_step_size_records) = global_config.max_step_size_records {
+            if old < max_step_size_records && old + num_records >= max_step_size_records {
+                circuit_thread_unparker.unpark();
+                backpressure_thread_unparker.unpark();
+            }
+        }
     }
 
     /// Update counters after receiving an end-of-input event on an input
@@ -471,9 +561,14 @@ pub struct InputEndpointMetrics {
 pub struct InputEndpointStatus {
     pub endpoint_name: String,
 
-    /// Endpoint configuration (doesn't change).
+    /// Endpoint configuration.
+    ///
+    /// Only `max_buffered_records` can change at runtime.
     pub config: InputEndpointConfig,
 
+    /// The endpoint has been paused by the user.
+    pub paused: AtomicBool,
+
     /// Performance metrics.
     pub metrics: InputEndpointMetrics,
 
@@ -486,6 +581,7 @@ impl InputEndpointStatus {
         Self {
             endpoint_name: endpoint_name.to_string(),
             config: config.clone(),
+            paused: AtomicBool::new(false),
             metrics: Default::default(),
             fatal_error: Mutex::new(None),
         }
@@ -571,7 +667,9 @@ pub struct OutputEndpointMetrics {
 pub struct OutputEndpointStatus {
     pub endpoint_name: String,
 
-    /// Endpoint configuration (doesn't change).
+    /// Endpoint configuration.
+    ///
+    /// Only `max_buffered_records` can change at runtime.
     pub config: OutputEndpointConfig,
 
     /// Performance metrics.
@@ -1,6 +1,7 @@
+use crate::RelationSchema;
 use dbsp::{algebra::ZRingValue, CollectionHandle, DBData, DBWeight, InputHandle, UpsertHandle};
 use erased_serde::{deserialize, Deserializer as ErasedDeserializer, Error as EError};
-use serde::Deserialize;
+use serde::{de::Error as _, Deserialize};
 
 /// Maximal buffer size reused across clock cycles.
 ///
@@ -163,6 +164,19 @@ pub trait DeCollectionHandle: Send {
     /// documentation for details.
     fn delete(&mut self, deserializer: &mut dyn ErasedDeserializer) -> Result<(), EError>;
 
+    /// Buffer an update with weight `weight`.
+    ///
+    /// Handles backed by a Z-set push a single update with the given weight,
+    /// regardless of its magnitude.  Handles backed by an [`UpsertHandle`],
+    /// which can only contain one copy of each record, treat a positive
+    /// weight as [`insert`](`Self::insert`) and a negative weight as
+    /// [`delete`](`Self::delete`).  Updates with weight 0 are ignored.
+    fn update_weighted(
+        &mut self,
+        deserializer: &mut dyn ErasedDeserializer,
+        weight: i64,
+    ) -> Result<(), EError>;
+
     /// Reserve space for at least `reservation` more updates in the
     /// internal input buffer.
     ///
@@ -194,6 +208,14 @@ pub trait DeCollectionHandle: Send {
     /// The new handle will use its own input buffer, but shares the
     /// underlying input stream handle with the original handle.
     fn fork(&self) -> Box<dyn DeCollectionHandle>;
+
+    /// Schema of the input stream, if known.
+    ///
+    /// Parsers use the schema to coerce input values to column types
+    /// before deserializing them (see [`RelationSchema`]).
+    fn schema(&self) -> Option<&RelationSchema> {
+        None
+    }
 }
 
 /// An input handle that wraps a [`CollectionHandle<V, R>`](`CollectionHandle`)
@@ -230,7 +252,7 @@ impl<K, R> DeZSetHandle<K, R> {
 impl<K, R> DeCollectionHandle for DeZSetHandle<K, R>
 where
     K: DBData + for<'de> Deserialize<'de>,
-    R: DBWeight + ZRingValue,
+    R: DBWeight + ZRingValue + TryFrom<i64>,
 {
     fn insert(&mut self, deserializer: &mut dyn ErasedDeserializer) -> Result<(), EError> {
         let key = deserialize::<K>(deserializer)?;
@@ -246,6 +268,21 @@ where
         Ok(())
     }
 
+    fn update_weighted(
+        &mut self,
+        deserializer: &mut dyn ErasedDeserializer,
+        weight: i64,
+    ) -> Result<(), EError> {
+        if weight == 0 {
+            return Ok(());
+        }
+        let key = deserialize::<K>(deserializer)?;
+
+        let weight = weight_from_i64(weight)?;
+        self.updates.push((key, weight));
+        Ok(())
+    }
+
     fn reserve(&mut self, reservation: usize) {
         self.updates.reserve(reservation);
     }
@@ -265,6 +302,20 @@ where
     }
 }
 
+/// Convert `weight` to the weight type of a Z-set.
+///
+/// Fails if `weight` is out of the range of `R`.
+fn weight_from_i64<R>(weight: i64) -> Result<R, EError>
+where
+    R: TryFrom<i64>,
+{
+    R::try_from(weight).map_err(|_| {
+        EError::custom(format!(
+            "weight {weight} is out of the range of the weight type of the input stream"
+        ))
+    })
+}
+
 /// An input handle that wraps a [`UpsertHandle<V, bool>`](`UpsertHandle`)
 /// returned by
 /// [`RootCircuit::add_input_set`](`dbsp::RootCircuit::add_input_set`).
@@ -308,6 +359,18 @@ where
         Ok(())
     }
 
+    fn update_weighted(
+        &mut self,
+        deserializer: &mut dyn ErasedDeserializer,
+        weight: i64,
+    ) -> Result<(), EError> {
+        match weight {
+            0 => Ok(()),
+            w if w > 0 => self.insert(deserializer),
+            _ => self.delete(deserializer),
+        }
+    }
+
     fn reserve(&mut self, reservation: usize) {
         self.updates.reserve(reservation);
     }
@@ -376,6 +439,18 @@ where
         Ok(())
     }
 
+    fn update_weighted(
+        &mut self,
+        deserializer: &mut dyn ErasedDeserializer,
+        weight: i64,
+    ) -> Result<(), EError> {
+        match weight {
+            0 => Ok(()),
+            w if w > 0 => self.insert(deserializer),
+            _ => self.delete(deserializer),
+        }
+    }
+
     fn reserve(&mut self, reservation: usize) {
         self.updates.reserve(reservation);
     }
@@ -397,6 +472,7 @@ where
 
 #[cfg(test)]
 mod test {
+    use super::weight_from_i64;
     use crate::{
         DeCollectionHandle, DeMapHandle, DeScalarHandle, DeScalarHandleImpl, DeSetHandle,
         DeZSetHandle,
@@ -723,4 +799,17 @@ mod test {
 
         dbsp.kill().unwrap();
     }
+
+    #[test]
+    fn test_weight_from_i64() {
+        for weight in [0, 1, -1, 2, 7, -100, 1_000_000_007, i64::MAX, i64::MIN] {
+            assert_eq!(weight_from_i64::<i64>(weight).unwrap(), weight);
+        }
+        assert_eq!(weight_from_i64::<i32>(-1_000_000).unwrap(), -1_000_000);
+        assert_eq!(
+            weight_from_i64::<i32>(i64::MIN).unwrap_err().to_string(),
+            "weight -9223372036854775808 is out of the range of the weight type of the input stream"
+        );
+        assert!(weight_from_i64::<i32>(1 << 31).is_err());
+    }
 }
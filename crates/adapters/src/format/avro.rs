@@ -0,0 +1,654 @@
+use crate::{
+    format::{
+        default_max_weight,
+        json::{input_json_update, DebeziumPayload, InsDelUpdate, WeightedUpdate},
+        repetitions, Encoder, InputFormat, JsonUpdateFormat, OutputFormat, ParseError, Parser,
+    },
+    DeCollectionHandle, OutputConsumer, RelationSchema, SerBatch,
+};
+use anyhow::{anyhow, Error as AnyError, Result as AnyResult};
+use apache_avro::{from_avro_datum, to_avro_datum, to_value as to_avro_value, Schema};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use serde_yaml::Value as YamlValue;
+use std::{
+    borrow::Cow,
+    cmp::min,
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use utoipa::ToSchema;
+
+/// Magic byte that starts every message in the Confluent wire format.
+const MAGIC_BYTE: u8 = 0;
+
+/// Length of the Confluent wire format header: magic byte followed by a
+/// 4-byte big-endian schema id.
+const HEADER_LEN: usize = 5;
+
+/// Schema registry request timeout.
+const REGISTRY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Delay before retrying a failed schema lookup for the first time.
+const LOOKUP_MIN_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Upper bound on the delay between retries of a failed schema lookup.
+/// The delay doubles after every failed attempt up to this value.
+const LOOKUP_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Avro format parser.
+pub struct AvroInputFormat;
+
+/// Avro parser configuration.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct AvroParserConfig {
+    /// Schema registry URL, e.g., `http://localhost:8081`.
+    ///
+    /// Exactly one of `registry_url` and `schema_dir` must be specified.
+    registry_url: Option<String>,
+
+    /// Directory that contains writer schemas.  The schema with id `<id>`
+    /// is read from file `<schema_dir>/<id>.avsc`.  Can be used instead of
+    /// a schema registry for testing.
+    schema_dir: Option<String>,
+
+    /// Representation of individual updates in the input stream.  Each
+    /// decoded Avro record is interpreted in the same way as a JSON value
+    /// by the JSON parser.
+    ///
+    /// Defaults to `raw`.
+    #[serde(default)]
+    update_format: JsonUpdateFormat,
+}
+
+impl InputFormat for AvroInputFormat {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("avro")
+    }
+
+    fn new_parser(
+        &self,
+        input_stream: &dyn DeCollectionHandle,
+        config: &YamlValue,
+    ) -> AnyResult<Box<dyn Parser>> {
+        let config = AvroParserConfig::deserialize(config)?;
+        let resolver = SchemaResolver::new(config.registry_url.clone(), config.schema_dir.clone())?;
+
+        Ok(Box::new(AvroParser::new(input_stream, config, resolver)) as Box<dyn Parser>)
+    }
+}
+
+/// Retrieves schemas by id from a schema registry or a local directory and
+/// caches them.
+///
+/// Failed lookups are cached too, so that a stream of messages with an
+/// unknown schema id doesn't turn into a request per message.  The lookup
+/// is retried after a delay that grows exponentially with the number of
+/// consecutive failures.
+#[derive(Clone)]
+struct SchemaResolver {
+    registry_url: Option<String>,
+    schema_dir: Option<String>,
+    schemas: BTreeMap<u32, Arc<Schema>>,
+    failures: BTreeMap<u32, LookupFailure>,
+}
+
+/// Cached failed schema lookup.
+#[derive(Clone)]
+struct LookupFailure {
+    /// Error returned by the last attempt.
+    error: String,
+    /// Delay between the last attempt and the next one.
+    backoff: Duration,
+    /// Don't retry the lookup before this time.
+    retry_at: Instant,
+}
+
+/// Schema registry response to the `GET /schemas/ids/{id}` request.
+#[derive(Deserialize)]
+struct RegistrySchema {
+    schema: String,
+}
+
+impl SchemaResolver {
+    fn new(registry_url: Option<String>, schema_dir: Option<String>) -> AnyResult<Self> {
+        if registry_url.is_some() == schema_dir.is_some() {
+            return Err(anyhow!(
+                "exactly one of 'registry_url' and 'schema_dir' must be specified"
+            ));
+        }
+
+        Ok(Self {
+            registry_url,
+            schema_dir,
+            schemas: BTreeMap::new(),
+            failures: BTreeMap::new(),
+        })
+    }
+
+    /// Lookup schema by id.
+    fn schema(&mut self, schema_id: u32) -> AnyResult<Arc<Schema>> {
+        if let Some(schema) = self.schemas.get(&schema_id) {
+            return Ok(schema.clone());
+        }
+
+        let now = Instant::now();
+        if let Some(failure) = self.failures.get(&schema_id) {
+            if now < failure.retry_at {
+                return Err(AnyError::msg(failure.error.clone()));
+            }
+        }
+
+        match self.fetch_schema(schema_id) {
+            Ok(schema) => {
+                self.failures.remove(&schema_id);
+                self.schemas.insert(schema_id, schema.clone());
+                Ok(schema)
+            }
+            Err(e) => {
+                let backoff = self
+                    .failures
+                    .get(&schema_id)
+                    .map(|failure| min(failure.backoff * 2, LOOKUP_MAX_BACKOFF))
+                    .unwrap_or(LOOKUP_MIN_BACKOFF);
+                self.failures.insert(
+                    schema_id,
+                    LookupFailure {
+                        error: e.to_string(),
+                        backoff,
+                        retry_at: now + backoff,
+                    },
+                );
+                Err(e)
+            }
+        }
+    }
+
+    /// Retrieve schema from the registry or the schema directory.
+    fn fetch_schema(&self, schema_id: u32) -> AnyResult<Arc<Schema>> {
+        let schema_str = if let Some(registry_url) = &self.registry_url {
+            let url = format!(
+                "{}/schemas/ids/{schema_id}",
+                registry_url.trim_end_matches('/')
+            );
+            let response = ureq::get(&url)
+                .timeout(REGISTRY_TIMEOUT)
+                .call()
+                .map_err(|e| AnyError::msg(format!("error retrieving schema from '{url}': {e}")))?
+                .into_string()?;
+            serde_json::from_str::<RegistrySchema>(&response)
+                .map_err(|e| {
+                    AnyError::msg(format!(
+                        "invalid schema registry response '{response}': {e}"
+                    ))
+                })?
+                .schema
+        } else {
+            let path =
+                Path::new(self.schema_dir.as_ref().unwrap()).join(format!("{schema_id}.avsc"));
+            fs::read_to_string(&path).map_err(|e| {
+                AnyError::msg(format!(
+                    "error reading schema file '{}': {e}",
+                    path.display()
+                ))
+            })?
+        };
+
+        let schema = Schema::parse_str(&schema_str)
+            .map_err(|e| AnyError::msg(format!("invalid Avro schema with id {schema_id}: {e}")))?;
+
+        Ok(Arc::new(schema))
+    }
+}
+
+struct AvroParser {
+    /// Input handle to push parsed data to.
+    input_stream: Box<dyn DeCollectionHandle>,
+
+    config: AvroParserConfig,
+
+    resolver: SchemaResolver,
+}
+
+impl AvroParser {
+    fn new(
+        input_stream: &dyn DeCollectionHandle,
+        config: AvroParserConfig,
+        resolver: SchemaResolver,
+    ) -> Self {
+        Self {
+            input_stream: input_stream.fork(),
+            config,
+            resolver,
+        }
+    }
+
+    /// Parse a single Confluent-framed Avro message.
+    fn parse_message(&mut self, data: &[u8]) -> AnyResult<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        if data.len() < HEADER_LEN || data[0] != MAGIC_BYTE {
+            return Err(anyhow!(
+                "invalid Avro message: expected a {HEADER_LEN}-byte header starting with magic byte {MAGIC_BYTE}"
+            ));
+        }
+
+        let schema_id = u32::from_be_bytes(data[1..HEADER_LEN].try_into().unwrap());
+        let schema = self.resolver.schema(schema_id)?;
+
+        let mut payload = &data[HEADER_LEN..];
+        let value = from_avro_datum(&schema, &mut payload, None).map_err(|e| {
+            AnyError::msg(format!(
+                "failed to decode Avro message with schema id {schema_id}: {e}"
+            ))
+        })?;
+        if !payload.is_empty() {
+            return Err(anyhow!(
+                "Avro message with schema id {schema_id} contains {} trailing bytes",
+                payload.len()
+            ));
+        }
+
+        let value = JsonValue::try_from(value)
+            .map_err(|e| AnyError::msg(format!("failed to convert Avro value to JSON: {e}")))?;
+
+        input_json_update(&mut *self.input_stream, self.config.update_format, &value)
+    }
+}
+
+impl Parser for AvroParser {
+    /// Parse a single Confluent-framed Avro message.
+    ///
+    /// Unlike text formats, Avro messages are not self-delimiting, so this
+    /// parser expects each buffer to contain exactly one complete message,
+    /// as produced by message-oriented transports such as Kafka.  Empty
+    /// buffers (e.g., Kafka tombstones) are ignored.
+    fn input(&mut self, data: &[u8]) -> (usize, Vec<ParseError>) {
+        match self.parse_message(data) {
+            Ok(num_records) => (num_records, Vec::new()),
+            Err(e) => (
+                0,
+                vec![ParseError::new(e.to_string(), None, Some(data.to_vec()))],
+            ),
+        }
+    }
+
+    fn eoi(&mut self) -> (usize, Vec<ParseError>) {
+        (0, Vec::new())
+    }
+
+    fn flush(&mut self) {
+        self.input_stream.flush();
+    }
+
+    fn clear(&mut self) {
+        self.input_stream.clear_buffer();
+    }
+
+    /// The parser is stateless: each buffer contains a complete message.
+    fn checkpoint(&self) -> AnyResult<Vec<u8>> {
+        Ok(Vec::new())
+    }
+
+    fn restore(&mut self, _state: &[u8]) -> AnyResult<()> {
+        Ok(())
+    }
+
+    fn fork(&self) -> Box<dyn Parser> {
+        Box::new(Self::new(
+            &*self.input_stream,
+            self.config.clone(),
+            self.resolver.clone(),
+        ))
+    }
+}
+
+/// Avro format encoder.
+pub struct AvroOutputFormat;
+
+/// Avro encoder configuration.
+#[derive(Deserialize, ToSchema)]
+pub struct AvroEncoderConfig {
+    /// Schema registry URL, e.g., `http://localhost:8081`.
+    ///
+    /// Exactly one of `registry_url` and `schema_dir` must be specified.
+    registry_url: Option<String>,
+
+    /// Directory that contains Avro schemas.  The schema with id `<id>`
+    /// is read from file `<schema_dir>/<id>.avsc`.
+    schema_dir: Option<String>,
+
+    /// Id of the schema used to encode output messages.  The schema id is
+    /// written to the header of each message.
+    schema_id: u32,
+
+    /// Representation of individual updates in the output stream.  The
+    /// schema must describe the corresponding envelope, e.g., a record with
+    /// `data` and `weight` fields for the `weighted` format.
+    ///
+    /// The `raw` format (default) encodes each record as is and fails on
+    /// deletions.
+    #[serde(default)]
+    update_format: JsonUpdateFormat,
+
+    /// Largest absolute weight of an output record in the `raw`,
+    /// `insert_delete` and `debezium` formats, which write a record with
+    /// weight `n` as `n` separate messages.  Records with larger weights are
+    /// reported as errors; use the `weighted` format to output them.
+    ///
+    /// Defaults to 10000.
+    #[serde(default = "default_max_weight")]
+    max_weight: u64,
+}
+
+impl OutputFormat for AvroOutputFormat {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("avro")
+    }
+
+    fn new_encoder(
+        &self,
+        config: &YamlValue,
+        _schema: Option<&RelationSchema>,
+        consumer: Box<dyn OutputConsumer>,
+    ) -> AnyResult<Box<dyn Encoder>> {
+        let config = AvroEncoderConfig::deserialize(config)?;
+        let mut resolver =
+            SchemaResolver::new(config.registry_url.clone(), config.schema_dir.clone())?;
+        let schema = resolver.schema(config.schema_id)?;
+
+        Ok(Box::new(AvroEncoder::new(consumer, config, schema)))
+    }
+}
+
+struct AvroEncoder {
+    /// Consumer to push serialized data to.
+    output_consumer: Box<dyn OutputConsumer>,
+
+    config: AvroEncoderConfig,
+
+    schema: Arc<Schema>,
+
+    buffer: Vec<u8>,
+}
+
+impl AvroEncoder {
+    fn new(
+        output_consumer: Box<dyn OutputConsumer>,
+        config: AvroEncoderConfig,
+        schema: Arc<Schema>,
+    ) -> Self {
+        Self {
+            output_consumer,
+            config,
+            schema,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Encode `update` as a Confluent-framed message in `self.buffer`.
+    fn encode_update<T>(&mut self, update: &T) -> AnyResult<()>
+    where
+        T: Serialize,
+    {
+        let value = to_avro_value(update)
+            .and_then(|value| value.resolve(&self.schema))
+            .map_err(|e| AnyError::msg(format!("failed to convert record to Avro: {e}")))?;
+        let datum = to_avro_datum(&self.schema, value)?;
+
+        self.buffer.clear();
+        self.buffer.push(MAGIC_BYTE);
+        self.buffer
+            .extend_from_slice(&self.config.schema_id.to_be_bytes());
+        self.buffer.extend_from_slice(&datum);
+
+        Ok(())
+    }
+}
+
+impl Encoder for AvroEncoder {
+    fn consumer(&mut self) -> &mut dyn OutputConsumer {
+        self.output_consumer.as_mut()
+    }
+
+    /// Push each update to the consumer as a separate message.
+    fn encode(&mut self, batches: &[Arc<dyn SerBatch>]) -> AnyResult<()> {
+        for batch in batches.iter() {
+            let mut cursor = batch.cursor();
+
+            while cursor.key_valid() {
+                let w = cursor.weight();
+                let record = cursor.key();
+
+                let repetitions = match self.config.update_format {
+                    JsonUpdateFormat::Raw => {
+                        if w < 0 {
+                            return Err(anyhow!(
+                                "'raw' update format cannot represent record deletions"
+                            ));
+                        }
+                        self.encode_update(&record)?;
+                        repetitions(w, self.config.max_weight)?
+                    }
+                    JsonUpdateFormat::InsertDelete => {
+                        self.encode_update(&InsDelUpdate::new(record, w))?;
+                        repetitions(w, self.config.max_weight)?
+                    }
+                    JsonUpdateFormat::Weighted => {
+                        self.encode_update(&WeightedUpdate::new(record, w))?;
+                        1
+                    }
+                    JsonUpdateFormat::Debezium => {
+                        self.encode_update(&DebeziumPayload::new(record, w))?;
+                        repetitions(w, self.config.max_weight)?
+                    }
+                };
+
+                for _ in 0..repetitions {
+                    self.output_consumer.push_buffer(&self.buffer);
+                }
+
+                cursor.step_key();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AvroInputFormat, AvroOutputFormat, SchemaResolver, LOOKUP_MIN_BACKOFF};
+    use crate::{
+        seroutput::SerBatchImpl,
+        test::{test_data, MockDeZSet, MockOutputConsumer, TestStruct},
+        InputFormat, OutputFormat, SerBatch,
+    };
+    use dbsp::{trace::Batch, OrdZSet};
+    use std::{
+        fs,
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread::{sleep, spawn},
+    };
+    use tempfile::tempdir;
+
+    const TEST_SCHEMA: &str = r#"{
+    "type": "record",
+    "name": "TestStruct",
+    "fields": [
+        {"name": "id", "type": "long"},
+        {"name": "b", "type": "boolean"},
+        {"name": "i", "type": ["null", "long"]},
+        {"name": "s", "type": "string"}
+    ]
+}"#;
+
+    #[test]
+    fn test_avro_roundtrip() {
+        let schema_dir = tempdir().unwrap();
+        fs::write(schema_dir.path().join("5.avsc"), TEST_SCHEMA).unwrap();
+        let schema_dir = schema_dir.path().to_str().unwrap();
+
+        let data = test_data();
+        let batch = OrdZSet::from_tuples((), data.iter().map(|v| (v.clone(), 1)).collect());
+        let batch = Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>;
+
+        // Encode the batch.
+        let consumer = MockOutputConsumer::default();
+        let mut encoder = AvroOutputFormat
+            .new_encoder(
+                &serde_yaml::from_str(&format!("schema_dir: {schema_dir:?}\nschema_id: 5"))
+                    .unwrap(),
+                None,
+                Box::new(consumer.clone()),
+            )
+            .unwrap();
+        encoder.encode(&[batch]).unwrap();
+
+        let messages = consumer.buffers();
+        assert_eq!(messages.len(), data.len());
+        for message in messages.iter() {
+            assert_eq!(message[0..5], [0, 0, 0, 0, 5]);
+        }
+
+        // Decode it back.
+        let zset = MockDeZSet::<TestStruct>::new();
+        let mut parser = AvroInputFormat
+            .new_parser(
+                &zset,
+                &serde_yaml::from_str(&format!("schema_dir: {schema_dir:?}")).unwrap(),
+            )
+            .unwrap();
+        for message in messages.iter() {
+            assert_eq!(parser.input(message), (1, Vec::new()));
+        }
+        parser.flush();
+
+        let expected = data.into_iter().map(|v| (v, true)).collect::<Vec<_>>();
+        assert_eq!(zset.state().flushed, expected);
+
+        // Unknown schema id.
+        let (num_records, errors) = parser.input(&[0, 0, 0, 0, 6, 2]);
+        assert_eq!(num_records, 0);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].invalid_bytes(), Some(&[0, 0, 0, 0, 6, 2][..]));
+
+        // Missing header.
+        assert_eq!(parser.input(&[1, 2, 3]).1.len(), 1);
+    }
+
+    #[test]
+    fn test_avro_raw_delete() {
+        let schema_dir = tempdir().unwrap();
+        fs::write(schema_dir.path().join("1.avsc"), TEST_SCHEMA).unwrap();
+
+        let batch = OrdZSet::from_tuples(
+            (),
+            vec![(
+                TestStruct {
+                    id: 1,
+                    b: true,
+                    i: None,
+                    s: "foo".to_string(),
+                },
+                -1,
+            )],
+        );
+        let batch = Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>;
+
+        let mut encoder = AvroOutputFormat
+            .new_encoder(
+                &serde_yaml::from_str(&format!(
+                    "schema_dir: {:?}\nschema_id: 1",
+                    schema_dir.path().to_str().unwrap()
+                ))
+                .unwrap(),
+                None,
+                Box::new(MockOutputConsumer::default()),
+            )
+            .unwrap();
+        assert!(encoder.encode(&[batch]).is_err());
+    }
+
+    /// Minimal schema registry that serves `TEST_SCHEMA` with id 5 and
+    /// responds with 404 to all other requests.  Returns registry URL and
+    /// request counter.
+    fn mock_registry() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let requests_clone = requests.clone();
+
+        spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                }
+                requests_clone.fetch_add(1, Ordering::AcqRel);
+
+                let (status, body) = if request_line.starts_with("GET /schemas/ids/5 ") {
+                    (
+                        "200 OK",
+                        serde_json::json!({ "schema": TEST_SCHEMA }).to_string(),
+                    )
+                } else {
+                    ("404 Not Found", r#"{"error_code":40403}"#.to_string())
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    #[test]
+    fn test_registry_lookup() {
+        let (url, requests) = mock_registry();
+        let mut resolver = SchemaResolver::new(Some(url), None).unwrap();
+
+        // Successful lookups are cached.
+        resolver.schema(5).unwrap();
+        resolver.schema(5).unwrap();
+        assert_eq!(requests.load(Ordering::Acquire), 1);
+
+        // So are failed ones, until the backoff expires.
+        let error = resolver.schema(6).unwrap_err().to_string();
+        assert_eq!(resolver.schema(6).unwrap_err().to_string(), error);
+        assert_eq!(requests.load(Ordering::Acquire), 2);
+
+        sleep(LOOKUP_MIN_BACKOFF);
+        resolver.schema(6).unwrap_err();
+        assert_eq!(requests.load(Ordering::Acquire), 3);
+        assert_eq!(
+            resolver.failures.get(&6).unwrap().backoff,
+            LOOKUP_MIN_BACKOFF * 2
+        );
+
+        // The second failure doubled the delay.
+        sleep(LOOKUP_MIN_BACKOFF);
+        resolver.schema(6).unwrap_err();
+        assert_eq!(requests.load(Ordering::Acquire), 3);
+    }
+}
@@ -1,10 +1,12 @@
 use crate::{
-    format::{Encoder, InputFormat, OutputFormat, Parser},
-    DeCollectionHandle, OutputConsumer, SerBatch,
+    format::{
+        default_max_weight, repetitions, Encoder, InputFormat, OutputFormat, ParseError, Parser,
+    },
+    DeCollectionHandle, OutputConsumer, RelationSchema, SerBatch, Step,
 };
 use anyhow::{Error as AnyError, Result as AnyResult};
 use csv::{
-    byte_record_deserializer, Reader as CsvReader, ReaderBuilder as CsvReaderBuilder,
+    byte_record_deserializer, ByteRecord, Position, ReaderBuilder as CsvReaderBuilder,
     WriterBuilder as CsvWriterBuilder,
 };
 use erased_serde::Deserializer as ErasedDeserializer;
@@ -16,8 +18,82 @@ use utoipa::ToSchema;
 /// CSV format parser.
 pub struct CsvInputFormat;
 
-#[derive(Deserialize, ToSchema)]
-pub struct CsvParserConfig;
+const fn default_delimiter() -> char {
+    ','
+}
+
+const fn default_quote() -> char {
+    '"'
+}
+
+/// CSV parser configuration.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct CsvParserConfig {
+    /// Field delimiter.  Must be an ASCII character.
+    ///
+    /// Defaults to `,`.
+    #[serde(default = "default_delimiter")]
+    delimiter: char,
+
+    /// Quote character.  Must be an ASCII character.
+    ///
+    /// Defaults to `"`.
+    #[serde(default = "default_quote")]
+    quote: char,
+
+    /// Escape character used for quotes inside quoted fields.  Must be an
+    /// ASCII character.
+    ///
+    /// When not specified, quotes are escaped by doubling them.
+    escape: Option<char>,
+
+    /// Set to `true` if the first line of the input stream is a header row
+    /// that must be skipped.  When the input consists of several
+    /// self-contained chunks, e.g., files ingested by the `directory`
+    /// transport, the first line of each chunk is skipped.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    headers: bool,
+
+    /// String that represents a missing value, e.g., `NULL`.  Fields that
+    /// match this string exactly are parsed as empty fields, which
+    /// deserialize to `None` for optional columns.
+    null_value: Option<String>,
+
+    /// Set to `true` if the last field of each record contains an integer
+    /// weight.  Records with positive weights are inserted into the
+    /// collection, records with negative weights are deleted from it.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    weighted: bool,
+
+    /// Lines that start with this character are skipped as comments, e.g.,
+    /// the `# step <N>` markers written by the CSV encoder with
+    /// `step_markers` enabled.  Must be an ASCII character.
+    ///
+    /// When not specified, no lines are treated as comments.
+    comment: Option<char>,
+
+    /// Allow records with a varying number of fields.
+    ///
+    /// When `false`, records whose length differs from the first record of
+    /// the input stream are reported as parse errors.  Defaults to `false`.
+    #[serde(default)]
+    flexible: bool,
+}
+
+/// Convert a configuration character to a byte.
+fn ascii_char(name: &str, c: char) -> AnyResult<u8> {
+    if c.is_ascii() {
+        Ok(c as u8)
+    } else {
+        Err(AnyError::msg(format!(
+            "CSV {name} must be an ASCII character, found '{c}'"
+        )))
+    }
+}
 
 impl InputFormat for CsvInputFormat {
     fn name(&self) -> Cow<'static, str> {
@@ -27,9 +103,10 @@ impl InputFormat for CsvInputFormat {
     fn new_parser(
         &self,
         input_stream: &dyn DeCollectionHandle,
-        _config: &YamlValue,
+        config: &YamlValue,
     ) -> AnyResult<Box<dyn Parser>> {
-        Ok(Box::new(CsvParser::new(input_stream)) as Box<dyn Parser>)
+        let config = CsvParserConfig::deserialize(config)?;
+        Ok(Box::new(CsvParser::new(input_stream, config)?) as Box<dyn Parser>)
     }
 }
 
@@ -37,6 +114,8 @@ struct CsvParser {
     /// Input handle to push parsed data to.
     input_stream: Box<dyn DeCollectionHandle>,
 
+    config: CsvParserConfig,
+
     /// Since we cannot assume that the input buffer ends on line end,
     /// we save the "leftover" part of the buffer after the last new-line
     /// character and prepend it to the next input buffer.
@@ -45,43 +124,229 @@ struct CsvParser {
     /// Builder used to create a new CSV reader for each received data
     /// buffer.
     builder: CsvReaderBuilder,
+
+    /// Number of complete lines processed so far.  Used to report line
+    /// numbers in parse errors.
+    num_lines: u64,
+
+    /// Number of bytes processed so far, not including `leftover`.  Used to
+    /// report offsets of invalid records in parse errors.
+    num_bytes: u64,
+
+    /// The next record is the header row and must be skipped.
+    skip_header: bool,
+
+    /// Number of fields in the first record of the stream, which all other
+    /// records must match unless `config.flexible` is `true`.
+    num_fields: Option<usize>,
 }
 
 impl CsvParser {
-    fn new(input_stream: &dyn DeCollectionHandle) -> Self {
+    fn new(input_stream: &dyn DeCollectionHandle, config: CsvParserConfig) -> AnyResult<Self> {
         let mut builder = CsvReaderBuilder::new();
-        builder.has_headers(false);
+        builder
+            .has_headers(false)
+            .delimiter(ascii_char("delimiter", config.delimiter)?)
+            .quote(ascii_char("quote", config.quote)?)
+            // The number of fields is checked by the parser across input
+            // buffers (see `num_fields`).
+            .flexible(true);
+        if let Some(escape) = config.escape {
+            builder
+                .escape(Some(ascii_char("escape", escape)?))
+                .double_quote(false);
+        }
+        if let Some(comment) = config.comment {
+            builder.comment(Some(ascii_char("comment", comment)?));
+        }
 
-        Self {
+        Ok(Self {
             input_stream: input_stream.fork(),
+            skip_header: config.headers,
+            config,
             leftover: Vec::new(),
             builder,
-        }
+            num_lines: 0,
+            num_bytes: 0,
+            num_fields: None,
+        })
     }
 
-    fn parse_from_reader<R>(
-        input_stream: &mut dyn DeCollectionHandle,
-        mut reader: CsvReader<R>,
-    ) -> AnyResult<usize>
-    where
-        R: Read,
-    {
+    /// Parse all complete records in `prefix` followed by `data`.
+    fn parse(&mut self, prefix: &[u8], data: &[u8]) -> (usize, Vec<ParseError>) {
+        let mut reader = self.builder.from_reader(Read::chain(prefix, data));
         let mut num_records = 0;
+        let mut errors = Vec::new();
+
+        loop {
+            let mut record = ByteRecord::new();
+            let position = reader.position().clone();
+            let result = reader.read_byte_record(&mut record);
+            let end = reader.position().byte();
+
+            let result = match result {
+                Ok(false) => break,
+                Ok(true) => match self.check_num_fields(&record) {
+                    Err(description) => Err((record.position().cloned(), description)),
+                    Ok(()) if self.skip_header => {
+                        self.skip_header = false;
+                        continue;
+                    }
+                    Ok(()) => self.input_record(record),
+                },
+                Err(e) => Err((
+                    e.position().cloned(),
+                    format!("failed to parse csv record: {e}"),
+                )),
+            };
+
+            match result {
+                Ok(n) => num_records += n,
+                Err((start, description)) => errors.push(self.parse_error(
+                    description,
+                    &start.unwrap_or(position),
+                    end,
+                    prefix,
+                    data,
+                )),
+            }
+        }
+
+        self.num_bytes += (prefix.len() + data.len()) as u64;
+        (num_records, errors)
+    }
 
-        for record in reader.byte_records() {
-            let record = record?;
+    /// Build a parse error for the record located between `start` and byte
+    /// offset `end` in `prefix` followed by `data`.
+    fn parse_error(
+        &self,
+        description: String,
+        start: &Position,
+        end: u64,
+        prefix: &[u8],
+        data: &[u8],
+    ) -> ParseError {
+        let (start_byte, end) = (start.byte() as usize, end as usize);
+        let mut raw = Vec::with_capacity(end - start_byte);
+        if start_byte < prefix.len() {
+            raw.extend_from_slice(&prefix[start_byte..end.min(prefix.len())]);
+        }
+        if end > prefix.len() {
+            raw.extend_from_slice(
+                &data[start_byte.max(prefix.len()) - prefix.len()..end - prefix.len()],
+            );
+        }
+
+        // Strip blank lines preceding the record and the record terminator.
+        let leading = raw
+            .iter()
+            .position(|&c| c != b'\n' && c != b'\r')
+            .unwrap_or(raw.len());
+        let skipped_lines = Self::count_lines(&raw[..leading]);
+        raw.drain(..leading);
+        while matches!(raw.last(), Some(b'\n' | b'\r')) {
+            raw.pop();
+        }
 
-            let mut deserializer = byte_record_deserializer(&record, None);
-            let mut deserializer = <dyn ErasedDeserializer>::erase(&mut deserializer);
-            input_stream.insert(&mut deserializer).map_err(|e| {
-                AnyError::msg(format!(
+        ParseError::new(
+            description,
+            Some(self.num_lines + start.line() + skipped_lines),
+            Some(raw),
+        )
+        .with_offset(self.num_bytes + start_byte as u64 + leading as u64)
+    }
+
+    /// Check that `record` has the same number of fields as the first record
+    /// of the stream, unless `config.flexible` is `true`.
+    fn check_num_fields(&mut self, record: &ByteRecord) -> Result<(), String> {
+        if self.config.flexible {
+            return Ok(());
+        }
+
+        match self.num_fields {
+            None => {
+                self.num_fields = Some(record.len());
+                Ok(())
+            }
+            Some(num_fields) if num_fields != record.len() => Err(format!(
+                "failed to parse csv record: found record with {} fields, but the previous record has {num_fields} fields",
+                record.len()
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Push a single parsed record to the input handle.
+    ///
+    /// On error, returns the position of the record and error description.
+    fn input_record(
+        &mut self,
+        mut record: ByteRecord,
+    ) -> Result<usize, (Option<Position>, String)> {
+        let position = record.position().cloned();
+        let error = |description: String| (position.clone(), description);
+
+        let weight = if self.config.weighted {
+            let weight = record
+                .len()
+                .checked_sub(1)
+                .and_then(|last| record.get(last))
+                .and_then(|weight| std::str::from_utf8(weight).ok())
+                .and_then(|weight| weight.trim().parse::<i64>().ok())
+                .ok_or_else(|| {
+                    error(
+                        "last field of a weighted csv record must be an integer weight".to_string(),
+                    )
+                })?;
+            record.truncate(record.len() - 1);
+            weight
+        } else {
+            1
+        };
+
+        if let Some(null) = &self.config.null_value {
+            if record.iter().any(|field| field == null.as_bytes()) {
+                record = ByteRecord::from(
+                    record
+                        .iter()
+                        .map(|field| {
+                            if field == null.as_bytes() {
+                                &[][..]
+                            } else {
+                                field
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                );
+            }
+        }
+
+        // Coerce fields to column types.
+        if let Some(schema) = self.input_stream.schema() {
+            record = {
+                let fields = record
+                    .iter()
+                    .map(std::str::from_utf8)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| error(format!("csv record is not valid UTF-8: {e}")))?;
+                let fields = schema
+                    .coerce_fields(&fields)
+                    .map_err(|e| error(format!("invalid csv record: {e}")))?;
+                ByteRecord::from(fields.iter().map(|f| f.as_bytes()).collect::<Vec<_>>())
+            };
+        }
+
+        let mut deserializer = byte_record_deserializer(&record, None);
+        let mut deserializer = <dyn ErasedDeserializer>::erase(&mut deserializer);
+        self.input_stream
+            .update_weighted(&mut deserializer, weight)
+            .map_err(|e| {
+                error(format!(
                     "failed to deserialize csv record '{record:?}': {e}"
                 ))
             })?;
-            num_records += 1;
-        }
 
-        Ok(num_records)
+        Ok((weight != 0) as usize)
     }
 
     /// Returns the index of the first character following the last newline
@@ -96,49 +361,47 @@ impl CsvParser {
 
         data_len - index
     }
+
+    fn count_lines(data: &[u8]) -> u64 {
+        data.iter().filter(|&&x| x == b'\n').count() as u64
+    }
 }
 
 impl Parser for CsvParser {
-    fn input(&mut self, data: &[u8]) -> AnyResult<usize> {
-        // println!("input {} bytes:\n{}\nself.leftover:\n{}", data.len(),
-        //    std::str::from_utf8(data).map(|s| s.to_string()).unwrap_or_else(|e|
-        // format!("invalid csv: {e}")),    std::str::from_utf8(&self.leftover).
-        // map(|s| s.to_string()).unwrap_or_else(|e| format!("invalid csv: {e}")));
-
+    fn input(&mut self, data: &[u8]) -> (usize, Vec<ParseError>) {
         let leftover = Self::split_on_newline(data);
 
-        // println!("leftover: {leftover}");
-
         if leftover == 0 {
             // `data` doesn't contain a new-line character; append it to
             // the `leftover` buffer so it gets processed with the next input
             // buffer.
             self.leftover.extend_from_slice(data);
-            Ok(0)
+            (0, Vec::new())
         } else {
-            let reader = self
-                .builder
-                .from_reader(Read::chain(&*self.leftover, &data[0..leftover]));
-
-            let res = Self::parse_from_reader(&mut *self.input_stream, reader);
-            // println!("parse returned: {res:?}");
+            let mut leftover_buf = take(&mut self.leftover);
+            let res = self.parse(&leftover_buf, &data[0..leftover]);
+            self.num_lines += Self::count_lines(&data[0..leftover]);
 
-            self.leftover.clear();
-            self.leftover.extend_from_slice(&data[leftover..]);
+            leftover_buf.clear();
+            leftover_buf.extend_from_slice(&data[leftover..]);
+            self.leftover = leftover_buf;
 
             res
         }
     }
 
-    fn eoi(&mut self) -> AnyResult<usize> {
-        if self.leftover.is_empty() {
-            return Ok(0);
-        }
-
+    fn eoi(&mut self) -> (usize, Vec<ParseError>) {
         // Try to interpret the leftover chunk as a complete CSV line.
-        let reader = self.builder.from_reader(&*self.leftover);
+        let leftover = take(&mut self.leftover);
+        let result = if leftover.is_empty() {
+            (0, Vec::new())
+        } else {
+            self.parse(&leftover, &[])
+        };
 
-        Self::parse_from_reader(&mut *self.input_stream, reader)
+        // The next chunk of input, if any, starts with its own header row.
+        self.skip_header = self.config.headers;
+        result
     }
 
     fn flush(&mut self) {
@@ -149,8 +412,36 @@ impl Parser for CsvParser {
         self.input_stream.clear_buffer();
     }
 
+    fn checkpoint(&self) -> AnyResult<Vec<u8>> {
+        Ok(bincode::encode_to_vec(
+            (
+                self.leftover.as_slice(),
+                self.num_lines,
+                self.num_bytes,
+                self.skip_header,
+                self.num_fields,
+            ),
+            bincode::config::standard(),
+        )?)
+    }
+
+    fn restore(&mut self, state: &[u8]) -> AnyResult<()> {
+        (
+            (
+                self.leftover,
+                self.num_lines,
+                self.num_bytes,
+                self.skip_header,
+                self.num_fields,
+            ),
+            _,
+        ) = bincode::decode_from_slice(state, bincode::config::standard())?;
+        Ok(())
+    }
+
     fn fork(&self) -> Box<dyn Parser> {
-        Box::new(Self::new(&*self.input_stream))
+        // The configuration has been validated when creating `self`.
+        Box::new(Self::new(&*self.input_stream, self.config.clone()).unwrap())
     }
 }
 
@@ -161,10 +452,68 @@ const fn default_buffer_size_records() -> usize {
     10_000
 }
 
+const fn default_weighted() -> bool {
+    true
+}
+
+/// CSV encoder configuration.
 #[derive(Deserialize, ToSchema)]
 pub struct CsvEncoderConfig {
+    /// Maximal number of records in a single buffer sent to the transport
+    /// endpoint.
     #[serde(default = "default_buffer_size_records")]
     buffer_size_records: usize,
+
+    /// Field delimiter.  Must be an ASCII character.
+    ///
+    /// Defaults to `,`.
+    #[serde(default = "default_delimiter")]
+    delimiter: char,
+
+    /// Quote character.  Must be an ASCII character.
+    ///
+    /// Defaults to `"`.
+    #[serde(default = "default_quote")]
+    quote: char,
+
+    /// Escape character used for quotes inside quoted fields.  Must be an
+    /// ASCII character.
+    ///
+    /// When not specified, quotes are escaped by doubling them.
+    escape: Option<char>,
+
+    /// Write a header row with column names before the first record of each
+    /// output file.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    headers: bool,
+
+    /// Write the weight of each record as its last field.
+    ///
+    /// When `false`, a record with weight `n` is written `n` times, and
+    /// deletions (negative weights) are reported as errors.  Defaults to
+    /// `true`.
+    #[serde(default = "default_weighted")]
+    weighted: bool,
+
+    /// Largest weight of an output record when `weighted` is `false`.
+    /// Records with larger weights are reported as errors.
+    ///
+    /// Defaults to 10000.
+    #[serde(default = "default_max_weight")]
+    max_weight: u64,
+
+    /// Write a `# step <N>` marker line after the records produced by each
+    /// circuit step.
+    ///
+    /// Markers allow consumers to tell where the output of one step ends and
+    /// the next begins, including steps that produced no records when the
+    /// output endpoint is configured to send heartbeats.  Markers are not
+    /// valid CSV records: configure the CSV parser with `comment: '#'` to
+    /// read output written with markers.  Defaults to `false`.
+    #[serde(default)]
+    step_markers: bool,
 }
 
 impl OutputFormat for CsvOutputFormat {
@@ -175,11 +524,12 @@ impl OutputFormat for CsvOutputFormat {
     fn new_encoder(
         &self,
         config: &YamlValue,
+        _schema: Option<&RelationSchema>,
         consumer: Box<dyn OutputConsumer>,
     ) -> AnyResult<Box<dyn Encoder>> {
         let config = CsvEncoderConfig::deserialize(config)?;
 
-        Ok(Box::new(CsvEncoder::new(consumer, config)))
+        Ok(Box::new(CsvEncoder::new(consumer, config)?))
     }
 }
 
@@ -191,28 +541,100 @@ struct CsvEncoder {
     /// buffer.
     builder: CsvWriterBuilder,
 
+    /// Builder used to generate the header row.
+    header_builder: CsvWriterBuilder,
+
     config: CsvEncoderConfig,
 
     buffer: Vec<u8>,
+
+    /// The header row has been written to the current output file.
+    header_written: bool,
 }
 
 impl CsvEncoder {
-    fn new(output_consumer: Box<dyn OutputConsumer>, config: CsvEncoderConfig) -> Self {
-        let mut builder = CsvWriterBuilder::new();
-        builder.has_headers(false);
+    fn new(output_consumer: Box<dyn OutputConsumer>, config: CsvEncoderConfig) -> AnyResult<Self> {
+        let delimiter = ascii_char("delimiter", config.delimiter)?;
+        let quote = ascii_char("quote", config.quote)?;
+        let escape = config
+            .escape
+            .map(|escape| ascii_char("escape", escape))
+            .transpose()?;
+
+        let make_builder = |has_headers| {
+            let mut builder = CsvWriterBuilder::new();
+            builder
+                .has_headers(has_headers)
+                .delimiter(delimiter)
+                .quote(quote);
+            if let Some(escape) = escape {
+                builder.escape(escape).double_quote(false);
+            }
+            builder
+        };
 
-        Self {
+        Ok(Self {
             output_consumer,
-            builder,
+            builder: make_builder(false),
+            header_builder: make_builder(true),
             config,
             buffer: Vec::new(),
+            header_written: false,
+        })
+    }
+
+    /// Generate the header row from the first record in `batches`.
+    ///
+    /// Returns `None` if `batches` are empty.
+    fn header(&self, batches: &[Arc<dyn SerBatch>]) -> AnyResult<Option<Vec<u8>>> {
+        let cursor = match batches
+            .iter()
+            .map(|batch| batch.cursor())
+            .find(|cursor| cursor.key_valid())
+        {
+            Some(cursor) => cursor,
+            None => return Ok(None),
+        };
+
+        // Serialize the record with headers enabled and keep the first line
+        // of the output, which contains column names.
+        let mut writer = self.header_builder.from_writer(Vec::new());
+        writer.serialize(cursor.key())?;
+        let mut header = writer.into_inner()?;
+        let header_len = header
+            .iter()
+            .position(|&c| c == b'\n')
+            .unwrap_or(header.len());
+        header.truncate(header_len);
+
+        if self.config.weighted {
+            header.push(self.config.delimiter as u8);
+            header.extend_from_slice(b"weight");
         }
+        header.push(b'\n');
+
+        Ok(Some(header))
     }
 }
 
 impl Encoder for CsvEncoder {
+    fn consumer(&mut self) -> &mut dyn OutputConsumer {
+        self.output_consumer.as_mut()
+    }
+
     fn encode(&mut self, batches: &[Arc<dyn SerBatch>]) -> AnyResult<()> {
-        let buffer = take(&mut self.buffer);
+        let mut buffer = take(&mut self.buffer);
+        if self.output_consumer.at_file_start() {
+            // The endpoint started a new file: repeat the header row.
+            self.header_written = false;
+        }
+        if self.config.headers && !self.header_written {
+            if let Some(header) = self.header(batches)? {
+                buffer.extend_from_slice(&header);
+                self.header_written = true;
+            }
+        }
+
         let mut writer = self.builder.from_writer(buffer);
         let mut num_records = 0;
 
@@ -221,12 +643,23 @@ impl Encoder for CsvEncoder {
 
             while cursor.key_valid() {
                 let w = cursor.weight();
-                writer.serialize((cursor.key(), w))?;
-                num_records += 1;
+                if self.config.weighted {
+                    writer.serialize((cursor.key(), w))?;
+                    num_records += 1;
+                } else if w < 0 {
+                    return Err(AnyError::msg(
+                        "unweighted CSV output cannot represent record deletions",
+                    ));
+                } else {
+                    let repetitions = repetitions(w, self.config.max_weight)?;
+                    for _ in 0..repetitions {
+                        writer.serialize(cursor.key())?;
+                    }
+                    num_records += repetitions as usize;
+                }
 
                 if num_records >= self.config.buffer_size_records {
                     let mut buffer = writer.into_inner()?;
-                    // println!("push_buffer {}", std::str::from_utf8(&buffer).unwrap());
                     self.output_consumer.push_buffer(&buffer);
                     buffer.clear();
                     num_records = 0;
@@ -248,4 +681,316 @@ impl Encoder for CsvEncoder {
 
         Ok(())
     }
+
+    fn encode_step(&mut self, step: Step, batches: &[Arc<dyn SerBatch>]) -> AnyResult<()> {
+        self.output_consumer.batch_start(step);
+        self.output_consumer
+            .batch_records(batches.iter().map(|batch| batch.len()).sum());
+        match self.encode(batches) {
+            Ok(()) => {
+                if self.config.step_markers {
+                    self.output_consumer
+                        .push_buffer(format!("# step {step}\n").as_bytes());
+                }
+                self.output_consumer.batch_end();
+                Ok(())
+            }
+            Err(e) => {
+                self.output_consumer.batch_abort();
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CsvInputFormat, CsvOutputFormat};
+    use crate::{
+        schema::SchemaHandle,
+        seroutput::SerBatchImpl,
+        test::{test_data, MockDeZSet, MockOutputConsumer, TestStruct},
+        ColumnSchema, ColumnType, InputFormat, OutputConsumer, OutputFormat, RelationSchema,
+        SerBatch, Step,
+    };
+    use dbsp::{trace::Batch, OrdZSet};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_config() {
+        let data = test_data();
+        let input = "id;b;i;s;weight\n1;true;10;foo;1\n2;false;NULL;bar;-1";
+
+        for split in 0..input.len() {
+            let zset = MockDeZSet::<TestStruct>::new();
+            let mut parser = CsvInputFormat
+                .new_parser(
+                    &zset,
+                    &serde_yaml::from_str(
+                        "delimiter: ';'\nheaders: true\nnull_value: \"NULL\"\nweighted: true",
+                    )
+                    .unwrap(),
+                )
+                .unwrap();
+
+            assert_eq!(parser.input(input[..split].as_bytes()).1, Vec::new());
+            assert_eq!(parser.input(input[split..].as_bytes()).1, Vec::new());
+            assert_eq!(parser.eoi().1, Vec::new());
+            parser.flush();
+
+            assert_eq!(
+                zset.state().flushed,
+                vec![(data[0].clone(), true), (data[1].clone(), false)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let data = test_data();
+        let zset = MockDeZSet::<TestStruct>::new();
+        let mut parser = CsvInputFormat
+            .new_parser(&zset, &serde_yaml::Value::Null)
+            .unwrap();
+
+        // Invalid records are skipped and reported with their line numbers;
+        // valid records are still ingested.
+        let (num_records, errors) = parser.input(b"1,true,10,foo\n");
+        assert_eq!((num_records, errors), (1, Vec::new()));
+
+        let (num_records, errors) = parser.input(b"x,true,10,foo\n2,false,,bar\n");
+        assert_eq!(num_records, 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line(), Some(2));
+        assert_eq!(errors[0].invalid_bytes(), Some(&b"x,true,10,foo"[..]));
+        assert_eq!(errors[0].offset(), Some(14));
+
+        // Invalid record split across input buffers.
+        assert_eq!(parser.input(b"3,tr"), (0, Vec::new()));
+        let (num_records, errors) = parser.input(b"ue,1\n");
+        assert_eq!(num_records, 0);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line(), Some(4));
+        assert_eq!(errors[0].invalid_bytes(), Some(&b"3,true,1"[..]));
+        assert_eq!(errors[0].offset(), Some(41));
+
+        parser.flush();
+        assert_eq!(
+            zset.state().flushed,
+            vec![(data[0].clone(), true), (data[1].clone(), true)]
+        );
+    }
+
+    #[test]
+    fn test_chunks() {
+        let data = test_data();
+        let zset = MockDeZSet::<TestStruct>::new();
+        let mut parser = CsvInputFormat
+            .new_parser(&zset, &serde_yaml::from_str("headers: true").unwrap())
+            .unwrap();
+
+        // Each chunk starts with a header row.
+        assert_eq!(parser.input(b"id,b,i,s\n1,true,10,foo\n"), (1, Vec::new()));
+        assert_eq!(parser.eoi(), (0, Vec::new()));
+        assert_eq!(parser.input(b"id,b,i,s\n2,false,,bar"), (0, Vec::new()));
+        assert_eq!(parser.eoi(), (1, Vec::new()));
+
+        // The number of fields is checked across input buffers and chunks.
+        let (num_records, errors) = parser.input(b"id,b,i,s\n2,false,,bar,x\n");
+        assert_eq!(num_records, 0);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line(), Some(5));
+        assert_eq!(errors[0].invalid_bytes(), Some(&b"2,false,,bar,x"[..]));
+        assert!(errors[0]
+            .to_string()
+            .contains("found record with 5 fields, but the previous record has 4 fields"));
+
+        parser.flush();
+        assert_eq!(
+            zset.state().flushed,
+            vec![(data[0].clone(), true), (data[1].clone(), true)]
+        );
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let data = test_data();
+        let config = serde_yaml::from_str("headers: true").unwrap();
+        let zset = MockDeZSet::<TestStruct>::new();
+        let mut parser = CsvInputFormat.new_parser(&zset, &config).unwrap();
+
+        // Checkpoint the parser in the middle of the header and in the
+        // middle of a record.
+        assert_eq!(parser.input(b"id,b,").1, Vec::new());
+        let state1 = parser.checkpoint().unwrap();
+        assert_eq!(parser.input(b"i,s\n1,true,").1, Vec::new());
+        let state2 = parser.checkpoint().unwrap();
+
+        let mut parser = CsvInputFormat.new_parser(&zset, &config).unwrap();
+        parser.restore(&state1).unwrap();
+        assert_eq!(parser.input(b"i,s\n1,true,10,foo\n"), (1, Vec::new()));
+
+        let mut parser = CsvInputFormat.new_parser(&zset, &config).unwrap();
+        parser.restore(&state2).unwrap();
+        assert_eq!(parser.input(b"10,foo\nx\n").1.len(), 1);
+        parser.flush();
+
+        assert_eq!(
+            zset.state().flushed,
+            vec![(data[0].clone(), true), (data[0].clone(), true)]
+        );
+    }
+
+    #[test]
+    fn test_schema_coercion() {
+        let data = test_data();
+        let zset = MockDeZSet::<TestStruct>::new();
+        let schema = RelationSchema::new(vec![
+            ColumnSchema::new("id", ColumnType::Integer, false),
+            ColumnSchema::new("b", ColumnType::Boolean, false),
+            ColumnSchema::new("i", ColumnType::Integer, true),
+            ColumnSchema::new("s", ColumnType::String, false),
+        ]);
+        let handle = SchemaHandle::new(Box::new(zset.clone()), Arc::new(schema));
+        let mut parser = CsvInputFormat
+            .new_parser(&handle, &serde_yaml::Value::Null)
+            .unwrap();
+
+        let (num_records, errors) = parser.input(b"1.0,yes,10,foo\nx,t,,bar\n2,F, ,bar\n");
+        assert_eq!(num_records, 2);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line(), Some(2));
+        assert!(errors[0]
+            .to_string()
+            .contains("column 'id': cannot convert 'x' to integer"));
+
+        parser.flush();
+        assert_eq!(
+            zset.state().flushed,
+            vec![(data[0].clone(), true), (data[1].clone(), true)]
+        );
+    }
+
+    fn encode(config: &str, batch: &Arc<dyn SerBatch>) -> anyhow::Result<String> {
+        let consumer = MockOutputConsumer::default();
+        let mut encoder = CsvOutputFormat
+            .new_encoder(
+                &serde_yaml::from_str(config).unwrap(),
+                None,
+                Box::new(consumer.clone()),
+            )
+            .unwrap();
+        encoder.encode(&[batch.clone()])?;
+
+        Ok(String::from_utf8(consumer.concat()).unwrap())
+    }
+
+    #[test]
+    fn test_encoder() {
+        let data = test_data();
+
+        let batch = OrdZSet::from_tuples((), vec![(data[0].clone(), 1), (data[1].clone(), 2)]);
+        let batch = Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>;
+
+        assert_eq!(
+            encode("{}", &batch).unwrap(),
+            "1,true,10,foo,1\n2,false,,bar,2\n"
+        );
+        assert_eq!(
+            encode("delimiter: '|'\nheaders: true\nweighted: false", &batch).unwrap(),
+            "id|b|i|s\n1|true|10|foo\n2|false||bar\n2|false||bar\n"
+        );
+
+        let batch = OrdZSet::from_tuples((), vec![(data[1].clone(), -1)]);
+        let batch = Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>;
+        assert!(encode("weighted: false", &batch).is_err());
+    }
+
+    #[test]
+    fn test_step_markers() {
+        let data = test_data();
+
+        let batch = OrdZSet::from_tuples((), vec![(data[0].clone(), 1)]);
+        let batch = Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>;
+
+        let consumer = MockOutputConsumer::default();
+        let mut encoder = CsvOutputFormat
+            .new_encoder(
+                &serde_yaml::from_str("step_markers: true").unwrap(),
+                None,
+                Box::new(consumer.clone()),
+            )
+            .unwrap();
+        encoder.encode_step(0, &[batch]).unwrap();
+        // Empty heartbeat step.
+        encoder.encode_step(1, &[]).unwrap();
+
+        let output = String::from_utf8(consumer.concat()).unwrap();
+        assert_eq!(output, "1,true,10,foo,1\n# step 0\n# step 1\n");
+
+        // The parser skips markers as comments.
+        let zset = MockDeZSet::<TestStruct>::new();
+        let mut parser = CsvInputFormat
+            .new_parser(
+                &zset,
+                &serde_yaml::from_str("weighted: true\ncomment: '#'").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(parser.input(output.as_bytes()), (1, Vec::new()));
+        parser.flush();
+        assert_eq!(zset.state().flushed, vec![(data[0].clone(), true)]);
+    }
+
+    /// Output consumer that writes the output of each step to a new file.
+    #[derive(Clone, Default)]
+    struct FilePerStepConsumer(Arc<Mutex<Vec<Vec<u8>>>>);
+
+    impl OutputConsumer for FilePerStepConsumer {
+        fn batch_start(&mut self, _step: Step) {
+            self.0.lock().unwrap().push(Vec::new());
+        }
+
+        fn push_buffer(&mut self, buffer: &[u8]) {
+            self.0
+                .lock()
+                .unwrap()
+                .last_mut()
+                .unwrap()
+                .extend_from_slice(buffer);
+        }
+
+        fn at_file_start(&self) -> bool {
+            self.0.lock().unwrap().last().unwrap().is_empty()
+        }
+
+        fn batch_end(&mut self) {}
+    }
+
+    #[test]
+    fn test_header_per_file() {
+        let data = test_data();
+        let consumer = FilePerStepConsumer::default();
+        let mut encoder = CsvOutputFormat
+            .new_encoder(
+                &serde_yaml::from_str("headers: true").unwrap(),
+                None,
+                Box::new(consumer.clone()),
+            )
+            .unwrap();
+
+        for (step, record) in data.iter().enumerate() {
+            let batch = OrdZSet::from_tuples((), vec![(record.clone(), 1)]);
+            let batch = Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>;
+            encoder.encode_step(step as Step, &[batch]).unwrap();
+        }
+
+        let files = consumer.0.lock().unwrap().clone();
+        assert_eq!(
+            files,
+            vec![
+                b"id,b,i,s,weight\n1,true,10,foo,1\n".to_vec(),
+                b"id,b,i,s,weight\n2,false,,bar,1\n".to_vec()
+            ]
+        );
+    }
 }
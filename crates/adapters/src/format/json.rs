@@ -0,0 +1,1089 @@
+use crate::{
+    format::{
+        default_max_weight, repetitions, Encoder, InputFormat, OutputFormat, ParseError, Parser,
+    },
+    DeCollectionHandle, OutputConsumer, RelationSchema, SerBatch,
+};
+use anyhow::{anyhow, Error as AnyError, Result as AnyResult};
+use erased_serde::{Deserializer as ErasedDeserializer, Serialize as ErasedSerialize};
+use serde::{Deserialize, Serialize};
+use serde_json::{Deserializer as JsonDeserializer, Value as JsonValue};
+use serde_yaml::Value as YamlValue;
+use std::{borrow::Cow, mem::take, sync::Arc};
+use utoipa::ToSchema;
+
+/// JSON format parser.
+pub struct JsonInputFormat;
+
+/// Representation of data change events in the JSON format.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonUpdateFormat {
+    /// Each JSON value is a record to be inserted into the collection.
+    ///
+    /// This format cannot represent deletions and is therefore only
+    /// supported by the parser.
+    #[default]
+    Raw,
+
+    /// Each JSON value is an object with either an `insert` or a `delete`
+    /// field (or both, in which case the deletion is applied first), e.g.,
+    /// `{"insert": {"id": 1, "name": "foo"}}`.
+    InsertDelete,
+
+    /// Each JSON value is an object with a `data` field that contains the
+    /// record and a `weight` field that specifies how many times the record
+    /// is inserted (positive weight) or deleted (negative weight), e.g.,
+    /// `{"data": {"id": 1, "name": "foo"}, "weight": -1}`.
+    Weighted,
+
+    /// Debezium change data capture format.
+    ///
+    /// Each JSON value is an object with a `payload` field that contains
+    /// `before` and `after` record states (the `payload` wrapper can be
+    /// omitted).  A non-null `before` state is deleted from the collection,
+    /// a non-null `after` state is inserted into the collection.  A `null`
+    /// value (Debezium tombstone message) is ignored.
+    Debezium,
+}
+
+/// JSON parser configuration.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct JsonParserConfig {
+    /// Representation of individual updates in the input stream.
+    ///
+    /// Defaults to `raw`.
+    #[serde(default)]
+    update_format: JsonUpdateFormat,
+
+    /// Set to `true` if each top-level JSON value in the input stream is an
+    /// array of updates rather than a single update.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    array: bool,
+}
+
+impl InputFormat for JsonInputFormat {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("json")
+    }
+
+    fn new_parser(
+        &self,
+        input_stream: &dyn DeCollectionHandle,
+        config: &YamlValue,
+    ) -> AnyResult<Box<dyn Parser>> {
+        let config = JsonParserConfig::deserialize(config)?;
+        Ok(Box::new(JsonParser::new(input_stream, config)) as Box<dyn Parser>)
+    }
+}
+
+struct JsonParser {
+    /// Input handle to push parsed data to.
+    input_stream: Box<dyn DeCollectionHandle>,
+
+    config: JsonParserConfig,
+
+    /// Since we cannot assume that the input buffer ends on a JSON value
+    /// boundary, we save the incomplete value at the end of the buffer and
+    /// prepend it to the next input buffer.
+    leftover: Vec<u8>,
+
+    /// Number of complete lines processed so far.  Used to report line
+    /// numbers in parse errors.
+    num_lines: u64,
+
+    /// Number of bytes processed so far, not including `leftover`.  Used to
+    /// report offsets of invalid records in parse errors.
+    num_bytes: u64,
+
+    /// `true` if the parser is skipping the remainder of an invalid value
+    /// that extends past the end of the last input buffer.
+    skipping: bool,
+}
+
+impl JsonParser {
+    fn new(input_stream: &dyn DeCollectionHandle, config: JsonParserConfig) -> Self {
+        Self {
+            input_stream: input_stream.fork(),
+            config,
+            leftover: Vec::new(),
+            num_lines: 0,
+            num_bytes: 0,
+            skipping: false,
+        }
+    }
+
+    /// Parse all complete JSON values in `self.leftover` followed by `data`.
+    ///
+    /// When `eoi` is `false`, an incomplete value at the end of the buffer is
+    /// saved in `self.leftover`.  When `eoi` is `true`, the incomplete value
+    /// is reported as an error.
+    ///
+    /// On a syntax error, the parser skips to the next line that starts
+    /// with `{` or `[` and resumes parsing from there (see
+    /// [`Self::skip_invalid`]).
+    fn parse(&mut self, data: &[u8], eoi: bool) -> (usize, Vec<ParseError>) {
+        let mut buffer = take(&mut self.leftover);
+        buffer.extend_from_slice(data);
+
+        let mut num_records = 0;
+        let mut errors = Vec::new();
+
+        // Line number at offset `line_pos` in `buffer`.
+        let mut line = self.num_lines + 1;
+        let mut line_pos = 0;
+
+        // Start of the unparsed part of the buffer.
+        let mut offset = if self.skipping {
+            self.skipping = false;
+            self.skip_invalid(&buffer, 0, eoi)
+        } else {
+            0
+        };
+
+        'outer: while offset < buffer.len() && !self.skipping {
+            let mut stream =
+                JsonDeserializer::from_slice(&buffer[offset..]).into_iter::<JsonValue>();
+
+            loop {
+                let start = offset + stream.byte_offset();
+                let value_start = start
+                    + buffer[start..]
+                        .iter()
+                        .position(|c| !c.is_ascii_whitespace())
+                        .unwrap_or(buffer.len() - start);
+                line += count_lines(&buffer[line_pos..value_start]);
+                line_pos = value_start;
+
+                match stream.next() {
+                    None => {
+                        offset = buffer.len();
+                        break 'outer;
+                    }
+                    Some(Ok(value)) => {
+                        let end = offset + stream.byte_offset();
+                        num_records += self.input_value(
+                            &value,
+                            line,
+                            self.num_bytes + value_start as u64,
+                            &buffer[value_start..end],
+                            &mut errors,
+                        );
+                    }
+                    Some(Err(e)) if e.is_eof() && !eoi => {
+                        // Incomplete value at the end of the buffer.
+                        self.leftover = buffer[value_start..].to_vec();
+                        offset = value_start;
+                        break 'outer;
+                    }
+                    Some(Err(e)) => {
+                        let next_value = self.skip_invalid(&buffer, value_start, eoi);
+                        errors.push(
+                            ParseError::new(
+                                format!("failed to parse JSON input: {e}"),
+                                Some(line),
+                                Some(buffer[value_start..next_value].to_vec()),
+                            )
+                            .with_offset(self.num_bytes + value_start as u64),
+                        );
+                        offset = next_value;
+                        continue 'outer;
+                    }
+                }
+            }
+        }
+
+        self.num_lines = line - 1 + count_lines(&buffer[line_pos..offset]);
+        self.num_bytes += offset as u64;
+
+        (num_records, errors)
+    }
+
+    /// Skip the invalid value that starts at offset `start` in `buffer`.
+    ///
+    /// Resumes at the next line that starts with `{` or `[`, i.e., at the
+    /// next top-level value in newline-delimited or pretty-printed input,
+    /// so that the remaining lines of a multi-line value are not reported as
+    /// separate errors.  Returns the offset to resume parsing from.  If there
+    /// is no such line in the buffer and `eoi` is `false`, sets
+    /// `self.skipping` to continue skipping in the next buffer.
+    fn skip_invalid(&mut self, buffer: &[u8], start: usize, eoi: bool) -> usize {
+        let next_value = buffer[start..]
+            .windows(2)
+            .position(|w| w[0] == b'\n' && (w[1] == b'{' || w[1] == b'['))
+            .map(|pos| start + pos + 1);
+
+        match next_value {
+            Some(next_value) => next_value,
+            None if eoi => buffer.len(),
+            None => {
+                self.skipping = true;
+                // Keep the trailing newline, if any, so that a value at the
+                // start of the next buffer is recognized.
+                if buffer.ends_with(b"\n") {
+                    self.leftover = b"\n".to_vec();
+                    buffer.len() - 1
+                } else {
+                    buffer.len()
+                }
+            }
+        }
+    }
+
+    /// Process a top-level JSON value, which is either a single update or,
+    /// if `config.array` is `true`, an array of updates.
+    ///
+    /// `line`, `offset` and `raw` are the line number, the offset in the
+    /// input stream and the raw contents of the value, used in error reports.
+    fn input_value(
+        &mut self,
+        value: &JsonValue,
+        line: u64,
+        offset: u64,
+        raw: &[u8],
+        errors: &mut Vec<ParseError>,
+    ) -> usize {
+        if self.config.array {
+            let updates = match value.as_array() {
+                Some(updates) => updates,
+                None => {
+                    errors.push(
+                        ParseError::new(
+                            format!("expected a JSON array of updates, found '{value}'"),
+                            Some(line),
+                            Some(raw.to_vec()),
+                        )
+                        .with_offset(offset),
+                    );
+                    return 0;
+                }
+            };
+
+            let mut num_records = 0;
+            for (update, (start, end)) in updates.iter().zip(array_elements(raw)) {
+                match self.input_update(update) {
+                    Ok(n) => num_records += n,
+                    Err(e) => errors.push(
+                        ParseError::new(
+                            e.to_string(),
+                            Some(line + count_lines(&raw[..start])),
+                            Some(raw[start..end].to_vec()),
+                        )
+                        .with_offset(offset + start as u64),
+                    ),
+                }
+            }
+            num_records
+        } else {
+            match self.input_update(value) {
+                Ok(n) => n,
+                Err(e) => {
+                    errors.push(
+                        ParseError::new(e.to_string(), Some(line), Some(raw.to_vec()))
+                            .with_offset(offset),
+                    );
+                    0
+                }
+            }
+        }
+    }
+
+    /// Push a single update to the input handle.
+    ///
+    /// Returns the number of records inserted or deleted by the update.
+    fn input_update(&mut self, update: &JsonValue) -> AnyResult<usize> {
+        input_json_update(&mut *self.input_stream, self.config.update_format, update)
+    }
+}
+
+fn count_lines(data: &[u8]) -> u64 {
+    data.iter().filter(|&&c| c == b'\n').count() as u64
+}
+
+/// Returns the start and end offsets of the elements of the well-formed JSON
+/// array `array`.
+fn array_elements(array: &[u8]) -> Vec<(usize, usize)> {
+    let mut elements = Vec::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    // Start of the current element, once its first character has been seen.
+    let mut start = None;
+    // End of the last character of the current element seen so far.
+    let mut end = 0;
+
+    for (i, &c) in array.iter().enumerate() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == b'\\' {
+                escaped = true;
+            } else if c == b'"' {
+                in_string = false;
+                end = i + 1;
+            }
+            continue;
+        }
+
+        match c {
+            b',' if depth == 1 => {
+                if let Some(start) = start.take() {
+                    elements.push((start, end));
+                }
+            }
+            b']' if depth == 1 => {
+                if let Some(start) = start.take() {
+                    elements.push((start, end));
+                }
+                break;
+            }
+            c if c.is_ascii_whitespace() => {}
+            _ => {
+                if depth == 1 && start.is_none() {
+                    start = Some(i);
+                }
+                match c {
+                    b'"' => in_string = true,
+                    b'[' | b'{' => depth += 1,
+                    b']' | b'}' => depth -= 1,
+                    _ => {}
+                }
+                end = i + 1;
+            }
+        }
+    }
+
+    elements
+}
+
+/// Push an update represented as a JSON value in the specified format to
+/// `input_stream`.
+///
+/// Used by the JSON parser and by other parsers that convert their input to
+/// JSON values.  Returns the number of records inserted or deleted by the
+/// update.
+pub(super) fn input_json_update(
+    input_stream: &mut dyn DeCollectionHandle,
+    update_format: JsonUpdateFormat,
+    update: &JsonValue,
+) -> AnyResult<usize> {
+    match update_format {
+        JsonUpdateFormat::Raw => {
+            insert_json(input_stream, update)?;
+            Ok(1)
+        }
+        JsonUpdateFormat::InsertDelete => {
+            let fields = update.as_object().ok_or_else(|| {
+                anyhow!("expected a JSON object with 'insert' or 'delete' field, found '{update}'")
+            })?;
+
+            let mut num_records = 0;
+            if let Some(record) = fields.get("delete") {
+                delete_json(input_stream, record)?;
+                num_records += 1;
+            }
+            if let Some(record) = fields.get("insert") {
+                insert_json(input_stream, record)?;
+                num_records += 1;
+            }
+
+            if num_records == 0 {
+                return Err(anyhow!(
+                    "update '{update}' contains neither 'insert' nor 'delete' field"
+                ));
+            }
+            Ok(num_records)
+        }
+        JsonUpdateFormat::Weighted => {
+            let record = update
+                .get("data")
+                .ok_or_else(|| anyhow!("update '{update}' is missing the 'data' field"))?;
+            let weight = update
+                .get("weight")
+                .and_then(JsonValue::as_i64)
+                .ok_or_else(|| anyhow!("update '{update}' is missing an integer 'weight' field"))?;
+
+            update_json(input_stream, record, weight)?;
+            Ok((weight != 0) as usize)
+        }
+        JsonUpdateFormat::Debezium => {
+            if update.is_null() {
+                // Tombstone message.
+                return Ok(0);
+            }
+
+            let payload = update.get("payload").unwrap_or(update);
+            if !payload.is_object() {
+                return Err(anyhow!(
+                    "expected a Debezium change event, found '{update}'"
+                ));
+            }
+
+            let mut num_records = 0;
+            if let Some(before) = payload.get("before").filter(|v| !v.is_null()) {
+                delete_json(input_stream, before)?;
+                num_records += 1;
+            }
+            if let Some(after) = payload.get("after").filter(|v| !v.is_null()) {
+                insert_json(input_stream, after)?;
+                num_records += 1;
+            }
+            Ok(num_records)
+        }
+    }
+}
+
+/// Coerce `record` to the schema of `input_stream`, if any.
+fn coerce_json<'a>(
+    input_stream: &dyn DeCollectionHandle,
+    record: &'a JsonValue,
+) -> AnyResult<Cow<'a, JsonValue>> {
+    match input_stream.schema() {
+        Some(schema) => schema
+            .coerce_json(record)
+            .map(Cow::Owned)
+            .map_err(|e| AnyError::msg(format!("invalid JSON record '{record}': {e}"))),
+        None => Ok(Cow::Borrowed(record)),
+    }
+}
+
+/// Insert a record represented as a JSON value into `input_stream`.
+pub(super) fn insert_json(
+    input_stream: &mut dyn DeCollectionHandle,
+    record: &JsonValue,
+) -> AnyResult<()> {
+    let record = coerce_json(input_stream, record)?;
+
+    let mut deserializer = <dyn ErasedDeserializer>::erase(&*record);
+    input_stream
+        .insert(&mut deserializer)
+        .map_err(|e| AnyError::msg(format!("failed to deserialize JSON record '{record}': {e}")))
+}
+
+/// Delete a record represented as a JSON value from `input_stream`.
+pub(super) fn delete_json(
+    input_stream: &mut dyn DeCollectionHandle,
+    record: &JsonValue,
+) -> AnyResult<()> {
+    let record = coerce_json(input_stream, record)?;
+
+    let mut deserializer = <dyn ErasedDeserializer>::erase(&*record);
+    input_stream
+        .delete(&mut deserializer)
+        .map_err(|e| AnyError::msg(format!("failed to deserialize JSON record '{record}': {e}")))
+}
+
+/// Push a record represented as a JSON value with weight `weight` to
+/// `input_stream` (see [`DeCollectionHandle::update_weighted`]).
+pub(super) fn update_json(
+    input_stream: &mut dyn DeCollectionHandle,
+    record: &JsonValue,
+    weight: i64,
+) -> AnyResult<()> {
+    let record = coerce_json(input_stream, record)?;
+
+    let mut deserializer = <dyn ErasedDeserializer>::erase(&*record);
+    input_stream
+        .update_weighted(&mut deserializer, weight)
+        .map_err(|e| AnyError::msg(format!("failed to deserialize JSON record '{record}': {e}")))
+}
+
+impl Parser for JsonParser {
+    fn input(&mut self, data: &[u8]) -> (usize, Vec<ParseError>) {
+        self.parse(data, false)
+    }
+
+    fn eoi(&mut self) -> (usize, Vec<ParseError>) {
+        if self.leftover.is_empty() {
+            self.skipping = false;
+            return (0, Vec::new());
+        }
+
+        // Try to interpret the leftover chunk as a complete JSON value.
+        self.parse(&[], true)
+    }
+
+    fn flush(&mut self) {
+        self.input_stream.flush();
+    }
+
+    fn clear(&mut self) {
+        self.input_stream.clear_buffer();
+    }
+
+    fn checkpoint(&self) -> AnyResult<Vec<u8>> {
+        Ok(bincode::encode_to_vec(
+            (
+                self.leftover.as_slice(),
+                self.num_lines,
+                self.num_bytes,
+                self.skipping,
+            ),
+            bincode::config::standard(),
+        )?)
+    }
+
+    fn restore(&mut self, state: &[u8]) -> AnyResult<()> {
+        (
+            (self.leftover, self.num_lines, self.num_bytes, self.skipping),
+            _,
+        ) = bincode::decode_from_slice(state, bincode::config::standard())?;
+        Ok(())
+    }
+
+    fn fork(&self) -> Box<dyn Parser> {
+        Box::new(Self::new(&*self.input_stream, self.config.clone()))
+    }
+}
+
+/// JSON format encoder.
+pub struct JsonOutputFormat;
+
+const fn default_buffer_size_records() -> usize {
+    10_000
+}
+
+const fn default_encoder_update_format() -> JsonUpdateFormat {
+    JsonUpdateFormat::InsertDelete
+}
+
+/// JSON encoder configuration.
+#[derive(Deserialize, ToSchema)]
+pub struct JsonEncoderConfig {
+    /// Maximal number of records in a single buffer sent to the transport
+    /// endpoint.
+    #[serde(default = "default_buffer_size_records")]
+    buffer_size_records: usize,
+
+    /// Representation of individual updates in the output stream.  The
+    /// `raw` format is not supported by the encoder, as it cannot represent
+    /// deletions.
+    ///
+    /// Defaults to `insert_delete`.
+    #[serde(default = "default_encoder_update_format")]
+    update_format: JsonUpdateFormat,
+
+    /// When `true`, each output buffer contains a single JSON array of
+    /// updates.  Otherwise, updates are written as newline-delimited JSON.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    array: bool,
+
+    /// Largest absolute weight of an output record in the `insert_delete`
+    /// and `debezium` formats, which write a record with weight `n` as `n`
+    /// separate updates.  Records with larger weights are reported as
+    /// errors; use the `weighted` format to output them.
+    ///
+    /// Defaults to 10000.
+    #[serde(default = "default_max_weight")]
+    max_weight: u64,
+}
+
+impl OutputFormat for JsonOutputFormat {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("json")
+    }
+
+    fn new_encoder(
+        &self,
+        config: &YamlValue,
+        _schema: Option<&RelationSchema>,
+        consumer: Box<dyn OutputConsumer>,
+    ) -> AnyResult<Box<dyn Encoder>> {
+        let config = JsonEncoderConfig::deserialize(config)?;
+
+        if config.update_format == JsonUpdateFormat::Raw {
+            return Err(anyhow!(
+                "'raw' update format is not supported by the JSON encoder"
+            ));
+        }
+
+        Ok(Box::new(JsonEncoder::new(consumer, config)))
+    }
+}
+
+/// `insert`/`delete` update envelope.
+#[derive(Serialize)]
+pub(super) struct InsDelUpdate<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    insert: Option<&'a dyn ErasedSerialize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delete: Option<&'a dyn ErasedSerialize>,
+}
+
+impl<'a> InsDelUpdate<'a> {
+    /// Insertion of `record` for positive `weight`, deletion otherwise.
+    pub(super) fn new(record: &'a dyn ErasedSerialize, weight: i64) -> Self {
+        if weight > 0 {
+            Self {
+                insert: Some(record),
+                delete: None,
+            }
+        } else {
+            Self {
+                insert: None,
+                delete: Some(record),
+            }
+        }
+    }
+}
+
+/// Weighted update envelope.
+#[derive(Serialize)]
+pub(super) struct WeightedUpdate<'a> {
+    data: &'a dyn ErasedSerialize,
+    weight: i64,
+}
+
+impl<'a> WeightedUpdate<'a> {
+    pub(super) fn new(data: &'a dyn ErasedSerialize, weight: i64) -> Self {
+        Self { data, weight }
+    }
+}
+
+/// Debezium change event.
+#[derive(Serialize)]
+pub(super) struct DebeziumPayload<'a> {
+    before: Option<&'a dyn ErasedSerialize>,
+    after: Option<&'a dyn ErasedSerialize>,
+    op: &'static str,
+}
+
+impl<'a> DebeziumPayload<'a> {
+    /// Create event (`op: "c"`) for positive `weight`, delete event
+    /// (`op: "d"`) otherwise.
+    pub(super) fn new(record: &'a dyn ErasedSerialize, weight: i64) -> Self {
+        if weight > 0 {
+            Self {
+                before: None,
+                after: Some(record),
+                op: "c",
+            }
+        } else {
+            Self {
+                before: Some(record),
+                after: None,
+                op: "d",
+            }
+        }
+    }
+}
+
+/// Debezium change event wrapped in a `payload` object, as produced by the
+/// Debezium JSON converter.
+#[derive(Serialize)]
+struct DebeziumUpdate<'a> {
+    payload: DebeziumPayload<'a>,
+}
+
+struct JsonEncoder {
+    /// Consumer to push serialized data to.
+    output_consumer: Box<dyn OutputConsumer>,
+
+    config: JsonEncoderConfig,
+
+    buffer: Vec<u8>,
+
+    /// Number of records in `buffer`.
+    num_records: usize,
+}
+
+impl JsonEncoder {
+    fn new(output_consumer: Box<dyn OutputConsumer>, config: JsonEncoderConfig) -> Self {
+        Self {
+            output_consumer,
+            config,
+            buffer: Vec::new(),
+            num_records: 0,
+        }
+    }
+
+    /// Append a serialized update to the buffer; push the buffer to the
+    /// consumer once it reaches `buffer_size_records`.
+    fn write_update<T>(&mut self, update: &T) -> AnyResult<()>
+    where
+        T: Serialize,
+    {
+        if self.config.array {
+            self.buffer
+                .push(if self.num_records == 0 { b'[' } else { b',' });
+        }
+        serde_json::to_writer(&mut self.buffer, update)?;
+        if !self.config.array {
+            self.buffer.push(b'\n');
+        }
+
+        self.num_records += 1;
+        if self.num_records >= self.config.buffer_size_records {
+            self.push_buffer();
+        }
+
+        Ok(())
+    }
+
+    /// Push buffered updates to the consumer.
+    fn push_buffer(&mut self) {
+        if self.num_records == 0 {
+            return;
+        }
+
+        if self.config.array {
+            self.buffer.push(b']');
+        }
+
+        self.output_consumer.push_buffer(&self.buffer);
+        self.buffer.clear();
+        self.num_records = 0;
+    }
+}
+
+impl Encoder for JsonEncoder {
+    fn consumer(&mut self) -> &mut dyn OutputConsumer {
+        self.output_consumer.as_mut()
+    }
+
+    fn encode(&mut self, batches: &[Arc<dyn SerBatch>]) -> AnyResult<()> {
+        // Discard the partial output of a step that failed to encode.
+        self.buffer.clear();
+        self.num_records = 0;
+
+        for batch in batches.iter() {
+            let mut cursor = batch.cursor();
+
+            while cursor.key_valid() {
+                let w = cursor.weight();
+                let record = cursor.key();
+
+                match self.config.update_format {
+                    JsonUpdateFormat::Weighted => {
+                        self.write_update(&WeightedUpdate::new(record, w))?;
+                    }
+                    JsonUpdateFormat::InsertDelete => {
+                        let update = InsDelUpdate::new(record, w);
+                        for _ in 0..repetitions(w, self.config.max_weight)? {
+                            self.write_update(&update)?;
+                        }
+                    }
+                    JsonUpdateFormat::Debezium => {
+                        let update = DebeziumUpdate {
+                            payload: DebeziumPayload::new(record, w),
+                        };
+                        for _ in 0..repetitions(w, self.config.max_weight)? {
+                            self.write_update(&update)?;
+                        }
+                    }
+                    JsonUpdateFormat::Raw => unreachable!(),
+                }
+
+                cursor.step_key();
+            }
+        }
+
+        self.push_buffer();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{JsonInputFormat, JsonOutputFormat};
+    use crate::{
+        seroutput::SerBatchImpl,
+        test::{test_data, MockDeZSet, MockOutputConsumer, TestStruct},
+        InputFormat, OutputFormat, SerBatch,
+    };
+    use dbsp::{trace::Batch, OrdZSet};
+    use std::sync::Arc;
+
+    /// Feed `input` to a JSON parser configured with `config` in two chunks
+    /// split at `split`; return flushed updates.
+    fn parse(config: &str, input: &str, split: usize) -> Vec<(TestStruct, bool)> {
+        let zset = MockDeZSet::<TestStruct>::new();
+        let mut parser = JsonInputFormat
+            .new_parser(&zset, &serde_yaml::from_str(config).unwrap())
+            .unwrap();
+
+        assert_eq!(parser.input(input[..split].as_bytes()).1, Vec::new());
+        assert_eq!(parser.input(input[split..].as_bytes()).1, Vec::new());
+        assert_eq!(parser.eoi().1, Vec::new());
+        parser.flush();
+
+        let flushed = zset.state().flushed.clone();
+        flushed
+    }
+
+    #[test]
+    fn test_raw() {
+        let data = test_data();
+        let input = data
+            .iter()
+            .map(|v| serde_json::to_string(v).unwrap() + "\n")
+            .collect::<String>();
+
+        for split in 0..input.len() {
+            let expected = data.iter().map(|v| (v.clone(), true)).collect::<Vec<_>>();
+            assert_eq!(parse("update_format: raw", &input, split), expected);
+        }
+    }
+
+    #[test]
+    fn test_array() {
+        let data = test_data();
+        let input = serde_json::to_string(&data).unwrap();
+
+        let expected = data.iter().map(|v| (v.clone(), true)).collect::<Vec<_>>();
+        assert_eq!(parse("array: true", &input, input.len() / 2), expected);
+    }
+
+    #[test]
+    fn test_insert_delete() {
+        let data = test_data();
+        let input = format!(
+            "{}\n{}\n",
+            serde_json::json!({"insert": data[0]}),
+            serde_json::json!({"delete": data[1]}),
+        );
+
+        assert_eq!(
+            parse("update_format: insert_delete", &input, 7),
+            vec![(data[0].clone(), true), (data[1].clone(), false)]
+        );
+    }
+
+    #[test]
+    fn test_weighted() {
+        let data = test_data();
+        let input = format!(
+            "{} {}",
+            serde_json::json!({"data": data[0], "weight": 2}),
+            serde_json::json!({"data": data[1], "weight": -1}),
+        );
+
+        assert_eq!(
+            parse("update_format: weighted", &input, 3),
+            vec![
+                (data[0].clone(), true),
+                (data[0].clone(), true),
+                (data[1].clone(), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_debezium() {
+        let data = test_data();
+        let input = format!(
+            "{}\nnull\n{}\n",
+            serde_json::json!({"payload": {"before": null, "after": data[0], "op": "c"}}),
+            serde_json::json!({"before": data[0], "after": data[1], "op": "u"}),
+        );
+
+        assert_eq!(
+            parse("update_format: debezium", &input, 20),
+            vec![
+                (data[0].clone(), true),
+                (data[0].clone(), false),
+                (data[1].clone(), true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let data = test_data();
+        let zset = MockDeZSet::<TestStruct>::new();
+        let mut parser = JsonInputFormat
+            .new_parser(&zset, &serde_yaml::Value::Null)
+            .unwrap();
+
+        // Invalid records are skipped and reported with their line numbers;
+        // valid records are still ingested.
+        let input = format!(
+            "{}\n{{\"id\": \"not a number\"}}\n{{\"id\": 1, \n{}\n",
+            serde_json::to_string(&data[0]).unwrap(),
+            serde_json::to_string(&data[1]).unwrap(),
+        );
+        let (num_records, errors) = parser.input(input.as_bytes());
+        assert_eq!(num_records, 2);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line(), Some(2));
+        assert_eq!(
+            errors[0].invalid_bytes(),
+            Some(&b"{\"id\": \"not a number\"}"[..])
+        );
+        assert_eq!(
+            errors[0].offset(),
+            input.find("{\"id\": \"not").map(|offset| offset as u64)
+        );
+        assert_eq!(errors[1].line(), Some(3));
+        assert_eq!(
+            errors[1].offset(),
+            input.find("{\"id\": 1,").map(|offset| offset as u64)
+        );
+
+        parser.flush();
+        assert_eq!(
+            zset.state().flushed,
+            vec![(data[0].clone(), true), (data[1].clone(), true)]
+        );
+    }
+
+    #[test]
+    fn test_parse_error_recovery() {
+        let data = test_data();
+        let zset = MockDeZSet::<TestStruct>::new();
+        let mut parser = JsonInputFormat
+            .new_parser(&zset, &serde_yaml::Value::Null)
+            .unwrap();
+
+        // The remaining lines of an invalid multi-line value are skipped,
+        // even when the value spans multiple buffers.
+        let (num_records, errors) = parser.input(b"{\n  \"id\": 1,,\n");
+        assert_eq!(num_records, 0);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line(), Some(1));
+        assert_eq!(errors[0].offset(), Some(0));
+
+        let input = format!(
+            "  \"b\": true\n}}\n{}\n",
+            serde_json::to_string(&data[0]).unwrap()
+        );
+        assert_eq!(parser.input(input.as_bytes()), (1, Vec::new()));
+        assert_eq!(parser.eoi(), (0, Vec::new()));
+
+        // Errors in array elements are reported with the line number, offset
+        // and contents of the element.
+        let mut parser = JsonInputFormat
+            .new_parser(&zset, &serde_yaml::from_str("array: true").unwrap())
+            .unwrap();
+        let input = format!(
+            "[{},\n {{\"id\": \"x\"}} ]",
+            serde_json::to_string(&data[1]).unwrap()
+        );
+        let (num_records, errors) = parser.input(input.as_bytes());
+        assert_eq!(num_records, 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line(), Some(2));
+        assert_eq!(errors[0].invalid_bytes(), Some(&b"{\"id\": \"x\"}"[..]));
+        assert_eq!(
+            errors[0].offset(),
+            input.find("{\"id\": \"x").map(|offset| offset as u64)
+        );
+
+        parser.flush();
+        assert_eq!(
+            zset.state().flushed,
+            vec![(data[0].clone(), true), (data[1].clone(), true)]
+        );
+    }
+
+    #[test]
+    fn test_encoder() {
+        let data = test_data();
+        let batch = OrdZSet::from_tuples((), vec![(data[0].clone(), 1), (data[1].clone(), -2)]);
+        let batch = Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>;
+
+        // Compare parsed JSON values, since `serde_json::json!` does not
+        // preserve the order of struct fields.
+        for (config, expected) in [
+            (
+                "update_format: insert_delete",
+                vec![
+                    serde_json::json!({"insert": data[0]}),
+                    serde_json::json!({"delete": data[1]}),
+                    serde_json::json!({"delete": data[1]}),
+                ],
+            ),
+            (
+                "update_format: weighted\narray: true",
+                vec![serde_json::json!([
+                    {"data": data[0], "weight": 1},
+                    {"data": data[1], "weight": -2},
+                ])],
+            ),
+        ] {
+            let consumer = MockOutputConsumer::default();
+            let mut encoder = JsonOutputFormat
+                .new_encoder(
+                    &serde_yaml::from_str(config).unwrap(),
+                    None,
+                    Box::new(consumer.clone()),
+                )
+                .unwrap();
+            encoder.encode(&[batch.clone()]).unwrap();
+
+            let buffers = consumer.buffers();
+            assert_eq!(buffers.len(), 1);
+            let output = serde_json::Deserializer::from_slice(&buffers[0])
+                .into_iter::<serde_json::Value>()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn test_encoder_error() {
+        let data = test_data();
+        let consumer = MockOutputConsumer::default();
+        let mut encoder = JsonOutputFormat
+            .new_encoder(
+                &serde_yaml::from_str("update_format: insert_delete\narray: true\nmax_weight: 10")
+                    .unwrap(),
+                None,
+                Box::new(consumer.clone()),
+            )
+            .unwrap();
+
+        // The second record of the first step exceeds `max_weight`.
+        let batch = OrdZSet::from_tuples((), vec![(data[0].clone(), 1), (data[1].clone(), 100)]);
+        let batch = Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>;
+        assert!(encoder.encode(&[batch]).is_err());
+
+        // The partial output of the failed step is discarded.
+        let batch = OrdZSet::from_tuples((), vec![(data[1].clone(), 1)]);
+        let batch = Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>;
+        encoder.encode(&[batch]).unwrap();
+
+        let buffers = consumer.buffers();
+        assert_eq!(buffers.len(), 1);
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&buffers[0]).unwrap(),
+            serde_json::json!([{"insert": data[1]}])
+        );
+    }
+
+    #[test]
+    fn test_encoder_max_weight() {
+        let data = test_data();
+        let batch = OrdZSet::from_tuples((), vec![(data[0].clone(), 1_000_000_000)]);
+        let batch = Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>;
+
+        let encode = |config: &str| {
+            let consumer = MockOutputConsumer::default();
+            let mut encoder = JsonOutputFormat
+                .new_encoder(
+                    &serde_yaml::from_str(config).unwrap(),
+                    None,
+                    Box::new(consumer.clone()),
+                )
+                .unwrap();
+            encoder.encode(&[batch.clone()]).map(|_| consumer.concat())
+        };
+
+        // Formats that repeat records reject large weights.
+        assert!(encode("update_format: insert_delete").is_err());
+        assert!(encode("update_format: debezium\nmax_weight: 100").is_err());
+
+        // The weighted format writes the record once.
+        let output = encode("update_format: weighted").unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&output).unwrap(),
+            serde_json::json!({"data": data[0], "weight": 1_000_000_000})
+        );
+    }
+}
@@ -1,23 +1,68 @@
-use crate::{DeCollectionHandle, SerBatch};
-use anyhow::Result as AnyResult;
+use crate::{DeCollectionHandle, RelationSchema, SerBatch, Step};
+use anyhow::{Error as AnyError, Result as AnyResult};
 use once_cell::sync::Lazy;
+use serde::Serialize;
 use serde_yaml::Value as YamlValue;
-use std::{borrow::Cow, collections::BTreeMap, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    error::Error as StdError,
+    fmt::{Display, Error as FmtError, Formatter},
+    sync::Arc,
+};
 
+#[cfg(feature = "with-avro")]
+mod avro;
 mod csv;
+mod json;
+#[cfg(feature = "with-parquet")]
+mod parquet;
+
+#[cfg(feature = "with-avro")]
+pub use self::avro::{AvroEncoderConfig, AvroParserConfig};
+#[cfg(feature = "with-avro")]
+use self::avro::{AvroInputFormat, AvroOutputFormat};
 
 pub use self::csv::{CsvEncoderConfig, CsvParserConfig};
 use self::csv::{CsvInputFormat, CsvOutputFormat};
+pub use self::json::{JsonEncoderConfig, JsonParserConfig, JsonUpdateFormat};
+use self::json::{JsonInputFormat, JsonOutputFormat};
+#[cfg(feature = "with-parquet")]
+pub use self::parquet::{ParquetEncoderConfig, ParquetParserConfig};
+#[cfg(feature = "with-parquet")]
+use self::parquet::{ParquetInputFormat, ParquetOutputFormat};
 
 /// Static map of supported input formats.
 // TODO: support for registering new formats at runtime in order to allow
 // external crates to implement new formats.
-static INPUT_FORMATS: Lazy<BTreeMap<&'static str, Box<dyn InputFormat>>> =
-    Lazy::new(|| BTreeMap::from([("csv", Box::new(CsvInputFormat) as Box<dyn InputFormat>)]));
+static INPUT_FORMATS: Lazy<BTreeMap<&'static str, Box<dyn InputFormat>>> = Lazy::new(|| {
+    BTreeMap::from([
+        #[cfg(feature = "with-avro")]
+        ("avro", Box::new(AvroInputFormat) as Box<dyn InputFormat>),
+        ("csv", Box::new(CsvInputFormat) as Box<dyn InputFormat>),
+        ("json", Box::new(JsonInputFormat) as Box<dyn InputFormat>),
+        #[cfg(feature = "with-parquet")]
+        (
+            "parquet",
+            Box::new(ParquetInputFormat) as Box<dyn InputFormat>,
+        ),
+    ])
+});
 
 /// Static map of supported output formats.
-static OUTPUT_FORMATS: Lazy<BTreeMap<&'static str, Box<dyn OutputFormat>>> =
-    Lazy::new(|| BTreeMap::from([("csv", Box::new(CsvOutputFormat) as Box<dyn OutputFormat>)]));
+static OUTPUT_FORMATS: Lazy<BTreeMap<&'static str, Box<dyn OutputFormat>>> = Lazy::new(|| {
+    BTreeMap::from([
+        #[cfg(feature = "with-avro")]
+        ("avro", Box::new(AvroOutputFormat) as Box<dyn OutputFormat>),
+        ("csv", Box::new(CsvOutputFormat) as Box<dyn OutputFormat>),
+        ("json", Box::new(JsonOutputFormat) as Box<dyn OutputFormat>),
+        #[cfg(feature = "with-parquet")]
+        (
+            "parquet",
+            Box::new(ParquetOutputFormat) as Box<dyn OutputFormat>,
+        ),
+    ])
+});
 
 /// Trait that represents a specific data format.
 ///
@@ -47,6 +92,71 @@ impl dyn InputFormat {
     }
 }
 
+/// Error parsing an individual input record.
+///
+/// Parse errors are not fatal: the parser skips the invalid record and
+/// continues parsing the rest of the input.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ParseError {
+    /// Error description.
+    description: String,
+
+    /// Line number in the input stream, starting from 1, where the invalid
+    /// record is located, if known.
+    line: Option<u64>,
+
+    /// Raw contents of the invalid record, if available.
+    invalid_bytes: Option<Vec<u8>>,
+
+    /// Offset in bytes of the invalid record from the start of the input
+    /// stream, if known.
+    offset: Option<u64>,
+}
+
+impl ParseError {
+    pub fn new(description: String, line: Option<u64>, invalid_bytes: Option<Vec<u8>>) -> Self {
+        Self {
+            description,
+            line,
+            invalid_bytes,
+            offset: None,
+        }
+    }
+
+    /// Set the offset of the invalid record in the input stream.
+    pub fn with_offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn line(&self) -> Option<u64> {
+        self.line
+    }
+
+    pub fn invalid_bytes(&self) -> Option<&[u8]> {
+        self.invalid_bytes.as_deref()
+    }
+
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+}
+
+impl StdError for ParseError {}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        if let Some(line) = self.line {
+            write!(f, "line {line}: ")?;
+        }
+        f.write_str(&self.description)
+    }
+}
+
 /// Parser that converts a raw byte stream into a stream of database records.
 pub trait Parser: Send {
     /// Push a chunk of data to the parser.
@@ -59,18 +169,25 @@ pub trait Parser: Send {
     /// that cannot be fully parsed until more data or an end-of-file
     /// notification is received.
     ///
-    /// Returns the number of records in the parsed representation or an error
-    /// if parsing fails.
-    fn input(&mut self, data: &[u8]) -> AnyResult<usize>;
+    /// Returns the number of records in the parsed representation and a list
+    /// of errors encountered while parsing `data`.  Invalid records are
+    /// skipped; valid records in the same chunk are still pushed to the
+    /// circuit.
+    fn input(&mut self, data: &[u8]) -> (usize, Vec<ParseError>);
 
     /// End-of-input-stream notification.
     ///
     /// No more data will be received from the stream.  The parser uses this
     /// notification to complete or discard any incompletely parsed records.
     ///
-    /// Returns the number of additional records pushed to the circuit or an
-    /// error if parsing fails.
-    fn eoi(&mut self) -> AnyResult<usize>;
+    /// This notification is also used to mark the end of a self-contained
+    /// chunk of input (see
+    /// [`InputConsumer::end_of_chunk`](`crate::InputConsumer::end_of_chunk`)),
+    /// in which case the parser may receive more data after this call.
+    ///
+    /// Returns the number of additional records pushed to the circuit and a
+    /// list of parse errors.
+    fn eoi(&mut self) -> (usize, Vec<ParseError>);
 
     /// Flush input handles.
     ///
@@ -86,6 +203,30 @@ pub trait Parser: Send {
     /// on all input handles modified by this parser.
     fn clear(&mut self);
 
+    /// Serialize the state of the parser, e.g., a partially received record.
+    ///
+    /// Invoked by the controller when writing a checkpoint, after all
+    /// records parsed so far have been flushed.  The state is passed to
+    /// [`restore`](`Self::restore`) when resuming from the checkpoint.
+    fn checkpoint(&self) -> AnyResult<Vec<u8>>;
+
+    /// Restore the state of a newly created parser from `state` returned by
+    /// [`checkpoint`](`Self::checkpoint`).
+    fn restore(&mut self, state: &[u8]) -> AnyResult<()>;
+
+    /// Number of bytes at the end of the input received so far that are not
+    /// covered by the state returned by [`checkpoint`](`Self::checkpoint`).
+    ///
+    /// The controller resumes the input stream of a restored parser from the
+    /// start of these bytes, so that the parser receives them again, e.g.,
+    /// when its state within a compressed stream cannot be serialized.
+    ///
+    /// The default implementation returns 0: the checkpoint covers all data
+    /// received by the parser.
+    fn replay_bytes(&self) -> u64 {
+        0
+    }
+
     /// Create a new parser with the same configuration as `self`.
     ///
     /// Used by multithreaded transport endpoints to create multiple parallel
@@ -93,6 +234,27 @@ pub trait Parser: Send {
     fn fork(&self) -> Box<dyn Parser>;
 }
 
+/// Default value of the `max_weight` setting of encoders.
+fn default_max_weight() -> u64 {
+    10_000
+}
+
+/// Returns the number of copies of a record with weight `weight` to write
+/// in an output format that represents each unit of weight as a separate
+/// update.
+///
+/// Fails if the absolute value of `weight` exceeds `max_weight`, so that a
+/// record with a huge weight cannot stall the output pipeline.
+fn repetitions(weight: i64, max_weight: u64) -> AnyResult<u64> {
+    let repetitions = weight.unsigned_abs();
+    if repetitions > max_weight {
+        return Err(AnyError::msg(format!(
+            "record weight {weight} exceeds the 'max_weight' limit ({max_weight}); use a format that represents weights explicitly to output records with large weights"
+        )));
+    }
+    Ok(repetitions)
+}
+
 pub trait OutputFormat: Send + Sync {
     /// Unique name of the data format.
     fn name(&self) -> Cow<'static, str>;
@@ -103,10 +265,16 @@ pub trait OutputFormat: Send + Sync {
     ///
     /// * `config` - Format-specific configuration.
     ///
+    /// * `schema` - Schema of the output stream, if it has one (see
+    ///   [`Catalog`](`crate::Catalog`)).  Formats that embed a schema in
+    ///   their output or store typed columns use it to make the layout of
+    ///   the output independent of the records in it.
+    ///
     /// * `consumer` - Consumer to send encoded data batches to.
     fn new_encoder(
         &self,
         config: &YamlValue,
+        schema: Option<&RelationSchema>,
         consumer: Box<dyn OutputConsumer>,
     ) -> AnyResult<Box<dyn Encoder>>;
 }
@@ -119,9 +287,81 @@ impl dyn OutputFormat {
 }
 
 pub trait Encoder: Send {
+    /// Returns a reference to the consumer that the encoder is connected to.
+    fn consumer(&mut self) -> &mut dyn OutputConsumer;
+
     fn encode(&mut self, batches: &[Arc<dyn SerBatch>]) -> AnyResult<()>;
+
+    /// Encode the output of circuit step `step`.
+    ///
+    /// Brackets the buffers produced by [`encode`](`Self::encode`) with
+    /// [`batch_start`](`OutputConsumer::batch_start`) and
+    /// [`batch_end`](`OutputConsumer::batch_end`) calls to the consumer, or
+    /// [`batch_abort`](`OutputConsumer::batch_abort`) if encoding fails.
+    /// `batches` may be empty, in which case the consumer receives an empty
+    /// "heartbeat" step.  Encoders can override this method to embed step
+    /// boundaries in the output stream.
+    fn encode_step(&mut self, step: Step, batches: &[Arc<dyn SerBatch>]) -> AnyResult<()> {
+        self.consumer().batch_start(step);
+        self.consumer()
+            .batch_records(batches.iter().map(|batch| batch.len()).sum());
+        match self.encode(batches) {
+            Ok(()) => {
+                self.consumer().batch_end();
+                Ok(())
+            }
+            Err(e) => {
+                self.consumer().batch_abort();
+                Err(e)
+            }
+        }
+    }
 }
 
 pub trait OutputConsumer: Send {
+    /// Notifies the consumer that the encoder is about to push buffers
+    /// containing the output of circuit step `step`.
+    ///
+    /// Step numbers increase monotonically, but need not be consecutive,
+    /// since steps that produce no output for the endpoint may be skipped.
+    fn batch_start(&mut self, step: Step);
+
+    /// Notifies the consumer of the number of records in the output of the
+    /// current step.
+    ///
+    /// Invoked after [`batch_start`](`Self::batch_start`), before the buffers
+    /// of the step are pushed.  The default implementation does nothing.
+    fn batch_records(&mut self, _num_records: usize) {}
+
     fn push_buffer(&mut self, buffer: &[u8]);
+
+    /// Returns `true` if the next buffer pushed to the consumer will be
+    /// written at the start of a new output file (see
+    /// [`OutputEndpoint::at_file_start`](`crate::OutputEndpoint::at_file_start`)).
+    ///
+    /// The default implementation returns `false`.
+    fn at_file_start(&self) -> bool {
+        false
+    }
+
+    /// Returns `true` if the consumer delivers the output of each step
+    /// separately (see
+    /// [`OutputEndpoint::separates_steps`](`crate::OutputEndpoint::separates_steps`)).
+    ///
+    /// The default implementation returns `false`.
+    fn separates_steps(&self) -> bool {
+        false
+    }
+
+    /// Notifies the consumer that all buffers for the current circuit step
+    /// have been pushed.
+    fn batch_end(&mut self);
+
+    /// Notifies the consumer that the encoder failed to encode the output of
+    /// the current step.
+    ///
+    /// Invoked instead of [`batch_end`](`Self::batch_end`); some of the
+    /// buffers of the step may have already been pushed.  The default
+    /// implementation does nothing.
+    fn batch_abort(&mut self) {}
 }
@@ -0,0 +1,1476 @@
+use crate::{
+    format::{Encoder, InputFormat, OutputFormat, ParseError, Parser},
+    schema::{date_from_days, date_to_days, timestamp_from_millis, timestamp_to_millis, Coerced},
+    ColumnSchema, ColumnType, DeCollectionHandle, OutputConsumer, RelationSchema, SerBatch,
+};
+use anyhow::{anyhow, Result as AnyResult};
+use arrow_array::{
+    builder::{
+        BooleanBuilder, Date32Builder, Float64Builder, Int64Builder, StringBuilder,
+        TimestampMillisecondBuilder,
+    },
+    cast::AsArray,
+    types::{
+        Date32Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type,
+        TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType,
+        TimestampSecondType, UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+    },
+    Array, ArrayRef, RecordBatch, RecordBatchReader,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use bytes::Bytes;
+use erased_serde::{
+    Deserializer as ErasedDeserializer, Error as EError, Serialize as ErasedSerialize,
+};
+use parquet::arrow::{
+    arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder},
+    ArrowWriter,
+};
+use serde::{
+    de::{DeserializeSeed, Error as DeError, IntoDeserializer, MapAccess, Visitor},
+    forward_to_deserialize_any,
+    ser::{Error as SerError, Impossible, SerializeStruct},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_yaml::Value as YamlValue;
+use std::{borrow::Cow, fmt::Display, mem::take, sync::Arc};
+use utoipa::ToSchema;
+
+/// Parquet format parser.
+pub struct ParquetInputFormat;
+
+/// Parquet parser configuration.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct ParquetParserConfig {
+    /// Name of an integer column that contains the weight of each record.
+    /// Records with positive weights are inserted into the collection,
+    /// records with negative weights are deleted from it.
+    ///
+    /// When not specified, each row is inserted into the collection once.
+    weight_column: Option<String>,
+}
+
+impl InputFormat for ParquetInputFormat {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("parquet")
+    }
+
+    fn new_parser(
+        &self,
+        input_stream: &dyn DeCollectionHandle,
+        config: &YamlValue,
+    ) -> AnyResult<Box<dyn Parser>> {
+        let config = ParquetParserConfig::deserialize(config)?;
+        Ok(Box::new(ParquetParser::new(input_stream, config)) as Box<dyn Parser>)
+    }
+}
+
+/// Parser that decodes complete Parquet files.
+///
+/// Parquet is not a streaming format: a file can only be decoded once its
+/// footer has been received.  The parser accumulates input bytes and decodes
+/// them as a single file at the end of the input or of the current chunk
+/// (see [`InputConsumer::end_of_chunk`](`crate::InputConsumer::end_of_chunk`)).
+/// It therefore requires a transport that reports the boundaries between
+/// files, e.g., the `file`, `directory` or `http` transport.
+///
+/// The file is decoded into Arrow record batches one batch of rows at a time,
+/// and each row is deserialized directly into the Rust type of the input
+/// stream, with columns matched with struct fields by name.
+///
+/// A file is decoded within a single [`eoi`](`Parser::eoi`) call, so a
+/// checkpoint never falls in the middle of a decoded file and the parser
+/// checkpoints no state.  The bytes of a partially received file are not
+/// checkpointed either: they are received again from the transport after
+/// restoring the checkpoint (see [`Parser::replay_bytes`]).
+struct ParquetParser {
+    /// Input handle to push parsed data to.
+    input_stream: Box<dyn DeCollectionHandle>,
+
+    config: ParquetParserConfig,
+
+    /// Contents of the current file received so far.
+    buffer: Vec<u8>,
+}
+
+impl ParquetParser {
+    fn new(input_stream: &dyn DeCollectionHandle, config: ParquetParserConfig) -> Self {
+        Self {
+            input_stream: input_stream.fork(),
+            config,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Push the records of a Parquet file to the input handle.
+    ///
+    /// Invalid rows are skipped and reported as parse errors.  An error
+    /// reading the file aborts decoding.
+    fn decode(&mut self, reader: ParquetRecordBatchReader) -> (usize, Vec<ParseError>) {
+        let weight_column = match &self.config.weight_column {
+            None => None,
+            Some(name) => match reader.schema().index_of(name) {
+                Ok(index) => Some(index),
+                Err(_) => return (0, vec![file_error(format!("no weight column '{name}'"))]),
+            },
+        };
+
+        let mut num_records = 0;
+        let mut errors = Vec::new();
+        let mut first_row = 0;
+
+        for batch in reader {
+            let batch = match batch {
+                Ok(batch) => batch,
+                Err(e) => {
+                    errors.push(file_error(e));
+                    break;
+                }
+            };
+
+            for row in 0..batch.num_rows() {
+                match self.input_row(&batch, row, weight_column) {
+                    Ok(n) => num_records += n,
+                    Err(e) => errors.push(ParseError::new(
+                        format!("error in row {} of Parquet file: {e}", first_row + row),
+                        None,
+                        None,
+                    )),
+                }
+            }
+            first_row += batch.num_rows();
+        }
+
+        (num_records, errors)
+    }
+
+    /// Push row `row` of `batch` to the input handle as one update with the
+    /// weight from the weight column.
+    ///
+    /// Returns the number of updates pushed to the handle.
+    fn input_row(
+        &mut self,
+        batch: &RecordBatch,
+        row: usize,
+        weight_column: Option<usize>,
+    ) -> AnyResult<usize> {
+        let weight = match weight_column {
+            None => 1,
+            Some(index) => integer_value(batch.column(index).as_ref(), row).ok_or_else(|| {
+                anyhow!(
+                    "weight column '{}' must contain non-NULL 64-bit integers",
+                    batch.schema_ref().field(index).name()
+                )
+            })?,
+        };
+
+        let mut deserializer = <dyn ErasedDeserializer>::erase(RowDeserializer {
+            batch,
+            row,
+            weight_column,
+        });
+        self.input_stream
+            .update_weighted(&mut deserializer, weight)?;
+        Ok((weight != 0) as usize)
+    }
+}
+
+fn file_error<E: Display>(error: E) -> ParseError {
+    ParseError::new(format!("error reading Parquet file: {error}"), None, None)
+}
+
+/// Returns the value of an integer column in row `row`, or `None` if the
+/// column does not contain integers, the value is `NULL`, or it doesn't fit
+/// in an `i64`.
+fn integer_value(array: &dyn Array, row: usize) -> Option<i64> {
+    if array.is_null(row) {
+        return None;
+    }
+
+    match array.data_type() {
+        DataType::Int8 => Some(array.as_primitive::<Int8Type>().value(row).into()),
+        DataType::Int16 => Some(array.as_primitive::<Int16Type>().value(row).into()),
+        DataType::Int32 => Some(array.as_primitive::<Int32Type>().value(row).into()),
+        DataType::Int64 => Some(array.as_primitive::<Int64Type>().value(row)),
+        DataType::UInt8 => Some(array.as_primitive::<UInt8Type>().value(row).into()),
+        DataType::UInt16 => Some(array.as_primitive::<UInt16Type>().value(row).into()),
+        DataType::UInt32 => Some(array.as_primitive::<UInt32Type>().value(row).into()),
+        DataType::UInt64 => array
+            .as_primitive::<UInt64Type>()
+            .value(row)
+            .try_into()
+            .ok(),
+        _ => None,
+    }
+}
+
+/// Deserializer that presents a row of a record batch as a struct with a
+/// field per column.
+#[derive(Clone, Copy)]
+struct RowDeserializer<'a> {
+    batch: &'a RecordBatch,
+    row: usize,
+
+    /// Index of the weight column, which is not a field of the struct.
+    weight_column: Option<usize>,
+}
+
+impl<'de, 'a> Deserializer<'de> for RowDeserializer<'a> {
+    type Error = EError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        visitor.visit_map(RowAccess {
+            row: self,
+            column: 0,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Feeds the columns of a row to a `Deserialize` implementation as the
+/// fields of a struct.
+struct RowAccess<'a> {
+    row: RowDeserializer<'a>,
+
+    /// The next column.
+    column: usize,
+}
+
+impl<'de, 'a> MapAccess<'de> for RowAccess<'a> {
+    type Error = EError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, EError> {
+        if Some(self.column) == self.row.weight_column {
+            self.column += 1;
+        }
+        if self.column >= self.row.batch.num_columns() {
+            return Ok(None);
+        }
+
+        let name = self.row.batch.schema_ref().field(self.column).name();
+        seed.deserialize(IntoDeserializer::<EError>::into_deserializer(name.as_str()))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, EError> {
+        let value = seed.deserialize(ValueDeserializer {
+            array: self.row.batch.column(self.column).as_ref(),
+            row: self.row.row,
+        });
+        self.column += 1;
+        value
+    }
+}
+
+/// Deserializer for the value of a column in a row.
+///
+/// Dates and timestamps are deserialized as strings in the canonical format
+/// of [`ColumnType::Date`] and [`ColumnType::Timestamp`] respectively.
+struct ValueDeserializer<'a> {
+    array: &'a dyn Array,
+    row: usize,
+}
+
+impl<'de, 'a> Deserializer<'de> for ValueDeserializer<'a> {
+    type Error = EError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        let (array, row) = (self.array, self.row);
+        if array.is_null(row) {
+            return visitor.visit_unit();
+        }
+
+        match array.data_type() {
+            DataType::Boolean => visitor.visit_bool(array.as_boolean().value(row)),
+            DataType::Int8 => visitor.visit_i8(array.as_primitive::<Int8Type>().value(row)),
+            DataType::Int16 => visitor.visit_i16(array.as_primitive::<Int16Type>().value(row)),
+            DataType::Int32 => visitor.visit_i32(array.as_primitive::<Int32Type>().value(row)),
+            DataType::Int64 => visitor.visit_i64(array.as_primitive::<Int64Type>().value(row)),
+            DataType::UInt8 => visitor.visit_u8(array.as_primitive::<UInt8Type>().value(row)),
+            DataType::UInt16 => visitor.visit_u16(array.as_primitive::<UInt16Type>().value(row)),
+            DataType::UInt32 => visitor.visit_u32(array.as_primitive::<UInt32Type>().value(row)),
+            DataType::UInt64 => visitor.visit_u64(array.as_primitive::<UInt64Type>().value(row)),
+            DataType::Float32 => visitor.visit_f32(array.as_primitive::<Float32Type>().value(row)),
+            DataType::Float64 => visitor.visit_f64(array.as_primitive::<Float64Type>().value(row)),
+            DataType::Utf8 => visitor.visit_str(array.as_string::<i32>().value(row)),
+            DataType::LargeUtf8 => visitor.visit_str(array.as_string::<i64>().value(row)),
+            DataType::Date32 => visitor.visit_string(date_from_days(
+                array.as_primitive::<Date32Type>().value(row).into(),
+            )),
+            DataType::Timestamp(unit, _) => {
+                let millis = match unit {
+                    TimeUnit::Second => array
+                        .as_primitive::<TimestampSecondType>()
+                        .value(row)
+                        .saturating_mul(1000),
+                    TimeUnit::Millisecond => {
+                        array.as_primitive::<TimestampMillisecondType>().value(row)
+                    }
+                    TimeUnit::Microsecond => array
+                        .as_primitive::<TimestampMicrosecondType>()
+                        .value(row)
+                        .div_euclid(1000),
+                    TimeUnit::Nanosecond => array
+                        .as_primitive::<TimestampNanosecondType>()
+                        .value(row)
+                        .div_euclid(1_000_000),
+                };
+                visitor.visit_string(timestamp_from_millis(millis))
+            }
+            data_type => Err(DeError::custom(format!(
+                "unsupported Parquet column type '{data_type}'"
+            ))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        if self.array.is_null(self.row) {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, EError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+impl Parser for ParquetParser {
+    fn input(&mut self, data: &[u8]) -> (usize, Vec<ParseError>) {
+        // The file is decoded in `eoi`, once it has been received in full.
+        self.buffer.extend_from_slice(data);
+        (0, Vec::new())
+    }
+
+    fn eoi(&mut self) -> (usize, Vec<ParseError>) {
+        if self.buffer.is_empty() {
+            return (0, Vec::new());
+        }
+
+        match ParquetRecordBatchReaderBuilder::try_new(Bytes::from(take(&mut self.buffer)))
+            .and_then(|builder| builder.build())
+        {
+            Ok(reader) => self.decode(reader),
+            Err(e) => (0, vec![file_error(e)]),
+        }
+    }
+
+    fn flush(&mut self) {
+        self.input_stream.flush();
+    }
+
+    fn clear(&mut self) {
+        self.input_stream.clear_buffer();
+    }
+
+    fn checkpoint(&self) -> AnyResult<Vec<u8>> {
+        Ok(Vec::new())
+    }
+
+    fn restore(&mut self, _state: &[u8]) -> AnyResult<()> {
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// The partially received file is replayed after restoring a checkpoint.
+    fn replay_bytes(&self) -> u64 {
+        self.buffer.len() as u64
+    }
+
+    fn fork(&self) -> Box<dyn Parser> {
+        Box::new(Self::new(&*self.input_stream, self.config.clone()))
+    }
+}
+
+/// Parquet format encoder.
+pub struct ParquetOutputFormat;
+
+fn default_weight_column() -> String {
+    "weight".to_string()
+}
+
+/// Parquet encoder configuration.
+#[derive(Deserialize, ToSchema)]
+pub struct ParquetEncoderConfig {
+    /// Name of the column that stores the weight of each output record.
+    ///
+    /// Defaults to `weight`.
+    #[serde(default = "default_weight_column")]
+    weight_column: String,
+}
+
+impl OutputFormat for ParquetOutputFormat {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("parquet")
+    }
+
+    fn new_encoder(
+        &self,
+        config: &YamlValue,
+        schema: Option<&RelationSchema>,
+        consumer: Box<dyn OutputConsumer>,
+    ) -> AnyResult<Box<dyn Encoder>> {
+        let config = ParquetEncoderConfig::deserialize(config)?;
+        let schema = schema.ok_or_else(|| {
+            anyhow!("Parquet encoder requires a schema of the output stream: records must be structs with fields of primitive types, or the stream must be registered with a schema")
+        })?;
+        if !consumer.separates_steps() {
+            return Err(anyhow!(
+                "Parquet encoder writes the output of each step as a separate file and requires a transport that keeps the output of different steps apart, e.g., the 'file' transport with 'max_file_steps: 1'"
+            ));
+        }
+        Ok(Box::new(ParquetEncoder::new(consumer, config, schema)?))
+    }
+}
+
+/// Encoder that writes the output of each step as a complete Parquet file.
+///
+/// The Arrow schema of the files is derived from the schema of the output
+/// stream, by default the schema of the Rust type of its records (see
+/// [`Catalog`](`crate::Catalog`)): a column per schema column, in schema
+/// order, followed by the weight column.  Records are serialized directly
+/// into Arrow arrays, matching struct fields with columns by name.  The
+/// output of each worker is written as a separate record batch, which the
+/// Parquet writer splits into row groups.
+///
+/// Each step is encoded as a self-contained file, including the footer, so
+/// the output of every step is readable as soon as the step completes.
+/// Since Parquet files can't be concatenated, the encoder requires an
+/// output endpoint that keeps the output of different steps apart (see
+/// [`OutputConsumer::separates_steps`]).
+struct ParquetEncoder {
+    /// Consumer to push serialized data to.
+    output_consumer: Box<dyn OutputConsumer>,
+
+    config: ParquetEncoderConfig,
+
+    /// Schema of the output stream.
+    relation_schema: RelationSchema,
+
+    /// Arrow schema of output files.
+    schema: Arc<Schema>,
+}
+
+impl ParquetEncoder {
+    fn new(
+        output_consumer: Box<dyn OutputConsumer>,
+        config: ParquetEncoderConfig,
+        schema: &RelationSchema,
+    ) -> AnyResult<Self> {
+        if schema.column(&config.weight_column).is_some() {
+            return Err(anyhow!(
+                "output stream has a column with the same name as the weight column '{}'",
+                config.weight_column
+            ));
+        }
+
+        let mut fields = schema
+            .columns
+            .iter()
+            .map(|column| {
+                let data_type = match column.column_type {
+                    ColumnType::Boolean => DataType::Boolean,
+                    ColumnType::Integer => DataType::Int64,
+                    ColumnType::Float => DataType::Float64,
+                    ColumnType::String => DataType::Utf8,
+                    ColumnType::Date => DataType::Date32,
+                    ColumnType::Timestamp => DataType::Timestamp(TimeUnit::Millisecond, None),
+                };
+                Field::new(&column.name, data_type, column.nullable)
+            })
+            .collect::<Vec<_>>();
+        fields.push(Field::new(&config.weight_column, DataType::Int64, false));
+
+        Ok(Self {
+            output_consumer,
+            config,
+            relation_schema: schema.clone(),
+            schema: Arc::new(Schema::new(fields)),
+        })
+    }
+}
+
+impl Encoder for ParquetEncoder {
+    fn consumer(&mut self) -> &mut dyn OutputConsumer {
+        self.output_consumer.as_mut()
+    }
+
+    fn encode(&mut self, batches: &[Arc<dyn SerBatch>]) -> AnyResult<()> {
+        let mut writer = None;
+
+        for batch in batches.iter().filter(|batch| !batch.is_empty()) {
+            let mut builder = RecordBuilder::new(&self.relation_schema, &self.config.weight_column);
+            let mut cursor = batch.cursor();
+
+            while cursor.key_valid() {
+                let w = cursor.weight();
+                builder.append(cursor.key(), w)?;
+                cursor.step_key();
+            }
+
+            if writer.is_none() {
+                writer = Some(ArrowWriter::try_new(Vec::new(), self.schema.clone(), None)?);
+            }
+            writer
+                .as_mut()
+                .unwrap()
+                .write(&builder.finish(self.schema.clone())?)?;
+        }
+
+        if let Some(writer) = writer {
+            // Write the file footer.
+            let file = writer.into_inner()?;
+            self.output_consumer.push_buffer(&file);
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds the columns of a record batch from serialized records.
+struct RecordBuilder<'a> {
+    columns: Vec<ColumnBuilder<'a>>,
+    weight_column: &'a str,
+    weights: Int64Builder,
+}
+
+impl<'a> RecordBuilder<'a> {
+    fn new(schema: &'a RelationSchema, weight_column: &'a str) -> Self {
+        Self {
+            columns: schema.columns.iter().map(ColumnBuilder::new).collect(),
+            weight_column,
+            weights: Int64Builder::new(),
+        }
+    }
+
+    /// Append a record with weight `weight`.
+    ///
+    /// On error, the builder is left in an inconsistent state and must be
+    /// discarded.
+    fn append(&mut self, record: &dyn ErasedSerialize, weight: i64) -> AnyResult<()> {
+        erased_serde::serialize(record, RecordSerializer { builder: self })
+            .map_err(|e| anyhow!("error encoding record: {e}"))?;
+        self.weights.append_value(weight);
+        Ok(())
+    }
+
+    fn finish(mut self, schema: Arc<Schema>) -> AnyResult<RecordBatch> {
+        let mut arrays = self
+            .columns
+            .iter_mut()
+            .map(|column| column.finish())
+            .collect::<Vec<_>>();
+        arrays.push(Arc::new(self.weights.finish()));
+        Ok(RecordBatch::try_new(schema, arrays)?)
+    }
+}
+
+/// Serializer that appends the fields of a struct to the columns of a
+/// [`RecordBuilder`] with the same names.
+///
+/// Fields that don't match any column are ignored.  Columns without a
+/// matching field receive `NULL` values.
+struct RecordSerializer<'a, 'b> {
+    builder: &'b mut RecordBuilder<'a>,
+}
+
+fn not_a_struct() -> EError {
+    SerError::custom("records must serialize as structs")
+}
+
+impl<'a, 'b> Serializer for RecordSerializer<'a, 'b> {
+    type Ok = ();
+    type Error = EError;
+    type SerializeSeq = Impossible<(), EError>;
+    type SerializeTuple = Impossible<(), EError>;
+    type SerializeTupleStruct = Impossible<(), EError>;
+    type SerializeTupleVariant = Impossible<(), EError>;
+    type SerializeMap = Impossible<(), EError>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), EError>;
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, EError> {
+        for column in self.builder.columns.iter_mut() {
+            column.has_value = false;
+        }
+        Ok(self)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), EError> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_char(self, _v: char) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_none(self) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_unit(self) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, EError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, EError> {
+        Err(not_a_struct())
+    }
+}
+
+impl<'a, 'b> SerializeStruct for RecordSerializer<'a, 'b> {
+    type Ok = ();
+    type Error = EError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), EError> {
+        if key == self.builder.weight_column {
+            return Err(SerError::custom(format!(
+                "record contains a field with the same name as the weight column '{key}'"
+            )));
+        }
+
+        if let Some(column) = self
+            .builder
+            .columns
+            .iter_mut()
+            .find(|column| column.schema.name == key)
+        {
+            if column.has_value {
+                return Err(column.error("duplicate field"));
+            }
+            value.serialize(&mut *column)?;
+            column.has_value = true;
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), EError> {
+        for column in self.builder.columns.iter_mut() {
+            if !column.has_value {
+                column.append_null()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Arrow array builder for a column type.
+enum ArrayBuilder {
+    Boolean(BooleanBuilder),
+    Integer(Int64Builder),
+    Float(Float64Builder),
+    String(StringBuilder),
+    Date(Date32Builder),
+    Timestamp(TimestampMillisecondBuilder),
+}
+
+/// Builds the array of a column from serialized values.
+///
+/// Values are converted to the type of the column the same way input values
+/// are coerced to column types (see [`ColumnType`]): e.g., dates and
+/// timestamps can be serialized as strings in any of the supported formats
+/// or as integers (days and milliseconds since the UNIX epoch respectively).
+struct ColumnBuilder<'a> {
+    schema: &'a ColumnSchema,
+    array: ArrayBuilder,
+
+    /// `true` if a value has been appended for the current record.
+    has_value: bool,
+}
+
+impl<'a> ColumnBuilder<'a> {
+    fn new(schema: &'a ColumnSchema) -> Self {
+        let array = match schema.column_type {
+            ColumnType::Boolean => ArrayBuilder::Boolean(BooleanBuilder::new()),
+            ColumnType::Integer => ArrayBuilder::Integer(Int64Builder::new()),
+            ColumnType::Float => ArrayBuilder::Float(Float64Builder::new()),
+            ColumnType::String => ArrayBuilder::String(StringBuilder::new()),
+            ColumnType::Date => ArrayBuilder::Date(Date32Builder::new()),
+            ColumnType::Timestamp => ArrayBuilder::Timestamp(TimestampMillisecondBuilder::new()),
+        };
+
+        Self {
+            schema,
+            array,
+            has_value: false,
+        }
+    }
+
+    fn error(&self, message: &str) -> EError {
+        SerError::custom(format!("column '{}': {message}", self.schema.name))
+    }
+
+    fn invalid<V: Display>(&self, value: V) -> EError {
+        self.error(&format!(
+            "cannot convert '{value}' to {}",
+            self.schema.column_type.name()
+        ))
+    }
+
+    fn append_null(&mut self) -> Result<(), EError> {
+        if !self.schema.nullable {
+            return Err(self.error("NULL value in a non-nullable column"));
+        }
+
+        match &mut self.array {
+            ArrayBuilder::Boolean(builder) => builder.append_null(),
+            ArrayBuilder::Integer(builder) => builder.append_null(),
+            ArrayBuilder::Float(builder) => builder.append_null(),
+            ArrayBuilder::String(builder) => builder.append_null(),
+            ArrayBuilder::Date(builder) => builder.append_null(),
+            ArrayBuilder::Timestamp(builder) => builder.append_null(),
+        }
+        Ok(())
+    }
+
+    fn append_bool(&mut self, v: bool) -> Result<(), EError> {
+        match &mut self.array {
+            ArrayBuilder::Boolean(builder) => builder.append_value(v),
+            ArrayBuilder::String(builder) => builder.append_value(v.to_string()),
+            _ => return Err(self.invalid(v)),
+        }
+        Ok(())
+    }
+
+    fn append_integer(&mut self, v: i64) -> Result<(), EError> {
+        match &mut self.array {
+            ArrayBuilder::Integer(builder) => builder.append_value(v),
+            ArrayBuilder::Float(builder) => builder.append_value(v as f64),
+            ArrayBuilder::String(builder) => builder.append_value(v.to_string()),
+            ArrayBuilder::Timestamp(builder) => builder.append_value(v),
+            ArrayBuilder::Date(builder) if i32::try_from(v).is_ok() => {
+                builder.append_value(v as i32)
+            }
+            _ => return Err(self.invalid(v)),
+        }
+        Ok(())
+    }
+
+    fn append_unsigned(&mut self, v: u64) -> Result<(), EError> {
+        match i64::try_from(v) {
+            Ok(v) => self.append_integer(v),
+            Err(_) => Err(self.invalid(v)),
+        }
+    }
+
+    fn append_float(&mut self, v: f64) -> Result<(), EError> {
+        match &mut self.array {
+            ArrayBuilder::Float(builder) => builder.append_value(v),
+            ArrayBuilder::String(builder) => builder.append_value(v.to_string()),
+            _ => return Err(self.invalid(v)),
+        }
+        Ok(())
+    }
+
+    fn append_str(&mut self, v: &str) -> Result<(), EError> {
+        if let ArrayBuilder::String(builder) = &mut self.array {
+            builder.append_value(v);
+            return Ok(());
+        }
+
+        let s = match self
+            .schema
+            .coerce_str(v)
+            .map_err(<EError as SerError>::custom)?
+        {
+            Coerced::Null => return self.append_null(),
+            Coerced::Str(s) => s,
+        };
+        // `coerce_str` has validated `s`.
+        match &mut self.array {
+            ArrayBuilder::Boolean(builder) => builder.append_value(s == "true"),
+            ArrayBuilder::Integer(builder) => builder.append_value(s.parse().unwrap()),
+            ArrayBuilder::Float(builder) => builder.append_value(s.parse().unwrap()),
+            ArrayBuilder::Date(builder) => builder.append_value(date_to_days(&s).unwrap() as i32),
+            ArrayBuilder::Timestamp(builder) => {
+                builder.append_value(timestamp_to_millis(&s).unwrap())
+            }
+            ArrayBuilder::String(_) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match &mut self.array {
+            ArrayBuilder::Boolean(builder) => Arc::new(builder.finish()),
+            ArrayBuilder::Integer(builder) => Arc::new(builder.finish()),
+            ArrayBuilder::Float(builder) => Arc::new(builder.finish()),
+            ArrayBuilder::String(builder) => Arc::new(builder.finish()),
+            ArrayBuilder::Date(builder) => Arc::new(builder.finish()),
+            ArrayBuilder::Timestamp(builder) => Arc::new(builder.finish()),
+        }
+    }
+
+    fn unsupported(&self) -> EError {
+        self.error("unsupported value type")
+    }
+}
+
+/// Serializer that appends a value to a column.
+impl<'a, 'b> Serializer for &'b mut ColumnBuilder<'a> {
+    type Ok = ();
+    type Error = EError;
+    type SerializeSeq = Impossible<(), EError>;
+    type SerializeTuple = Impossible<(), EError>;
+    type SerializeTupleStruct = Impossible<(), EError>;
+    type SerializeTupleVariant = Impossible<(), EError>;
+    type SerializeMap = Impossible<(), EError>;
+    type SerializeStruct = Impossible<(), EError>;
+    type SerializeStructVariant = Impossible<(), EError>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), EError> {
+        self.append_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), EError> {
+        self.append_integer(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), EError> {
+        self.append_integer(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), EError> {
+        self.append_integer(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), EError> {
+        self.append_integer(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), EError> {
+        self.append_integer(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), EError> {
+        self.append_integer(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), EError> {
+        self.append_integer(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), EError> {
+        self.append_unsigned(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), EError> {
+        self.append_float(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), EError> {
+        self.append_float(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), EError> {
+        self.append_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), EError> {
+        self.append_str(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), EError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_none(self) -> Result<(), EError> {
+        self.append_null()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), EError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), EError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), EError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), EError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), EError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), EError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, EError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, EError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, EError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, EError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, EError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, EError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, EError> {
+        Err(self.unsupported())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ParquetInputFormat, ParquetOutputFormat};
+    use crate::{
+        seroutput::SerBatchImpl,
+        test::{test_circuit, wait, MockDeZSet, MockOutputConsumer, TestStruct},
+        ColumnSchema, ColumnType, Controller, InputFormat, OutputConsumer, OutputFormat,
+        PipelineConfig, RelationSchema, SerBatch, Step,
+    };
+    use bincode::{Decode, Encode};
+    use csv::WriterBuilder as CsvWriterBuilder;
+    use dbsp::{trace::Batch, OrdZSet};
+    use serde::{Deserialize, Serialize};
+    use size_of::SizeOf;
+    use std::{fs, sync::Arc};
+    use tempfile::{tempdir, NamedTempFile};
+
+    fn test_data() -> Vec<TestStruct> {
+        vec![
+            TestStruct {
+                id: 1,
+                b: true,
+                i: Some(10),
+                s: "foo".to_string(),
+            },
+            TestStruct {
+                id: 2,
+                b: false,
+                i: None,
+                s: "bar".to_string(),
+            },
+            TestStruct {
+                id: 3,
+                b: false,
+                i: Some(-1),
+                s: "".to_string(),
+            },
+        ]
+    }
+
+    /// Parse Parquet files, feeding each one to the parser in small chunks.
+    fn parse_files<T>(files: &[Vec<u8>]) -> (Vec<usize>, Vec<(T, bool)>)
+    where
+        T: for<'de> Deserialize<'de> + Clone + Send + 'static,
+    {
+        let zset = MockDeZSet::<T>::new();
+        let mut parser = ParquetInputFormat
+            .new_parser(
+                &zset,
+                &serde_yaml::from_str("weight_column: weight").unwrap(),
+            )
+            .unwrap();
+
+        let mut num_records = Vec::new();
+        for file in files.iter() {
+            for chunk in file.chunks(100) {
+                assert_eq!(parser.input(chunk), (0, Vec::new()));
+            }
+            let (n, errors) = parser.eoi();
+            assert!(errors.is_empty(), "{errors:?}");
+            num_records.push(n);
+        }
+        parser.flush();
+
+        let flushed = zset.state().flushed.drain(..).collect();
+        (num_records, flushed)
+    }
+
+    #[test]
+    fn test_parquet_roundtrip() {
+        let data = test_data();
+
+        // Write two steps, the second one containing a deletion.
+        let consumer = MockOutputConsumer::default();
+        let mut encoder = ParquetOutputFormat
+            .new_encoder(
+                &serde_yaml::Value::Null,
+                Some(&RelationSchema::of::<TestStruct>().unwrap()),
+                Box::new(consumer.clone()),
+            )
+            .unwrap();
+
+        let batch1 = OrdZSet::from_tuples((), vec![(data[0].clone(), 1), (data[1].clone(), 2)]);
+        let batch2 = OrdZSet::from_tuples((), vec![(data[0].clone(), -1), (data[2].clone(), 1)]);
+        for (step, batch) in [batch1, batch2].into_iter().enumerate() {
+            let batch = Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>;
+            encoder.encode_step(step as u64, &[batch]).unwrap();
+        }
+
+        // Each step is written as a complete file.
+        let files = consumer.buffers();
+        assert_eq!(files.len(), 2);
+
+        let (num_records, flushed) = parse_files::<TestStruct>(&files);
+        assert_eq!(num_records, vec![2, 2]);
+        assert_eq!(
+            flushed,
+            vec![
+                (data[0].clone(), true),
+                (data[1].clone(), true),
+                (data[1].clone(), true),
+                (data[0].clone(), false),
+                (data[2].clone(), true),
+            ]
+        );
+    }
+
+    /// A record with dates and timestamps represented as strings and
+    /// integers.
+    #[derive(
+        Clone,
+        Debug,
+        PartialEq,
+        Eq,
+        PartialOrd,
+        Ord,
+        Hash,
+        Serialize,
+        Deserialize,
+        SizeOf,
+        Encode,
+        Decode,
+    )]
+    struct Event {
+        day: String,
+        ts: Option<String>,
+        ts_millis: i64,
+    }
+
+    #[test]
+    fn test_parquet_dates() {
+        // Columns of a hand-written schema override the types of the record
+        // fields.
+        let schema = RelationSchema::new(vec![
+            ColumnSchema::new("day", ColumnType::Date, false),
+            ColumnSchema::new("ts", ColumnType::Timestamp, true),
+            ColumnSchema::new("ts_millis", ColumnType::Timestamp, false),
+        ]);
+        let event = Event {
+            day: "2023/03/01".to_string(),
+            ts: Some("2023-03-01T10:20:30.5Z".to_string()),
+            ts_millis: 1677666030000,
+        };
+
+        let consumer = MockOutputConsumer::default();
+        let mut encoder = ParquetOutputFormat
+            .new_encoder(
+                &serde_yaml::Value::Null,
+                Some(&schema),
+                Box::new(consumer.clone()),
+            )
+            .unwrap();
+        let batch = OrdZSet::from_tuples((), vec![(event, 1)]);
+        let batch = Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>;
+        encoder.encode_step(0, &[batch]).unwrap();
+
+        /// Dates and timestamps are parsed as strings in canonical form.
+        #[derive(Clone, Debug, PartialEq, Deserialize)]
+        struct ParsedEvent {
+            day: String,
+            ts: Option<String>,
+            ts_millis: String,
+        }
+
+        let (_, flushed) = parse_files::<ParsedEvent>(&consumer.buffers());
+        assert_eq!(
+            flushed,
+            vec![(
+                ParsedEvent {
+                    day: "2023-03-01".to_string(),
+                    ts: Some("2023-03-01 10:20:30.500".to_string()),
+                    ts_millis: "2023-03-01 10:20:30".to_string(),
+                },
+                true
+            )]
+        );
+
+        // Values that can't be converted to the column type fail the step.
+        let invalid = Event {
+            day: "yesterday".to_string(),
+            ts: None,
+            ts_millis: 0,
+        };
+        let batch = OrdZSet::from_tuples((), vec![(invalid, 1)]);
+        let batch = Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>;
+        assert_eq!(
+            encoder.encode_step(1, &[batch]).unwrap_err().to_string(),
+            "error encoding record: column 'day': cannot convert 'yesterday' to date"
+        );
+    }
+
+    /// Consumer that concatenates the output of all steps.
+    struct StreamConsumer;
+
+    impl OutputConsumer for StreamConsumer {
+        fn batch_start(&mut self, _step: Step) {}
+        fn push_buffer(&mut self, _buffer: &[u8]) {}
+        fn batch_end(&mut self) {}
+    }
+
+    #[test]
+    fn test_parquet_schema() {
+        // The encoder requires a schema.
+        assert!(ParquetOutputFormat
+            .new_encoder(
+                &serde_yaml::Value::Null,
+                None,
+                Box::new(MockOutputConsumer::default()),
+            )
+            .is_err());
+
+        // The schema can't contain the weight column.
+        let mut schema = RelationSchema::of::<TestStruct>().unwrap();
+        schema
+            .columns
+            .push(ColumnSchema::new("weight", ColumnType::Integer, false));
+        assert!(ParquetOutputFormat
+            .new_encoder(
+                &serde_yaml::Value::Null,
+                Some(&schema),
+                Box::new(MockOutputConsumer::default()),
+            )
+            .is_err());
+
+        // The output of different steps must be kept apart.
+        assert!(ParquetOutputFormat
+            .new_encoder(
+                &serde_yaml::Value::Null,
+                Some(&RelationSchema::of::<TestStruct>().unwrap()),
+                Box::new(StreamConsumer),
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_parquet_truncated() {
+        let zset = MockDeZSet::<TestStruct>::new();
+        let mut parser = ParquetInputFormat
+            .new_parser(&zset, &serde_yaml::Value::Null)
+            .unwrap();
+
+        assert_eq!(parser.input(b"PAR1garbage"), (0, Vec::new()));
+        assert_eq!(parser.eoi().1.len(), 1);
+    }
+
+    fn output_config(input_path: &str, output_config: &str) -> PipelineConfig {
+        let config_str = format!(
+            r#"
+step_trigger: manual
+inputs:
+    test_input1:
+        stream: test_input1
+        transport:
+            name: file
+            config:
+                path: {input_path:?}
+                follow: true
+        format:
+            name: csv
+outputs:
+    test_output1:
+        stream: test_output1
+        transport:
+            name: file
+            config:
+                {output_config}
+        format:
+            name: parquet
+"#
+        );
+        serde_yaml::from_str(&config_str).unwrap()
+    }
+
+    #[test]
+    fn test_parquet_file_output() {
+        let data = test_data();
+        let input_file = NamedTempFile::new().unwrap();
+        let output_dir = tempdir().unwrap();
+        let input_path = input_file.path().to_str().unwrap();
+
+        // Appending the output of a step to a complete Parquet file would
+        // corrupt it.
+        let single_file_dir = tempdir().unwrap();
+        let output_path = single_file_dir.path().join("out.parquet");
+        let (circuit, catalog) = test_circuit(4);
+        let error = Controller::with_config(
+            circuit,
+            catalog,
+            &output_config(input_path, &format!("path: {:?}", output_path)),
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .err()
+        .unwrap();
+        assert!(error.to_string().contains("max_file_steps: 1"), "{error}");
+
+        // Write each step to a new file.  The schema of the output is derived
+        // from the type of its records.
+        let output_path = output_dir.path().join("out-{step}.parquet");
+        let (circuit, catalog) = test_circuit(4);
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &output_config(
+                input_path,
+                &format!("{{path: {:?}, max_file_steps: 1}}", output_path),
+            ),
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+        controller.start();
+
+        let mut writer = CsvWriterBuilder::new()
+            .has_headers(false)
+            .from_writer(input_file.as_file());
+        for (step, records) in [&data[..2], &data[2..]].into_iter().enumerate() {
+            for record in records.iter() {
+                writer.serialize(record).unwrap();
+            }
+            writer.flush().unwrap();
+
+            let num_records = if step == 0 { 2 } else { data.len() };
+            wait(
+                || controller.status().num_total_input_records() == num_records as u64,
+                None,
+            );
+            controller.step().unwrap();
+        }
+        wait(
+            || {
+                controller
+                    .status()
+                    .output_status()
+                    .get(&0)
+                    .unwrap()
+                    .transmitted_records()
+                    == data.len() as u64
+            },
+            None,
+        );
+        controller.stop().unwrap();
+
+        let mut paths = fs::read_dir(output_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(paths.len(), 2);
+        let files = paths
+            .iter()
+            .map(|path| fs::read(path).unwrap())
+            .collect::<Vec<_>>();
+
+        let (num_records, mut flushed) = parse_files::<TestStruct>(&files);
+        flushed.sort();
+        assert_eq!(num_records.iter().sum::<usize>(), data.len());
+        assert_eq!(
+            flushed,
+            data.into_iter()
+                .map(|record| (record, true))
+                .collect::<Vec<_>>()
+        );
+    }
+}
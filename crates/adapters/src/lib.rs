@@ -112,6 +112,7 @@ mod catalog;
 mod controller;
 mod deinput;
 pub mod format;
+mod schema;
 mod seroutput;
 #[cfg(feature = "server")]
 pub mod server;
@@ -136,17 +137,22 @@ pub use catalog::Catalog;
 pub use deinput::{
     DeCollectionHandle, DeMapHandle, DeScalarHandle, DeScalarHandleImpl, DeSetHandle, DeZSetHandle,
 };
-pub use format::{Encoder, InputFormat, OutputConsumer, OutputFormat, Parser};
-pub use seroutput::{SerBatch, SerCursor, SerOutputBatchHandle};
+pub use format::{Encoder, InputFormat, OutputConsumer, OutputFormat, ParseError, Parser};
+pub use schema::{CatalogSchema, ColumnSchema, ColumnType, RelationSchema};
+pub use seroutput::{
+    SerBatch, SerCursor, SerMaterializedHandle, SerOutputBatchHandle, SerSnapshot,
+};
 
 pub use controller::{
-    Controller, ControllerError, ControllerStatus, FormatConfig, GlobalPipelineConfig,
-    InputEndpointConfig, OutputEndpointConfig, PipelineConfig, TransportConfig,
+    Controller, ControllerError, ControllerHandle, ControllerStatus, DeadLetter, DeadLetterConfig,
+    FormatConfig, GlobalPipelineConfig, InputEndpointConfig, OutputEndpointConfig, OutputMode,
+    OutputQuery, PipelineConfig, StepTrigger, TransportConfig,
 };
+pub use transport::DirectoryInputTransport;
 pub use transport::{
     FileInputTransport, InputConsumer, InputEndpoint, InputTransport, OutputEndpoint,
-    OutputTransport,
+    OutputTransport, SnapshotSink, Step,
 };
 
 #[cfg(feature = "server")]
-pub use transport::{HttpInputTransport, HttpOutputTransport};
+pub use transport::{HttpInputTransport, HttpOutputConfig, HttpOutputTransport};
@@ -0,0 +1,925 @@
+//! Schemas of input and output streams.
+//!
+//! A [`RelationSchema`] describes the columns of the records carried by a
+//! stream.  Schemas are registered in the [`Catalog`](`crate::Catalog`)
+//! alongside stream handles and are reported by the `/metadata` endpoint of
+//! the server.
+//!
+//! Parsers use the schema of an input stream to coerce input values to the
+//! declared column types before deserializing them into the Rust types of the
+//! stream, e.g., to accept numbers encoded as strings or timestamps in
+//! several common formats, and to report errors that identify the offending
+//! column instead of opaque deserialization errors.
+//!
+//! Output streams are described by schemas derived from the Rust types of
+//! their records (see [`RelationSchema::of`]), unless they are registered
+//! with an explicit schema.  Formats that store typed columns, e.g., Parquet,
+//! use output schemas to lay out their output.
+
+use crate::DeCollectionHandle;
+use anyhow::{anyhow, Result as AnyResult};
+use erased_serde::{Deserializer as ErasedDeserializer, Error as EError};
+use serde::{
+    de::{DeserializeOwned, DeserializeSeed, Error as _, IntoDeserializer, MapAccess, Visitor},
+    forward_to_deserialize_any, Deserialize, Deserializer, Serialize,
+};
+use serde_json::{Number as JsonNumber, Value as JsonValue};
+use std::{any::type_name, borrow::Cow, collections::BTreeMap, slice, sync::Arc};
+use utoipa::ToSchema;
+
+/// Column type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnType {
+    /// Boolean.  Accepts `true`/`false`, `t`/`f`, `yes`/`no`, and `1`/`0`
+    /// (case-insensitive) in string form.
+    Boolean,
+
+    /// Signed integer.  Accepts integers encoded as strings and floating
+    /// point numbers without a fractional part.
+    Integer,
+
+    /// Floating point number.  Accepts numbers encoded as strings.
+    Float,
+
+    /// String.  Numbers and booleans are converted to strings.
+    String,
+
+    /// Calendar date.  Accepts `YYYY-MM-DD` and `YYYY/MM/DD`; converted to
+    /// `YYYY-MM-DD`.
+    Date,
+
+    /// Date and time without a time zone.  Accepts `YYYY-MM-DD HH:MM:SS`
+    /// and `YYYY-MM-DDTHH:MM:SS`, with optional fractional seconds and a
+    /// `Z` or `+00:00` suffix, as well as integer milliseconds since the
+    /// UNIX epoch; converted to `YYYY-MM-DD HH:MM:SS[.fff]`.
+    Timestamp,
+}
+
+impl ColumnType {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Boolean => "boolean",
+            Self::Integer => "integer",
+            Self::Float => "float",
+            Self::String => "string",
+            Self::Date => "date",
+            Self::Timestamp => "timestamp",
+        }
+    }
+}
+
+/// Column description.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ColumnSchema {
+    /// Column name.
+    pub name: String,
+
+    /// Column type.
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+
+    /// `true` if the column accepts `NULL` values.
+    #[serde(default)]
+    pub nullable: bool,
+}
+
+impl ColumnSchema {
+    pub fn new(name: &str, column_type: ColumnType, nullable: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            column_type,
+            nullable,
+        }
+    }
+
+    /// Coerce a JSON value to the type of the column.
+    ///
+    /// `None` represents a missing field.
+    fn coerce_json(&self, value: Option<&JsonValue>) -> AnyResult<JsonValue> {
+        let value = match value {
+            None | Some(JsonValue::Null) if self.nullable => return Ok(JsonValue::Null),
+            None => return Err(self.error("missing value of a non-nullable column")),
+            Some(JsonValue::Null) => return Err(self.error("NULL value in a non-nullable column")),
+            Some(value) => value,
+        };
+
+        let invalid = || {
+            self.error(&format!(
+                "cannot convert '{value}' to {}",
+                self.column_type.name()
+            ))
+        };
+
+        match (self.column_type, value) {
+            (ColumnType::Boolean, JsonValue::Bool(_))
+            | (ColumnType::Float, JsonValue::Number(_))
+            | (ColumnType::String, JsonValue::String(_)) => Ok(value.clone()),
+            (ColumnType::Boolean, JsonValue::Number(n)) => match n.as_i64() {
+                Some(0) => Ok(JsonValue::Bool(false)),
+                Some(1) => Ok(JsonValue::Bool(true)),
+                _ => Err(invalid()),
+            },
+            (ColumnType::Integer, JsonValue::Number(n)) => {
+                if n.is_i64() || n.is_u64() {
+                    Ok(value.clone())
+                } else {
+                    n.as_f64()
+                        .and_then(float_to_integer)
+                        .map(JsonValue::from)
+                        .ok_or_else(invalid)
+                }
+            }
+            (ColumnType::String, JsonValue::Number(_) | JsonValue::Bool(_)) => {
+                Ok(JsonValue::String(value.to_string()))
+            }
+            (ColumnType::Timestamp, JsonValue::Number(n)) => n
+                .as_i64()
+                .map(|millis| JsonValue::String(timestamp_from_millis(millis)))
+                .ok_or_else(invalid),
+            (_, JsonValue::String(s)) => match self.coerce_str(s)? {
+                Coerced::Null => Ok(JsonValue::Null),
+                Coerced::Str(s) => Ok(match self.column_type {
+                    ColumnType::Boolean => JsonValue::Bool(s == "true"),
+                    ColumnType::Integer => JsonValue::from(s.parse::<i64>().unwrap()),
+                    ColumnType::Float => s
+                        .parse::<f64>()
+                        .ok()
+                        .and_then(JsonNumber::from_f64)
+                        .map(JsonValue::Number)
+                        .ok_or_else(invalid)?,
+                    _ => JsonValue::String(s.into_owned()),
+                }),
+            },
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Coerce the text representation of a value, e.g., a CSV field, to the
+    /// canonical text representation of the column type.
+    ///
+    /// An empty string represents `NULL` for all types except `String`.
+    pub(crate) fn coerce_str<'a>(&self, s: &'a str) -> AnyResult<Coerced<'a>> {
+        if self.column_type != ColumnType::String && s.trim().is_empty() {
+            return if self.nullable {
+                Ok(Coerced::Null)
+            } else {
+                Err(self.error("NULL value in a non-nullable column"))
+            };
+        }
+
+        let invalid = || {
+            self.error(&format!(
+                "cannot convert '{s}' to {}",
+                self.column_type.name()
+            ))
+        };
+        let trimmed = s.trim();
+
+        let coerced = match self.column_type {
+            ColumnType::String => Cow::Borrowed(s),
+            ColumnType::Boolean => match trimmed.to_ascii_lowercase().as_str() {
+                "true" | "t" | "yes" | "y" | "1" => Cow::Borrowed("true"),
+                "false" | "f" | "no" | "n" | "0" => Cow::Borrowed("false"),
+                _ => return Err(invalid()),
+            },
+            ColumnType::Integer => match trimmed.parse::<i64>() {
+                Ok(_) => Cow::Borrowed(trimmed),
+                Err(_) => trimmed
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(float_to_integer)
+                    .map(|i| Cow::Owned(i.to_string()))
+                    .ok_or_else(invalid)?,
+            },
+            ColumnType::Float => {
+                trimmed.parse::<f64>().map_err(|_| invalid())?;
+                Cow::Borrowed(trimmed)
+            }
+            ColumnType::Date => {
+                let (year, month, day) = parse_date(trimmed).ok_or_else(invalid)?;
+                Cow::Owned(format!("{year:04}-{month:02}-{day:02}"))
+            }
+            ColumnType::Timestamp => Cow::Owned(parse_timestamp(trimmed).ok_or_else(invalid)?),
+        };
+
+        Ok(Coerced::Str(coerced))
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        anyhow!("column '{}': {message}", self.name)
+    }
+}
+
+/// Result of coercing a value in text form.
+pub(crate) enum Coerced<'a> {
+    Null,
+    Str(Cow<'a, str>),
+}
+
+/// Description of the records in a stream.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RelationSchema {
+    /// Columns in the order in which they appear in positional formats like
+    /// CSV.
+    pub columns: Vec<ColumnSchema>,
+}
+
+impl RelationSchema {
+    pub fn new(columns: Vec<ColumnSchema>) -> Self {
+        Self { columns }
+    }
+
+    /// Derive the schema of records of type `T` from its `Deserialize`
+    /// implementation.
+    ///
+    /// `T` must be a struct whose fields are booleans, integers, floating
+    /// point numbers, characters or strings, or `Option`s of those, possibly
+    /// wrapped in newtype structs.  The schema has a column per field, in
+    /// field order, named after the field, including `serde` renames.
+    /// `Option` fields are nullable.
+    pub fn of<T: DeserializeOwned>() -> AnyResult<Self> {
+        let mut columns = Vec::new();
+        T::deserialize(RecordTracer {
+            columns: &mut columns,
+        })
+        .map_err(|e| {
+            anyhow!(
+                "cannot derive the schema of type '{}': {e}",
+                type_name::<T>()
+            )
+        })?;
+        Ok(Self::new(columns))
+    }
+
+    /// Look up column by name.
+    pub fn column(&self, name: &str) -> Option<&ColumnSchema> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// Coerce the fields of a record represented as a JSON object or array
+    /// to column types.
+    ///
+    /// Object fields are matched with columns by name; fields that don't
+    /// match any column are passed through unmodified.  Array elements are
+    /// matched with columns by position.  Other values are returned
+    /// unmodified.
+    pub fn coerce_json(&self, record: &JsonValue) -> AnyResult<JsonValue> {
+        match record {
+            JsonValue::Object(fields) => {
+                let mut result = fields.clone();
+                for column in self.columns.iter() {
+                    let value = column.coerce_json(fields.get(&column.name))?;
+                    if value.is_null() && !fields.contains_key(&column.name) {
+                        continue;
+                    }
+                    result.insert(column.name.clone(), value);
+                }
+                Ok(JsonValue::Object(result))
+            }
+            JsonValue::Array(values) => {
+                self.check_len(values.len())?;
+                Ok(JsonValue::Array(
+                    self.columns
+                        .iter()
+                        .zip(values.iter())
+                        .map(|(column, value)| column.coerce_json(Some(value)))
+                        .collect::<AnyResult<Vec<_>>>()?,
+                ))
+            }
+            _ => Ok(record.clone()),
+        }
+    }
+
+    /// Coerce the fields of a record in text form, e.g., a CSV record, to
+    /// canonical representations of column types.
+    ///
+    /// Fields are matched with columns by position.  Empty fields represent
+    /// `NULL` values.
+    pub fn coerce_fields<'a>(&self, fields: &[&'a str]) -> AnyResult<Vec<Cow<'a, str>>> {
+        self.check_len(fields.len())?;
+        self.columns
+            .iter()
+            .zip(fields.iter())
+            .map(|(column, field)| match column.coerce_str(field)? {
+                Coerced::Null => Ok(Cow::Borrowed("")),
+                Coerced::Str(s) => Ok(s),
+            })
+            .collect()
+    }
+
+    fn check_len(&self, len: usize) -> AnyResult<()> {
+        if len != self.columns.len() {
+            return Err(anyhow!(
+                "expected {} columns, found {len}",
+                self.columns.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Deserializer that records the fields of the struct it deserializes as
+/// columns (see [`RelationSchema::of`]).
+struct RecordTracer<'a> {
+    columns: &'a mut Vec<ColumnSchema>,
+}
+
+impl<'de, 'a> Deserializer<'de> for RecordTracer<'a> {
+    type Error = EError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, EError> {
+        Err(EError::custom("records must be structs"))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, EError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, EError> {
+        visitor.visit_map(FieldTracer {
+            fields: fields.iter(),
+            columns: self.columns,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct seq tuple tuple_struct map enum
+        identifier ignored_any
+    }
+}
+
+/// Feeds the fields of a struct to its `Deserialize` implementation, adding
+/// a column for each field.
+struct FieldTracer<'a> {
+    fields: slice::Iter<'static, &'static str>,
+    columns: &'a mut Vec<ColumnSchema>,
+}
+
+impl<'de, 'a> MapAccess<'de> for FieldTracer<'a> {
+    type Error = EError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, EError> {
+        match self.fields.next() {
+            None => Ok(None),
+            Some(field) => {
+                // The type of the column is set by `next_value_seed`.
+                self.columns
+                    .push(ColumnSchema::new(field, ColumnType::String, false));
+                seed.deserialize(IntoDeserializer::<EError>::into_deserializer(*field))
+                    .map(Some)
+            }
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, EError> {
+        let column = self.columns.last_mut().unwrap();
+        seed.deserialize(ColumnTracer { column })
+    }
+}
+
+/// Deserializer that records the type of a column and produces a
+/// placeholder value of that type.
+struct ColumnTracer<'a> {
+    column: &'a mut ColumnSchema,
+}
+
+impl<'de, 'a> Deserializer<'de> for ColumnTracer<'a> {
+    type Error = EError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, EError> {
+        Err(EError::custom(format!(
+            "column '{}' has an unsupported type",
+            self.column.name
+        )))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.column.column_type = ColumnType::Boolean;
+        visitor.visit_bool(false)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.column.column_type = ColumnType::Integer;
+        visitor.visit_i8(0)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.column.column_type = ColumnType::Integer;
+        visitor.visit_i16(0)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.column.column_type = ColumnType::Integer;
+        visitor.visit_i32(0)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.column.column_type = ColumnType::Integer;
+        visitor.visit_i64(0)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.column.column_type = ColumnType::Integer;
+        visitor.visit_u8(0)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.column.column_type = ColumnType::Integer;
+        visitor.visit_u16(0)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.column.column_type = ColumnType::Integer;
+        visitor.visit_u32(0)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.column.column_type = ColumnType::Integer;
+        visitor.visit_u64(0)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.column.column_type = ColumnType::Float;
+        visitor.visit_f32(0.0)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.column.column_type = ColumnType::Float;
+        visitor.visit_f64(0.0)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.column.column_type = ColumnType::String;
+        visitor.visit_char(' ')
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.column.column_type = ColumnType::String;
+        visitor.visit_str("")
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EError> {
+        self.column.nullable = true;
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, EError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 bytes byte_buf unit unit_struct seq tuple tuple_struct map
+        struct enum identifier ignored_any
+    }
+}
+
+/// Schemas of the input and output streams of a circuit.
+///
+/// Returned by [`Catalog::schema`](`crate::Catalog::schema`).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CatalogSchema {
+    /// Input stream schemas by stream name.
+    pub inputs: BTreeMap<String, RelationSchema>,
+
+    /// Output stream schemas by stream name.
+    pub outputs: BTreeMap<String, RelationSchema>,
+}
+
+/// Input handle that attaches a schema to another handle.
+///
+/// Created by [`Catalog`](`crate::Catalog`) for input streams registered
+/// with a schema.  Deleted records are coerced using the same schema as
+/// inserted records, so a schema should only be attached to streams that
+/// support deletion by value, e.g., Z-sets and sets.
+pub(crate) struct SchemaHandle {
+    handle: Box<dyn DeCollectionHandle>,
+    schema: Arc<RelationSchema>,
+}
+
+impl SchemaHandle {
+    pub(crate) fn new(handle: Box<dyn DeCollectionHandle>, schema: Arc<RelationSchema>) -> Self {
+        Self { handle, schema }
+    }
+}
+
+impl DeCollectionHandle for SchemaHandle {
+    fn insert(&mut self, deserializer: &mut dyn ErasedDeserializer) -> Result<(), EError> {
+        self.handle.insert(deserializer)
+    }
+
+    fn delete(&mut self, deserializer: &mut dyn ErasedDeserializer) -> Result<(), EError> {
+        self.handle.delete(deserializer)
+    }
+
+    fn update_weighted(
+        &mut self,
+        deserializer: &mut dyn ErasedDeserializer,
+        weight: i64,
+    ) -> Result<(), EError> {
+        self.handle.update_weighted(deserializer, weight)
+    }
+
+    fn reserve(&mut self, reservation: usize) {
+        self.handle.reserve(reservation)
+    }
+
+    fn flush(&mut self) {
+        self.handle.flush()
+    }
+
+    fn clear_buffer(&mut self) {
+        self.handle.clear_buffer()
+    }
+
+    fn fork(&self) -> Box<dyn DeCollectionHandle> {
+        Box::new(Self::new(self.handle.fork(), self.schema.clone()))
+    }
+
+    fn schema(&self) -> Option<&RelationSchema> {
+        Some(&self.schema)
+    }
+}
+
+/// Convert a float without a fractional part to an integer.
+///
+/// `i64::MAX as f64` rounds up to 2^63, which does not fit in an `i64`, so
+/// the upper bound is exclusive.
+fn float_to_integer(f: f64) -> Option<i64> {
+    if f.fract() == 0.0 && (-9223372036854775808.0..9223372036854775808.0).contains(&f) {
+        Some(f as i64)
+    } else {
+        None
+    }
+}
+
+/// Parse a string of at most `max_len` decimal digits.
+fn parse_digits(s: &str, max_len: usize) -> Option<u32> {
+    if s.is_empty() || s.len() > max_len || !s.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn is_leap_year(year: u32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parse a date in `YYYY-MM-DD` or `YYYY/MM/DD` format.
+fn parse_date(s: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = s.split(|c| c == '-' || c == '/');
+    let year = parse_digits(parts.next()?, 4)?;
+    let month = parse_digits(parts.next()?, 2)?;
+    let day = parse_digits(parts.next()?, 2)?;
+    if parts.next().is_some()
+        || !(1..=12).contains(&month)
+        || day == 0
+        || day > days_in_month(year, month)
+    {
+        return None;
+    }
+    Some((year, month, day))
+}
+
+/// Parse a time in `HH:MM:SS[.fff]` format; returns the time in canonical
+/// form.
+fn parse_time(s: &str) -> Option<String> {
+    let (hms, fraction) = match s.split_once('.') {
+        Some((hms, fraction)) => (hms, Some(fraction)),
+        None => (s, None),
+    };
+    let mut parts = hms.split(':');
+    let hour = parse_digits(parts.next()?, 2)?;
+    let minute = parse_digits(parts.next()?, 2)?;
+    let second = parse_digits(parts.next()?, 2)?;
+    if parts.next().is_some() || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let mut time = format!("{hour:02}:{minute:02}:{second:02}");
+    if let Some(fraction) = fraction {
+        parse_digits(fraction, 9)?;
+        time.push('.');
+        time.push_str(fraction);
+    }
+    Some(time)
+}
+
+/// Parse a timestamp in one of the formats accepted by
+/// [`ColumnType::Timestamp`]; returns the timestamp in canonical form.
+fn parse_timestamp(s: &str) -> Option<String> {
+    if let Ok(millis) = s.parse::<i64>() {
+        return Some(timestamp_from_millis(millis));
+    }
+
+    let s = s
+        .strip_suffix('Z')
+        .or_else(|| s.strip_suffix("+00:00"))
+        .unwrap_or(s);
+    let (date, time) = s.split_once(|c| c == 'T' || c == ' ')?;
+    let (year, month, day) = parse_date(date)?;
+    let time = parse_time(time.trim_start())?;
+    Some(format!("{year:04}-{month:02}-{day:02} {time}"))
+}
+
+/// Convert a number of days since the UNIX epoch to a civil date (see
+/// http://howardhinnant.github.io/date_algorithms.html#civil_from_days).
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Convert a civil date to the number of days since the UNIX epoch (see
+/// http://howardhinnant.github.io/date_algorithms.html#days_from_civil).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
+
+/// Convert a number of days since the UNIX epoch to a canonical date.
+pub(crate) fn date_from_days(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Convert a date in one of the formats accepted by [`ColumnType::Date`] to
+/// the number of days since the UNIX epoch.
+pub(crate) fn date_to_days(s: &str) -> Option<i64> {
+    let (year, month, day) = parse_date(s)?;
+    Some(days_from_civil(year as i64, month as i64, day as i64))
+}
+
+/// Convert a timestamp in one of the formats accepted by
+/// [`ColumnType::Timestamp`] to milliseconds since the UNIX epoch.
+///
+/// Digits of fractional seconds beyond milliseconds are truncated.
+pub(crate) fn timestamp_to_millis(s: &str) -> Option<i64> {
+    let timestamp = parse_timestamp(s)?;
+    let (date, time) = timestamp.split_once(' ')?;
+    let (hms, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut parts = hms.split(':').map(|part| part.parse::<i64>().ok());
+    let (hour, minute, second) = (parts.next()??, parts.next()??, parts.next()??);
+    let millis = format!("{:0<3}", &fraction[..fraction.len().min(3)])
+        .parse::<i64>()
+        .ok()?;
+
+    Some(date_to_days(date)? * 86_400_000 + ((hour * 60 + minute) * 60 + second) * 1000 + millis)
+}
+
+/// Convert milliseconds since the UNIX epoch to a canonical timestamp.
+pub(crate) fn timestamp_from_millis(millis: i64) -> String {
+    let (year, month, day) = civil_from_days(millis.div_euclid(86_400_000));
+    let millis = millis.rem_euclid(86_400_000);
+
+    let seconds = millis / 1000;
+    let mut timestamp = format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    );
+    if millis % 1000 != 0 {
+        timestamp.push_str(&format!(".{:03}", millis % 1000));
+    }
+    timestamp
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        date_from_days, date_to_days, timestamp_from_millis, timestamp_to_millis, ColumnSchema,
+        ColumnType, RelationSchema,
+    };
+    use crate::test::TestStruct;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    fn test_schema() -> RelationSchema {
+        RelationSchema::new(vec![
+            ColumnSchema::new("id", ColumnType::Integer, false),
+            ColumnSchema::new("b", ColumnType::Boolean, false),
+            ColumnSchema::new("ts", ColumnType::Timestamp, true),
+            ColumnSchema::new("s", ColumnType::String, false),
+        ])
+    }
+
+    #[test]
+    fn test_coerce_fields() {
+        let schema = test_schema();
+
+        assert_eq!(
+            schema
+                .coerce_fields(&[" 5 ", "Yes", "2023-03-01T10:20:30.5Z", "foo"])
+                .unwrap(),
+            vec!["5", "true", "2023-03-01 10:20:30.5", "foo"]
+        );
+        assert_eq!(
+            schema.coerce_fields(&["5.0", "0", "", ""]).unwrap(),
+            vec!["5", "false", "", ""]
+        );
+        assert_eq!(
+            schema
+                .coerce_fields(&["1", "f", "1677666030000", "x"])
+                .unwrap(),
+            vec!["1", "false", "2023-03-01 10:20:30", "x"]
+        );
+
+        assert_eq!(
+            schema
+                .coerce_fields(&["x", "true", "", ""])
+                .unwrap_err()
+                .to_string(),
+            "column 'id': cannot convert 'x' to integer"
+        );
+        assert_eq!(
+            schema
+                .coerce_fields(&["", "true", "", ""])
+                .unwrap_err()
+                .to_string(),
+            "column 'id': NULL value in a non-nullable column"
+        );
+        assert_eq!(
+            schema
+                .coerce_fields(&["1", "true", "2023-02-29 00:00:00", ""])
+                .unwrap_err()
+                .to_string(),
+            "column 'ts': cannot convert '2023-02-29 00:00:00' to timestamp"
+        );
+        assert!(schema.coerce_fields(&["1", "true", ""]).is_err());
+
+        // 2^63 is out of range, -2^63 is not.
+        assert_eq!(
+            schema
+                .coerce_fields(&["9223372036854775808.0", "true", "", ""])
+                .unwrap_err()
+                .to_string(),
+            "column 'id': cannot convert '9223372036854775808.0' to integer"
+        );
+        assert_eq!(
+            schema
+                .coerce_fields(&["-9223372036854775808.0", "true", "", ""])
+                .unwrap(),
+            vec!["-9223372036854775808", "true", "", ""]
+        );
+    }
+
+    #[test]
+    fn test_coerce_json() {
+        let schema = test_schema();
+
+        let record = schema
+            .coerce_json(&json!({"id": "5", "b": 1, "ts": 0, "s": 10, "extra": null}))
+            .unwrap();
+        assert_eq!(
+            record,
+            json!({"id": 5, "b": true, "ts": "1970-01-01 00:00:00", "s": "10", "extra": null})
+        );
+
+        // Missing nullable fields remain missing.
+        let record = schema
+            .coerce_json(&json!({"id": 5.0, "b": "false", "s": "foo"}))
+            .unwrap();
+        assert!(!record.as_object().unwrap().contains_key("ts"));
+        assert_eq!(record["id"], json!(5));
+
+        assert_eq!(
+            schema
+                .coerce_json(&json!({"id": 9223372036854775808.0, "b": true, "s": ""}))
+                .unwrap_err()
+                .to_string(),
+            "column 'id': cannot convert '9.223372036854776e18' to integer"
+        );
+
+        assert_eq!(
+            schema
+                .coerce_json(&json!({"id": 5, "b": true}))
+                .unwrap_err()
+                .to_string(),
+            "column 's': missing value of a non-nullable column"
+        );
+        assert_eq!(
+            schema
+                .coerce_json(&json!([1, "maybe", null, "foo"]))
+                .unwrap_err()
+                .to_string(),
+            "column 'b': cannot convert 'maybe' to boolean"
+        );
+        assert_eq!(
+            schema.coerce_json(&json!([1, "t", null, "foo"])).unwrap(),
+            json!([1, true, null, "foo"])
+        );
+    }
+
+    #[test]
+    fn test_schema_of() {
+        assert_eq!(
+            RelationSchema::of::<TestStruct>().unwrap(),
+            RelationSchema::new(vec![
+                ColumnSchema::new("id", ColumnType::Integer, false),
+                ColumnSchema::new("b", ColumnType::Boolean, false),
+                ColumnSchema::new("i", ColumnType::Integer, true),
+                ColumnSchema::new("s", ColumnType::String, false),
+            ])
+        );
+
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Score(f64);
+
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Record {
+            #[serde(rename = "name")]
+            n: Option<String>,
+            score: Score,
+            c: char,
+        }
+        assert_eq!(
+            RelationSchema::of::<Record>().unwrap(),
+            RelationSchema::new(vec![
+                ColumnSchema::new("name", ColumnType::String, true),
+                ColumnSchema::new("score", ColumnType::Float, false),
+                ColumnSchema::new("c", ColumnType::String, false),
+            ])
+        );
+
+        // Records must be structs of primitive values.
+        assert!(RelationSchema::of::<(i64, String)>().is_err());
+
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Nested {
+            id: i64,
+            values: Vec<i64>,
+        }
+        assert_eq!(
+            RelationSchema::of::<Nested>().unwrap_err().to_string(),
+            format!(
+                "cannot derive the schema of type '{}': column 'values' has an unsupported type",
+                std::any::type_name::<Nested>()
+            )
+        );
+    }
+
+    #[test]
+    fn test_date_time_conversions() {
+        assert_eq!(date_to_days("1970-01-01"), Some(0));
+        assert_eq!(date_to_days("2000/03/01"), Some(11017));
+        assert_eq!(date_to_days("1969-12-31"), Some(-1));
+        assert_eq!(date_from_days(11017), "2000-03-01");
+        assert_eq!(date_from_days(-1), "1969-12-31");
+        assert_eq!(date_to_days("2023-02-29"), None);
+
+        assert_eq!(
+            timestamp_to_millis("2023-03-01T10:20:30.5Z"),
+            Some(1677666030500)
+        );
+        assert_eq!(timestamp_to_millis("1677666030000"), Some(1677666030000));
+        assert_eq!(timestamp_to_millis("1969-12-31 23:59:59.999999"), Some(-1));
+        assert_eq!(timestamp_from_millis(-1), "1969-12-31 23:59:59.999");
+        assert_eq!(timestamp_to_millis("2023-03-01"), None);
+    }
+}
@@ -1,10 +1,13 @@
+use crate::RelationSchema;
+use anyhow::{Error as AnyError, Result as AnyResult};
 use dbsp::{
     trace::{Batch, BatchReader, Cursor},
     OutputHandle,
 };
 use erased_serde::Serialize as ErasedSerialize;
-use serde::Serialize;
-use std::sync::Arc;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::{any::Any, sync::Arc};
 
 /// A type-erased batch whose contents can be serialized.
 ///
@@ -25,6 +28,35 @@ pub trait SerBatch: Send + Sync {
     /// Cursor over the batch.
     fn cursor<'a>(&'a self) -> Box<dyn SerCursor + 'a>;
 
+    /// Merge `self` with all batches in `other`.
+    ///
+    /// All batches in `other` must have the same concrete type as `self`,
+    /// e.g., they must be produced by the same output handle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the batches in `other` has a different type.
+    fn merge(self: Arc<Self>, other: Vec<Arc<dyn SerBatch>>) -> Arc<dyn SerBatch>;
+
+    /// Convert the batch to a batch of per-key [`UpsertEvent`]s.
+    ///
+    /// When `multimap` is `false`, produces at most one event per key:
+    /// an `upsert` event if a value was inserted for the key, and a `delete`
+    /// event if a value was deleted and no value was inserted.  Returns an
+    /// error if more than one value was inserted or deleted for the same
+    /// key.
+    ///
+    /// When `multimap` is `true`, produces an `insert` or `delete` event for
+    /// each inserted or deleted key/value pair.  The weight of the event is
+    /// the number of copies of the pair inserted or deleted.
+    ///
+    /// The events of the resulting batch are its keys.
+    fn upserts(&self, multimap: bool) -> AnyResult<Arc<dyn SerBatch>>;
+
+    /// Convert to `Any` reference, used to downcast the batch to its
+    /// concrete type.
+    fn as_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+
     // fn fork(&self) -> Box<dyn SerBatch>;
 }
 
@@ -106,9 +138,9 @@ where
 
 impl<B> SerBatch for SerBatchImpl<B>
 where
-    B: BatchReader<Time = ()> + Clone + Send + Sync,
-    B::Key: Serialize,
-    B::Val: Serialize,
+    B: Batch<Time = ()> + Send + Sync,
+    B::Key: Serialize + Sync,
+    B::Val: Serialize + Sync,
     B::R: Into<i64>,
 {
     fn key_count(&self) -> usize {
@@ -123,6 +155,83 @@ where
         Box::new(SerBatchCursor::new(&*self.batch))
     }
 
+    fn merge(self: Arc<Self>, other: Vec<Arc<dyn SerBatch>>) -> Arc<dyn SerBatch> {
+        let mut merged: Option<B> = None;
+
+        for other in other.into_iter() {
+            let other = other
+                .as_any()
+                .downcast::<Self>()
+                .expect("SerBatch::merge: batch type mismatch");
+            merged = Some(match &merged {
+                None => self.batch.merge(&other.batch),
+                Some(batch) => batch.merge(&other.batch),
+            });
+        }
+
+        match merged {
+            None => self,
+            Some(batch) => Arc::new(Self::new(batch)),
+        }
+    }
+
+    fn upserts(&self, multimap: bool) -> AnyResult<Arc<dyn SerBatch>> {
+        let mut events = Vec::new();
+        let mut cursor = self.batch.cursor();
+
+        while cursor.key_valid() {
+            let mut inserted = None;
+            let mut deleted = None;
+
+            while cursor.val_valid() {
+                let weight: i64 = cursor.weight().into();
+                let op = if weight > 0 {
+                    UpsertOp::Insert
+                } else {
+                    UpsertOp::Delete
+                };
+
+                if multimap {
+                    events.push((
+                        UpsertEvent::new(op, cursor.key(), cursor.val()),
+                        weight.unsigned_abs() as i64,
+                    ));
+                } else {
+                    let slot = if weight > 0 {
+                        &mut inserted
+                    } else {
+                        &mut deleted
+                    };
+                    if slot.is_some() || weight.unsigned_abs() > 1 {
+                        return Err(AnyError::msg(format!(
+                            "key '{}' is associated with multiple values",
+                            serde_json::to_string(cursor.key()).unwrap_or_default()
+                        )));
+                    }
+                    *slot = Some(cursor.val().clone());
+                }
+                cursor.step_val();
+            }
+
+            match (inserted, deleted) {
+                (Some(val), _) => {
+                    events.push((UpsertEvent::new(UpsertOp::Upsert, cursor.key(), &val), 1))
+                }
+                (None, Some(val)) => {
+                    events.push((UpsertEvent::new(UpsertOp::Delete, cursor.key(), &val), 1))
+                }
+                (None, None) => {}
+            }
+            cursor.step_key();
+        }
+
+        Ok(Arc::new(UpsertBatch::new(events)))
+    }
+
+    fn as_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+
     /*fn fork(&self) -> Box<dyn SerBatch> {
         Box::new(Self {
             batch: self.batch.clone(),
@@ -196,6 +305,155 @@ where
     }
 }
 
+/// Type of an [`UpsertEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpsertOp {
+    /// Set the key to a new value.
+    Upsert,
+
+    /// Add a value to the key (multimap mode only).
+    Insert,
+
+    /// Remove the value from the key.
+    Delete,
+}
+
+/// A per-key event produced by [`SerBatch::upserts`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct UpsertEvent<K, V> {
+    pub op: UpsertOp,
+    pub key: K,
+    pub value: V,
+}
+
+impl<K, V> UpsertEvent<K, V>
+where
+    K: Clone,
+    V: Clone,
+{
+    fn new(op: UpsertOp, key: &K, value: &V) -> Self {
+        Self {
+            op,
+            key: key.clone(),
+            value: value.clone(),
+        }
+    }
+}
+
+/// [`SerBatch`] implementation that stores a sequence of upsert events.
+///
+/// Each event is represented as a key with unit value.  The weight of the
+/// key is the number of occurrences of the event, which is always positive,
+/// as the direction of the update is given by the event type.
+struct UpsertBatch<K, V> {
+    events: Vec<(UpsertEvent<K, V>, i64)>,
+}
+
+impl<K, V> UpsertBatch<K, V> {
+    fn new(events: Vec<(UpsertEvent<K, V>, i64)>) -> Self {
+        Self { events }
+    }
+}
+
+impl<K, V> SerBatch for UpsertBatch<K, V>
+where
+    K: Serialize + Clone + Send + Sync + 'static,
+    V: Serialize + Clone + Send + Sync + 'static,
+{
+    fn key_count(&self) -> usize {
+        self.events.len()
+    }
+
+    fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    fn cursor<'a>(&'a self) -> Box<dyn SerCursor + 'a> {
+        Box::new(UpsertCursor {
+            events: &self.events,
+            key: 0,
+            val_valid: true,
+        })
+    }
+
+    fn merge(self: Arc<Self>, other: Vec<Arc<dyn SerBatch>>) -> Arc<dyn SerBatch> {
+        if other.is_empty() {
+            return self;
+        }
+
+        let mut events = self.events.clone();
+        for other in other.into_iter() {
+            let other = other
+                .as_any()
+                .downcast::<Self>()
+                .expect("SerBatch::merge: batch type mismatch");
+            events.extend_from_slice(&other.events);
+        }
+        Arc::new(Self::new(events))
+    }
+
+    fn upserts(&self, _multimap: bool) -> AnyResult<Arc<dyn SerBatch>> {
+        Err(AnyError::msg("batch already contains upsert events"))
+    }
+
+    fn as_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+/// [`SerCursor`] over an [`UpsertBatch`].
+struct UpsertCursor<'a, K, V> {
+    events: &'a [(UpsertEvent<K, V>, i64)],
+    key: usize,
+    val_valid: bool,
+}
+
+impl<'a, K, V> SerCursor for UpsertCursor<'a, K, V>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn key_valid(&self) -> bool {
+        self.key < self.events.len()
+    }
+
+    fn val_valid(&self) -> bool {
+        self.key_valid() && self.val_valid
+    }
+
+    fn key(&self) -> &dyn ErasedSerialize {
+        &self.events[self.key].0
+    }
+
+    fn val(&self) -> &dyn ErasedSerialize {
+        assert!(self.val_valid());
+        &()
+    }
+
+    fn weight(&mut self) -> i64 {
+        self.events[self.key].1
+    }
+
+    fn step_key(&mut self) {
+        self.key += 1;
+        self.val_valid = true;
+    }
+
+    fn step_val(&mut self) {
+        self.val_valid = false;
+    }
+
+    fn rewind_keys(&mut self) {
+        self.key = 0;
+        self.val_valid = true;
+    }
+
+    fn rewind_vals(&mut self) {
+        self.val_valid = true;
+    }
+}
+
 /// A handle to an output stream of a circuit that yields type-erased
 /// output batches.
 ///
@@ -216,13 +474,17 @@ pub trait SerOutputBatchHandle: Send + Sync {
 
     /// Returns an alias to `self`.
     fn fork(&self) -> Box<dyn SerOutputBatchHandle>;
+
+    /// Schema of the records of the stream, derived from their Rust type
+    /// (see [`RelationSchema::of`]).
+    fn relation_schema(&self) -> AnyResult<RelationSchema>;
 }
 
 impl<B> SerOutputBatchHandle for OutputHandle<B>
 where
     B: Batch<Time = ()> + Send + Sync,
-    B::Key: Serialize,
-    B::Val: Serialize,
+    B::Key: Serialize + DeserializeOwned + Sync,
+    B::Val: Serialize + Sync,
     B::R: Into<i64>,
 {
     fn take_from_worker(&self, worker: usize) -> Option<Box<dyn SerBatch>> {
@@ -245,4 +507,293 @@ where
     fn fork(&self) -> Box<dyn SerOutputBatchHandle> {
         Box::new(self.clone())
     }
+
+    fn relation_schema(&self) -> AnyResult<RelationSchema> {
+        RelationSchema::of::<B::Key>()
+    }
+}
+
+/// A type-erased snapshot of the contents of a materialized output stream.
+///
+/// The snapshot consists of the partitions of the stream computed by
+/// individual worker threads.  Partitions are not merged until the snapshot
+/// is queried, and only records within the queried range are merged.
+pub trait SerSnapshot: Send + Sync {
+    /// Returns a batch that contains the records of the snapshot whose keys
+    /// are within the `[from, to]` range, inclusive.
+    ///
+    /// Range bounds are specified as JSON values that are deserialized into
+    /// the key type of the stream.  A bound of `None` means that the range is
+    /// unbounded in the respective direction.  When `limit` is specified, the
+    /// returned batch contains at most `limit` records with the smallest keys
+    /// in the range.
+    fn key_range(
+        &self,
+        from: Option<&JsonValue>,
+        to: Option<&JsonValue>,
+        limit: Option<usize>,
+    ) -> AnyResult<Arc<dyn SerBatch>>;
+}
+
+/// A handle to the integral of an output stream that yields type-erased
+/// snapshots of the contents of the stream.
+///
+/// A trait for a type that wraps around an [`OutputHandle<Batch>`] attached
+/// to the output of the `integrate` operator, e.g.,
+/// `stream.integrate().output()`.
+pub trait SerMaterializedHandle: Send + Sync {
+    /// Take the contents of the stream computed during the last clock cycle
+    /// from all worker threads.
+    ///
+    /// Returns `None` if the contents has already been taken (see
+    /// [`OutputHandle::take_from_all`]).
+    fn take_snapshot(&self) -> Option<Arc<dyn SerSnapshot>>;
+
+    /// Returns an alias to `self`.
+    fn fork(&self) -> Box<dyn SerMaterializedHandle>;
+}
+
+impl<B> SerMaterializedHandle for OutputHandle<B>
+where
+    B: Batch<Time = ()> + Send + Sync,
+    B::Key: Serialize + DeserializeOwned + Sync,
+    B::Val: Serialize + Sync,
+    B::R: Into<i64>,
+{
+    fn take_snapshot(&self) -> Option<Arc<dyn SerSnapshot>> {
+        let partitions = self.take_from_all();
+
+        if partitions.is_empty() {
+            None
+        } else {
+            Some(Arc::new(SerSnapshotImpl { partitions }))
+        }
+    }
+
+    fn fork(&self) -> Box<dyn SerMaterializedHandle> {
+        Box::new(self.clone())
+    }
+}
+
+/// [`SerSnapshot`] implementation that stores per-worker batches.
+struct SerSnapshotImpl<B> {
+    partitions: Vec<B>,
+}
+
+impl<B> SerSnapshotImpl<B>
+where
+    B: Batch<Time = ()>,
+{
+    /// Returns up to `limit` tuples of `batch` with the smallest keys in the
+    /// `[from, to]` range.
+    fn range_tuples(
+        batch: &B,
+        from: Option<&B::Key>,
+        to: Option<&B::Key>,
+        limit: usize,
+    ) -> Vec<(B::Item, B::R)> {
+        let mut tuples = Vec::new();
+        let mut cursor = batch.cursor();
+        if let Some(from) = from {
+            cursor.seek_key(from);
+        }
+
+        while cursor.key_valid() && tuples.len() < limit {
+            if matches!(to, Some(to) if cursor.key() > to) {
+                break;
+            }
+            while cursor.val_valid() && tuples.len() < limit {
+                let weight = cursor.weight();
+                tuples.push((
+                    B::item_from(cursor.key().clone(), cursor.val().clone()),
+                    weight,
+                ));
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+
+        tuples
+    }
+}
+
+impl<B> SerSnapshot for SerSnapshotImpl<B>
+where
+    B: Batch<Time = ()> + Send + Sync,
+    B::Key: Serialize + DeserializeOwned + Sync,
+    B::Val: Serialize + Sync,
+    B::R: Into<i64>,
+{
+    fn key_range(
+        &self,
+        from: Option<&JsonValue>,
+        to: Option<&JsonValue>,
+        limit: Option<usize>,
+    ) -> AnyResult<Arc<dyn SerBatch>> {
+        let bound = |bound: Option<&JsonValue>| {
+            bound
+                .map(|bound| {
+                    B::Key::deserialize(bound)
+                        .map_err(|e| AnyError::msg(format!("invalid key range bound: {e}")))
+                })
+                .transpose()
+        };
+        let from = bound(from)?;
+        let to = bound(to)?;
+        let limit = limit.unwrap_or(usize::MAX);
+
+        // Each partition contributes at most `limit` tuples; the smallest
+        // `limit` tuples of their union form the result.
+        let tuples = self
+            .partitions
+            .iter()
+            .flat_map(|batch| Self::range_tuples(batch, from.as_ref(), to.as_ref(), limit))
+            .collect();
+        let mut result = B::from_tuples((), tuples);
+
+        if result.len() > limit {
+            result = B::from_tuples((), Self::range_tuples(&result, None, None, limit));
+        }
+
+        Ok(Arc::new(SerBatchImpl::new(result)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SerBatch, SerBatchImpl, SerSnapshot, SerSnapshotImpl};
+    use dbsp::{trace::Batch, OrdIndexedZSet, OrdZSet};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn batch(tuples: Vec<(u64, i64)>) -> Arc<dyn SerBatch> {
+        Arc::new(SerBatchImpl::new(OrdZSet::from_tuples((), tuples)))
+    }
+
+    fn contents(batch: &dyn SerBatch) -> Vec<(String, i64)> {
+        let mut result = Vec::new();
+        let mut cursor = batch.cursor();
+
+        while cursor.key_valid() {
+            let key = serde_json::to_string(cursor.key()).unwrap();
+            result.push((key, cursor.weight()));
+            cursor.step_key();
+        }
+
+        result
+    }
+
+    #[test]
+    fn test_merge() {
+        let merged = batch(vec![(1, 1), (2, 1)]).merge(vec![
+            batch(vec![(2, -1), (3, 2)]),
+            batch(vec![]),
+            batch(vec![(1, 1)]),
+        ]);
+        assert_eq!(
+            contents(&*merged),
+            vec![("1".to_string(), 2), ("3".to_string(), 2)]
+        );
+
+        let unchanged = batch(vec![(1, 1)]).merge(Vec::new());
+        assert_eq!(contents(&*unchanged), vec![("1".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_key_range() {
+        let snapshot = SerSnapshotImpl {
+            partitions: vec![
+                OrdZSet::<u64, i64>::from_tuples((), vec![(1, 1), (3, 2)]),
+                OrdZSet::<u64, i64>::from_tuples((), vec![(2, -1), (5, 1)]),
+            ],
+        };
+
+        let range = snapshot.key_range(Some(&json!(2)), Some(&json!(4)), None);
+        assert_eq!(
+            contents(&*range.unwrap()),
+            vec![("2".to_string(), -1), ("3".to_string(), 2)]
+        );
+
+        let range = snapshot.key_range(None, None, Some(3));
+        assert_eq!(
+            contents(&*range.unwrap()),
+            vec![
+                ("1".to_string(), 1),
+                ("2".to_string(), -1),
+                ("3".to_string(), 2)
+            ]
+        );
+
+        let range = snapshot.key_range(Some(&json!(4)), None, Some(1));
+        assert_eq!(contents(&*range.unwrap()), vec![("5".to_string(), 1)]);
+
+        assert!(snapshot.key_range(Some(&json!("foo")), None, None).is_err());
+    }
+
+    #[test]
+    fn test_upserts() {
+        let zset: OrdIndexedZSet<u64, String, i64> = OrdIndexedZSet::from_tuples(
+            (),
+            vec![
+                ((1, "a".to_string()), -1),
+                ((1, "b".to_string()), 1),
+                ((2, "c".to_string()), 1),
+                ((3, "d".to_string()), -1),
+            ],
+        );
+        let batch: Arc<dyn SerBatch> = Arc::new(SerBatchImpl::new(zset));
+
+        let upserts = batch.upserts(false).unwrap();
+        assert_eq!(
+            contents(&*upserts),
+            vec![
+                (r#"{"op":"upsert","key":1,"value":"b"}"#.to_string(), 1),
+                (r#"{"op":"upsert","key":2,"value":"c"}"#.to_string(), 1),
+                (r#"{"op":"delete","key":3,"value":"d"}"#.to_string(), 1),
+            ]
+        );
+
+        let multimap = batch.upserts(true).unwrap();
+        assert_eq!(
+            contents(&*multimap),
+            vec![
+                (r#"{"op":"delete","key":1,"value":"a"}"#.to_string(), 1),
+                (r#"{"op":"insert","key":1,"value":"b"}"#.to_string(), 1),
+                (r#"{"op":"insert","key":2,"value":"c"}"#.to_string(), 1),
+                (r#"{"op":"delete","key":3,"value":"d"}"#.to_string(), 1),
+            ]
+        );
+
+        let zset: OrdIndexedZSet<u64, String, i64> = OrdIndexedZSet::from_tuples(
+            (),
+            vec![((1, "a".to_string()), 1), ((1, "b".to_string()), 1)],
+        );
+        let batch: Arc<dyn SerBatch> = Arc::new(SerBatchImpl::new(zset));
+        assert_eq!(
+            batch.upserts(false).err().unwrap().to_string(),
+            "key '1' is associated with multiple values"
+        );
+        assert_eq!(contents(&*batch.upserts(true).unwrap()).len(), 2);
+
+        // Multiple copies of a key/value pair are reported as a single event
+        // with the number of copies as its weight.
+        let zset: OrdIndexedZSet<u64, String, i64> = OrdIndexedZSet::from_tuples(
+            (),
+            vec![
+                ((1, "a".to_string()), 1_000_000_000),
+                ((2, "b".to_string()), -3),
+            ],
+        );
+        let batch: Arc<dyn SerBatch> = Arc::new(SerBatchImpl::new(zset));
+        assert_eq!(
+            contents(&*batch.upserts(true).unwrap()),
+            vec![
+                (
+                    r#"{"op":"insert","key":1,"value":"a"}"#.to_string(),
+                    1_000_000_000
+                ),
+                (r#"{"op":"delete","key":2,"value":"b"}"#.to_string(), 3),
+            ]
+        );
+    }
 }
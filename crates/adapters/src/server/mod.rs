@@ -1,11 +1,14 @@
 use crate::{
-    Catalog, Controller, ControllerError, HttpInputTransport, HttpOutputTransport, PipelineConfig,
+    transport::format_config_from_args, Catalog, CatalogSchema, Controller, ControllerError,
+    HttpInputTransport, HttpOutputTransport, InputEndpointConfig, OutputEndpointConfig,
+    OutputQuery, PipelineConfig,
 };
 use actix_web::{
+    delete,
     dev::{Server, ServiceFactory, ServiceRequest},
     get,
     middleware::Logger,
-    rt, web,
+    patch, post, put, rt, web,
     web::Data as WebData,
     App, Error as ActixError, HttpRequest, HttpResponse, HttpServer, Responder,
 };
@@ -15,8 +18,9 @@ use clap::Parser;
 use dbsp::DBSPHandle;
 use env_logger::Env;
 use log::{error, info};
-use serde::Serialize;
-use std::{net::TcpListener, sync::Mutex};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use std::{collections::HashMap, fmt::Display, net::TcpListener, sync::Mutex};
 use tokio::{
     spawn,
     sync::mpsc::{channel, Receiver, Sender},
@@ -164,6 +168,30 @@ where
     Ok(())
 }
 
+/// Add stream schemas registered in the catalog to pipeline metadata.
+///
+/// Schemas are added as the `schema` field of the metadata JSON object.
+/// Metadata that is not a JSON object is returned unmodified.
+fn add_schema_to_metadata(meta: String, schema: &CatalogSchema) -> String {
+    if schema.inputs.is_empty() && schema.outputs.is_empty() {
+        return meta;
+    }
+
+    let mut fields = if meta.trim().is_empty() {
+        JsonMap::new()
+    } else {
+        match serde_json::from_str::<JsonValue>(&meta) {
+            Ok(JsonValue::Object(fields)) => fields,
+            _ => return meta,
+        }
+    };
+
+    fields
+        .entry("schema")
+        .or_insert_with(|| serde_json::to_value(schema).unwrap_or_default());
+    JsonValue::Object(fields).to_string()
+}
+
 pub fn create_server<F>(
     circuit_factory: &F,
     yaml_config: &str,
@@ -177,6 +205,7 @@ where
         .map_err(|e| AnyError::msg(format!("error parsing pipeline configuration: {e}")))?;
 
     let (circuit, catalog) = circuit_factory(config.global.workers as usize);
+    let meta = add_schema_to_metadata(meta, &catalog.schema());
 
     let controller = Controller::with_config(
         circuit,
@@ -243,8 +272,22 @@ where
         .service(metrics)
         .service(metadata)
         .service(dump_profile)
+        .service(checkpoint)
+        .service(step)
         .service(input_endpoint)
+        .service(input_endpoint_post)
+        .service(input_endpoint_dead_letters)
+        .service(input_endpoint_pause)
+        .service(input_endpoint_start)
+        .service(input_endpoint_connect)
+        .service(input_endpoint_disconnect)
+        .service(input_endpoint_update)
         .service(output_endpoint)
+        .service(output_endpoint_stream)
+        .service(output_endpoint_connect)
+        .service(output_endpoint_disconnect)
+        .service(output_endpoint_update)
+        .service(query)
 }
 
 #[get("/start")]
@@ -324,6 +367,56 @@ async fn dump_profile(state: WebData<ServerState>) -> impl Responder {
     }
 }
 
+/// Write a checkpoint to the checkpoint directory specified in the pipeline
+/// configuration.
+#[post("/checkpoint")]
+async fn checkpoint(state: WebData<ServerState>) -> impl Responder {
+    // Don't hold the controller lock while waiting for the checkpoint.
+    let controller = match &*state.controller.lock().unwrap() {
+        Some(controller) => controller.handle(),
+        None => {
+            return HttpResponse::Conflict()
+                .json(&ErrorResponse::new("The pipeline has been terminated"))
+        }
+    };
+
+    match web::block(move || controller.checkpoint()).await {
+        Ok(Ok(())) => HttpResponse::Ok().json("Checkpoint complete"),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(&ErrorResponse::new(&format!(
+            "Failed to checkpoint the pipeline: {e}"
+        ))),
+        Err(e) => HttpResponse::InternalServerError().json(&ErrorResponse::new(&format!(
+            "Failed to checkpoint the pipeline: {e}"
+        ))),
+    }
+}
+
+/// Trigger a circuit step and wait for it to complete.
+///
+/// Returns the number of the completed step.  This is the only way to
+/// advance a pipeline configured with the `manual` step trigger.
+#[post("/step")]
+async fn step(state: WebData<ServerState>) -> impl Responder {
+    // Don't hold the controller lock while waiting for the step.
+    let controller = match &*state.controller.lock().unwrap() {
+        Some(controller) => controller.handle(),
+        None => {
+            return HttpResponse::Conflict()
+                .json(&ErrorResponse::new("The pipeline has been terminated"))
+        }
+    };
+
+    match web::block(move || controller.step()).await {
+        Ok(Ok(step)) => HttpResponse::Ok().json(step),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(&ErrorResponse::new(&format!(
+            "Failed to step the pipeline: {e}"
+        ))),
+        Err(e) => HttpResponse::InternalServerError().json(&ErrorResponse::new(&format!(
+            "Failed to step the pipeline: {e}"
+        ))),
+    }
+}
+
 #[get("/shutdown")]
 async fn shutdown(state: WebData<ServerState>) -> impl Responder {
     let controller = state.controller.lock().unwrap().take();
@@ -360,6 +453,49 @@ async fn input_endpoint(req: HttpRequest, stream: web::Payload) -> impl Responde
     }
 }
 
+/// Pushes the body of the request to an HTTP input endpoint.
+///
+/// See `HttpInputTransport::post_endpoint_data` for supported query
+/// parameters.
+#[post("/input_endpoint/{endpoint_name}")]
+async fn input_endpoint_post(req: HttpRequest, payload: web::Payload) -> impl Responder {
+    match req.match_info().get("endpoint_name") {
+        None => HttpResponse::BadRequest().body("Missing endpoint name argument"),
+        Some(endpoint_name) => HttpInputTransport::post_endpoint_data(endpoint_name, &req, payload)
+            .await
+            .unwrap_or_else(|e| {
+                HttpResponse::BadRequest().json(&ErrorResponse::new(&format!(
+                    "Failed to push data to input HTTP endpoint: {e}"
+                )))
+            }),
+    }
+}
+
+/// Returns the most recent records rejected by the parser of an input
+/// endpoint configured with a dead-letter queue.
+#[get("/input_endpoint/{endpoint_name}/dead_letters")]
+async fn input_endpoint_dead_letters(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+) -> impl Responder {
+    let endpoint_name = match req.match_info().get("endpoint_name") {
+        None => return HttpResponse::BadRequest().body("Missing endpoint name argument"),
+        Some(endpoint_name) => endpoint_name,
+    };
+
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => match controller.input_endpoint_dead_letters(endpoint_name) {
+            Some(dead_letters) => HttpResponse::Ok().json(dead_letters),
+            None => HttpResponse::NotFound().json(&ErrorResponse::new(&format!(
+                "Input endpoint '{endpoint_name}' does not exist or does not have a dead-letter queue"
+            ))),
+        },
+        None => {
+            HttpResponse::Conflict().json(&ErrorResponse::new("The pipeline has been terminated"))
+        }
+    }
+}
+
 #[get("/output_endpoint/{endpoint_name}")]
 async fn output_endpoint(req: HttpRequest, stream: web::Payload) -> impl Responder {
     match req.match_info().get("endpoint_name") {
@@ -376,6 +512,501 @@ async fn output_endpoint(req: HttpRequest, stream: web::Payload) -> impl Respond
     }
 }
 
+/// Subscribes to the output of an HTTP output endpoint using a streaming HTTP
+/// response.
+///
+/// See `HttpOutputTransport::get_endpoint_stream` for supported query
+/// parameters.
+#[get("/output_endpoint/{endpoint_name}/stream")]
+async fn output_endpoint_stream(req: HttpRequest) -> impl Responder {
+    match req.match_info().get("endpoint_name") {
+        None => HttpResponse::BadRequest().body("Missing endpoint name argument"),
+        Some(endpoint_name) => HttpOutputTransport::get_endpoint_stream(endpoint_name, &req)
+            .unwrap_or_else(|e| {
+                HttpResponse::BadRequest().json(&ErrorResponse::new(&format!(
+                    "Failed to subscribe to output HTTP endpoint: {e}"
+                )))
+            }),
+    }
+}
+
+/// Endpoint settings that can be changed at runtime.
+#[derive(Deserialize)]
+struct EndpointUpdate {
+    max_buffered_records: u64,
+}
+
+/// Invoke `action` on the endpoint whose name is specified in the request
+/// path.
+fn endpoint_action<E: Display>(
+    state: &ServerState,
+    req: &HttpRequest,
+    action: impl FnOnce(&Controller, &str) -> Result<(), E>,
+    success: &str,
+) -> HttpResponse {
+    let endpoint_name = match req.match_info().get("endpoint_name") {
+        None => return HttpResponse::BadRequest().body("Missing endpoint name argument"),
+        Some(endpoint_name) => endpoint_name,
+    };
+
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => match action(controller, endpoint_name) {
+            Ok(()) => HttpResponse::Ok().json(format!("Endpoint '{endpoint_name}' {success}")),
+            Err(e) => HttpResponse::BadRequest().json(&ErrorResponse::new(&e.to_string())),
+        },
+        None => {
+            HttpResponse::Conflict().json(&ErrorResponse::new("The pipeline has been terminated"))
+        }
+    }
+}
+
+/// Pause an input endpoint.  The endpoint remains paused until resumed via
+/// `/input_endpoint/{endpoint_name}/start`.
+#[get("/input_endpoint/{endpoint_name}/pause")]
+async fn input_endpoint_pause(state: WebData<ServerState>, req: HttpRequest) -> impl Responder {
+    endpoint_action(
+        &state,
+        &req,
+        |controller, endpoint_name| controller.pause_input_endpoint(endpoint_name),
+        "paused",
+    )
+}
+
+/// Resume a paused input endpoint.
+#[get("/input_endpoint/{endpoint_name}/start")]
+async fn input_endpoint_start(state: WebData<ServerState>, req: HttpRequest) -> impl Responder {
+    endpoint_action(
+        &state,
+        &req,
+        |controller, endpoint_name| controller.start_input_endpoint(endpoint_name),
+        "started",
+    )
+}
+
+/// Connect a new input endpoint.  The request body contains endpoint
+/// configuration in JSON format.
+#[put("/input_endpoint/{endpoint_name}")]
+async fn input_endpoint_connect(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+    config: web::Json<InputEndpointConfig>,
+) -> impl Responder {
+    endpoint_action(
+        &state,
+        &req,
+        |controller, endpoint_name| controller.connect_input(endpoint_name, &config),
+        "connected",
+    )
+}
+
+/// Disconnect and destroy an input endpoint.
+#[delete("/input_endpoint/{endpoint_name}")]
+async fn input_endpoint_disconnect(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+) -> impl Responder {
+    endpoint_action(
+        &state,
+        &req,
+        |controller, endpoint_name| controller.disconnect_input(endpoint_name),
+        "disconnected",
+    )
+}
+
+/// Change settings of an input endpoint.  The request body is a JSON object
+/// with the new value of `max_buffered_records`.
+#[patch("/input_endpoint/{endpoint_name}")]
+async fn input_endpoint_update(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+    update: web::Json<EndpointUpdate>,
+) -> impl Responder {
+    endpoint_action(
+        &state,
+        &req,
+        |controller, endpoint_name| {
+            controller.set_input_max_buffered_records(endpoint_name, update.max_buffered_records)
+        },
+        "updated",
+    )
+}
+
+/// Connect a new output endpoint.  The request body contains endpoint
+/// configuration in JSON format.
+#[put("/output_endpoint/{endpoint_name}")]
+async fn output_endpoint_connect(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+    config: web::Json<OutputEndpointConfig>,
+) -> impl Responder {
+    endpoint_action(
+        &state,
+        &req,
+        |controller, endpoint_name| controller.connect_output(endpoint_name, &config),
+        "connected",
+    )
+}
+
+/// Disconnect and destroy an output endpoint.
+#[delete("/output_endpoint/{endpoint_name}")]
+async fn output_endpoint_disconnect(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+) -> impl Responder {
+    endpoint_action(
+        &state,
+        &req,
+        |controller, endpoint_name| controller.disconnect_output(endpoint_name),
+        "disconnected",
+    )
+}
+
+/// Change settings of an output endpoint.  The request body is a JSON object
+/// with the new value of `max_buffered_records`.
+#[patch("/output_endpoint/{endpoint_name}")]
+async fn output_endpoint_update(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+    update: web::Json<EndpointUpdate>,
+) -> impl Responder {
+    endpoint_action(
+        &state,
+        &req,
+        |controller, endpoint_name| {
+            controller.set_output_max_buffered_records(endpoint_name, update.max_buffered_records)
+        },
+        "updated",
+    )
+}
+
+/// Returns the current contents of a materialized output stream.
+///
+/// Supports the following query parameters:
+///
+/// * `format` - output format used to encode the result, `json` by default.
+///
+/// * `from`, `to` - JSON-encoded smallest and largest keys to include in the
+///   result.
+///
+/// * `limit` - maximal number of records to return.
+///
+/// All other query parameters are passed to the encoder as format
+/// configuration options.
+#[get("/query/{stream_name}")]
+async fn query(state: WebData<ServerState>, req: HttpRequest) -> impl Responder {
+    let stream_name = match req.match_info().get("stream_name") {
+        None => return HttpResponse::BadRequest().body("Missing stream name argument"),
+        Some(stream_name) => stream_name,
+    };
+
+    let query = match parse_output_query(req.query_string()) {
+        Ok(query) => query,
+        Err(e) => {
+            return HttpResponse::BadRequest()
+                .json(&ErrorResponse::new(&format!("Invalid query: {e}")))
+        }
+    };
+
+    let content_type = match query.format.name.as_ref() {
+        "json" => mime::APPLICATION_JSON,
+        "csv" => mime::TEXT_CSV,
+        _ => mime::APPLICATION_OCTET_STREAM,
+    };
+
+    // Don't hold the controller lock while running the query.
+    let controller = match &*state.controller.lock().unwrap() {
+        Some(controller) => controller.handle(),
+        None => {
+            return HttpResponse::Conflict()
+                .json(&ErrorResponse::new("The pipeline has been terminated"))
+        }
+    };
+
+    let stream = stream_name.to_string();
+    match web::block(move || controller.query_output(&stream, &query)).await {
+        Ok(Ok(result)) => HttpResponse::Ok().content_type(content_type).body(result),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(&ErrorResponse::new(&format!(
+            "Failed to query output stream '{stream_name}': {e}"
+        ))),
+        Err(e) => HttpResponse::InternalServerError().json(&ErrorResponse::new(&format!(
+            "Failed to query output stream '{stream_name}': {e}"
+        ))),
+    }
+}
+
+/// Parse the query string of a `/query` request.
+fn parse_output_query(query_string: &str) -> AnyResult<OutputQuery> {
+    let mut args = web::Query::<HashMap<String, String>>::from_query(query_string)?.into_inner();
+
+    let bound = |bound: Option<String>| {
+        bound
+            .map(|bound| {
+                serde_json::from_str::<JsonValue>(&bound)
+                    .map_err(|e| AnyError::msg(format!("invalid key '{bound}': {e}")))
+            })
+            .transpose()
+    };
+    let from = bound(args.remove("from"))?;
+    let to = bound(args.remove("to"))?;
+    let limit = args
+        .remove("limit")
+        .map(|limit| {
+            limit
+                .parse::<usize>()
+                .map_err(|_| AnyError::msg(format!("invalid limit '{limit}'")))
+        })
+        .transpose()?;
+    let format = args.remove("format").unwrap_or_else(|| "json".to_string());
+
+    Ok(OutputQuery {
+        format: format_config_from_args(&format, args),
+        from,
+        to,
+        limit,
+    })
+}
+
+#[cfg(test)]
+#[cfg(feature = "server")]
+mod test {
+    use super::{build_app, PrometheusMetrics, ServerState};
+    use crate::{
+        test::{generate_test_batch, test_circuit, wait, TestStruct},
+        Controller, PipelineConfig,
+    };
+    use actix_web::{http::StatusCode, web::Data as WebData, App};
+    use csv::{ReaderBuilder as CsvReaderBuilder, WriterBuilder as CsvWriterBuilder};
+    use proptest::{
+        strategy::{Strategy, ValueTree},
+        test_runner::TestRunner,
+    };
+    use serde_json::Value as JsonValue;
+    use tempfile::NamedTempFile;
+
+    #[actix_web::test]
+    async fn test_query() {
+        let mut expected = generate_test_batch(100)
+            .new_tree(&mut TestRunner::default())
+            .unwrap()
+            .current();
+        expected.sort();
+        expected.dedup();
+
+        let temp_input_file = NamedTempFile::new().unwrap();
+        let mut writer = CsvWriterBuilder::new()
+            .has_headers(false)
+            .from_writer(temp_input_file.as_file());
+        for val in expected.iter() {
+            writer.serialize(val).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let config_str = format!(
+            r#"
+inputs:
+    test_input1:
+        stream: test_input1
+        transport:
+            name: file
+            config:
+                path: {:?}
+                follow: false
+        format:
+            name: csv
+"#,
+            temp_input_file.path().to_str().unwrap(),
+        );
+        let config: PipelineConfig = serde_yaml::from_str(&config_str).unwrap();
+
+        let (circuit, catalog) = test_circuit(4);
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+
+        let prometheus = PrometheusMetrics::new(&controller).unwrap();
+        let state = WebData::new(ServerState::new(
+            controller,
+            prometheus,
+            "metadata".to_string(),
+            None,
+        ));
+        let server = {
+            let state = state.clone();
+            actix_test::start(move || build_app(App::new(), state.clone()))
+        };
+
+        let resp = server.get("/start").send().await.unwrap();
+        assert!(resp.status().is_success());
+        wait(
+            || {
+                state
+                    .controller
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .unwrap()
+                    .pipeline_complete()
+            },
+            None,
+        );
+
+        let query = |path: &'static str| {
+            let server = &server;
+            async move {
+                let mut resp = server.get(path).send().await.unwrap();
+                assert!(resp.status().is_success());
+                let body = resp.body().await.unwrap();
+                CsvReaderBuilder::new()
+                    .has_headers(false)
+                    .from_reader(&*body)
+                    .deserialize::<(TestStruct, i32)>()
+                    .map(|res| res.unwrap().0)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(query("/query/test_output1?format=csv").await, expected);
+        assert_eq!(
+            query("/query/test_output1?format=csv&limit=5").await,
+            expected[0..expected.len().min(5)]
+        );
+
+        let resp = server
+            .get("/query/test_output1?format=csv&limit=foo")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        // Only materialized streams can be queried.
+        let resp = server.get("/query/test_output2").send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = server.get("/shutdown").send().await.unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    #[cfg(feature = "with-compression")]
+    #[actix_web::test]
+    async fn test_dead_letters() {
+        use flate2::read::MultiGzDecoder;
+        use std::io::Read;
+
+        let temp_input_file = NamedTempFile::new().unwrap();
+        std::fs::write(
+            temp_input_file.path(),
+            "1,true,10,foo\nx,true,10,foo\n2,false,,bar\n3,true\n",
+        )
+        .unwrap();
+        let temp_dead_letter_file = NamedTempFile::new().unwrap();
+
+        let config_str = format!(
+            r#"
+inputs:
+    test_input1:
+        stream: test_input1
+        transport:
+            name: file
+            config:
+                path: {:?}
+                follow: false
+        format:
+            name: csv
+        dead_letter:
+            transport:
+                name: file
+                config:
+                    path: {:?}
+                compression: gzip
+"#,
+            temp_input_file.path().to_str().unwrap(),
+            temp_dead_letter_file.path().to_str().unwrap(),
+        );
+        let config: PipelineConfig = serde_yaml::from_str(&config_str).unwrap();
+
+        let (circuit, catalog) = test_circuit(4);
+        let controller =
+            Controller::with_config(circuit, catalog, &config, Box::new(|_| {})).unwrap();
+
+        let prometheus = PrometheusMetrics::new(&controller).unwrap();
+        let state = WebData::new(ServerState::new(
+            controller,
+            prometheus,
+            "metadata".to_string(),
+            None,
+        ));
+        let server = {
+            let state = state.clone();
+            actix_test::start(move || build_app(App::new(), state.clone()))
+        };
+
+        let resp = server.get("/start").send().await.unwrap();
+        assert!(resp.status().is_success());
+        wait(
+            || {
+                state
+                    .controller
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .unwrap()
+                    .pipeline_complete()
+            },
+            None,
+        );
+
+        // Rejected records are reported with their contents and locations
+        // in the input stream.
+        let mut resp = server
+            .get("/input_endpoint/test_input1/dead_letters")
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        let dead_letters: Vec<JsonValue> = resp.json().await.unwrap();
+        assert_eq!(dead_letters.len(), 2);
+        assert_eq!(dead_letters[0]["endpoint_name"], "test_input1");
+        // Record contents are base64-encoded.
+        assert_eq!(dead_letters[0]["record"], "eCx0cnVlLDEwLGZvbw==");
+        assert_eq!(dead_letters[0]["offset"], 14);
+        assert_eq!(dead_letters[0]["line"], 2);
+        assert_eq!(dead_letters[1]["record"], "Myx0cnVl");
+        assert_eq!(dead_letters[1]["offset"], 41);
+        assert_eq!(dead_letters[1]["line"], 4);
+
+        // The same records are written to the dead-letter transport,
+        // compressed as configured.
+        let read_dead_letters = || {
+            let mut contents = String::new();
+            MultiGzDecoder::new(std::fs::File::open(temp_dead_letter_file.path()).unwrap())
+                .read_to_string(&mut contents)
+                .unwrap();
+            contents
+        };
+        wait(|| read_dead_letters().lines().count() == 2, None);
+        let written = read_dead_letters()
+            .lines()
+            .map(|line| serde_json::from_str::<JsonValue>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(written, dead_letters);
+
+        // Endpoints without a dead-letter queue.
+        let resp = server
+            .get("/input_endpoint/test_input2/dead_letters")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = server.get("/shutdown").send().await.unwrap();
+        assert!(resp.status().is_success());
+    }
+}
+
 #[cfg(test)]
 #[cfg(feature = "with-kafka")]
 #[cfg(feature = "server")]
@@ -387,7 +1018,7 @@ mod test_with_kafka {
             kafka::{BufferConsumer, KafkaResources, TestProducer},
             test_circuit, wait,
             websocket::{TestWsReceiver, TestWsSender},
-            TEST_LOGGER,
+            TestStruct, TEST_LOGGER,
         },
         Controller, ControllerError, PipelineConfig,
     };
@@ -396,6 +1027,7 @@ mod test_with_kafka {
     use bytes::Bytes;
     use bytestring::ByteString;
     use crossbeam::queue::SegQueue;
+    use csv::{ReaderBuilder as CsvReaderBuilder, WriterBuilder as CsvWriterBuilder};
     use futures::{SinkExt, StreamExt};
     use log::{error, LevelFilter};
     use proptest::{
@@ -623,6 +1255,79 @@ outputs:
         buffer_consumer.wait_for_output_unordered(&data);
         buffer_consumer.clear();
 
+        let mut writer = CsvWriterBuilder::new()
+            .has_headers(false)
+            .from_writer(Vec::new());
+        for val in data.iter().flatten() {
+            writer.serialize(val).unwrap();
+        }
+        let body = writer.into_inner().unwrap();
+
+        let resp = server
+            .post("/input_endpoint/test_input_http")
+            .send_body(body.clone())
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+
+        buffer_consumer.wait_for_output_unordered(&data);
+        buffer_consumer.clear();
+
+        let mut output_stream = server
+            .get("/output_endpoint/test_output_http/stream")
+            .send()
+            .await
+            .unwrap();
+        assert!(output_stream.status().is_success());
+
+        // Snapshots are not enabled for the endpoint.
+        let resp = server
+            .get("/output_endpoint/test_output_http/stream?snapshot=true")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = server
+            .post("/input_endpoint/test_input_http?format=csv&wait=false")
+            .send_body(body.clone())
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+
+        buffer_consumer.wait_for_output_unordered(&data);
+        buffer_consumer.clear();
+
+        // Read the output of the POST request from the output stream.  Chunk
+        // boundaries don't necessarily align with records, so we re-parse all
+        // data received so far after each chunk.
+        let num_records: usize = data.iter().map(Vec::len).sum();
+        let mut output = Vec::new();
+        let mut received = Vec::new();
+        while received.len() < num_records {
+            output.extend_from_slice(&output_stream.next().await.unwrap().unwrap());
+            received = CsvReaderBuilder::new()
+                .has_headers(false)
+                .from_reader(output.as_slice())
+                .deserialize::<(TestStruct, i32)>()
+                .map_while(Result::ok)
+                .map(|(record, _w)| record)
+                .collect::<Vec<_>>();
+        }
+        received.sort();
+        let mut expected = data.iter().flatten().cloned().collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(expected, received);
+        drop(output_stream);
+
+        // Format options without a format name.
+        let resp = server
+            .post("/input_endpoint/test_input_http?delimiter=x")
+            .send_body(body)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
         println!("/pause");
         let resp = server.get("/pause").send().await.unwrap();
         assert!(resp.status().is_success());
@@ -71,3 +71,21 @@ pub fn generate_test_batches(
             .collect::<Vec<_>>()
     })
 }
+
+/// A small fixed data set used by format unit tests.
+pub fn test_data() -> Vec<TestStruct> {
+    vec![
+        TestStruct {
+            id: 1,
+            b: true,
+            i: Some(10),
+            s: "foo".to_string(),
+        },
+        TestStruct {
+            id: 2,
+            b: false,
+            i: None,
+            s: "bar".to_string(),
+        },
+    ]
+}
@@ -67,7 +67,7 @@ impl<T> MockDeZSet<T> {
 
 impl<T> DeCollectionHandle for MockDeZSet<T>
 where
-    T: for<'de> Deserialize<'de> + Send + 'static,
+    T: for<'de> Deserialize<'de> + Clone + Send + 'static,
 {
     fn insert(&mut self, deserializer: &mut dyn ErasedDeserializer) -> Result<(), EError> {
         let val = deserialize::<T>(deserializer)?;
@@ -81,6 +81,21 @@ where
         Ok(())
     }
 
+    /// Records an update with weight `w` as `|w|` inserts or deletes, so
+    /// that tests can compare the output with a list of unit updates.
+    fn update_weighted(
+        &mut self,
+        deserializer: &mut dyn ErasedDeserializer,
+        weight: i64,
+    ) -> Result<(), EError> {
+        let val = deserialize::<T>(deserializer)?;
+        let mut state = self.0.lock().unwrap();
+        for _ in 0..weight.unsigned_abs() {
+            state.buffered.push((val.clone(), weight > 0));
+        }
+        Ok(())
+    }
+
     fn reserve(&mut self, _reservation: usize) {}
 
     fn flush(&mut self) {
@@ -1,4 +1,7 @@
-use crate::{controller::FormatConfig, DeCollectionHandle, InputConsumer, InputFormat, Parser};
+use crate::{
+    controller::FormatConfig, DeCollectionHandle, InputConsumer, InputFormat, ParseError, Parser,
+    Step,
+};
 use anyhow::{Error as AnyError, Result as AnyResult};
 use std::sync::{Arc, Mutex, MutexGuard};
 
@@ -12,15 +15,18 @@ pub struct MockInputConsumerState {
     /// `eoi` has been received since the last `reset`.
     pub eoi: bool,
 
-    /// The last error received from the endpoint since the last `reset`.
+    /// The last transport or parse error reported since the last `reset`.
     pub endpoint_error: Option<AnyError>,
 
     /// The last result returned by the parser.
-    pub parser_result: Option<AnyResult<usize>>,
+    pub parser_result: Option<(usize, Vec<ParseError>)>,
 
     /// Parser to push data to.
     parser: Box<dyn Parser>,
 
+    /// Input handle used to create parsers for other data formats.
+    input_handle: Box<dyn DeCollectionHandle>,
+
     /// Callback to invoke on transport or parser error.
     ///
     /// Panics on error if `None`.
@@ -28,13 +34,14 @@ pub struct MockInputConsumerState {
 }
 
 impl MockInputConsumerState {
-    fn new(parser: Box<dyn Parser>) -> Self {
+    fn new(parser: Box<dyn Parser>, input_handle: Box<dyn DeCollectionHandle>) -> Self {
         Self {
             data: Vec::new(),
             eoi: false,
             endpoint_error: None,
             parser_result: None,
             parser,
+            input_handle,
             error_cb: None,
         }
     }
@@ -47,7 +54,36 @@ impl MockInputConsumerState {
         let parser = format
             .new_parser(input_handle, &format_config.config)
             .unwrap();
-        Self::new(parser)
+        Self::new(parser, input_handle.fork())
+    }
+
+    /// Report parse errors, flush the parser.
+    ///
+    /// The mock consumer does not run a circuit; it reports all data as
+    /// belonging to the first step.
+    fn parsed(&mut self, parser_result: (usize, Vec<ParseError>)) -> Option<Step> {
+        for e in parser_result.1.iter() {
+            self.error(AnyError::new(e.clone()));
+        }
+        let num_records = parser_result.0;
+        self.parser_result = Some(parser_result);
+        self.parser.flush();
+
+        if num_records > 0 {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    /// Invoke the error callback; panic if there isn't one.
+    fn error(&mut self, error: AnyError) {
+        if let Some(error_cb) = &mut self.error_cb {
+            error_cb(&error);
+        } else {
+            panic!("mock_input_consumer: error '{error}'");
+        }
+        self.endpoint_error = Some(error);
     }
 
     /// Reset all fields to defaults.
@@ -88,33 +124,24 @@ impl MockInputConsumer {
 }
 
 impl InputConsumer for MockInputConsumer {
-    fn input(&mut self, data: &[u8]) {
+    fn input(&mut self, data: &[u8]) -> Option<Step> {
         // println!("input");
         let mut state = self.state();
 
         state.data.extend_from_slice(data);
         let parser_result = state.parser.input(data);
-        // println!("parser returned '{:?}'", state.parser_result);
-        if let Err(e) = &parser_result {
-            if let Some(error_cb) = &mut state.error_cb {
-                error_cb(e);
-            } else {
-                panic!("mock_input_consumer: parse error '{e}'");
-            }
-        }
-        state.parser_result = Some(parser_result);
-        state.parser.flush();
+        state.parsed(parser_result)
     }
 
-    fn error(&mut self, _fatal: bool, error: AnyError) {
+    fn end_of_chunk(&mut self) -> Option<Step> {
         let mut state = self.state();
 
-        if let Some(error_cb) = &mut state.error_cb {
-            error_cb(&error);
-        } else {
-            panic!("mock_input_consumer: transport error '{error}'");
-        }
-        state.endpoint_error = Some(error);
+        let parser_result = state.parser.eoi();
+        state.parsed(parser_result)
+    }
+
+    fn error(&mut self, _fatal: bool, error: AnyError) {
+        self.state().error(error);
     }
 
     fn eoi(&mut self) {
@@ -124,4 +151,9 @@ impl InputConsumer for MockInputConsumer {
     fn fork(&self) -> Box<dyn InputConsumer> {
         Box::new(self.clone())
     }
+
+    fn fork_with_format(&self, format: &FormatConfig) -> AnyResult<Box<dyn InputConsumer>> {
+        let consumer = Self::from_handle(&*self.state().input_handle, format);
+        Ok(Box::new(consumer))
+    }
 }
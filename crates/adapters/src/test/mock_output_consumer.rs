@@ -0,0 +1,40 @@
+use crate::{OutputConsumer, Step};
+use std::sync::{Arc, Mutex};
+
+/// Output consumer that accumulates buffers pushed by an encoder in memory.
+///
+/// Clones of the consumer share the same buffers, so the test can keep a
+/// clone after handing the consumer over to the encoder under test.
+#[derive(Clone, Default)]
+pub struct MockOutputConsumer(Arc<Mutex<Vec<Vec<u8>>>>);
+
+impl MockOutputConsumer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffers received so far.
+    pub fn buffers(&self) -> Vec<Vec<u8>> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Concatenation of all buffers received so far.
+    pub fn concat(&self) -> Vec<u8> {
+        self.0.lock().unwrap().concat()
+    }
+}
+
+impl OutputConsumer for MockOutputConsumer {
+    fn push_buffer(&mut self, buffer: &[u8]) {
+        self.0.lock().unwrap().push(buffer.to_vec());
+    }
+
+    fn batch_start(&mut self, _step: Step) {}
+
+    /// Buffers are stored separately (see [`MockOutputConsumer::buffers`]).
+    fn separates_steps(&self) -> bool {
+        true
+    }
+
+    fn batch_end(&mut self) {}
+}
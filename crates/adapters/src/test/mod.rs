@@ -19,10 +19,12 @@ pub mod websocket;
 
 mod mock_dezset;
 mod mock_input_consumer;
+mod mock_output_consumer;
 
-pub use data::{generate_test_batch, generate_test_batches, TestStruct};
+pub use data::{generate_test_batch, generate_test_batches, test_data, TestStruct};
 pub use mock_dezset::MockDeZSet;
 pub use mock_input_consumer::MockInputConsumer;
+pub use mock_output_consumer::MockOutputConsumer;
 
 pub struct TestLogger;
 pub static TEST_LOGGER: TestLogger = TestLogger;
@@ -76,7 +78,7 @@ pub fn mock_input_pipeline<T>(
     config: InputEndpointConfig,
 ) -> (Box<dyn InputEndpoint>, MockInputConsumer, MockDeZSet<T>)
 where
-    T: for<'de> Deserialize<'de> + Send + 'static,
+    T: for<'de> Deserialize<'de> + Clone + Send + 'static,
 {
     let input_handle = <MockDeZSet<T>>::new();
 
@@ -95,20 +97,21 @@ where
 }
 
 /// Create a simple test circuit that passes the input stream right through to
-/// the output.
+/// the output.  The output stream is materialized.
 // TODO: parameterize with the number (and types?) of input and output streams.
 pub fn test_circuit(workers: usize) -> (DBSPHandle, Catalog) {
-    let (circuit, (input, output)) = Runtime::init_circuit(workers, |circuit| {
+    let (circuit, (input, output, integral)) = Runtime::init_circuit(workers, |circuit| {
         let (input, hinput) = circuit.add_input_zset::<TestStruct, i32>();
 
         let houtput = input.output();
-        (hinput, houtput)
+        let hintegral = input.integrate().output();
+        (hinput, houtput, hintegral)
     })
     .unwrap();
 
     let mut catalog = Catalog::new();
     catalog.register_input_zset_handle("test_input1", input);
-    catalog.register_output_batch_handle("test_output1", output);
+    catalog.register_materialized_output_batch_handle("test_output1", output, integral);
 
     (circuit, catalog)
 }
@@ -0,0 +1,708 @@
+//! Compression of data received and sent by transport endpoints.
+//!
+//! Input data is decompressed by a [`DecompressParser`] inserted in front of
+//! the parser of the endpoint, so that the controller counts compressed
+//! bytes when resuming an endpoint from a checkpoint.  Within a compressed
+//! stream, the parser is resumed by replaying the stream from its start (see
+//! [`Parser::replay_bytes`]).  Output data is compressed by a
+//! [`CompressEndpoint`] that wraps the transport endpoint.
+//!
+//! gzip and Zstandard support requires the `with-compression` feature.
+//! Without it, endpoints configured with either algorithm fail to
+//! initialize.
+
+use super::{OutputEndpoint, SnapshotSink, Step};
+use crate::{OutputConsumer, ParseError, Parser};
+use anyhow::{Error as AnyError, Result as AnyResult};
+#[cfg(feature = "with-compression")]
+use flate2::{
+    write::{GzEncoder, MultiGzDecoder},
+    Compression as GzLevel,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{Error as IoError, ErrorKind, Result as IoResult, Write},
+    mem::take,
+};
+use utoipa::ToSchema;
+#[cfg(feature = "with-compression")]
+use zstd::stream::write::{Decoder as ZstdDecoder, Encoder as ZstdEncoder};
+
+/// Magic bytes at the start of a gzip stream.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Magic bytes at the start of a Zstandard frame.
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression algorithm.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    /// No compression.
+    #[default]
+    None,
+
+    /// gzip compression.
+    Gzip,
+
+    /// Zstandard compression.
+    Zstd,
+
+    /// Detect the compression algorithm of input data from its first bytes.
+    ///
+    /// Data that starts with neither a gzip nor a Zstandard header is
+    /// treated as uncompressed.  Only supported by input endpoints.
+    Auto,
+}
+
+/// Detect compression from the first bytes of a stream.
+///
+/// Returns `None` if more bytes are needed.
+fn detect(header: &[u8]) -> Option<Compression> {
+    let is_prefix = |magic: &[u8]| magic.starts_with(&header[..header.len().min(magic.len())]);
+
+    if header.starts_with(GZIP_MAGIC) {
+        Some(Compression::Gzip)
+    } else if header.starts_with(ZSTD_MAGIC) {
+        Some(Compression::Zstd)
+    } else if is_prefix(GZIP_MAGIC) || is_prefix(ZSTD_MAGIC) {
+        None
+    } else {
+        Some(Compression::None)
+    }
+}
+
+/// Fails if `compression` is not supported by this build.
+pub(crate) fn check_supported(compression: Compression) -> IoResult<()> {
+    match compression {
+        #[cfg(not(feature = "with-compression"))]
+        Compression::Gzip | Compression::Zstd => Err(IoError::new(
+            ErrorKind::Unsupported,
+            format!(
+                "{} compression requires the 'with-compression' feature",
+                if compression == Compression::Gzip {
+                    "gzip"
+                } else {
+                    "zstd"
+                }
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Writer that compresses data before writing it to the underlying writer.
+pub(crate) enum CompressWriter<W: Write> {
+    None(W),
+    #[cfg(feature = "with-compression")]
+    Gzip(GzEncoder<W>),
+    #[cfg(feature = "with-compression")]
+    Zstd(ZstdEncoder<'static, W>),
+}
+
+impl<W: Write> CompressWriter<W> {
+    /// Create a writer with compression algorithm `compression`.
+    ///
+    /// Fails for `Compression::Auto`, which only applies to input data, and
+    /// for algorithms not supported by this build.
+    pub(crate) fn new(compression: Compression, writer: W) -> IoResult<Self> {
+        check_supported(compression)?;
+
+        Ok(match compression {
+            Compression::None => Self::None(writer),
+            #[cfg(feature = "with-compression")]
+            Compression::Gzip => Self::Gzip(GzEncoder::new(writer, GzLevel::default())),
+            #[cfg(feature = "with-compression")]
+            Compression::Zstd => Self::Zstd(ZstdEncoder::new(writer, 0)?),
+            #[cfg(not(feature = "with-compression"))]
+            Compression::Gzip | Compression::Zstd => unreachable!(),
+            Compression::Auto => {
+                return Err(IoError::new(
+                    ErrorKind::InvalidInput,
+                    "'auto' compression is only supported by input endpoints",
+                ))
+            }
+        })
+    }
+
+    /// Write the end of the compressed stream and return the underlying
+    /// writer.
+    pub(crate) fn finish(self) -> IoResult<W> {
+        match self {
+            Self::None(writer) => Ok(writer),
+            #[cfg(feature = "with-compression")]
+            Self::Gzip(encoder) => encoder.finish(),
+            #[cfg(feature = "with-compression")]
+            Self::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for CompressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        match self {
+            Self::None(writer) => writer.write(buf),
+            #[cfg(feature = "with-compression")]
+            Self::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "with-compression")]
+            Self::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    /// Flush all data written so far to the underlying writer.
+    ///
+    /// For compressed streams, this completes the current compressed block,
+    /// so that all data written so far can be decompressed by a reader.
+    fn flush(&mut self) -> IoResult<()> {
+        match self {
+            Self::None(writer) => writer.flush(),
+            #[cfg(feature = "with-compression")]
+            Self::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "with-compression")]
+            Self::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Compress `buffer` as a self-contained gzip member or Zstandard frame.
+///
+/// Concatenated members (frames) form a valid gzip (Zstandard) stream.
+fn compress_buffer(compression: Compression, buffer: &[u8]) -> IoResult<Vec<u8>> {
+    let mut writer = CompressWriter::new(compression, Vec::new())?;
+    writer.write_all(buffer)?;
+    writer.finish()
+}
+
+/// Streaming decompressor.
+enum Decoder {
+    /// Compression has not been detected yet; contains the bytes received
+    /// so far.
+    Detecting(Vec<u8>),
+    None,
+    #[cfg(feature = "with-compression")]
+    Gzip(MultiGzDecoder<Vec<u8>>),
+    #[cfg(feature = "with-compression")]
+    Zstd(ZstdDecoder<'static, Vec<u8>>),
+}
+
+impl Decoder {
+    fn new(compression: Compression) -> IoResult<Self> {
+        check_supported(compression)?;
+
+        Ok(match compression {
+            Compression::None => Self::None,
+            #[cfg(feature = "with-compression")]
+            Compression::Gzip => Self::Gzip(MultiGzDecoder::new(Vec::new())),
+            #[cfg(feature = "with-compression")]
+            Compression::Zstd => Self::Zstd(ZstdDecoder::new(Vec::new())?),
+            #[cfg(not(feature = "with-compression"))]
+            Compression::Gzip | Compression::Zstd => unreachable!(),
+            Compression::Auto => Self::Detecting(Vec::new()),
+        })
+    }
+
+    /// Decompress `data`; returns decompressed bytes available so far.
+    fn decode(&mut self, data: &[u8]) -> IoResult<Vec<u8>> {
+        match self {
+            Self::Detecting(header) => {
+                header.extend_from_slice(data);
+                match detect(header) {
+                    None => Ok(Vec::new()),
+                    Some(compression) => {
+                        let header = take(header);
+                        *self = Self::new(compression)?;
+                        self.decode(&header)
+                    }
+                }
+            }
+            Self::None => Ok(data.to_vec()),
+            #[cfg(feature = "with-compression")]
+            Self::Gzip(decoder) => {
+                decoder.write_all(data)?;
+                decoder.flush()?;
+                Ok(take(decoder.get_mut()))
+            }
+            #[cfg(feature = "with-compression")]
+            Self::Zstd(decoder) => {
+                decoder.write_all(data)?;
+                decoder.flush()?;
+                Ok(take(decoder.get_mut()))
+            }
+        }
+    }
+
+    /// Complete decompression at the end of the stream; returns remaining
+    /// decompressed bytes.
+    fn finish(&mut self) -> IoResult<Vec<u8>> {
+        match self {
+            Self::Detecting(header) => Ok(take(header)),
+            Self::None => Ok(Vec::new()),
+            #[cfg(feature = "with-compression")]
+            Self::Gzip(decoder) => {
+                decoder.try_finish()?;
+                Ok(take(decoder.get_mut()))
+            }
+            #[cfg(feature = "with-compression")]
+            Self::Zstd(decoder) => {
+                decoder.flush()?;
+                Ok(take(decoder.get_mut()))
+            }
+        }
+    }
+}
+
+/// Parser that decompresses input data before passing it to another parser.
+///
+/// The end of a chunk ([`Parser::eoi`]) terminates the compressed stream;
+/// the data that follows is treated as a new stream.  Within a stream,
+/// multiple concatenated gzip members or Zstandard frames are supported, so
+/// that, e.g., a sequence of individually compressed Kafka messages forms a
+/// valid stream.
+pub(crate) struct DecompressParser {
+    compression: Compression,
+    decoder: Decoder,
+    /// Number of bytes received since the start of the current stream.
+    received: u64,
+    /// Number of decompressed bytes produced since the start of the current
+    /// stream.
+    decoded: u64,
+    /// Number of decompressed bytes at the start of the current stream that
+    /// were consumed before the checkpoint the parser was restored from.
+    skip: u64,
+    parser: Box<dyn Parser>,
+}
+
+impl DecompressParser {
+    pub(crate) fn new(compression: Compression, parser: Box<dyn Parser>) -> AnyResult<Self> {
+        Ok(Self {
+            compression,
+            decoder: Decoder::new(compression)?,
+            received: 0,
+            decoded: 0,
+            skip: 0,
+            parser,
+        })
+    }
+
+    /// Pass decompressed `data` to the parser, discarding the part of the
+    /// stream consumed before the checkpoint.
+    fn decoded(&mut self, data: &[u8]) -> (usize, Vec<ParseError>) {
+        let skip = self
+            .skip
+            .saturating_sub(self.decoded)
+            .min(data.len() as u64) as usize;
+        self.decoded += data.len() as u64;
+        if skip == data.len() {
+            (0, Vec::new())
+        } else {
+            self.parser.input(&data[skip..])
+        }
+    }
+
+    /// Start a new stream.
+    fn reset_stream(&mut self) {
+        self.received = 0;
+        self.decoded = 0;
+        self.skip = 0;
+    }
+
+    fn error(&mut self, error: std::io::Error) -> (usize, Vec<ParseError>) {
+        // Discard the rest of the stream.  `Decoder::new` only fails for
+        // `Zstd`, which has already been successfully created once.
+        self.decoder = Decoder::new(self.compression).unwrap();
+        self.reset_stream();
+        self.parser.clear();
+        (
+            0,
+            vec![ParseError::new(
+                format!("failed to decompress input: {error}"),
+                None,
+                None,
+            )],
+        )
+    }
+}
+
+impl Parser for DecompressParser {
+    fn input(&mut self, data: &[u8]) -> (usize, Vec<ParseError>) {
+        self.received += data.len() as u64;
+        match self.decoder.decode(data) {
+            Ok(data) => self.decoded(&data),
+            Err(e) => self.error(e),
+        }
+    }
+
+    fn eoi(&mut self) -> (usize, Vec<ParseError>) {
+        let result = self.decoder.finish();
+        let (mut num_records, mut errors) = match result {
+            Ok(data) => self.decoded(&data),
+            Err(e) => self.error(e),
+        };
+
+        let (eoi_records, eoi_errors) = self.parser.eoi();
+        num_records += eoi_records;
+        errors.extend(eoi_errors);
+
+        // Start a new stream.
+        self.reset_stream();
+        match Decoder::new(self.compression) {
+            Ok(decoder) => self.decoder = decoder,
+            Err(e) => errors.push(ParseError::new(
+                format!("failed to initialize decompressor: {e}"),
+                None,
+                None,
+            )),
+        }
+
+        (num_records, errors)
+    }
+
+    fn flush(&mut self) {
+        self.parser.flush();
+    }
+
+    fn clear(&mut self) {
+        self.parser.clear();
+    }
+
+    /// The state of the decompressor is not serializable.  Instead, the
+    /// checkpoint records the number of decompressed bytes of the current
+    /// stream consumed by the parser, which are discarded when the stream is
+    /// replayed after restoring the parser.  Uncompressed input, detected by
+    /// `auto` compression, is not replayed.
+    fn checkpoint(&self) -> AnyResult<Vec<u8>> {
+        let uncompressed = matches!(self.decoder, Decoder::None);
+        let skip = if uncompressed {
+            0
+        } else {
+            self.decoded.max(self.skip)
+        };
+        Ok(bincode::encode_to_vec(
+            (uncompressed, skip, self.parser.checkpoint()?),
+            bincode::config::standard(),
+        )?)
+    }
+
+    fn restore(&mut self, state: &[u8]) -> AnyResult<()> {
+        let ((uncompressed, skip, parser), _): ((bool, u64, Vec<u8>), _) =
+            bincode::decode_from_slice(state, bincode::config::standard())?;
+        if uncompressed {
+            self.decoder = Decoder::None;
+        }
+        self.skip = skip;
+        self.parser.restore(&parser)
+    }
+
+    fn replay_bytes(&self) -> u64 {
+        if matches!(self.decoder, Decoder::None) {
+            0
+        } else {
+            self.received
+        }
+    }
+
+    fn fork(&self) -> Box<dyn Parser> {
+        Box::new(Self {
+            compression: self.compression,
+            decoder: Decoder::new(self.compression).unwrap(),
+            received: 0,
+            decoded: 0,
+            skip: 0,
+            parser: self.parser.fork(),
+        })
+    }
+}
+
+/// Output endpoint that compresses each buffer before passing it to another
+/// endpoint.
+///
+/// Each buffer is compressed as a self-contained gzip member or Zstandard
+/// frame, so that it can be decompressed on its own (e.g., when the endpoint
+/// sends each buffer as a separate Kafka message) or as part of the stream
+/// of all buffers (e.g., when the endpoint writes buffers to a file).
+pub(crate) struct CompressEndpoint {
+    compression: Compression,
+    endpoint: Box<dyn OutputEndpoint>,
+}
+
+impl CompressEndpoint {
+    pub(crate) fn new(compression: Compression, endpoint: Box<dyn OutputEndpoint>) -> Self {
+        Self {
+            compression,
+            endpoint,
+        }
+    }
+
+    /// Compress the output of `endpoint` as configured by `compression`.
+    ///
+    /// Returns `endpoint` itself if `compression` is `none`.  Fails for
+    /// `auto` compression and for endpoints that cannot deliver compressed
+    /// buffers intact (see [`OutputEndpoint::check_compression`]), as well
+    /// as for algorithms not supported by this build.
+    pub(crate) fn wrap(
+        compression: Compression,
+        endpoint: Box<dyn OutputEndpoint>,
+    ) -> AnyResult<Box<dyn OutputEndpoint>> {
+        match compression {
+            Compression::None => Ok(endpoint),
+            Compression::Auto => Err(AnyError::msg(
+                "'auto' compression is only supported by input endpoints",
+            )),
+            compression => {
+                check_supported(compression)?;
+                endpoint.check_compression()?;
+                Ok(Box::new(Self::new(compression, endpoint)))
+            }
+        }
+    }
+}
+
+impl OutputEndpoint for CompressEndpoint {
+    fn batch_start(&mut self, step: Step) -> AnyResult<()> {
+        self.endpoint.batch_start(step)
+    }
+
+    fn batch_records(&mut self, num_records: usize) -> AnyResult<()> {
+        self.endpoint.batch_records(num_records)
+    }
+
+    fn push_buffer(&mut self, buffer: &[u8]) -> AnyResult<()> {
+        self.endpoint
+            .push_buffer(&compress_buffer(self.compression, buffer)?)
+    }
+
+    fn at_file_start(&self) -> bool {
+        self.endpoint.at_file_start()
+    }
+
+    fn separates_steps(&self) -> bool {
+        self.endpoint.separates_steps()
+    }
+
+    fn batch_end(&mut self) -> AnyResult<()> {
+        self.endpoint.batch_end()
+    }
+
+    fn batch_abort(&mut self) -> AnyResult<()> {
+        self.endpoint.batch_abort()
+    }
+
+    fn snapshot_sink(
+        &self,
+        request_callback: Box<dyn Fn() + Send + Sync>,
+    ) -> Option<Box<dyn SnapshotSink>> {
+        self.endpoint.snapshot_sink(request_callback).map(|sink| {
+            Box::new(CompressSnapshotSink {
+                compression: self.compression,
+                sink,
+            }) as Box<dyn SnapshotSink>
+        })
+    }
+}
+
+/// Snapshot sink that compresses snapshots sent to another sink.
+struct CompressSnapshotSink {
+    compression: Compression,
+    sink: Box<dyn SnapshotSink>,
+}
+
+impl SnapshotSink for CompressSnapshotSink {
+    fn snapshot_requested(&self) -> bool {
+        self.sink.snapshot_requested()
+    }
+
+    fn snapshot_consumer(&self) -> Box<dyn OutputConsumer> {
+        Box::new(CompressConsumer {
+            compression: self.compression,
+            consumer: self.sink.snapshot_consumer(),
+        })
+    }
+}
+
+/// Output consumer that compresses each buffer before passing it to another
+/// consumer.
+struct CompressConsumer {
+    compression: Compression,
+    consumer: Box<dyn OutputConsumer>,
+}
+
+impl OutputConsumer for CompressConsumer {
+    fn batch_start(&mut self, step: Step) {
+        self.consumer.batch_start(step);
+    }
+
+    fn batch_records(&mut self, num_records: usize) {
+        self.consumer.batch_records(num_records);
+    }
+
+    fn push_buffer(&mut self, buffer: &[u8]) {
+        // Compressing into a `Vec` never fails.
+        self.consumer
+            .push_buffer(&compress_buffer(self.compression, buffer).unwrap());
+    }
+
+    fn separates_steps(&self) -> bool {
+        self.consumer.separates_steps()
+    }
+
+    fn batch_end(&mut self) {
+        self.consumer.batch_end();
+    }
+
+    fn batch_abort(&mut self) {
+        self.consumer.batch_abort();
+    }
+}
+
+#[cfg(all(test, feature = "with-compression"))]
+mod test {
+    use super::{compress_buffer, CompressEndpoint, Compression, DecompressParser};
+    use crate::{
+        test::{test_data, MockDeZSet, TestStruct},
+        InputFormat, OutputEndpoint, OutputTransport, Parser,
+    };
+    use anyhow::Error as AnyError;
+    use flate2::read::MultiGzDecoder;
+    use std::{
+        fs::{read, File},
+        io::Read,
+    };
+    use tempfile::tempdir;
+
+    fn parser(compression: Compression, zset: &MockDeZSet<TestStruct>) -> DecompressParser {
+        let parser = <dyn InputFormat>::get_format("csv")
+            .unwrap()
+            .new_parser(zset, &serde_yaml::Value::Null)
+            .unwrap();
+        DecompressParser::new(compression, parser).unwrap()
+    }
+
+    #[test]
+    fn test_decompress() {
+        for (compression, input) in [
+            (Compression::Gzip, Compression::Gzip),
+            (Compression::Zstd, Compression::Zstd),
+            (Compression::Auto, Compression::Gzip),
+            (Compression::Auto, Compression::Zstd),
+            (Compression::Auto, Compression::None),
+        ] {
+            let data = test_data();
+            let zset = MockDeZSet::<TestStruct>::new();
+            let mut parser = parser(compression, &zset);
+
+            // Two concatenated members/frames, split into small buffers.
+            let mut compressed = compress_buffer(input, b"1,true,10,foo\n").unwrap();
+            compressed.extend(compress_buffer(input, b"2,false,,bar").unwrap());
+            for chunk in compressed.chunks(3) {
+                assert_eq!(parser.input(chunk).1, Vec::new());
+            }
+            assert_eq!(parser.eoi().1, Vec::new());
+            parser.flush();
+
+            assert_eq!(
+                zset.state().flushed,
+                vec![(data[0].clone(), true), (data[1].clone(), true)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_decompress_error() {
+        let zset = MockDeZSet::<TestStruct>::new();
+        let mut parser = parser(Compression::Gzip, &zset);
+
+        let (num_records, errors) = parser.input(b"1,true,10,foo\n");
+        assert_eq!(num_records, 0);
+        assert_eq!(errors.len(), 1);
+        parser.eoi();
+
+        // The parser recovers at the start of the next stream.
+        let compressed = compress_buffer(Compression::Gzip, b"1,true,10,foo\n").unwrap();
+        assert_eq!(parser.input(&compressed).1, Vec::new());
+        assert_eq!(parser.eoi().1, Vec::new());
+        parser.flush();
+        assert_eq!(zset.state().flushed, vec![(test_data()[0].clone(), true)]);
+    }
+
+    #[test]
+    fn test_decompress_checkpoint() {
+        for (compression, input) in [
+            (Compression::Gzip, Compression::Gzip),
+            (Compression::Zstd, Compression::Zstd),
+            (Compression::Auto, Compression::Gzip),
+            (Compression::Auto, Compression::None),
+        ] {
+            let data = test_data();
+            let mut stream = compress_buffer(input, b"1,true,10,foo\n2,fal").unwrap();
+            let received = stream.len();
+            stream.extend(compress_buffer(input, b"se,,bar\n").unwrap());
+
+            // Checkpoint the parser in the middle of the stream.
+            let zset = MockDeZSet::<TestStruct>::new();
+            let mut checkpointed = parser(compression, &zset);
+            assert_eq!(checkpointed.input(&stream[..received]).1, Vec::new());
+            checkpointed.flush();
+            assert_eq!(zset.state().flushed, vec![(data[0].clone(), true)]);
+            let state = checkpointed.checkpoint().unwrap();
+            let replay = checkpointed.replay_bytes() as usize;
+            if input == Compression::None {
+                assert_eq!(replay, 0);
+            } else {
+                assert_eq!(replay, received);
+            }
+
+            // The restored parser receives the stream again from the start
+            // of the replayed bytes and only parses data that follows the
+            // checkpoint.
+            let zset = MockDeZSet::<TestStruct>::new();
+            let mut restored = parser(compression, &zset);
+            restored.restore(&state).unwrap();
+            for chunk in stream[received - replay..].chunks(3) {
+                assert_eq!(restored.input(chunk).1, Vec::new());
+            }
+            assert_eq!(restored.eoi().1, Vec::new());
+            restored.flush();
+            assert_eq!(zset.state().flushed, vec![(data[1].clone(), true)]);
+        }
+    }
+
+    #[test]
+    fn test_compress_endpoint() {
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let temp_dir = tempdir().unwrap();
+            let path = temp_dir.path().join("out.csv");
+            let endpoint = <dyn OutputTransport>::get_transport("file")
+                .unwrap()
+                .new_endpoint(
+                    "test_output",
+                    &serde_yaml::from_str(&format!("path: {:?}", path.to_str().unwrap())).unwrap(),
+                    Box::new(|_: bool, e: AnyError| panic!("{e}")),
+                )
+                .unwrap();
+            endpoint.check_compression().unwrap();
+            let mut endpoint = CompressEndpoint::new(compression, endpoint);
+
+            for (step, buffers) in [(0, &["1,true,10,foo\n", "2,false,,bar\n"][..]), (1, &[])] {
+                endpoint.batch_start(step).unwrap();
+                for buffer in buffers {
+                    endpoint.push_buffer(buffer.as_bytes()).unwrap();
+                }
+                endpoint.batch_end().unwrap();
+            }
+            drop(endpoint);
+
+            // The file is a valid stream of concatenated members/frames.
+            let contents = match compression {
+                Compression::Gzip => {
+                    let mut contents = Vec::new();
+                    MultiGzDecoder::new(File::open(&path).unwrap())
+                        .read_to_end(&mut contents)
+                        .unwrap();
+                    contents
+                }
+                _ => zstd::decode_all(read(&path).unwrap().as_slice()).unwrap(),
+            };
+            assert_eq!(contents, b"1,true,10,foo\n2,false,,bar\n");
+        }
+    }
+}
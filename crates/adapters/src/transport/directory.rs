@@ -0,0 +1,708 @@
+use super::{InputConsumer, InputEndpoint, InputTransport, Step};
+use crate::PipelineState;
+use anyhow::{Error as AnyError, Result as AnyResult};
+use crossbeam::sync::{Parker, Unparker};
+use num_traits::FromPrimitive;
+use serde::Deserialize;
+use serde_yaml::Value as YamlValue;
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, VecDeque},
+    fs::{read_dir, read_to_string, rename, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread::spawn,
+    time::{Duration, SystemTime},
+};
+use utoipa::ToSchema;
+
+/// Default name of the file that lists consumed input files.
+const DEFAULT_STATE_FILE: &str = ".dbsp_consumed";
+
+/// `InputTransport` implementation that ingests files added to a directory.
+pub struct DirectoryInputTransport;
+
+impl InputTransport for DirectoryInputTransport {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("directory")
+    }
+
+    fn new_endpoint(
+        &self,
+        _name: &str,
+        config: &YamlValue,
+        consumer: Box<dyn InputConsumer>,
+    ) -> AnyResult<Box<dyn InputEndpoint>> {
+        let config = DirectoryInputConfig::deserialize(config)?;
+        let ep = DirectoryInputEndpoint::new(config, consumer)?;
+        Ok(Box::new(ep))
+    }
+}
+
+/// Order in which files in a directory are ingested.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOrder {
+    /// Lexicographic order of file names.
+    #[default]
+    Name,
+
+    /// Order of last modification times; files with identical modification
+    /// times are ordered by name.
+    Mtime,
+}
+
+/// Configuration of an input endpoint that watches a directory.
+///
+/// The endpoint polls the directory for files whose names match `pattern`
+/// and ingests each file exactly once, one file at a time, so that a single
+/// buffer passed to the consumer never contains data from more than one
+/// file.  Files whose names start with `.` are ignored, so a file can be
+/// written under a hidden name and renamed once complete to avoid ingesting
+/// it partially.
+///
+/// A file is considered consumed once all its contents have been processed
+/// by the circuit (see
+/// [`InputEndpoint::completed_step`](`crate::InputEndpoint::completed_step`)).
+/// At this point the file name is appended to the state file and, if
+/// `archive_dir` is specified, the file is moved to the archive directory.
+/// Files listed in the state file are skipped when the endpoint is
+/// restarted.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct DirectoryInputConfig {
+    /// Directory path.
+    path: String,
+
+    /// Glob pattern that file names must match in order to be ingested.
+    ///
+    /// Supports `*`, which matches any sequence of characters, and `?`, which
+    /// matches any single character.  The default pattern `*` matches all
+    /// files.
+    #[serde(default = "default_pattern")]
+    pattern: String,
+
+    /// Order in which files are ingested.
+    #[serde(default)]
+    order: FileOrder,
+
+    /// Interval between two scans of the directory in milliseconds.
+    ///
+    /// The default is 1000.
+    #[serde(default = "default_poll_interval_ms")]
+    poll_interval_ms: u64,
+
+    /// Read buffer size.
+    ///
+    /// Default: when this parameter is not specified, a platform-specific
+    /// default is used.
+    buffer_size_bytes: Option<usize>,
+
+    /// File that lists consumed files.
+    ///
+    /// Defaults to `.dbsp_consumed` in the input directory.
+    state_file: Option<String>,
+
+    /// Directory to move consumed files to.
+    ///
+    /// When not specified, consumed files remain in the input directory.
+    archive_dir: Option<String>,
+}
+
+fn default_pattern() -> String {
+    "*".to_string()
+}
+
+fn default_poll_interval_ms() -> u64 {
+    1000
+}
+
+impl DirectoryInputConfig {
+    fn state_file(&self) -> PathBuf {
+        match &self.state_file {
+            Some(state_file) => PathBuf::from(state_file),
+            None => Path::new(&self.path).join(DEFAULT_STATE_FILE),
+        }
+    }
+}
+
+/// Returns `true` if `name` matches glob `pattern`.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and the position in `name`
+    // matched against it, used to backtrack on mismatch.
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// A file that has been fully read, but not yet recorded as consumed.
+struct ReadFile {
+    name: String,
+
+    /// The last step that consumed data from the file, if any.
+    step: Option<Step>,
+
+    /// Number of bytes received by the endpoint up to the end of the file.
+    end_offset: u64,
+}
+
+/// State shared by the endpoint and its worker thread.
+struct DirectoryInputInner {
+    config: DirectoryInputConfig,
+    status: AtomicU32,
+
+    /// Number of bytes at the start of the input to skip (see
+    /// [`InputEndpoint::seek`]).
+    skip_bytes: AtomicU64,
+
+    /// Number of bytes received by the endpoint up to the end of the last
+    /// file recorded in the state file.
+    consumed_bytes: u64,
+
+    /// The last step completed by the circuit.
+    completed_step: Mutex<Option<Step>>,
+}
+
+struct DirectoryInputEndpoint {
+    inner: Arc<DirectoryInputInner>,
+    unparker: Unparker,
+}
+
+impl DirectoryInputEndpoint {
+    fn new(config: DirectoryInputConfig, consumer: Box<dyn InputConsumer>) -> AnyResult<Self> {
+        if !Path::new(&config.path).is_dir() {
+            return Err(AnyError::msg(format!(
+                "Input path '{}' is not a directory",
+                config.path
+            )));
+        }
+
+        let (consumed, consumed_bytes) = Self::read_state(&config.state_file())?;
+
+        let inner = Arc::new(DirectoryInputInner {
+            config,
+            status: AtomicU32::new(PipelineState::Paused as u32),
+            skip_bytes: AtomicU64::new(0),
+            consumed_bytes,
+            completed_step: Mutex::new(None),
+        });
+
+        let parker = Parker::new();
+        let unparker = parker.unparker().clone();
+        let worker = DirectoryWorker::new(inner.clone(), consumer, consumed);
+        let _worker = spawn(move || worker.run(parker));
+
+        Ok(Self { inner, unparker })
+    }
+
+    /// Read the list of consumed files from the state file.
+    ///
+    /// Each line of the state file contains the number of bytes received by
+    /// the endpoint up to the end of a file, followed by a space and the name
+    /// of the file.  Returns the set of file names and the byte count of the
+    /// last file.
+    fn read_state(path: &Path) -> AnyResult<(BTreeSet<String>, u64)> {
+        if !path.exists() {
+            return Ok((BTreeSet::new(), 0));
+        }
+
+        let state = read_to_string(path).map_err(|e| {
+            AnyError::msg(format!(
+                "Failed to read state file '{}': {e}",
+                path.display()
+            ))
+        })?;
+
+        let mut consumed = BTreeSet::new();
+        let mut consumed_bytes = 0;
+
+        for line in state.lines().filter(|line| !line.is_empty()) {
+            let (end_offset, name) = line
+                .split_once(' ')
+                .and_then(|(end_offset, name)| Some((end_offset.parse::<u64>().ok()?, name)))
+                .ok_or_else(|| {
+                    AnyError::msg(format!(
+                        "Invalid line in state file '{}': '{line}'",
+                        path.display()
+                    ))
+                })?;
+            consumed.insert(name.to_string());
+            consumed_bytes = end_offset;
+        }
+
+        Ok((consumed, consumed_bytes))
+    }
+}
+
+impl InputEndpoint for DirectoryInputEndpoint {
+    fn pause(&self) -> AnyResult<()> {
+        // Notify worker thread via the status flag.  The worker may
+        // send another buffer downstream before the flag takes effect.
+        self.inner
+            .status
+            .store(PipelineState::Paused as u32, Ordering::Release);
+        Ok(())
+    }
+
+    fn start(&self) -> AnyResult<()> {
+        self.inner
+            .status
+            .store(PipelineState::Running as u32, Ordering::Release);
+
+        // Wake up the worker if it's paused.
+        self.unparker.unpark();
+        Ok(())
+    }
+
+    fn disconnect(&self) {
+        self.inner
+            .status
+            .store(PipelineState::Terminated as u32, Ordering::Release);
+
+        // Wake up the worker if it's paused.
+        self.unparker.unpark();
+    }
+
+    fn completed_step(&self, step: Step) {
+        *self.inner.completed_step.lock().unwrap() = Some(step);
+
+        // Wake up the worker to record consumed files.
+        self.unparker.unpark();
+    }
+
+    /// Files recorded in the state file are not received again, so the
+    /// offset is relative to the end of the last such file.  Fails if the
+    /// checkpoint precedes the end of this file, since the data between
+    /// the checkpoint and the end of the file can no longer be replayed.
+    fn seek(&self, offset: u64, _position: Option<&[u8]>) -> AnyResult<()> {
+        let skip = offset.checked_sub(self.inner.consumed_bytes).ok_or_else(|| {
+            AnyError::msg(format!(
+                "cannot resume from a checkpoint taken before the files listed in state file '{}' were consumed",
+                self.inner.config.state_file().display()
+            ))
+        })?;
+        self.inner.skip_bytes.store(skip, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl Drop for DirectoryInputEndpoint {
+    fn drop(&mut self) {
+        self.disconnect();
+    }
+}
+
+/// Worker thread of a directory input endpoint.
+struct DirectoryWorker {
+    inner: Arc<DirectoryInputInner>,
+    consumer: Box<dyn InputConsumer>,
+
+    /// Names of files that have been consumed, read, or queued for reading.
+    seen: BTreeSet<String>,
+
+    /// Files queued for reading.
+    queue: VecDeque<String>,
+
+    /// The file being read, with the last step that consumed data from it.
+    current: Option<(String, BufReader<File>, Option<Step>)>,
+
+    /// Files that have been read, but not yet recorded as consumed.
+    read: VecDeque<ReadFile>,
+
+    /// Number of bytes received by the endpoint, including the contents of
+    /// files consumed before the endpoint was created.
+    offset: u64,
+}
+
+impl DirectoryWorker {
+    fn new(
+        inner: Arc<DirectoryInputInner>,
+        consumer: Box<dyn InputConsumer>,
+        consumed: BTreeSet<String>,
+    ) -> Self {
+        let offset = inner.consumed_bytes;
+        Self {
+            inner,
+            consumer,
+            seen: consumed,
+            queue: VecDeque::new(),
+            current: None,
+            read: VecDeque::new(),
+            offset,
+        }
+    }
+
+    fn run(mut self, parker: Parker) {
+        let poll_interval = Duration::from_millis(self.inner.config.poll_interval_ms);
+
+        loop {
+            if let Err(e) = self.record_consumed() {
+                self.consumer.error(false, e);
+            }
+
+            match PipelineState::from_u32(self.inner.status.load(Ordering::Acquire)) {
+                Some(PipelineState::Paused) => parker.park_timeout(poll_interval),
+                Some(PipelineState::Running) => {
+                    if self.current.is_none() {
+                        match self.next_file() {
+                            Ok(true) => {}
+                            // No new files -- wait for the next scan.
+                            Ok(false) => {
+                                parker.park_timeout(poll_interval);
+                                continue;
+                            }
+                            Err(e) => {
+                                self.consumer.error(true, e);
+                                return;
+                            }
+                        }
+                    }
+                    if let Err(e) = self.read_buffer() {
+                        self.consumer.error(true, e);
+                        return;
+                    }
+                }
+                Some(PipelineState::Terminated) => return,
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    /// Open the next file to read, scanning the directory if the queue is
+    /// empty.  Returns `false` if there are no new files.
+    fn next_file(&mut self) -> AnyResult<bool> {
+        if self.queue.is_empty() {
+            self.scan()?;
+        }
+
+        let name = match self.queue.pop_front() {
+            Some(name) => name,
+            None => return Ok(false),
+        };
+
+        let path = Path::new(&self.inner.config.path).join(&name);
+        let file = File::open(&path).map_err(|e| {
+            AnyError::msg(format!(
+                "Failed to open input file '{}': {e}",
+                path.display()
+            ))
+        })?;
+        let reader = match self.inner.config.buffer_size_bytes {
+            Some(buffer_size) if buffer_size > 0 => BufReader::with_capacity(buffer_size, file),
+            _ => BufReader::new(file),
+        };
+        self.current = Some((name, reader, None));
+
+        Ok(true)
+    }
+
+    /// Add new files in the directory that match the pattern to the queue.
+    fn scan(&mut self) -> AnyResult<()> {
+        let config = &self.inner.config;
+
+        let entries = read_dir(&config.path).map_err(|e| {
+            AnyError::msg(format!(
+                "Failed to read input directory '{}': {e}",
+                config.path
+            ))
+        })?;
+
+        let mut files = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                // Skip file names that are not valid UTF-8.
+                Err(_) => continue,
+            };
+            if name.starts_with('.')
+                || self.seen.contains(&name)
+                || !glob_match(&config.pattern, &name)
+                || !entry.path().is_file()
+            {
+                continue;
+            }
+            let mtime = match config.order {
+                FileOrder::Name => SystemTime::UNIX_EPOCH,
+                FileOrder::Mtime => entry.metadata()?.modified()?,
+            };
+            files.push((mtime, name));
+        }
+        files.sort();
+
+        for (_, name) in files.into_iter() {
+            self.seen.insert(name.clone());
+            self.queue.push_back(name);
+        }
+
+        Ok(())
+    }
+
+    /// Push the next buffer of the current file to the consumer.
+    fn read_buffer(&mut self) -> AnyResult<()> {
+        let (name, reader, step) = self.current.as_mut().unwrap();
+
+        let data = reader.fill_buf()?;
+        if data.is_empty() {
+            let name = name.clone();
+            let step = *step;
+            self.current = None;
+            // Each file is a self-contained chunk of input.
+            let chunk_step = self.consumer.end_of_chunk();
+            self.read.push_back(ReadFile {
+                name,
+                step: step.max(chunk_step),
+                end_offset: self.offset,
+            });
+            return Ok(());
+        }
+
+        // Skip data consumed before the checkpoint the pipeline was restored
+        // from.
+        let skip = self
+            .inner
+            .skip_bytes
+            .load(Ordering::Acquire)
+            .min(data.len() as u64);
+        if skip > 0 {
+            self.inner.skip_bytes.fetch_sub(skip, Ordering::AcqRel);
+            self.offset += skip;
+            reader.consume(skip as usize);
+            return Ok(());
+        }
+
+        let len = data.len();
+        *step = (*step).max(self.consumer.input(data));
+        self.offset += len as u64;
+        reader.consume(len);
+        Ok(())
+    }
+
+    /// Record files whose contents have been fully processed by the circuit
+    /// in the state file and move them to the archive directory.
+    fn record_consumed(&mut self) -> AnyResult<()> {
+        let completed_step = *self.inner.completed_step.lock().unwrap();
+        let is_completed = |file: &ReadFile| match file.step {
+            None => true,
+            Some(step) => completed_step.map_or(false, |completed| step <= completed),
+        };
+
+        if !self.read.front().map_or(false, is_completed) {
+            return Ok(());
+        }
+
+        let config = &self.inner.config;
+        let state_file = config.state_file();
+        let mut state = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&state_file)
+            .map_err(|e| {
+                AnyError::msg(format!(
+                    "Failed to open state file '{}': {e}",
+                    state_file.display()
+                ))
+            })?;
+
+        while self.read.front().map_or(false, is_completed) {
+            let file = self.read.pop_front().unwrap();
+            writeln!(state, "{} {}", file.end_offset, file.name)?;
+            state.sync_data()?;
+
+            if let Some(archive_dir) = &config.archive_dir {
+                let from = Path::new(&config.path).join(&file.name);
+                let to = Path::new(archive_dir).join(&file.name);
+                rename(&from, &to).map_err(|e| {
+                    AnyError::msg(format!(
+                        "Failed to move '{}' to '{}': {e}",
+                        from.display(),
+                        to.display()
+                    ))
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::glob_match;
+    use crate::test::{mock_input_pipeline, wait};
+    use csv::WriterBuilder as CsvWriterBuilder;
+    use serde::{Deserialize, Serialize};
+    use std::{fs::read_to_string, path::Path};
+    use tempfile::tempdir;
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+    struct TestStruct {
+        s: String,
+        i: i64,
+    }
+
+    impl TestStruct {
+        fn new(s: &str, i: i64) -> Self {
+            Self {
+                s: s.to_string(),
+                i,
+            }
+        }
+    }
+
+    fn write_file(path: &Path, val: &TestStruct) {
+        let mut writer = CsvWriterBuilder::new()
+            .has_headers(false)
+            .from_path(path)
+            .unwrap();
+        writer.serialize(val).unwrap();
+        writer.flush().unwrap();
+    }
+
+    fn config(path: &Path, extra: &str) -> String {
+        format!(
+            r#"
+stream: test_input
+transport:
+    name: directory
+    config:
+        path: {:?}
+        pattern: "*.csv"
+        poll_interval_ms: 10
+        buffer_size_bytes: 5{extra}
+format:
+    name: csv
+"#,
+            path.to_str().unwrap()
+        )
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "foo.csv"));
+        assert!(glob_match("*.csv", "foo.csv"));
+        assert!(glob_match("f?o*.c*v", "foo.bar.csv"));
+        assert!(glob_match("*a*b", "xaxxab"));
+        assert!(!glob_match("*.csv", "foo.json"));
+        assert!(!glob_match("f?o", "fo"));
+        assert!(!glob_match("*a*b", "xaxxa"));
+    }
+
+    #[test]
+    fn test_directory_input() {
+        let data = [
+            TestStruct::new("foo", 0),
+            TestStruct::new("bar", 1),
+            TestStruct::new("baz", 2),
+            TestStruct::new("qux", 3),
+        ];
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path();
+
+        write_file(&input_dir.join("1.csv"), &data[1]);
+        write_file(&input_dir.join("0.csv"), &data[0]);
+        write_file(&input_dir.join("ignored.json"), &data[3]);
+
+        let config_str = config(input_dir, "");
+        let (endpoint, _consumer, zset) =
+            mock_input_pipeline::<TestStruct>(serde_yaml::from_str(&config_str).unwrap());
+
+        endpoint.start().unwrap();
+        wait(|| zset.state().flushed.len() == 2, None);
+        assert_eq!(
+            zset.state().flushed,
+            vec![(data[0].clone(), true), (data[1].clone(), true)]
+        );
+
+        // Files are recorded as consumed once processed by the circuit.
+        let state_file = input_dir.join(".dbsp_consumed");
+        assert!(!state_file.exists());
+        endpoint.completed_step(0);
+        wait(
+            || read_to_string(&state_file).map_or(false, |s| s.lines().count() == 2),
+            None,
+        );
+
+        // New files are picked up.
+        write_file(&input_dir.join("2.csv"), &data[2]);
+        wait(|| zset.state().flushed.len() == 3, None);
+        assert_eq!(zset.state().flushed[2], (data[2].clone(), true));
+        wait(
+            || read_to_string(&state_file).map_or(false, |s| s.lines().count() == 3),
+            None,
+        );
+        endpoint.disconnect();
+        drop(endpoint);
+
+        // After a restart, consumed files are skipped.
+        write_file(&input_dir.join("3.csv"), &data[3]);
+        let (endpoint, _consumer, zset) =
+            mock_input_pipeline::<TestStruct>(serde_yaml::from_str(&config_str).unwrap());
+        endpoint.start().unwrap();
+        wait(|| zset.state().flushed.len() == 1, None);
+        assert_eq!(zset.state().flushed, vec![(data[3].clone(), true)]);
+    }
+
+    #[test]
+    fn test_directory_archive() {
+        let data = [TestStruct::new("foo", 0), TestStruct::new("bar", 1)];
+        let input_dir = tempdir().unwrap();
+        let archive_dir = tempdir().unwrap();
+
+        write_file(&input_dir.path().join("0.csv"), &data[0]);
+        write_file(&input_dir.path().join("1.csv"), &data[1]);
+
+        let config_str = config(
+            input_dir.path(),
+            &format!(
+                "\n        archive_dir: {:?}",
+                archive_dir.path().to_str().unwrap()
+            ),
+        );
+        let (endpoint, _consumer, zset) =
+            mock_input_pipeline::<TestStruct>(serde_yaml::from_str(&config_str).unwrap());
+
+        endpoint.start().unwrap();
+        wait(|| zset.state().flushed.len() == 2, None);
+        endpoint.completed_step(0);
+
+        wait(
+            || {
+                archive_dir.path().join("0.csv").exists()
+                    && archive_dir.path().join("1.csv").exists()
+            },
+            None,
+        );
+        assert!(!input_dir.path().join("0.csv").exists());
+        assert!(!input_dir.path().join("1.csv").exists());
+    }
+}
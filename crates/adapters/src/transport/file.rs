@@ -1,20 +1,25 @@
-use super::{InputConsumer, InputEndpoint, InputTransport, OutputEndpoint, OutputTransport};
-use crate::PipelineState;
+use super::{
+    compression::{check_supported, CompressWriter, Compression},
+    InputConsumer, InputEndpoint, InputTransport, OutputEndpoint, OutputTransport, Step,
+};
+use crate::{schema::civil_from_days, PipelineState};
 use anyhow::{Error as AnyError, Result as AnyResult};
 use crossbeam::sync::{Parker, Unparker};
+use log::error;
 use num_traits::FromPrimitive;
 use serde::Deserialize;
 use serde_yaml::Value as YamlValue;
 use std::{
     borrow::Cow,
-    fs::File,
-    io::{BufRead, BufReader, Write},
+    fs::{rename, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicU32, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
     },
-    thread::{sleep, spawn},
-    time::Duration,
+    thread::{sleep, spawn, JoinHandle},
+    time::{Duration, Instant, SystemTime},
 };
 use utoipa::ToSchema;
 
@@ -44,6 +49,8 @@ impl InputTransport for FileInputTransport {
 #[derive(Deserialize, ToSchema)]
 pub struct FileInputConfig {
     /// File path.
+    ///
+    /// Use the `directory` transport to ingest files from a directory.
     path: String,
 
     /// Read buffer size.
@@ -65,6 +72,9 @@ pub struct FileInputConfig {
 struct FileInputEndpoint {
     config: FileInputConfig,
     status: Arc<AtomicU32>,
+    /// Number of bytes at the start of the input to skip (see
+    /// [`InputEndpoint::seek`]).
+    skip_bytes: Arc<AtomicU64>,
     unparker: Option<Unparker>,
 }
 
@@ -73,11 +83,19 @@ impl FileInputEndpoint {
         Self {
             config,
             status: Arc::new(AtomicU32::new(PipelineState::Paused as u32)),
+            skip_bytes: Arc::new(AtomicU64::new(0)),
             unparker: None,
         }
     }
 
     fn connect(&mut self, consumer: Box<dyn InputConsumer>) -> AnyResult<()> {
+        if Path::new(&self.config.path).is_dir() {
+            return Err(AnyError::msg(format!(
+                "Input path '{}' is a directory: use the 'directory' transport to ingest files from a directory",
+                self.config.path
+            )));
+        }
+
         let file = File::open(&self.config.path).map_err(|e| {
             AnyError::msg(format!(
                 "Failed to open input file '{}': {e}",
@@ -92,8 +110,11 @@ impl FileInputEndpoint {
         let parker = Parker::new();
         self.unparker = Some(parker.unparker().clone());
         let status = self.status.clone();
+        let skip_bytes = self.skip_bytes.clone();
         let follow = self.config.follow;
-        let _worker = spawn(move || Self::worker_thread(reader, consumer, parker, status, follow));
+        let _worker = spawn(move || {
+            Self::worker_thread(reader, consumer, parker, status, skip_bytes, follow)
+        });
         Ok(())
     }
 
@@ -108,6 +129,7 @@ impl FileInputEndpoint {
         mut consumer: Box<dyn InputConsumer>,
         parker: Parker,
         status: Arc<AtomicU32>,
+        skip_bytes: Arc<AtomicU64>,
         follow: bool,
     ) {
         loop {
@@ -129,6 +151,15 @@ impl FileInputEndpoint {
                             }
                         }
                         Ok(data) => {
+                            // Skip data consumed before the checkpoint the pipeline
+                            // was restored from.
+                            let skip = skip_bytes.load(Ordering::Acquire).min(data.len() as u64);
+                            if skip > 0 {
+                                skip_bytes.fetch_sub(skip, Ordering::AcqRel);
+                                reader.consume(skip as usize);
+                                continue;
+                            }
+
                             // println!("read {} bytes from file", data.len());
                             consumer.input(data);
                             let len = data.len();
@@ -168,6 +199,11 @@ impl InputEndpoint for FileInputEndpoint {
         // Wake up the worker if it's paused.
         self.unpark();
     }
+
+    fn seek(&self, offset: u64, _position: Option<&[u8]>) -> AnyResult<()> {
+        self.skip_bytes.store(offset, Ordering::Release);
+        Ok(())
+    }
 }
 
 impl Drop for FileInputEndpoint {
@@ -188,10 +224,10 @@ impl OutputTransport for FileOutputTransport {
         &self,
         _name: &str,
         config: &YamlValue,
-        _async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+        async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
     ) -> AnyResult<Box<dyn OutputEndpoint>> {
         let config = FileOutputConfig::deserialize(config)?;
-        let ep = FileOutputEndpoint::new(config)?;
+        let ep = FileOutputEndpoint::new(config, async_error_callback)?;
 
         Ok(Box::new(ep))
     }
@@ -200,39 +236,434 @@ impl OutputTransport for FileOutputTransport {
 #[derive(Deserialize, ToSchema)]
 pub struct FileOutputConfig {
     /// File path.
+    ///
+    /// When file rotation is enabled by any of the `max_file_*` settings, the
+    /// path is a template for the names of output files, which may contain
+    /// the following placeholders:
+    ///
+    /// * `{step}` - the number of the first step written to the file.
+    ///
+    /// * `{timestamp}` - the time when the file was created, in the
+    ///   `YYYYMMDDTHHMMSSZ` format (UTC).
+    ///
+    /// The template must contain at least one of the placeholders.  Existing
+    /// files are never overwritten: if a file with the generated name already
+    /// exists, a numeric suffix is appended to the name, e.g., `out-0-1.csv`.
     path: String,
+
+    /// Start a new file once the current file reaches this size, in bytes,
+    /// before compression.
+    max_file_size_bytes: Option<u64>,
+
+    /// Start a new file once the current file contains this many records.
+    max_file_records: Option<u64>,
+
+    /// Start a new file once the current file contains the output of this
+    /// many steps.
+    ///
+    /// Formats that encode each step as a self-contained file, e.g., Parquet,
+    /// require this to be set to 1.
+    max_file_steps: Option<u64>,
+
+    /// Start a new file once the current file has been open for this many
+    /// seconds.  The file is completed when it reaches this age even if the
+    /// endpoint doesn't receive any more output.
+    max_file_age_secs: Option<u64>,
+
+    /// Compression algorithm; `auto` is not supported.
+    #[serde(default)]
+    compression: Compression,
 }
 
-struct FileOutputEndpoint {
-    file: File,
+impl FileOutputConfig {
+    fn rotation_enabled(&self) -> bool {
+        self.max_file_size_bytes.is_some()
+            || self.max_file_records.is_some()
+            || self.max_file_steps.is_some()
+            || self.max_file_age_secs.is_some()
+    }
 }
 
-impl FileOutputEndpoint {
-    fn new(config: FileOutputConfig) -> AnyResult<Self> {
-        let file = File::create(&config.path).map_err(|e| {
+/// An output file written by [`FileOutputEndpoint`].
+struct OutputFile {
+    writer: CompressWriter<BufWriter<File>>,
+
+    /// Path of the file.
+    path: PathBuf,
+
+    /// When file rotation is enabled, the file is written under a temporary
+    /// name and renamed to `path` once complete.
+    tmp_path: Option<PathBuf>,
+
+    created: Instant,
+
+    /// Number of bytes written to the file before compression.
+    bytes: u64,
+
+    /// Number of records written to the file.
+    records: u64,
+
+    /// Number of steps written to the file.
+    steps: u64,
+}
+
+impl OutputFile {
+    fn create(
+        path: PathBuf,
+        tmp_path: Option<PathBuf>,
+        compression: Compression,
+    ) -> AnyResult<Self> {
+        // Temporary files are created by `FileOutputConfig::open_file`, which
+        // picks a name that isn't taken; fail rather than clobber a file
+        // created concurrently by another writer.
+        let file = match &tmp_path {
+            Some(tmp_path) => File::options().write(true).create_new(true).open(tmp_path),
+            None => File::create(&path),
+        }
+        .map_err(|e| {
             AnyError::msg(format!(
                 "Failed to create output file '{}': {e}",
-                config.path
+                path.display()
             ))
         })?;
-        Ok(Self { file })
+        Ok(Self {
+            writer: CompressWriter::new(compression, BufWriter::new(file))?,
+            path,
+            tmp_path,
+            created: Instant::now(),
+            bytes: 0,
+            records: 0,
+            steps: 0,
+        })
+    }
+
+    /// Complete the file and move it to its final location.
+    fn close(self) -> AnyResult<()> {
+        let file = self
+            .writer
+            .finish()?
+            .into_inner()
+            .map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        if let Some(tmp_path) = &self.tmp_path {
+            rename(tmp_path, &self.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Output endpoint that writes to a file.
+///
+/// Output is buffered in memory and flushed to the file at the end of each
+/// step, so that the file contains the complete output of every step the
+/// endpoint has finished processing.
+///
+/// When file rotation is enabled, output is split across multiple files.
+/// Files are only rotated between steps, so the output of a step is never
+/// split across files, and a file can exceed the size and record limits by
+/// the output of one step.  Each file is written under a hidden temporary
+/// name and renamed once complete, so readers never observe partially
+/// written files.  When `max_file_age_secs` is set, a background thread
+/// completes the current file once it reaches the age limit, even if the
+/// endpoint receives no more output.
+struct FileOutputEndpoint {
+    config: Arc<FileOutputConfig>,
+
+    /// State shared with the age rotation thread.
+    state: Arc<Mutex<FileOutputState>>,
+
+    /// The current step.
+    step: Step,
+
+    /// Number of records in the output of the current step.
+    step_records: u64,
+
+    /// Age rotation thread, if any, and the flag that tells it to exit.
+    age_thread: Option<(Arc<AtomicBool>, Unparker, JoinHandle<()>)>,
+}
+
+struct FileOutputState {
+    /// The current output file, if any.
+    file: Option<OutputFile>,
+
+    /// `true` while the endpoint is writing the output of a step.
+    in_batch: bool,
+}
+
+impl FileOutputState {
+    /// Close the current file if it has reached one of the configured limits.
+    fn rotate_if_full(&mut self, config: &FileOutputConfig) -> AnyResult<()> {
+        if config.rotation_enabled()
+            && self
+                .file
+                .as_ref()
+                .map_or(false, |file| config.file_full(file))
+        {
+            self.file.take().unwrap().close()?;
+        }
+        Ok(())
+    }
+}
+
+impl FileOutputEndpoint {
+    fn new(
+        config: FileOutputConfig,
+        async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+    ) -> AnyResult<Self> {
+        if config.compression == Compression::Auto {
+            return Err(AnyError::msg(
+                "'auto' compression is only supported by input endpoints",
+            ));
+        }
+        check_supported(config.compression)?;
+
+        let mut file = None;
+        if config.rotation_enabled() {
+            if !config.path.contains("{step}") && !config.path.contains("{timestamp}") {
+                return Err(AnyError::msg(format!(
+                    "Output file path '{}' must contain '{{step}}' or '{{timestamp}}' when file rotation is enabled",
+                    config.path
+                )));
+            }
+        } else {
+            file = Some(OutputFile::create(
+                PathBuf::from(&config.path),
+                None,
+                config.compression,
+            )?);
+        }
+
+        let config = Arc::new(config);
+        let state = Arc::new(Mutex::new(FileOutputState {
+            file,
+            in_batch: false,
+        }));
+
+        let age_thread = config.max_file_age_secs.map(|max_age| {
+            let terminate = Arc::new(AtomicBool::new(false));
+            let parker = Parker::new();
+            let unparker = parker.unparker().clone();
+            let handle = {
+                let config = config.clone();
+                let state = state.clone();
+                let terminate = terminate.clone();
+                spawn(move || {
+                    Self::rotate_by_age(
+                        Duration::from_secs(max_age),
+                        config,
+                        state,
+                        parker,
+                        terminate,
+                        async_error_callback,
+                    )
+                })
+            };
+            (terminate, unparker, handle)
+        });
+
+        Ok(Self {
+            config,
+            state,
+            step: 0,
+            step_records: 0,
+            age_thread,
+        })
+    }
+
+    /// Completes the current file once it has been open for `max_age`,
+    /// unless the endpoint is in the middle of a step, in which case the
+    /// file is rotated at the end of the step.
+    fn rotate_by_age(
+        max_age: Duration,
+        config: Arc<FileOutputConfig>,
+        state: Arc<Mutex<FileOutputState>>,
+        parker: Parker,
+        terminate: Arc<AtomicBool>,
+        async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+    ) {
+        loop {
+            let timeout = match &state.lock().unwrap().file {
+                Some(file) => max_age.saturating_sub(file.created.elapsed()),
+                None => max_age,
+            };
+            parker.park_timeout(timeout.max(Duration::from_millis(SLEEP_MS)));
+            if terminate.load(Ordering::Acquire) {
+                return;
+            }
+
+            let mut state = state.lock().unwrap();
+            if !state.in_batch {
+                if let Err(e) = state.rotate_if_full(&config) {
+                    async_error_callback(false, e);
+                }
+            }
+        }
+    }
+}
+
+impl FileOutputConfig {
+    /// Open a new output file for the output of step `step`.
+    ///
+    /// Never overwrites an existing file: if the path generated from the
+    /// template is taken, e.g., by a file created within the same second or
+    /// before the pipeline was restarted, adds a numeric suffix to the file
+    /// name (`out-0.csv` becomes `out-0-1.csv`, `out-0-2.csv`, etc.).
+    fn open_file(&self, step: Step) -> AnyResult<OutputFile> {
+        let path = PathBuf::from(
+            self.path
+                .replace("{step}", &step.to_string())
+                .replace("{timestamp}", &file_timestamp(SystemTime::now())),
+        );
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| AnyError::msg(format!("Invalid output file path '{}'", path.display())))?
+            .to_string_lossy()
+            .into_owned();
+
+        let mut suffix = 0;
+        loop {
+            let name = numbered_file_name(&file_name, suffix);
+            let tmp_path = path.with_file_name(format!(".{name}.part"));
+            let path = path.with_file_name(name);
+            if !path.exists() && !tmp_path.exists() {
+                return OutputFile::create(path, Some(tmp_path), self.compression);
+            }
+            suffix += 1;
+        }
+    }
+
+    /// Returns `true` if `file` has reached one of the configured limits.
+    fn file_full(&self, file: &OutputFile) -> bool {
+        let reached = |value: u64, limit: Option<u64>| limit.map_or(false, |limit| value >= limit);
+
+        reached(file.bytes, self.max_file_size_bytes)
+            || reached(file.records, self.max_file_records)
+            || reached(file.steps, self.max_file_steps)
+            || reached(file.created.elapsed().as_secs(), self.max_file_age_secs)
+    }
+}
+
+/// Insert `-<suffix>` into `name` before its extension(s), unless `suffix`
+/// is 0.
+fn numbered_file_name(name: &str, suffix: u64) -> String {
+    if suffix == 0 {
+        return name.to_string();
+    }
+
+    // Skip the first character, so that the name of a hidden file is not
+    // mistaken for an extension.
+    match name.char_indices().skip(1).find(|(_, c)| *c == '.') {
+        Some((pos, _)) => format!("{}-{suffix}{}", &name[..pos], &name[pos..]),
+        None => format!("{name}-{suffix}"),
     }
 }
 
+/// Format `time` as `YYYYMMDDTHHMMSSZ`.
+fn file_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs()) as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let secs = secs.rem_euclid(86_400);
+
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
 impl OutputEndpoint for FileOutputEndpoint {
+    fn batch_start(&mut self, step: Step) -> AnyResult<()> {
+        self.step = step;
+        self.step_records = 0;
+
+        let mut state = self.state.lock().unwrap();
+        state.in_batch = true;
+        state.rotate_if_full(&self.config)
+    }
+
+    fn batch_records(&mut self, num_records: usize) -> AnyResult<()> {
+        self.step_records += num_records as u64;
+        Ok(())
+    }
+
     fn push_buffer(&mut self, buffer: &[u8]) -> AnyResult<()> {
-        self.file.write_all(buffer)?;
+        let mut state = self.state.lock().unwrap();
+        if state.file.is_none() {
+            state.file = Some(self.config.open_file(self.step)?);
+        }
+
+        let file = state.file.as_mut().unwrap();
+        file.writer.write_all(buffer)?;
+        file.bytes += buffer.len() as u64;
+        Ok(())
+    }
+
+    fn at_file_start(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .file
+            .as_ref()
+            .map_or(true, |file| file.bytes == 0)
+    }
+
+    fn separates_steps(&self) -> bool {
+        self.config.max_file_steps == Some(1)
+    }
+
+    fn batch_end(&mut self) -> AnyResult<()> {
+        let mut state = self.state.lock().unwrap();
+        state.in_batch = false;
+        if let Some(file) = &mut state.file {
+            file.writer.flush()?;
+            file.records += self.step_records;
+            file.steps += 1;
+        }
+        self.step_records = 0;
+        state.rotate_if_full(&self.config)
+    }
+
+    fn check_compression(&self) -> AnyResult<()> {
+        if self.config.compression != Compression::None {
+            return Err(AnyError::msg(
+                "'compression' cannot be specified both for the endpoint and in the 'file' transport configuration",
+            ));
+        }
         Ok(())
     }
 }
 
+impl Drop for FileOutputEndpoint {
+    fn drop(&mut self) {
+        if let Some((terminate, unparker, handle)) = self.age_thread.take() {
+            terminate.store(true, Ordering::Release);
+            unparker.unpark();
+            let _ = handle.join();
+        }
+
+        if let Some(file) = self.state.lock().unwrap().file.take() {
+            if let Err(e) = file.close() {
+                error!("Failed to close output file: {e}");
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::test::{mock_input_pipeline, wait};
+    use super::numbered_file_name;
+    use crate::{
+        test::{mock_input_pipeline, wait},
+        OutputEndpoint, OutputTransport,
+    };
+    use anyhow::Error as AnyError;
     use csv::WriterBuilder as CsvWriterBuilder;
     use serde::{Deserialize, Serialize};
-    use std::{io::Write, thread::sleep, time::Duration};
-    use tempfile::NamedTempFile;
+    use std::{fs::read_dir, io::Write, path::Path, thread::sleep, time::Duration};
+    use tempfile::{tempdir, NamedTempFile};
 
     #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
     struct TestStruct {
@@ -369,7 +800,7 @@ format:
             || {
                 let state = consumer.state();
                 // println!("result: {:?}", state.parser_result);
-                state.parser_result.is_some() && state.parser_result.as_ref().unwrap().is_err()
+                state.parser_result.is_some() && !state.parser_result.as_ref().unwrap().1.is_empty()
             },
             None,
         );
@@ -379,4 +810,219 @@ format:
 
         endpoint.disconnect();
     }
+
+    fn output_endpoint(config: &str) -> Box<dyn OutputEndpoint> {
+        <dyn OutputTransport>::get_transport("file")
+            .unwrap()
+            .new_endpoint(
+                "test_output",
+                &serde_yaml::from_str(config).unwrap(),
+                Box::new(|_: bool, e: AnyError| panic!("{e}")),
+            )
+            .unwrap()
+    }
+
+    fn write_step(endpoint: &mut dyn OutputEndpoint, step: u64, records: &[&str]) {
+        endpoint.batch_start(step).unwrap();
+        endpoint.batch_records(records.len()).unwrap();
+        for record in records {
+            endpoint.push_buffer(record.as_bytes()).unwrap();
+        }
+        endpoint.batch_end().unwrap();
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names = read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_file_rotation() {
+        let temp_dir = tempdir().unwrap();
+        let config = format!(
+            "path: {:?}\nmax_file_records: 3",
+            temp_dir.path().join("out-{step}.csv").to_str().unwrap()
+        );
+        let mut endpoint = output_endpoint(&config);
+
+        endpoint.batch_start(0).unwrap();
+        assert!(endpoint.at_file_start());
+        endpoint.batch_end().unwrap();
+
+        write_step(&mut *endpoint, 0, &["1\n", "2\n"]);
+        assert!(!endpoint.at_file_start());
+        // Empty steps don't create new files.
+        write_step(&mut *endpoint, 1, &[]);
+
+        // The file being written is hidden.
+        assert_eq!(file_names(temp_dir.path()), vec![".out-0.csv.part"]);
+
+        write_step(&mut *endpoint, 2, &["3\n", "4\n"]);
+        // The first file is full.
+        assert!(endpoint.at_file_start());
+        write_step(&mut *endpoint, 4, &["5\n"]);
+        assert_eq!(
+            file_names(temp_dir.path()),
+            vec![".out-4.csv.part", "out-0.csv"]
+        );
+
+        // Dropping the endpoint completes the last file.
+        drop(endpoint);
+        assert_eq!(file_names(temp_dir.path()), vec!["out-0.csv", "out-4.csv"]);
+
+        let read = |name: &str| std::fs::read_to_string(temp_dir.path().join(name)).unwrap();
+        assert_eq!(read("out-0.csv"), "1\n2\n3\n4\n");
+        assert_eq!(read("out-4.csv"), "5\n");
+    }
+
+    #[cfg(feature = "with-compression")]
+    #[test]
+    fn test_file_rotation_gzip() {
+        use flate2::read::GzDecoder;
+        use std::{fs::File, io::Read};
+
+        let temp_dir = tempdir().unwrap();
+        let config = format!(
+            "path: {:?}\nmax_file_steps: 1\ncompression: gzip",
+            temp_dir.path().join("out-{step}.csv.gz").to_str().unwrap()
+        );
+        let mut endpoint = output_endpoint(&config);
+
+        write_step(&mut *endpoint, 0, &["1\n", "2\n"]);
+        write_step(&mut *endpoint, 1, &["3\n"]);
+        assert_eq!(
+            file_names(temp_dir.path()),
+            vec!["out-0.csv.gz", "out-1.csv.gz"]
+        );
+
+        let read = |name: &str| {
+            let mut contents = String::new();
+            GzDecoder::new(File::open(temp_dir.path().join(name)).unwrap())
+                .read_to_string(&mut contents)
+                .unwrap();
+            contents
+        };
+        assert_eq!(read("out-0.csv.gz"), "1\n2\n");
+        assert_eq!(read("out-1.csv.gz"), "3\n");
+    }
+
+    #[test]
+    fn test_file_rotation_no_overwrite() {
+        let temp_dir = tempdir().unwrap();
+        let config = format!(
+            "path: {:?}\nmax_file_steps: 1",
+            temp_dir.path().join("out-{step}.csv").to_str().unwrap()
+        );
+
+        // A restarted pipeline starts numbering steps from 0 again.
+        for records in [&["1\n"], &["2\n"], &["3\n"]] {
+            let mut endpoint = output_endpoint(&config);
+            write_step(&mut *endpoint, 0, records);
+        }
+        assert_eq!(
+            file_names(temp_dir.path()),
+            vec!["out-0-1.csv", "out-0-2.csv", "out-0.csv"]
+        );
+
+        let read = |name: &str| std::fs::read_to_string(temp_dir.path().join(name)).unwrap();
+        assert_eq!(read("out-0.csv"), "1\n");
+        assert_eq!(read("out-0-1.csv"), "2\n");
+        assert_eq!(read("out-0-2.csv"), "3\n");
+    }
+
+    #[test]
+    fn test_numbered_file_name() {
+        assert_eq!(numbered_file_name("out.csv.gz", 0), "out.csv.gz");
+        assert_eq!(numbered_file_name("out.csv.gz", 2), "out-2.csv.gz");
+        assert_eq!(numbered_file_name("out", 1), "out-1");
+        assert_eq!(numbered_file_name(".out.csv", 1), ".out-1.csv");
+    }
+
+    #[test]
+    fn test_file_rotation_idle() {
+        let temp_dir = tempdir().unwrap();
+        let config = format!(
+            "path: {:?}\nmax_file_age_secs: 1",
+            temp_dir.path().join("out-{step}.csv").to_str().unwrap()
+        );
+        let mut endpoint = output_endpoint(&config);
+
+        write_step(&mut *endpoint, 0, &["1\n", "2\n"]);
+        assert_eq!(file_names(temp_dir.path()), vec![".out-0.csv.part"]);
+
+        // The file is completed once it reaches the age limit, without
+        // waiting for the next step.
+        assert!(wait(
+            || file_names(temp_dir.path()) == vec!["out-0.csv"],
+            Some(5_000)
+        )
+        .is_some());
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("out-0.csv")).unwrap(),
+            "1\n2\n"
+        );
+
+        write_step(&mut *endpoint, 1, &["3\n"]);
+        drop(endpoint);
+        assert_eq!(file_names(temp_dir.path()), vec!["out-0.csv", "out-1.csv"]);
+    }
+
+    #[test]
+    fn test_file_rotation_requires_template() {
+        let temp_dir = tempdir().unwrap();
+        let config = format!(
+            "path: {:?}\nmax_file_steps: 1",
+            temp_dir.path().join("out.csv").to_str().unwrap()
+        );
+        assert!(<dyn OutputTransport>::get_transport("file")
+            .unwrap()
+            .new_endpoint(
+                "test_output",
+                &serde_yaml::from_str(&config).unwrap(),
+                Box::new(|_: bool, _: AnyError| {}),
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_file_compression_config() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("out.csv");
+        let path = path.to_str().unwrap();
+
+        // `auto` only applies to input data.
+        assert!(<dyn OutputTransport>::get_transport("file")
+            .unwrap()
+            .new_endpoint(
+                "test_output",
+                &serde_yaml::from_str(&format!("path: {path:?}\ncompression: auto")).unwrap(),
+                Box::new(|_: bool, _: AnyError| {}),
+            )
+            .is_err());
+
+        // An endpoint that compresses files can't also compress each buffer.
+        #[cfg(feature = "with-compression")]
+        {
+            let endpoint = output_endpoint(&format!("path: {path:?}\ncompression: gzip"));
+            assert!(endpoint.check_compression().is_err());
+        }
+
+        // Compression algorithms are only available with `with-compression`.
+        #[cfg(not(feature = "with-compression"))]
+        assert!(<dyn OutputTransport>::get_transport("file")
+            .unwrap()
+            .new_endpoint(
+                "test_output",
+                &serde_yaml::from_str(&format!("path: {path:?}\ncompression: gzip")).unwrap(),
+                Box::new(|_: bool, _: AnyError| {}),
+            )
+            .is_err());
+
+        let endpoint = output_endpoint(&format!("path: {path:?}"));
+        assert!(endpoint.check_compression().is_ok());
+    }
 }
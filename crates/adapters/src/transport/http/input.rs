@@ -1,8 +1,11 @@
-use super::MAX_SOCKETS_PER_ENDPOINT;
-use crate::{InputConsumer, InputEndpoint, InputTransport, PipelineState};
+use super::{format_config_from_args, MAX_SOCKETS_PER_ENDPOINT};
+use crate::{InputConsumer, InputEndpoint, InputTransport, PipelineState, Step};
 use actix::{Actor, ActorContext, Addr, AsyncContext, Handler, Message, StreamHandler};
 use actix_http::ws::Item as WsItem;
-use actix_web::{web::Payload, HttpRequest, HttpResponse};
+use actix_web::{
+    web::{self, Payload, Query},
+    HttpRequest, HttpResponse,
+};
 use actix_web_actors::ws::{
     self, CloseCode as WsCloseCode, CloseReason as WsCloseReason, Message as WsMessage,
     ProtocolError as WsProtocolError, WebsocketContext,
@@ -10,6 +13,7 @@ use actix_web_actors::ws::{
 use anyhow::{anyhow, Result as AnyResult};
 use byteorder::{BigEndian, WriteBytesExt};
 use bytes::Bytes;
+use futures::StreamExt;
 use log::{debug, info};
 use num_traits::FromPrimitive;
 use once_cell::sync::Lazy;
@@ -17,12 +21,13 @@ use serde::Deserialize;
 use serde_yaml::Value as YamlValue;
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     sync::{
         atomic::{AtomicU32, Ordering},
         Arc, Mutex, RwLock,
     },
 };
+use tokio::sync::watch;
 use utoipa::ToSchema;
 
 /// Global map of input HTTP endpoints.
@@ -30,7 +35,7 @@ static INPUT_HTTP_ENDPOINTS: Lazy<RwLock<BTreeMap<String, HttpInputEndpoint>>> =
     Lazy::new(|| RwLock::new(BTreeMap::new()));
 
 /// `InputTransport` implementation that receives data from an HTTP endpoint
-/// via a websocket.
+/// via a websocket or HTTP `POST` requests.
 pub struct HttpInputTransport;
 
 impl InputTransport for HttpInputTransport {
@@ -72,6 +77,109 @@ impl HttpInputTransport {
         info!("HTTP input endpoint '{endpoint_name}': opened websocket");
         Ok(resp)
     }
+
+    /// Handle a `POST /input_endpoint/{endpoint_name}` request.
+    ///
+    /// Streams the request body, which may use chunked transfer encoding, to
+    /// the endpoint's parser.  The body must consist of complete records.
+    ///
+    /// The following query parameters are supported:
+    ///
+    /// * `format` - name of the data format of the request body, overriding
+    ///   the format configured for the endpoint.  All other query parameters,
+    ///   except `wait`, are passed to the parser as format configuration
+    ///   options.
+    ///
+    /// * `wait` - when `true` (the default), the response is sent after all
+    ///   records in the body have been processed by the circuit.  When
+    ///   `false`, the response is sent as soon as the body has been parsed.
+    pub(crate) async fn post_endpoint_data(
+        endpoint_name: &str,
+        req: &HttpRequest,
+        mut payload: Payload,
+    ) -> AnyResult<HttpResponse> {
+        let endpoint = INPUT_HTTP_ENDPOINTS
+            .read()
+            .unwrap()
+            .get(endpoint_name)
+            .map(Clone::clone)
+            .ok_or_else(|| anyhow!("unknown HTTP input endpoint '{endpoint_name}'"))?;
+
+        let mut args = Query::<HashMap<String, String>>::from_query(req.query_string())
+            .map_err(|e| anyhow!("invalid query string: {e}"))?
+            .into_inner();
+
+        let wait = match args.remove("wait") {
+            None => true,
+            Some(wait) => wait
+                .parse::<bool>()
+                .map_err(|_| anyhow!("invalid value of the 'wait' argument: '{wait}'"))?,
+        };
+
+        let mut consumer = match args.remove("format") {
+            None if args.is_empty() => endpoint.inner.consumer.lock().unwrap().fork(),
+            None => {
+                return Err(anyhow!(
+                    "format options can only be specified along with the 'format' argument"
+                ))
+            }
+            Some(format) => {
+                let format = format_config_from_args(&format, args);
+                endpoint
+                    .inner
+                    .consumer
+                    .lock()
+                    .unwrap()
+                    .fork_with_format(&format)?
+            }
+        };
+
+        if endpoint.state() != PipelineState::Running {
+            return Ok(HttpResponse::ServiceUnavailable().body(format!(
+                "HTTP input endpoint '{endpoint_name}' is not running"
+            )));
+        }
+
+        debug!("HTTP input endpoint '{endpoint_name}': receiving POST request");
+
+        // Largest step number that will process records in the request.
+        let mut step = None;
+        while let Some(bytes) = payload.next().await {
+            let bytes = bytes.map_err(|e| anyhow!("error receiving HTTP request body: {e}"))?;
+            let (returned, chunk_step) =
+                Self::parse(consumer, move |consumer| consumer.input(&bytes)).await?;
+            consumer = returned;
+            step = step.max(chunk_step);
+        }
+        let (_, chunk_step) = Self::parse(consumer, |consumer| consumer.end_of_chunk()).await?;
+        step = step.max(chunk_step);
+
+        if wait {
+            if let Some(step) = step {
+                endpoint.wait_for_step(step).await?;
+            }
+        }
+
+        Ok(HttpResponse::Ok().finish())
+    }
+
+    /// Invoke `f` on `consumer` in the blocking thread pool, so that parsing
+    /// does not stall the async runtime.  Returns the consumer back along
+    /// with the result of `f`.
+    async fn parse<F>(
+        mut consumer: Box<dyn InputConsumer>,
+        f: F,
+    ) -> AnyResult<(Box<dyn InputConsumer>, Option<Step>)>
+    where
+        F: FnOnce(&mut dyn InputConsumer) -> Option<Step> + Send + 'static,
+    {
+        web::block(move || {
+            let step = f(consumer.as_mut());
+            (consumer, step)
+        })
+        .await
+        .map_err(|e| anyhow!("error parsing HTTP request body: {e}"))
+    }
 }
 
 #[derive(Clone, Deserialize, ToSchema)]
@@ -92,6 +200,12 @@ struct HttpInputEndpointInner {
     /// This field is used to notify all websocket actors about endpoint
     /// state changes.
     socket_addrs: RwLock<HashSet<Addr<HttpInputWs>>>,
+
+    /// The latest step reported as completed by the controller.
+    ///
+    /// `POST` requests subscribe to this channel to wait for their data to be
+    /// processed.
+    completed_step: watch::Sender<Option<Step>>,
 }
 
 impl HttpInputEndpointInner {
@@ -101,6 +215,7 @@ impl HttpInputEndpointInner {
             state: AtomicU32::new(PipelineState::Paused as u32),
             consumer: Mutex::new(consumer),
             socket_addrs: RwLock::new(HashSet::new()),
+            completed_step: watch::channel(None).0,
         }
     }
 }
@@ -170,6 +285,25 @@ impl HttpInputEndpoint {
     fn push_bytes(&self, bytes: &[u8]) {
         self.inner.consumer.lock().unwrap().input(bytes);
     }
+
+    /// Wait for the controller to complete step `step`.
+    async fn wait_for_step(&self, step: Step) -> AnyResult<()> {
+        let mut receiver = self.inner.completed_step.subscribe();
+
+        loop {
+            if matches!(*receiver.borrow(), Some(completed) if completed >= step) {
+                return Ok(());
+            }
+            if self.state() == PipelineState::Terminated {
+                return Err(anyhow!(
+                    "HTTP input endpoint '{}' disconnected before the data was processed",
+                    self.name()
+                ));
+            }
+            // The sender is owned by the endpoint, which outlives the request.
+            let _ = receiver.changed().await;
+        }
+    }
 }
 
 impl InputEndpoint for HttpInputEndpoint {
@@ -196,6 +330,22 @@ impl InputEndpoint for HttpInputEndpoint {
             .state
             .store(PipelineState::Terminated as u32, Ordering::Release);
         self.notify_sockets();
+
+        // Wake up `POST` requests waiting for their data to be processed.
+        self.inner.completed_step.send_modify(|_| {});
+
+        // Release the endpoint name, so it can be reused by a new endpoint.
+        let mut endpoint_map = INPUT_HTTP_ENDPOINTS.write().unwrap();
+        if endpoint_map
+            .get(self.name())
+            .map_or(false, |ep| Arc::ptr_eq(&ep.inner, &self.inner))
+        {
+            endpoint_map.remove(self.name());
+        }
+    }
+
+    fn completed_step(&self, step: Step) {
+        self.inner.completed_step.send_replace(Some(step));
     }
 }
 
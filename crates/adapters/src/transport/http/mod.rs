@@ -1,7 +1,29 @@
+use crate::FormatConfig;
+use serde_yaml::{Mapping as YamlMapping, Value as YamlValue};
+use std::{borrow::Cow, collections::HashMap};
+
 mod input;
 mod output;
 
 pub(self) static MAX_SOCKETS_PER_ENDPOINT: usize = 5;
 
 pub use input::HttpInputTransport;
-pub use output::HttpOutputTransport;
+pub use output::{HttpOutputConfig, HttpOutputTransport};
+
+/// Build format configuration from HTTP query arguments.
+///
+/// `args` contains format-specific configuration options.  Option values are
+/// interpreted as YAML scalars, so that, e.g., `array=true` is parsed as a
+/// boolean.
+pub(crate) fn format_config_from_args(name: &str, args: HashMap<String, String>) -> FormatConfig {
+    let mut config = YamlMapping::new();
+    for (key, value) in args.into_iter() {
+        let value = serde_yaml::from_str::<YamlValue>(&value).unwrap_or(YamlValue::String(value));
+        config.insert(YamlValue::String(key), value);
+    }
+
+    FormatConfig {
+        name: Cow::Owned(name.to_string()),
+        config: YamlValue::Mapping(config),
+    }
+}
@@ -1,28 +1,40 @@
 use super::MAX_SOCKETS_PER_ENDPOINT;
-use crate::{OutputEndpoint, OutputTransport};
+use crate::{OutputConsumer, OutputEndpoint, OutputTransport, SnapshotSink, Step};
 use actix::{Actor, ActorContext, Addr, AsyncContext, Handler, Message, StreamHandler};
-use actix_web::{web::Payload, HttpRequest, HttpResponse};
+use actix_web::{
+    http::header::{CacheControl, CacheDirective},
+    web::{Payload, Query},
+    HttpRequest, HttpResponse,
+};
 use actix_web_actors::ws::{
     self, Message as WsMessage, ProtocolError as WsProtocolError, WebsocketContext,
 };
 use anyhow::{anyhow, Error as AnyError, Result as AnyResult};
-use futures::executor::block_on;
+use bytes::Bytes;
+use futures::{executor::block_on, stream};
 use log::{debug, info};
 use once_cell::sync::Lazy;
 use serde::Deserialize;
 use serde_yaml::Value as YamlValue;
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, HashSet},
-    sync::{Arc, RwLock},
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::Infallible,
+    mem::take,
+    sync::{Arc, Mutex, RwLock, Weak},
 };
+use tokio::sync::mpsc::{self, error::TrySendError};
 use utoipa::ToSchema;
 
 /// Global map of output HTTP endpoints.
-static OUTPUT_HTTP_ENDPOINTS: Lazy<RwLock<BTreeMap<String, HttpOutputEndpoint>>> =
+///
+/// The map holds weak references, so that an endpoint disappears from the map
+/// once the controller destroys it, and its name can be reused.
+static OUTPUT_HTTP_ENDPOINTS: Lazy<RwLock<BTreeMap<String, Weak<HttpOutputEndpointInner>>>> =
     Lazy::new(|| RwLock::new(BTreeMap::new()));
 
-/// `OutputTransport` implementation that sends data to websockets.
+/// `OutputTransport` implementation that sends data to websockets and to
+/// HTTP clients subscribed to the endpoint using streaming HTTP responses.
 pub struct HttpOutputTransport;
 
 impl OutputTransport for HttpOutputTransport {
@@ -33,11 +45,18 @@ impl OutputTransport for HttpOutputTransport {
     fn new_endpoint(
         &self,
         name: &str,
-        _config: &YamlValue,
+        config: &YamlValue,
         async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
     ) -> AnyResult<Box<dyn OutputEndpoint>> {
-        // let _config = HttpOutputConfig::deserialize(config)?;
-        let ep = HttpOutputEndpoint::new(name, async_error_callback)?;
+        let config = if config.is_null() {
+            HttpOutputConfig::default()
+        } else {
+            HttpOutputConfig::deserialize(config)?
+        };
+        if config.subscriber_queue_size == 0 {
+            return Err(anyhow!("'subscriber_queue_size' must be greater than 0"));
+        }
+        let ep = HttpOutputEndpoint::new(name, config, async_error_callback)?;
         Ok(Box::new(ep))
     }
 }
@@ -48,11 +67,7 @@ impl HttpOutputTransport {
         req: &HttpRequest,
         stream: Payload,
     ) -> AnyResult<HttpResponse> {
-        let endpoint = OUTPUT_HTTP_ENDPOINTS
-            .read()
-            .unwrap()
-            .get(endpoint_name)
-            .map(Clone::clone)
+        let endpoint = HttpOutputEndpoint::lookup(endpoint_name)
             .ok_or_else(|| anyhow!("unknown HTTP output endpoint '{endpoint_name}'"))?;
         if endpoint.num_sockets() >= MAX_SOCKETS_PER_ENDPOINT {
             return Err(anyhow!(
@@ -64,10 +79,196 @@ impl HttpOutputTransport {
         info!("HTTP output endpoint '{endpoint_name}': opened websocket");
         Ok(resp)
     }
+
+    /// Handle a `GET /output_endpoint/{endpoint_name}/stream` request.
+    ///
+    /// Subscribes the client to the output of the endpoint.  Output buffers
+    /// produced by the encoder are streamed to the client in the body of the
+    /// response until the client disconnects.
+    ///
+    /// The following query parameters are supported:
+    ///
+    /// * `mode` - `chunked` (the default) streams raw output buffers using
+    ///   chunked transfer encoding.  `sse` sends each buffer as a server-sent
+    ///   event, whose `data` field contains the lines of the buffer.
+    ///
+    /// * `snapshot` - when `true`, the client first receives a snapshot of
+    ///   the integrated contents of the output stream, followed by changes
+    ///   to the stream.  In `sse` mode, snapshot buffers are sent as
+    ///   `snapshot` events, and changes are sent as `delta` events.  Requires
+    ///   the endpoint to be configured with `snapshots: true`.
+    ///
+    /// In `sse` mode, the output of each circuit step, as well as the
+    /// snapshot, is followed by a `step` event, whose `data` field contains
+    /// the number of the step.  A `step` event without preceding `delta`
+    /// events denotes a step that produced no output (a heartbeat).
+    pub(crate) fn get_endpoint_stream(
+        endpoint_name: &str,
+        req: &HttpRequest,
+    ) -> AnyResult<HttpResponse> {
+        let endpoint = HttpOutputEndpoint::lookup(endpoint_name)
+            .ok_or_else(|| anyhow!("unknown HTTP output endpoint '{endpoint_name}'"))?;
+
+        let args = Query::<HashMap<String, String>>::from_query(req.query_string())
+            .map_err(|e| anyhow!("invalid query string: {e}"))?
+            .into_inner();
+
+        let mode = match args.get("mode").map(String::as_str) {
+            None | Some("chunked") => StreamMode::Chunked,
+            Some("sse") => StreamMode::Sse,
+            Some(mode) => return Err(anyhow!("unknown streaming mode '{mode}'")),
+        };
+
+        let snapshot = match args.get("snapshot") {
+            None => false,
+            Some(snapshot) => snapshot
+                .parse::<bool>()
+                .map_err(|_| anyhow!("invalid value of the 'snapshot' argument: '{snapshot}'"))?,
+        };
+
+        if snapshot && !endpoint.inner.config.snapshots {
+            return Err(anyhow!(
+                "snapshots are not enabled for HTTP output endpoint '{endpoint_name}'"
+            ));
+        }
+
+        if endpoint.num_sockets() + endpoint.num_subscribers() >= MAX_SOCKETS_PER_ENDPOINT {
+            return Err(anyhow!(
+                "maximum number of connections per HTTP endpoint exceeded"
+            ));
+        }
+
+        let (sender, receiver) = mpsc::channel(endpoint.inner.config.subscriber_queue_size);
+        endpoint.add_subscriber(Subscriber { mode, sender }, snapshot);
+        info!("HTTP output endpoint '{endpoint_name}': new {mode:?} subscriber");
+
+        let body = stream::unfold(receiver, |mut receiver| async move {
+            receiver
+                .recv()
+                .await
+                .map(|bytes| (Ok::<_, Infallible>(bytes), receiver))
+        });
+
+        let mut response = HttpResponse::Ok();
+        match mode {
+            StreamMode::Chunked => response.content_type(mime::APPLICATION_OCTET_STREAM),
+            StreamMode::Sse => response
+                .content_type(mime::TEXT_EVENT_STREAM)
+                .insert_header(CacheControl(vec![CacheDirective::NoCache])),
+        };
+
+        Ok(response.streaming(body))
+    }
+}
+
+const fn default_subscriber_queue_size() -> usize {
+    16
 }
 
+/// HTTP output endpoint configuration.
 #[derive(Clone, Deserialize, ToSchema)]
-pub struct HttpOutputConfig;
+pub struct HttpOutputConfig {
+    /// Maximum number of steps whose output is queued for each streaming
+    /// HTTP subscriber.
+    ///
+    /// The output of each step is queued as a single message.  A subscriber
+    /// whose queue is full is disconnected, so that a slow subscriber cannot
+    /// stall the pipeline or other subscribers.  The default is 16.
+    #[serde(default = "default_subscriber_queue_size")]
+    pub subscriber_queue_size: usize,
+
+    /// Allow subscribers to request a snapshot of the integrated contents of
+    /// the output stream before receiving changes to the stream.
+    ///
+    /// Snapshots are served from the materialized contents of the stream;
+    /// the endpoint fails to connect to a stream that is not materialized.
+    /// The default is `false`.
+    #[serde(default)]
+    pub snapshots: bool,
+}
+
+impl Default for HttpOutputConfig {
+    fn default() -> Self {
+        Self {
+            subscriber_queue_size: default_subscriber_queue_size(),
+            snapshots: false,
+        }
+    }
+}
+
+/// Framing of output buffers sent to a streaming HTTP subscriber.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StreamMode {
+    /// Raw buffers sent using chunked transfer encoding.
+    Chunked,
+
+    /// Server-sent events.
+    Sse,
+}
+
+/// A client subscribed to the endpoint via a streaming HTTP response.
+struct Subscriber {
+    mode: StreamMode,
+
+    /// Bounded queue of buffers to send to the client.
+    sender: mpsc::Sender<Bytes>,
+}
+
+impl Subscriber {
+    /// Frame the output of step `step`, consisting of `buffers`, for the
+    /// subscriber.
+    ///
+    /// Chunked streams carry the raw output of the encoder.  In `Sse` mode,
+    /// each buffer is sent as an `event` event, followed by a `step` event.
+    fn encode(&self, buffers: &[Vec<u8>], event: &str, step: Step) -> Bytes {
+        match self.mode {
+            StreamMode::Chunked => Bytes::from(buffers.concat()),
+            StreamMode::Sse => {
+                let mut bytes = Vec::new();
+                for buffer in buffers {
+                    sse_event(&mut bytes, event, buffer);
+                }
+                sse_event(&mut bytes, "step", step.to_string().as_bytes());
+                Bytes::from(bytes)
+            }
+        }
+    }
+
+    /// Queue `bytes` for the subscriber without blocking.
+    ///
+    /// Returns `false` if the subscriber has disconnected or if its queue is
+    /// full, in which case the subscriber must be dropped.
+    fn send(&self, endpoint_name: &str, bytes: Bytes) -> bool {
+        if bytes.is_empty() {
+            return !self.sender.is_closed();
+        }
+
+        match self.sender.try_send(bytes) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                info!(
+                    "HTTP output endpoint '{endpoint_name}': disconnecting {:?} subscriber that is not keeping up with the output",
+                    self.mode
+                );
+                false
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+/// Append a server-sent event with name `event` whose `data` field contains
+/// the lines of `data` to `bytes`.
+fn sse_event(bytes: &mut Vec<u8>, event: &str, data: &[u8]) {
+    bytes.extend_from_slice(format!("event: {event}\n").as_bytes());
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    for line in data.split(|c| *c == b'\n') {
+        bytes.extend_from_slice(b"data: ");
+        bytes.extend_from_slice(line);
+        bytes.push(b'\n');
+    }
+    bytes.push(b'\n');
+}
 
 struct HttpOutputEndpointInner {
     name: String,
@@ -82,50 +283,103 @@ struct HttpOutputEndpointInner {
     /// This field is used to notify all websocket actors about new data
     /// buffers to send out.
     socket_addrs: RwLock<HashSet<Addr<HttpOutputWs>>>,
+
+    /// Streaming HTTP subscribers that receive all new output buffers.
+    subscribers: Mutex<Vec<Subscriber>>,
+
+    /// Streaming HTTP subscribers waiting for a snapshot.  A subscriber is
+    /// moved to `subscribers` once it has received the snapshot.
+    pending_subscribers: Mutex<Vec<Subscriber>>,
+
+    /// Callback used to notify the controller that a subscriber is waiting
+    /// for a snapshot (see [`OutputEndpoint::snapshot_sink`]).
+    snapshot_request_callback: Mutex<Option<Box<dyn Fn() + Send + Sync>>>,
+
+    config: HttpOutputConfig,
     _async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
 }
 
 impl HttpOutputEndpointInner {
-    fn new(name: &str, async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>) -> Self {
+    fn new(
+        name: &str,
+        config: HttpOutputConfig,
+        async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+    ) -> Self {
         Self {
             name: name.to_string(),
             socket_addrs: RwLock::new(HashSet::new()),
+            subscribers: Mutex::new(Vec::new()),
+            pending_subscribers: Mutex::new(Vec::new()),
+            snapshot_request_callback: Mutex::new(None),
+            config,
             _async_error_callback: async_error_callback,
         }
     }
 }
 
 /// Output endpoint that establishes websocket connections with clients on
-/// demand and sends output batches to these websockets.
+/// demand and sends output batches to these websockets and to streaming HTTP
+/// subscribers.
 ///
 /// This implementation provides no support for reliable delivery
 /// and is mostly intended for browser-based testing.
 #[derive(Clone)]
 struct HttpOutputEndpoint {
     inner: Arc<HttpOutputEndpointInner>,
+
+    /// The step whose output is being sent.
+    step: Step,
+
+    /// Buffers of the current step, sent to streaming HTTP subscribers at
+    /// the end of the step.
+    buffers: Vec<Vec<u8>>,
 }
 
 impl HttpOutputEndpoint {
     fn new(
         name: &str,
+        config: HttpOutputConfig,
         async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
     ) -> AnyResult<Self> {
         let mut endpoint_map = OUTPUT_HTTP_ENDPOINTS.write().unwrap();
 
-        if endpoint_map.contains_key(name) {
+        if endpoint_map
+            .get(name)
+            .map_or(false, |ep| ep.strong_count() > 0)
+        {
             return Err(anyhow!(format!(
                 "duplicate HTTP output endpoint name '{name}'"
             )));
         }
 
         let endpoint = Self {
-            inner: Arc::new(HttpOutputEndpointInner::new(name, async_error_callback)),
+            inner: Arc::new(HttpOutputEndpointInner::new(
+                name,
+                config,
+                async_error_callback,
+            )),
+            step: 0,
+            buffers: Vec::new(),
         };
 
-        endpoint_map.insert(name.to_string(), endpoint.clone());
+        endpoint_map.insert(name.to_string(), Arc::downgrade(&endpoint.inner));
         Ok(endpoint)
     }
 
+    /// Lookup a live endpoint by name.
+    fn lookup(name: &str) -> Option<Self> {
+        OUTPUT_HTTP_ENDPOINTS
+            .read()
+            .unwrap()
+            .get(name)
+            .and_then(Weak::upgrade)
+            .map(|inner| Self {
+                inner,
+                step: 0,
+                buffers: Vec::new(),
+            })
+    }
+
     fn name(&self) -> &str {
         self.inner.name.as_str()
     }
@@ -144,15 +398,148 @@ impl HttpOutputEndpoint {
     fn remove_socket(&self, addr: &Addr<HttpOutputWs>) {
         self.inner.socket_addrs.write().unwrap().remove(addr);
     }
+
+    /// Number of streaming HTTP subscribers.
+    fn num_subscribers(&self) -> usize {
+        self.inner.subscribers.lock().unwrap().len()
+            + self.inner.pending_subscribers.lock().unwrap().len()
+    }
+
+    /// Register new streaming HTTP subscriber.
+    ///
+    /// When `snapshot` is `true`, the subscriber starts receiving output
+    /// buffers after it has received a snapshot.
+    fn add_subscriber(&self, subscriber: Subscriber, snapshot: bool) {
+        if snapshot {
+            self.inner
+                .pending_subscribers
+                .lock()
+                .unwrap()
+                .push(subscriber);
+            if let Some(callback) = &*self.inner.snapshot_request_callback.lock().unwrap() {
+                callback();
+            }
+        } else {
+            self.inner.subscribers.lock().unwrap().push(subscriber);
+        }
+    }
 }
 
 impl OutputEndpoint for HttpOutputEndpoint {
+    fn batch_start(&mut self, step: Step) -> AnyResult<()> {
+        self.step = step;
+        Ok(())
+    }
+
     fn push_buffer(&mut self, buffer: &[u8]) -> AnyResult<()> {
         for addr in self.inner.socket_addrs.read().unwrap().iter() {
             block_on(addr.send(Event::Buffer(Vec::from(buffer))))?;
         }
+
+        if !self.inner.subscribers.lock().unwrap().is_empty() {
+            self.buffers.push(buffer.to_vec());
+        }
+
+        Ok(())
+    }
+
+    fn batch_end(&mut self) -> AnyResult<()> {
+        let buffers = take(&mut self.buffers);
+        self.inner.subscribers.lock().unwrap().retain(|subscriber| {
+            subscriber.send(
+                &self.inner.name,
+                subscriber.encode(&buffers, "delta", self.step),
+            )
+        });
+
         Ok(())
     }
+
+    /// Subscribers never receive the partial output of a step.
+    fn batch_abort(&mut self) -> AnyResult<()> {
+        self.buffers.clear();
+        Ok(())
+    }
+
+    /// Server-sent events and WebSocket messages carry text, and chunked
+    /// responses would need a `Content-Encoding` header negotiated with each
+    /// client, so compressed buffers cannot be delivered as is.
+    fn check_compression(&self) -> AnyResult<()> {
+        Err(anyhow!(
+            "compression is not supported by the 'http' output transport"
+        ))
+    }
+
+    fn snapshot_sink(
+        &self,
+        request_callback: Box<dyn Fn() + Send + Sync>,
+    ) -> Option<Box<dyn SnapshotSink>> {
+        if self.inner.config.snapshots {
+            *self.inner.snapshot_request_callback.lock().unwrap() = Some(request_callback);
+            Some(Box::new(self.clone()))
+        } else {
+            None
+        }
+    }
+}
+
+impl SnapshotSink for HttpOutputEndpoint {
+    fn snapshot_requested(&self) -> bool {
+        !self.inner.pending_subscribers.lock().unwrap().is_empty()
+    }
+
+    fn snapshot_consumer(&self) -> Box<dyn OutputConsumer> {
+        let subscribers = take(&mut *self.inner.pending_subscribers.lock().unwrap());
+        Box::new(HttpSnapshotConsumer {
+            endpoint: self.clone(),
+            subscribers,
+            step: 0,
+            buffers: Vec::new(),
+        })
+    }
+}
+
+/// Consumer that sends a snapshot to subscribers waiting for it.
+struct HttpSnapshotConsumer {
+    endpoint: HttpOutputEndpoint,
+    subscribers: Vec<Subscriber>,
+
+    /// The last step included in the snapshot.
+    step: Step,
+
+    /// Buffers of the snapshot, sent to subscribers as a single message.
+    buffers: Vec<Vec<u8>>,
+}
+
+impl OutputConsumer for HttpSnapshotConsumer {
+    fn batch_start(&mut self, step: Step) {
+        self.step = step;
+    }
+
+    fn push_buffer(&mut self, buffer: &[u8]) {
+        self.buffers.push(buffer.to_vec());
+    }
+
+    /// The snapshot is sent as a single message.
+    fn separates_steps(&self) -> bool {
+        true
+    }
+
+    fn batch_end(&mut self) {
+        let name = &self.endpoint.inner.name;
+        let (buffers, step) = (&self.buffers, self.step);
+        self.subscribers.retain(|subscriber| {
+            subscriber.send(name, subscriber.encode(buffers, "snapshot", step))
+        });
+
+        // Snapshot complete: subscribers start receiving new output buffers.
+        self.endpoint
+            .inner
+            .subscribers
+            .lock()
+            .unwrap()
+            .append(&mut self.subscribers);
+    }
 }
 
 #[derive(Message)]
@@ -225,3 +612,201 @@ impl Handler<Event> for HttpOutputWs {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{HttpOutputConfig, HttpOutputEndpoint, StreamMode, Subscriber};
+    use crate::{
+        test::{test_circuit, test_data, wait, TestStruct},
+        Controller, OutputEndpoint, PipelineConfig, Step,
+    };
+    use csv::{ReaderBuilder as CsvReaderBuilder, WriterBuilder as CsvWriterBuilder};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tempfile::NamedTempFile;
+    use tokio::sync::mpsc;
+
+    fn endpoint(name: &str, snapshots: bool) -> HttpOutputEndpoint {
+        HttpOutputEndpoint::new(
+            name,
+            HttpOutputConfig {
+                subscriber_queue_size: 2,
+                snapshots,
+            },
+            Box::new(|_, e| panic!("{e}")),
+        )
+        .unwrap()
+    }
+
+    fn subscribe(
+        endpoint: &HttpOutputEndpoint,
+        mode: StreamMode,
+        snapshot: bool,
+    ) -> mpsc::Receiver<bytes::Bytes> {
+        let (sender, receiver) = mpsc::channel(endpoint.inner.config.subscriber_queue_size);
+        endpoint.add_subscriber(Subscriber { mode, sender }, snapshot);
+        receiver
+    }
+
+    fn write_step(endpoint: &mut HttpOutputEndpoint, step: Step, buffers: &[&str]) {
+        endpoint.batch_start(step).unwrap();
+        for buffer in buffers {
+            endpoint.push_buffer(buffer.as_bytes()).unwrap();
+        }
+        endpoint.batch_end().unwrap();
+    }
+
+    #[test]
+    fn test_lagging_subscriber() {
+        let mut endpoint = endpoint("test_lagging_subscriber", false);
+        let mut slow = subscribe(&endpoint, StreamMode::Chunked, false);
+        let mut fast = subscribe(&endpoint, StreamMode::Sse, false);
+
+        // The output of each step is queued as a single message.
+        write_step(&mut endpoint, 0, &["1\n", "2\n"]);
+        assert_eq!(
+            fast.try_recv().unwrap(),
+            "event: delta\ndata: 1\n\nevent: delta\ndata: 2\n\nevent: step\ndata: 0\n\n"
+        );
+        write_step(&mut endpoint, 1, &["3\n"]);
+        assert_eq!(
+            fast.try_recv().unwrap(),
+            "event: delta\ndata: 3\n\nevent: step\ndata: 1\n\n"
+        );
+
+        // The queue of the slow subscriber is full: the subscriber is
+        // disconnected without delaying the other subscriber.
+        write_step(&mut endpoint, 2, &["4\n"]);
+        assert_eq!(endpoint.num_subscribers(), 1);
+        assert_eq!(
+            fast.try_recv().unwrap(),
+            "event: delta\ndata: 4\n\nevent: step\ndata: 2\n\n"
+        );
+        assert_eq!(slow.try_recv().unwrap(), "1\n2\n");
+        assert_eq!(slow.try_recv().unwrap(), "3\n");
+        assert!(slow.try_recv().is_err());
+
+        // Subscribers don't receive the output of aborted steps.
+        endpoint.batch_start(3).unwrap();
+        endpoint.push_buffer(b"5\n").unwrap();
+        endpoint.batch_abort().unwrap();
+        assert!(fast.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_snapshot_subscriber() {
+        let mut endpoint = endpoint("test_snapshot_subscriber", true);
+        let requests = Arc::new(AtomicUsize::new(0));
+        let sink = {
+            let requests = requests.clone();
+            endpoint
+                .snapshot_sink(Box::new(move || {
+                    requests.fetch_add(1, Ordering::AcqRel);
+                }))
+                .unwrap()
+        };
+        assert!(!sink.snapshot_requested());
+
+        // The endpoint notifies the controller about the request.
+        let mut receiver = subscribe(&endpoint, StreamMode::Sse, true);
+        assert_eq!(requests.load(Ordering::Acquire), 1);
+        assert!(sink.snapshot_requested());
+
+        // Changes are only sent after the snapshot.
+        write_step(&mut endpoint, 0, &["1\n"]);
+        assert!(receiver.try_recv().is_err());
+
+        let mut consumer = sink.snapshot_consumer();
+        assert!(!sink.snapshot_requested());
+        consumer.batch_start(0);
+        consumer.push_buffer(b"1\n");
+        consumer.push_buffer(b"2\n");
+        consumer.batch_end();
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            "event: snapshot\ndata: 1\n\nevent: snapshot\ndata: 2\n\nevent: step\ndata: 0\n\n"
+        );
+
+        write_step(&mut endpoint, 1, &["3\n"]);
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            "event: delta\ndata: 3\n\nevent: step\ndata: 1\n\n"
+        );
+    }
+
+    /// Snapshots of an endpoint connected to a controller contain the
+    /// contents of the materialized output stream.
+    #[test]
+    fn test_controller_snapshot() {
+        let temp_input_file = NamedTempFile::new().unwrap();
+        let config_str = format!(
+            r#"
+inputs:
+    test_input1:
+        stream: test_input1
+        transport:
+            name: file
+            config:
+                path: {:?}
+        format:
+            name: csv
+outputs:
+    test_controller_snapshot:
+        stream: test_output1
+        transport:
+            name: http
+            config:
+                snapshots: true
+        format:
+            name: csv
+"#,
+            temp_input_file.path().to_str().unwrap(),
+        );
+        let config: PipelineConfig = serde_yaml::from_str(&config_str).unwrap();
+
+        let mut data = test_data();
+        let mut writer = CsvWriterBuilder::new()
+            .has_headers(false)
+            .from_writer(temp_input_file.as_file());
+        for val in data.iter() {
+            writer.serialize(val).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        let (circuit, catalog) = test_circuit(4);
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+        controller.start();
+        wait(|| controller.pipeline_complete(), None);
+
+        // A subscriber that connects after the data has been processed
+        // receives it as part of the snapshot, possibly followed by changes
+        // not yet included in the snapshot.
+        let endpoint = HttpOutputEndpoint::lookup("test_controller_snapshot").unwrap();
+        let mut receiver = subscribe(&endpoint, StreamMode::Chunked, true);
+        let mut output = Vec::new();
+        let mut received = Vec::new();
+        while received.len() < data.len() {
+            output.extend_from_slice(&receiver.blocking_recv().unwrap());
+            received = CsvReaderBuilder::new()
+                .has_headers(false)
+                .from_reader(output.as_slice())
+                .deserialize::<(TestStruct, i32)>()
+                .map(|record| record.unwrap().0)
+                .collect::<Vec<_>>();
+        }
+        received.sort();
+        data.sort();
+        assert_eq!(received, data);
+
+        controller.stop().unwrap();
+    }
+}
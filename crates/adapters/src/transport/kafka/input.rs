@@ -1,13 +1,13 @@
 use super::{refine_kafka_error, KafkaLogLevel};
-use crate::{InputConsumer, InputEndpoint, InputTransport, PipelineState};
+use crate::{InputConsumer, InputEndpoint, InputTransport, PipelineState, Step};
 use anyhow::{Error as AnyError, Result as AnyResult};
 use log::debug;
 use num_traits::FromPrimitive;
 use rdkafka::{
     config::{FromClientConfigAndContext, RDKafkaLogLevel},
-    consumer::{BaseConsumer, Consumer, ConsumerContext, Rebalance, RebalanceProtocol},
+    consumer::{BaseConsumer, CommitMode, Consumer, ConsumerContext, Rebalance, RebalanceProtocol},
     error::{KafkaError, KafkaResult},
-    ClientConfig, ClientContext, Message,
+    ClientConfig, ClientContext, Message, Offset, TopicPartitionList,
 };
 use serde::Deserialize;
 use serde_yaml::Value as YamlValue;
@@ -15,6 +15,7 @@ use std::{
     borrow::Cow,
     collections::BTreeMap,
     env,
+    mem::replace,
     sync::{
         atomic::{AtomicU32, Ordering},
         Arc, Mutex, Weak,
@@ -32,6 +33,10 @@ use utoipa::{
 
 const POLL_TIMEOUT: Duration = Duration::from_millis(100);
 
+/// Timeout for seeking to a checkpointed offset when resuming from a
+/// checkpoint.
+const SEEK_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// On startup, the endpoint waits to join the consumer group.
 /// This constant defines the default wait timeout.
 const fn default_group_join_timeout_secs() -> u32 {
@@ -70,6 +75,13 @@ pub struct KafkaInputConfig {
     ///
     /// * "enable.auto.commit", if present, must be set to "false",
     /// * "enable.auto.offset.store", if present, must be set to "false"
+    ///
+    /// The endpoint commits the offset of each message only after the
+    /// controller has fully processed the message.  Set "group.id" to a
+    /// stable value to resume from the last committed offsets after a
+    /// restart.  When "group.id" is not specified, the endpoint creates a
+    /// new consumer group and starts consuming according to the
+    /// "auto.offset.reset" policy.
     #[serde(flatten)]
     kafka_options: BTreeMap<String, String>,
 
@@ -156,10 +168,11 @@ impl KafkaInputConfig {
             &env::var("REDPANDA_BROKERS").unwrap_or_else(|_| "localhost".to_string()),
         );
 
-        // Commit automatically.
+        // Offsets are committed by the endpoint once the corresponding data has
+        // been processed by the circuit.
         // See https://docs.confluent.io/platform/current/clients/consumer.html#offset-management
-        self.enforce_option("enable.auto.commit", "true")?;
-        self.enforce_option("enable.auto.offset.store", "true")?;
+        self.enforce_option("enable.auto.commit", "false")?;
+        self.enforce_option("enable.auto.offset.store", "false")?;
 
         let group_id = format!(
             "{}",
@@ -210,6 +223,16 @@ impl KafkaInputContext {
 impl ClientContext for KafkaInputContext {}
 
 impl ConsumerContext for KafkaInputContext {
+    fn pre_rebalance(&self, rebalance: &Rebalance<'_>) {
+        if let Rebalance::Revoke(partitions) = rebalance {
+            if let Some(endpoint) = self.endpoint.lock().unwrap().upgrade() {
+                // TODO: handle errors by storing them inside `endpoint`
+                // for later processing in `poll`.
+                let _ = endpoint.revoke_partitions(partitions);
+            }
+        }
+    }
+
     fn post_rebalance(&self, rebalance: &Rebalance<'_>) {
         // println!("Rebalance: {rebalance:?}");
         if matches!(rebalance, Rebalance::Assign(_)) {
@@ -228,9 +251,40 @@ impl ConsumerContext for KafkaInputContext {
     }
 }
 
+/// Maps `(topic, partition)` to the largest offset received from this
+/// partition.
+type PartitionOffsets = BTreeMap<(String, i32), i64>;
+
+/// Add `new_offsets` to `offsets`, keeping the largest offset for each
+/// partition.
+fn merge_offsets<I>(offsets: &mut PartitionOffsets, new_offsets: I)
+where
+    I: Iterator<Item = ((String, i32), i64)>,
+{
+    for (topic_partition, offset) in new_offsets {
+        let max_offset = offsets.entry(topic_partition).or_insert(offset);
+        *max_offset = (*max_offset).max(offset);
+    }
+}
+
 struct KafkaInputEndpointInner {
     state: AtomicU32,
     kafka_consumer: BaseConsumer<KafkaInputContext>,
+
+    /// Held by the worker thread while pushing a message to the input
+    /// consumer and recording its offset, so that [`Self::position`]
+    /// observes the offsets of all messages consumed by completed steps.
+    input_lock: Mutex<()>,
+
+    /// Offsets of messages pushed to the input consumer that have not been
+    /// committed yet, indexed by the step that processes them.
+    pending_offsets: Mutex<BTreeMap<Step, PartitionOffsets>>,
+
+    /// Largest committed offset in each partition.
+    committed_offsets: Mutex<PartitionOffsets>,
+
+    /// The latest step reported as completed by the controller.
+    completed_step: Mutex<Option<Step>>,
 }
 
 impl KafkaInputEndpointInner {
@@ -258,6 +312,10 @@ impl KafkaInputEndpointInner {
         let endpoint = Arc::new(Self {
             state: AtomicU32::new(PipelineState::Paused as u32),
             kafka_consumer,
+            input_lock: Mutex::new(()),
+            pending_offsets: Mutex::new(BTreeMap::new()),
+            committed_offsets: Mutex::new(PartitionOffsets::new()),
+            completed_step: Mutex::new(None),
         });
 
         *endpoint.kafka_consumer.context().endpoint.lock().unwrap() = Arc::downgrade(&endpoint);
@@ -333,6 +391,145 @@ impl KafkaInputEndpointInner {
         Ok(())
     }
 
+    /// Record the offset of a message processed by step `step`.
+    ///
+    /// Messages that don't contain any valid records (`step` is `None`) don't
+    /// need to be processed by the circuit, but must not be committed ahead
+    /// of earlier messages, so they are attached to the latest pending step.
+    fn record_offset(&self, step: Option<Step>, topic: &str, partition: i32, offset: i64) {
+        let mut pending_offsets = self.pending_offsets.lock().unwrap();
+        // With no pending steps, any completed step commits the offset.
+        let step = step.unwrap_or_else(|| pending_offsets.keys().next_back().copied().unwrap_or(0));
+        let offsets = pending_offsets.entry(step).or_default();
+        let max_offset = offsets
+            .entry((topic.to_string(), partition))
+            .or_insert(offset);
+        *max_offset = (*max_offset).max(offset);
+    }
+
+    /// Remove offsets of messages processed by completed steps from
+    /// `pending_offsets`.
+    ///
+    /// Returns the list of offsets to commit, or `None` if there is nothing
+    /// to commit.
+    fn take_completed_offsets(&self) -> KafkaResult<Option<TopicPartitionList>> {
+        let completed_step = match *self.completed_step.lock().unwrap() {
+            Some(step) => step,
+            None => return Ok(None),
+        };
+
+        let mut pending_offsets = self.pending_offsets.lock().unwrap();
+        let remaining = pending_offsets.split_off(&(completed_step + 1));
+        let completed = replace(&mut *pending_offsets, remaining);
+
+        if completed.is_empty() {
+            return Ok(None);
+        }
+
+        let mut offsets = PartitionOffsets::new();
+        merge_offsets(&mut offsets, completed.into_values().flatten());
+
+        // Update committed offsets before releasing `pending_offsets`, so
+        // that `position` doesn't miss these offsets.
+        merge_offsets(
+            &mut self.committed_offsets.lock().unwrap(),
+            offsets.clone().into_iter(),
+        );
+        drop(pending_offsets);
+
+        // The committed offset is the offset of the next message to consume.
+        let mut partitions = TopicPartitionList::new();
+        for ((topic, partition), offset) in offsets.iter() {
+            partitions.add_partition_offset(topic, *partition, Offset::Offset(offset + 1))?;
+        }
+
+        Ok(Some(partitions))
+    }
+
+    /// Commit offsets of all messages processed by completed steps.
+    fn commit_completed(&self, mode: CommitMode) -> KafkaResult<()> {
+        if let Some(partitions) = self.take_completed_offsets()? {
+            self.kafka_consumer.commit(&partitions, mode)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the offsets of the last messages consumed by steps up to
+    /// `step` in each partition.
+    fn position(&self, step: Step) -> PartitionOffsets {
+        // Wait for the worker to record the offset of the message it is
+        // currently pushing to the input consumer, if any.
+        let _input_guard = self.input_lock.lock().unwrap();
+
+        let pending_offsets = self.pending_offsets.lock().unwrap();
+        let mut offsets = self.committed_offsets.lock().unwrap().clone();
+        merge_offsets(
+            &mut offsets,
+            pending_offsets
+                .range(..=step)
+                .flat_map(|(_, offsets)| offsets.clone()),
+        );
+        offsets
+    }
+
+    /// Resume consuming partitions after the messages at `offsets`.
+    ///
+    /// Commits the offsets, so that partitions assigned to the consumer
+    /// later, e.g., after rebalancing, resume from them, and moves the
+    /// consumer to these offsets in currently assigned partitions.
+    fn seek(&self, offsets: PartitionOffsets) -> AnyResult<()> {
+        if offsets.is_empty() {
+            return Ok(());
+        }
+
+        // The committed offset is the offset of the next message to consume.
+        let mut partitions = TopicPartitionList::new();
+        for ((topic, partition), offset) in offsets.iter() {
+            partitions.add_partition_offset(topic, *partition, Offset::Offset(offset + 1))?;
+        }
+        self.kafka_consumer.commit(&partitions, CommitMode::Sync)?;
+
+        let assignment = self.kafka_consumer.assignment()?;
+        for ((topic, partition), offset) in offsets.iter() {
+            if assignment.find_partition(topic, *partition).is_some() {
+                self.kafka_consumer.seek(
+                    topic,
+                    *partition,
+                    Offset::Offset(offset + 1),
+                    SEEK_TIMEOUT,
+                )?;
+            }
+        }
+
+        *self.committed_offsets.lock().unwrap() = offsets;
+        Ok(())
+    }
+
+    /// Handle partitions revoked from the consumer during rebalancing.
+    ///
+    /// Commits offsets processed so far and forgets the remaining pending
+    /// offsets for revoked partitions, since the consumer is no longer
+    /// allowed to commit them.  Messages from these partitions that are still
+    /// being processed may be delivered again to the new owner of the
+    /// partition.
+    fn revoke_partitions(&self, partitions: &TopicPartitionList) -> KafkaResult<()> {
+        self.commit_completed(CommitMode::Sync)?;
+
+        let revoked = partitions
+            .elements()
+            .iter()
+            .map(|elem| (elem.topic().to_string(), elem.partition()))
+            .collect::<Vec<_>>();
+
+        for offsets in self.pending_offsets.lock().unwrap().values_mut() {
+            for topic_partition in revoked.iter() {
+                offsets.remove(topic_partition);
+            }
+        }
+
+        Ok(())
+    }
+
     fn refine_error(&self, e: KafkaError) -> (bool, AnyError) {
         refine_kafka_error(self.kafka_consumer.client(), e)
     }
@@ -362,6 +559,15 @@ impl KafkaInputEndpointInner {
                 _ => {}
             }
 
+            // Commit offsets of messages processed by completed steps.
+            if let Err(e) = endpoint.commit_completed(CommitMode::Async) {
+                let (fatal, e) = endpoint.refine_error(e);
+                consumer.error(fatal, e);
+                if fatal {
+                    return;
+                }
+            }
+
             // According to `rdkafka` docs, we must keep polling even while
             // the consumer is paused as `BaseConsumer` processes control
             // messages (including rebalancing) within the polling thread.
@@ -384,9 +590,19 @@ impl KafkaInputEndpointInner {
                     // println!("received {} bytes", message.payload().unwrap().len());
                     // message.payload().map(|payload| consumer.input(payload));
 
-                    if let Some(payload) = message.payload() {
-                        consumer.input(payload);
-                    }
+                    // Record the offset even if the message is empty or all
+                    // its records fail to parse, so that invalid messages are
+                    // not re-read after a restart.
+                    let _input_guard = endpoint.input_lock.lock().unwrap();
+                    let step = message
+                        .payload()
+                        .and_then(|payload| consumer.input(payload));
+                    endpoint.record_offset(
+                        step,
+                        message.topic(),
+                        message.partition(),
+                        message.offset(),
+                    );
                 }
             }
         }
@@ -409,6 +625,31 @@ impl InputEndpoint for KafkaInputEndpoint {
     fn disconnect(&self) {
         self.0.set_state(PipelineState::Terminated);
     }
+
+    fn completed_step(&self, step: Step) {
+        // The worker thread commits the corresponding offsets.
+        *self.0.completed_step.lock().unwrap() = Some(step);
+    }
+
+    fn position(&self, step: Step) -> AnyResult<Option<Vec<u8>>> {
+        Ok(Some(bincode::encode_to_vec(
+            self.0.position(step),
+            bincode::config::standard(),
+        )?))
+    }
+
+    /// Resumes from the Kafka offsets recorded in `position`; `offset` is
+    /// ignored.
+    fn seek(&self, _offset: u64, position: Option<&[u8]>) -> AnyResult<()> {
+        match position {
+            None => Ok(()),
+            Some(position) => {
+                let (offsets, _) =
+                    bincode::decode_from_slice(position, bincode::config::standard())?;
+                self.0.seek(offsets)
+            }
+        }
+    }
 }
 
 impl Drop for KafkaInputEndpoint {
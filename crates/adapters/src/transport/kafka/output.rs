@@ -1,10 +1,11 @@
 use super::KafkaLogLevel;
-use crate::{OutputEndpoint, OutputTransport};
+use crate::{OutputEndpoint, OutputTransport, Step};
 use anyhow::{Error as AnyError, Result as AnyResult};
 use crossbeam::sync::{Parker, Unparker};
 use log::debug;
 use rdkafka::{
     config::{FromClientConfigAndContext, RDKafkaLogLevel},
+    message::{Header, OwnedHeaders},
     producer::{BaseRecord, DeliveryResult, Producer, ProducerContext, ThreadedProducer},
     ClientConfig, ClientContext,
 };
@@ -21,6 +22,13 @@ use utoipa::{
 
 const OUTPUT_POLLING_INTERVAL: Duration = Duration::from_millis(100);
 
+/// Timeout for initializing and committing Kafka transactions.
+const TRANSACTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Name of the message header that carries the number of the circuit step
+/// that produced the message.
+const STEP_HEADER: &str = "step";
+
 /// `OutputTransport` implementation that writes to a Kafka topic.
 pub struct KafkaOutputTransport;
 
@@ -53,6 +61,17 @@ pub struct KafkaOutputConfig {
     ///
     /// See [`librdkafka` options](https://github.com/edenhill/librdkafka/blob/master/CONFIGURATION.md)
     /// used to configure the Kafka producer.
+    ///
+    /// Setting the "transactional.id" option enables transactional mode, in
+    /// which all messages produced for a single circuit step are written in
+    /// one Kafka transaction.  Consumers configured with
+    /// `isolation.level=read_committed` observe the output of each step
+    /// atomically.
+    ///
+    /// Regardless of this option, every message carries a `step` header
+    /// with the decimal number of the circuit step that produced it.  An
+    /// empty step (see `heartbeats` in the output endpoint configuration) is
+    /// sent as a message without a payload.
     #[serde(flatten)]
     kafka_options: BTreeMap<String, String>,
 
@@ -80,6 +99,11 @@ pub struct KafkaOutputConfig {
 }
 
 impl KafkaOutputConfig {
+    /// `true` if the producer is configured with a transactional id.
+    fn transactional(&self) -> bool {
+        self.kafka_options.contains_key("transactional.id")
+    }
+
     /// Set `option` to `val`, if missing.
     fn set_option_if_missing(&mut self, option: &str, val: &str) {
         self.kafka_options
@@ -136,7 +160,11 @@ Defaults to 1000."#)),
                         .description(Some(r#"Options passed directly to `rdkafka`.
 
 See [`librdkafka` options](https://github.com/edenhill/librdkafka/blob/master/CONFIGURATION.md)
-used to configure the Kafka producer."#))))
+used to configure the Kafka producer.
+
+Setting the "transactional.id" option enables transactional mode, in
+which all messages produced for a single circuit step are written in
+one Kafka transaction."#))))
                 .into(),
         )
     }
@@ -189,6 +217,18 @@ struct KafkaOutputEndpoint {
     topic: String,
     max_inflight_messages: u32,
     parker: Parker,
+
+    /// Wrap the output of each step in a transaction.
+    transactional: bool,
+
+    /// A transaction started by `batch_start` is in progress.
+    in_transaction: bool,
+
+    /// The step whose output is being sent.
+    step: Step,
+
+    /// No buffers have been sent for the current step yet.
+    empty_step: bool,
 }
 
 impl KafkaOutputEndpoint {
@@ -218,17 +258,26 @@ impl KafkaOutputEndpoint {
         // Create Kafka producer.
         let kafka_producer = ThreadedProducer::from_config_and_context(&client_config, context)?;
 
+        let transactional = config.transactional();
+        if transactional {
+            kafka_producer.init_transactions(TRANSACTION_TIMEOUT)?;
+        }
+
         Ok(Self {
             kafka_producer,
             topic: config.topic,
             max_inflight_messages: config.max_inflight_messages,
             parker,
+            transactional,
+            in_transaction: false,
+            step: 0,
+            empty_step: false,
         })
     }
-}
 
-impl OutputEndpoint for KafkaOutputEndpoint {
-    fn push_buffer(&mut self, buffer: &[u8]) -> AnyResult<()> {
+    /// Send a message labeled with the current step; a message without a
+    /// payload marks an empty step.
+    fn send(&mut self, buffer: Option<&[u8]>) -> AnyResult<()> {
         // Wait for the number of unacknowledged messages to drop
         // below `max_inflight_messages`.
         while self.kafka_producer.in_flight_count() as i64 > self.max_inflight_messages as i64 {
@@ -241,10 +290,97 @@ impl OutputEndpoint for KafkaOutputEndpoint {
             self.parker.park_timeout(OUTPUT_POLLING_INTERVAL);
         }
 
-        let record = <BaseRecord<(), [u8], ()>>::to(&self.topic).payload(buffer);
+        let step = self.step.to_string();
+        let headers = OwnedHeaders::new().insert(Header {
+            key: STEP_HEADER,
+            value: Some(step.as_bytes()),
+        });
+        let mut record = <BaseRecord<(), [u8], ()>>::to(&self.topic).headers(headers);
+        if let Some(buffer) = buffer {
+            record = record.payload(buffer);
+        }
         self.kafka_producer
             .send(record)
             .map_err(|(err, _record)| err)?;
         Ok(())
     }
+
+    /// Commit the current transaction; abort it if the commit fails.
+    fn commit_transaction(&mut self) -> AnyResult<()> {
+        self.in_transaction = false;
+        if let Err(e) = self.kafka_producer.commit_transaction(TRANSACTION_TIMEOUT) {
+            let _ = self.kafka_producer.abort_transaction(TRANSACTION_TIMEOUT);
+            return Err(AnyError::msg(format!(
+                "failed to commit Kafka transaction: {e}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl OutputEndpoint for KafkaOutputEndpoint {
+    fn batch_start(&mut self, step: Step) -> AnyResult<()> {
+        self.step = step;
+        self.empty_step = true;
+        if self.transactional {
+            self.kafka_producer.begin_transaction()?;
+            self.in_transaction = true;
+        }
+        Ok(())
+    }
+
+    fn push_buffer(&mut self, buffer: &[u8]) -> AnyResult<()> {
+        self.empty_step = false;
+        if !self.transactional {
+            return self.send(Some(buffer));
+        }
+
+        if !self.in_transaction {
+            return Err(AnyError::msg(
+                "transactional Kafka output endpoint received a buffer outside of a transaction",
+            ));
+        }
+
+        // Abort the transaction on error, so that consumers never observe
+        // partial output of a step.
+        self.send(Some(buffer)).map_err(|e| {
+            self.in_transaction = false;
+            let _ = self.kafka_producer.abort_transaction(TRANSACTION_TIMEOUT);
+            e
+        })
+    }
+
+    /// Each buffer is sent as a separate message.
+    fn separates_steps(&self) -> bool {
+        true
+    }
+
+    fn batch_end(&mut self) -> AnyResult<()> {
+        // Send an empty message to mark a step that produced no output.
+        if self.empty_step {
+            self.empty_step = false;
+            if let Err(e) = self.send(None) {
+                if self.in_transaction {
+                    self.in_transaction = false;
+                    let _ = self.kafka_producer.abort_transaction(TRANSACTION_TIMEOUT);
+                }
+                return Err(e);
+            }
+        }
+        if self.in_transaction {
+            self.commit_transaction()?;
+        }
+        Ok(())
+    }
+
+    fn batch_abort(&mut self) -> AnyResult<()> {
+        self.empty_step = false;
+        if self.in_transaction {
+            self.in_transaction = false;
+            self.kafka_producer
+                .abort_transaction(TRANSACTION_TIMEOUT)
+                .map_err(|e| AnyError::msg(format!("failed to abort Kafka transaction: {e}")))?;
+        }
+        Ok(())
+    }
 }
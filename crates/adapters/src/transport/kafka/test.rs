@@ -139,19 +139,41 @@ format:
 
     #[test]
     fn proptest_kafka_end_to_end(data in generate_test_batches(100, 1000)) {
-        let _ = log::set_logger(&TEST_LOGGER);
-        log::set_max_level(LevelFilter::Debug);
+        kafka_end_to_end_test("end_to_end_test", "", data);
+    }
 
-        // Create topics.
-        let kafka_resources = KafkaResources::create_topics(&[("end_to_end_test_input_topic", 1), ("end_to_end_test_output_topic", 1)]);
+    #[test]
+    fn proptest_kafka_end_to_end_transactional(data in generate_test_batches(100, 1000)) {
+        kafka_end_to_end_test(
+            "end_to_end_transactional_test",
+            r#"transactional.id: "end_to_end_transactional_test""#,
+            data,
+        );
+    }
+}
 
-        // Create controller.
+/// Run a circuit that reads from `<test_name>_input_topic` and writes to
+/// `<test_name>_output_topic`.  `output_options` contains additional
+/// options for the output endpoint in YAML format.
+fn kafka_end_to_end_test(test_name: &str, output_options: &str, data: Vec<Vec<TestStruct>>) {
+    let _ = log::set_logger(&TEST_LOGGER);
+    log::set_max_level(LevelFilter::Debug);
 
-        // auto.offset.reset: "earliest" - guarantees that on startup the
-        // consumer will observe all messages sent by the producer even if
-        // the producer starts earlier (the consumer won't start until the
-        // rebalancing protocol kicks in).
-        let config_str = r#"
+    let input_topic = format!("{test_name}_input_topic");
+    let output_topic = format!("{test_name}_output_topic");
+
+    // Create topics.
+    let kafka_resources =
+        KafkaResources::create_topics(&[(input_topic.as_str(), 1), (output_topic.as_str(), 1)]);
+
+    // Create controller.
+
+    // auto.offset.reset: "earliest" - guarantees that on startup the
+    // consumer will observe all messages sent by the producer even if
+    // the producer starts earlier (the consumer won't start until the
+    // rebalancing protocol kicks in).
+    let config_str = format!(
+        r#"
 inputs:
     test_input1:
         stream: test_input1
@@ -161,7 +183,7 @@ inputs:
                 bootstrap.servers: "localhost"
                 auto.offset.reset: "earliest"
                 group.instance.id: "group0"
-                topics: [end_to_end_test_input_topic]
+                topics: [{input_topic}]
                 log_level: debug
         format:
             name: csv
@@ -172,43 +194,112 @@ outputs:
             name: kafka
             config:
                 bootstrap.servers: "localhost"
-                topic: end_to_end_test_output_topic
+                topic: {output_topic}
                 max_inflight_messages: 0
+                {output_options}
         format:
             name: csv
-"#;
+"#
+    );
 
-        println!("Creating circuit");
-        let (circuit, catalog) = test_circuit(4);
+    println!("Creating circuit");
+    let (circuit, catalog) = test_circuit(4);
 
-        println!("Starting controller");
-        let config: PipelineConfig = serde_yaml::from_str(config_str).unwrap();
+    println!("Starting controller");
+    let config: PipelineConfig = serde_yaml::from_str(&config_str).unwrap();
 
-        let controller = Controller::with_config(
-            circuit,
-            catalog,
-            &config,
-            Box::new(|e| panic!("error: {e}"))
-        ).unwrap();
+    let controller = Controller::with_config(
+        circuit,
+        catalog,
+        &config,
+        Box::new(|e| panic!("error: {e}")),
+    )
+    .unwrap();
 
-        let buffer_consumer = BufferConsumer::new("end_to_end_test_output_topic");
+    let buffer_consumer = BufferConsumer::new(&output_topic);
 
-        let producer = TestProducer::new();
-        producer.send_to_topic(&data, "end_to_end_test_input_topic");
+    let producer = TestProducer::new();
+    producer.send_to_topic(&data, &input_topic);
 
-        // Start controller.
-        controller.start();
+    // Start controller.
+    controller.start();
 
-        // Wait for output buffer to contain all of `data`.
+    // Wait for output buffer to contain all of `data`.
 
-        buffer_consumer.wait_for_output_unordered(&data);
+    buffer_consumer.wait_for_output_unordered(&data);
 
-        drop(buffer_consumer);
+    drop(buffer_consumer);
 
-        controller.stop().unwrap();
-        sleep(Duration::from_millis(100));
+    controller.stop().unwrap();
+    sleep(Duration::from_millis(100));
 
-        println!("Delete Kafka resources");
-        drop(kafka_resources);
-    }
+    println!("Delete Kafka resources");
+    drop(kafka_resources);
+}
+
+/// Test that the input endpoint commits offsets of processed messages and
+/// resumes from committed offsets after restart.
+#[test]
+fn test_kafka_input_resume() {
+    let _ = log::set_logger(&TEST_LOGGER);
+    log::set_max_level(LevelFilter::Debug);
+
+    let kafka_resources = KafkaResources::create_topics(&[("resume_test_topic", 1)]);
+
+    let batch = |start: u32| {
+        vec![(start..start + 10)
+            .map(|id| TestStruct {
+                id,
+                b: id % 2 == 0,
+                i: Some(id as i64),
+                s: id.to_string(),
+            })
+            .collect::<Vec<_>>()]
+    };
+
+    let config_str = r#"
+stream: test_input
+transport:
+    name: kafka
+    config:
+        bootstrap.servers: "localhost"
+        auto.offset.reset: "earliest"
+        group.id: "resume_test_group"
+        topics: [resume_test_topic]
+        log_level: debug
+format:
+    name: csv
+"#;
+
+    let producer = TestProducer::new();
+
+    // Receive the first batch and report it as processed.
+    let (endpoint, _consumer, zset) =
+        mock_input_pipeline::<TestStruct>(serde_yaml::from_str(config_str).unwrap());
+    endpoint.start().unwrap();
+
+    producer.send_to_topic(&batch(0), "resume_test_topic");
+    wait_for_output_ordered(&zset, &batch(0));
+
+    // `MockInputConsumer` labels all inputs with step 0.
+    endpoint.completed_step(0);
+
+    // Give the endpoint time to commit offsets and leave the consumer group.
+    sleep(Duration::from_millis(1000));
+    drop(endpoint);
+    sleep(Duration::from_millis(1000));
+
+    // A new endpoint in the same consumer group only receives new messages.
+    let (endpoint, _consumer, zset) =
+        mock_input_pipeline::<TestStruct>(serde_yaml::from_str(config_str).unwrap());
+    endpoint.start().unwrap();
+
+    producer.send_to_topic(&batch(10), "resume_test_topic");
+    wait_for_output_ordered(&zset, &batch(10));
+
+    sleep(Duration::from_millis(1000));
+    assert_eq!(zset.state().flushed.len(), 10);
+
+    drop(endpoint);
+    drop(kafka_resources);
 }
@@ -1,9 +1,12 @@
+use crate::{FormatConfig, OutputConsumer};
 use anyhow::{Error as AnyError, Result as AnyResult};
 use once_cell::sync::Lazy;
 use serde_yaml::Value as YamlValue;
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 
+mod compression;
+mod directory;
 mod file;
 
 #[cfg(feature = "server")]
@@ -12,21 +15,42 @@ mod http;
 #[cfg(feature = "with-kafka")]
 mod kafka;
 
+#[cfg(feature = "with-postgres")]
+mod postgres;
+
+pub use compression::Compression;
+pub(crate) use compression::{CompressEndpoint, DecompressParser};
+pub use directory::{DirectoryInputConfig, DirectoryInputTransport, FileOrder};
 pub use file::{FileInputConfig, FileInputTransport, FileOutputConfig, FileOutputTransport};
 
 #[cfg(feature = "server")]
-pub use http::{HttpInputTransport, HttpOutputTransport};
+pub use http::{HttpInputTransport, HttpOutputConfig, HttpOutputTransport};
+
+#[cfg(feature = "server")]
+pub(crate) use http::format_config_from_args;
 
 #[cfg(feature = "with-kafka")]
 pub use kafka::{
     KafkaInputConfig, KafkaInputTransport, KafkaLogLevel, KafkaOutputConfig, KafkaOutputTransport,
 };
 
+#[cfg(feature = "with-postgres")]
+pub use postgres::{PostgresCdcInputConfig, PostgresCdcInputTransport};
+
+/// Sequence number of a DBSP step performed by the controller.
+///
+/// Steps are numbered consecutively starting from 0.
+pub type Step = u64;
+
 /// Static map of supported input transports.
 // TODO: support for registering new transports at runtime in order to allow
 // external crates to implement new transports.
 static INPUT_TRANSPORT: Lazy<BTreeMap<&'static str, Box<dyn InputTransport>>> = Lazy::new(|| {
     BTreeMap::from([
+        (
+            "directory",
+            Box::new(DirectoryInputTransport) as Box<dyn InputTransport>,
+        ),
         (
             "file",
             Box::new(FileInputTransport) as Box<dyn InputTransport>,
@@ -41,6 +65,11 @@ static INPUT_TRANSPORT: Lazy<BTreeMap<&'static str, Box<dyn InputTransport>>> =
             "kafka",
             Box::new(KafkaInputTransport) as Box<dyn InputTransport>,
         ),
+        #[cfg(feature = "with-postgres")]
+        (
+            "postgres_cdc",
+            Box::new(PostgresCdcInputTransport) as Box<dyn InputTransport>,
+        ),
     ])
 });
 
@@ -129,6 +158,68 @@ pub trait InputEndpoint: Send {
     /// data buffers may be pushed downstream before the endpoint gets
     /// disconnected.
     fn disconnect(&self);
+
+    /// Notifies the endpoint that step `step` has completed.
+    ///
+    /// All data pushed to the [`InputConsumer`] by this endpoint with a step
+    /// number less than or equal to `step` (see [`InputConsumer::input`]) has
+    /// been fully processed by the circuit.  Endpoints that support
+    /// acknowledging data to their source, e.g., by committing offsets, can
+    /// safely do so for this data.
+    ///
+    /// The default implementation does nothing.
+    fn completed_step(&self, _step: Step) {}
+
+    /// Returns the position of the endpoint in its input stream after step
+    /// `step`.
+    ///
+    /// Invoked by the controller when writing a checkpoint after step `step`
+    /// has completed.  Endpoints that track their position in the data
+    /// source, e.g., Kafka offsets, return the position that follows all
+    /// data consumed by steps up to `step`.  The position is stored in the
+    /// checkpoint and passed to [`seek`](`Self::seek`) when resuming from
+    /// the checkpoint.
+    ///
+    /// The default implementation returns `None`: the endpoint resumes from
+    /// the number of bytes consumed before the checkpoint.
+    fn position(&self, _step: Step) -> AnyResult<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    /// Resume reading the input stream from a checkpoint.
+    ///
+    /// Invoked before the endpoint is started when restoring a pipeline from
+    /// a checkpoint: the first `offset` bytes received by the endpoint's
+    /// consumer are covered by the checkpoint, i.e., they have been consumed
+    /// by the circuit or are part of the restored state of the parser.
+    /// `position` is the position returned by [`position`](`Self::position`)
+    /// at the time of the checkpoint.
+    ///
+    /// The default implementation fails unless `offset` is 0.
+    fn seek(&self, offset: u64, _position: Option<&[u8]>) -> AnyResult<()> {
+        if offset == 0 {
+            Ok(())
+        } else {
+            Err(AnyError::msg(
+                "endpoint does not support resuming from a checkpoint",
+            ))
+        }
+    }
+
+    /// Returns `true` if consumers forked by the endpoint with
+    /// [`InputConsumer::fork`] resume from the state of the consumers it
+    /// had forked at the time of the checkpoint.
+    ///
+    /// Endpoints that fork a consumer for each of a fixed set of parallel
+    /// input pipelines, e.g., one per partition, return `true`: when
+    /// resuming from a checkpoint, the consumers they fork are assigned the
+    /// checkpointed states in the order of creation.
+    ///
+    /// The default implementation returns `false`: forked consumers, e.g.,
+    /// consumers of individual requests, start from a clean state.
+    fn resume_forks(&self) -> bool {
+        false
+    }
 }
 
 /// Input stream consumer.
@@ -138,11 +229,32 @@ pub trait InputEndpoint: Send {
 // TODO: `input_owned`.
 pub trait InputConsumer: Send {
     /// Push a chunk of data to the consumer.
-    fn input(&mut self, data: &[u8]);
+    ///
+    /// Returns the number of the step that will process records parsed from
+    /// `data` or `None` if `data` did not contain any complete valid records.
+    /// Once the controller reports completion of this step via
+    /// [`InputEndpoint::completed_step`], the records have been fully
+    /// processed.
+    fn input(&mut self, data: &[u8]) -> Option<Step>;
 
-    /// Endpoint failed.
+    /// End of a self-contained chunk of input, e.g., the body of an HTTP
+    /// request.
+    ///
+    /// The consumer completes parsing any partially received records.  Unlike
+    /// [`eoi`](`Self::eoi`), this notification does not terminate the input
+    /// stream: the endpoint may push more data after this call.
     ///
-    /// Endpoint failed; no more data will be received from this endpoint.
+    /// Returns the number of the step that will process the records parsed
+    /// at this point, or `None` if there were no such records (see
+    /// [`input`](`Self::input`)).
+    fn end_of_chunk(&mut self) -> Option<Step>;
+
+    /// Report an error.
+    ///
+    /// A transport error with `fatal` set means that the endpoint failed; no
+    /// more data will be received from this endpoint.  Errors parsing
+    /// individual input records are reported as non-fatal
+    /// [`ParseError`](`crate::ParseError`)s.
     fn error(&mut self, fatal: bool, error: AnyError);
 
     /// End-of-input-stream notification.
@@ -153,8 +265,17 @@ pub trait InputConsumer: Send {
     /// Create a new consumer instance.
     ///
     /// Used by multithreaded transport endpoints to create multiple parallel
-    /// input pipelines.
+    /// input pipelines.  The state of forked consumers is included in
+    /// checkpoints (see [`InputEndpoint::resume_forks`]).
     fn fork(&self) -> Box<dyn InputConsumer>;
+
+    /// Create a new consumer instance that parses data using `format`
+    /// instead of the data format configured for the endpoint.
+    ///
+    /// Used by transport endpoints that allow clients to choose the data
+    /// format of individual requests.  Unlike consumers created with
+    /// [`fork`](`Self::fork`), these consumers are not checkpointed.
+    fn fork_with_format(&self, format: &FormatConfig) -> AnyResult<Box<dyn InputConsumer>>;
 }
 
 /// Trait that represents a specific data transport.
@@ -207,5 +328,118 @@ impl dyn OutputTransport {
 }
 
 pub trait OutputEndpoint: Send {
+    /// Notifies the endpoint that the following buffers, up to the next
+    /// [`batch_end`](`Self::batch_end`) call, contain the output of circuit
+    /// step `step`.
+    ///
+    /// A `batch_start`/`batch_end` pair without buffers in between denotes
+    /// an empty step (a heartbeat).  Transactional endpoints use this
+    /// notification to start a new transaction.  The default implementation
+    /// does nothing.
+    fn batch_start(&mut self, _step: Step) -> AnyResult<()> {
+        Ok(())
+    }
+
+    /// Notifies the endpoint of the number of records in the output of the
+    /// current step.
+    ///
+    /// Invoked after [`batch_start`](`Self::batch_start`), before the buffers
+    /// of the step are pushed.  The default implementation does nothing.
+    fn batch_records(&mut self, _num_records: usize) -> AnyResult<()> {
+        Ok(())
+    }
+
     fn push_buffer(&mut self, buffer: &[u8]) -> AnyResult<()>;
+
+    /// Returns `true` if the next buffer pushed to the endpoint will be
+    /// written at the start of a new output file.
+    ///
+    /// Invoked after [`batch_start`](`Self::batch_start`).  Encoders use this
+    /// to repeat per-file content, such as a header row, in every file of an
+    /// endpoint that splits its output into multiple files.  The default
+    /// implementation returns `false`.
+    fn at_file_start(&self) -> bool {
+        false
+    }
+
+    /// Returns `true` if the endpoint delivers the output of each step
+    /// separately, e.g., as a separate file or message, so that the output
+    /// of one step is never appended to the output of another.
+    ///
+    /// Encoders that produce a self-contained file for each step, e.g., the
+    /// Parquet encoder, use this to reject endpoints that would concatenate
+    /// such files.  The default implementation returns `false`.
+    fn separates_steps(&self) -> bool {
+        false
+    }
+
+    /// Notifies the endpoint that all buffers for the current circuit step
+    /// have been pushed.
+    ///
+    /// Transactional endpoints use this notification to commit the
+    /// transaction started by [`batch_start`](`Self::batch_start`).  The
+    /// default implementation does nothing.
+    fn batch_end(&mut self) -> AnyResult<()> {
+        Ok(())
+    }
+
+    /// Notifies the endpoint that the output of the current step could not
+    /// be produced in its entirety.
+    ///
+    /// Invoked instead of [`batch_end`](`Self::batch_end`), after some of the
+    /// buffers of the step may have been pushed.  Transactional endpoints use
+    /// this notification to roll back the transaction started by
+    /// [`batch_start`](`Self::batch_start`), so that the partial output of
+    /// the step is never observed.  The default implementation does nothing.
+    fn batch_abort(&mut self) -> AnyResult<()> {
+        Ok(())
+    }
+
+    /// Checks that the output of the endpoint can be compressed as
+    /// configured by
+    /// [`TransportConfig::compression`](`crate::TransportConfig::compression`),
+    /// which compresses each output buffer separately.
+    ///
+    /// Fails for endpoints that cannot deliver compressed buffers intact,
+    /// e.g., because they embed buffers in a text-based protocol, or that
+    /// already compress their output.  The default implementation succeeds.
+    fn check_compression(&self) -> AnyResult<()> {
+        Ok(())
+    }
+
+    /// Returns a handle used by the controller to deliver snapshots of the
+    /// integrated contents of the output stream to the endpoint.
+    ///
+    /// When this method returns `Some`, the controller sends the contents of
+    /// the output stream, which must be materialized (see
+    /// [`Catalog::register_materialized_output_batch_handle`](`crate::Catalog::register_materialized_output_batch_handle`)),
+    /// to the endpoint whenever the endpoint requests it.  The endpoint
+    /// invokes `request_callback` when it starts waiting for a snapshot (see
+    /// [`SnapshotSink::snapshot_requested`]).  The default implementation
+    /// returns `None`.
+    fn snapshot_sink(
+        &self,
+        _request_callback: Box<dyn Fn() + Send + Sync>,
+    ) -> Option<Box<dyn SnapshotSink>> {
+        None
+    }
+}
+
+/// Handle used by the controller to send snapshots of the integrated contents
+/// of an output stream to an endpoint (see
+/// [`OutputEndpoint::snapshot_sink`]).
+pub trait SnapshotSink: Send {
+    /// Returns `true` if the endpoint is waiting for a snapshot.
+    fn snapshot_requested(&self) -> bool;
+
+    /// Create a consumer for the next snapshot.
+    ///
+    /// The controller encodes the snapshot using a new encoder instance
+    /// that pushes its output to this consumer.  All buffers of the snapshot
+    /// are pushed between a single pair of
+    /// [`batch_start`](`OutputConsumer::batch_start`) and
+    /// [`batch_end`](`OutputConsumer::batch_end`) calls.  Output batches
+    /// pushed to the endpoint after `batch_end` are not included in the
+    /// snapshot.
+    fn snapshot_consumer(&self) -> Box<dyn OutputConsumer>;
 }
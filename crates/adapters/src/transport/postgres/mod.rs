@@ -0,0 +1,843 @@
+use super::{InputConsumer, InputEndpoint, InputTransport, Step};
+use crate::PipelineState;
+use anyhow::{Error as AnyError, Result as AnyResult};
+use crossbeam::sync::{Parker, Unparker};
+use num_traits::FromPrimitive;
+use postgres::{fallible_iterator::FallibleIterator, types::ToSql, Client, IsolationLevel, NoTls};
+use serde::Deserialize;
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+use serde_yaml::Value as YamlValue;
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    fs::{read_to_string, OpenOptions},
+    io::Write,
+    iter::empty,
+    path::Path,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread::spawn,
+    time::Duration,
+};
+use utoipa::ToSchema;
+
+mod replication;
+
+use replication::{ReplicationConnection, ReplicationSlot};
+
+/// Logical decoding output plugin used to decode changes.
+const OUTPUT_PLUGIN: &str = "wal2json";
+
+/// Number of bytes of snapshot data or decoded changes accumulated before
+/// pushing them to the consumer.
+const BUFFER_SIZE: usize = 1_000_000;
+
+/// `InputTransport` implementation that captures changes to a Postgres table
+/// using logical replication.
+pub struct PostgresCdcInputTransport;
+
+impl InputTransport for PostgresCdcInputTransport {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("postgres_cdc")
+    }
+
+    fn new_endpoint(
+        &self,
+        _name: &str,
+        config: &YamlValue,
+        consumer: Box<dyn InputConsumer>,
+    ) -> AnyResult<Box<dyn InputEndpoint>> {
+        let config = PostgresCdcInputConfig::deserialize(config)?;
+        let ep = PostgresCdcInputEndpoint::new(config, consumer)?;
+        Ok(Box::new(ep))
+    }
+}
+
+/// Configuration of an input endpoint that captures changes to a Postgres
+/// table.
+///
+/// The endpoint decodes changes from a logical replication slot using the
+/// `wal2json` output plugin, which must be installed on the server (the
+/// server must be configured with `wal_level = logical`).  When the endpoint
+/// creates the slot, it first sends a snapshot of the contents of the table
+/// exported by the slot, followed by all changes committed after the
+/// snapshot, so that each transaction is either included in the snapshot or
+/// decoded from the slot.
+///
+/// Changes are sent to the parser in the `json` format with
+/// `update_format: weighted`: each inserted row is encoded as
+/// `{"data": <row>, "weight": 1}`, each deleted row as
+/// `{"data": <row>, "weight": -1}`, and each update as the deletion of the
+/// old row followed by the insertion of the new row.  Deletions and updates
+/// carry the complete old row only if the table is configured with
+/// `REPLICA IDENTITY FULL`, which is therefore required.  Note that Postgres
+/// encodes some types, e.g., timestamps, differently in the snapshot and in
+/// the decoded changes; such columns must be declared in the catalog schema
+/// so that both representations are coerced to the same value.
+///
+/// The position of the slot is advanced past a transaction once all its
+/// changes have been processed by the circuit (see
+/// [`InputEndpoint::completed_step`](`crate::InputEndpoint::completed_step`)).
+/// If `state_file` is specified, the endpoint also records the position in
+/// this file; when the endpoint is restarted with a non-empty state file, it
+/// resumes from the recorded position without taking a new snapshot.
+/// Otherwise, the endpoint drops and recreates the slot on startup.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct PostgresCdcInputConfig {
+    /// Postgres connection string, e.g.,
+    /// `host=localhost user=postgres password=postgres dbname=db`.
+    uri: String,
+
+    /// Schema-qualified name of the table, e.g., `public.orders`.
+    table: String,
+
+    /// Name of the logical replication slot.
+    slot: String,
+
+    /// Interval between two polls of the replication slot in milliseconds.
+    ///
+    /// The default is 1000.
+    #[serde(default = "default_poll_interval_ms")]
+    poll_interval_ms: u64,
+
+    /// Maximal number of changes decoded from the replication slot by a
+    /// single poll.
+    ///
+    /// Each poll decodes changes starting from the last position processed
+    /// by the circuit, so this also bounds how far the endpoint reads ahead
+    /// of the circuit.  A transaction is always decoded in its entirety, even
+    /// if it contains more changes.  The default is 10000.
+    #[serde(default = "default_max_poll_changes")]
+    max_poll_changes: u32,
+
+    /// File that records the position of the replication slot.
+    ///
+    /// When not specified, the endpoint takes a new snapshot of the table
+    /// every time it is created.
+    state_file: Option<String>,
+}
+
+fn default_poll_interval_ms() -> u64 {
+    1000
+}
+
+fn default_max_poll_changes() -> u32 {
+    10_000
+}
+
+/// Parse a Postgres LSN in the `XXX/XXX` format.
+fn parse_lsn(lsn: &str) -> AnyResult<u64> {
+    lsn.split_once('/')
+        .and_then(|(hi, lo)| {
+            Some((u64::from_str_radix(hi, 16).ok()? << 32) | u64::from_str_radix(lo, 16).ok()?)
+        })
+        .ok_or_else(|| AnyError::msg(format!("invalid LSN '{lsn}'")))
+}
+
+/// Format LSN in the `XXX/XXX` format.
+fn format_lsn(lsn: u64) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn & 0xffff_ffff)
+}
+
+/// Position of the replication slot recorded in the state file.
+#[derive(Clone, Copy)]
+struct SlotPosition {
+    /// Number of bytes received by the endpoint up to this position.
+    offset: u64,
+
+    /// All transactions whose commit record ends at or before this LSN have
+    /// been consumed.
+    lsn: u64,
+}
+
+/// A snapshot or transaction that has been sent to the consumer, but not
+/// yet confirmed.
+struct PendingChunk {
+    /// The last step that consumed data from the chunk, if any.
+    step: Option<Step>,
+
+    /// Position of the replication slot after the chunk.
+    position: SlotPosition,
+}
+
+/// State shared by the endpoint and its worker thread.
+struct PostgresCdcInputInner {
+    config: PostgresCdcInputConfig,
+    status: AtomicU32,
+
+    /// Number of bytes at the start of the input to skip (see
+    /// [`InputEndpoint::seek`]).
+    skip_bytes: AtomicU64,
+
+    /// Position recorded in the state file when the endpoint was created.
+    restored: Option<SlotPosition>,
+
+    /// The last step completed by the circuit.
+    completed_step: Mutex<Option<Step>>,
+}
+
+struct PostgresCdcInputEndpoint {
+    inner: Arc<PostgresCdcInputInner>,
+    unparker: Unparker,
+}
+
+impl PostgresCdcInputEndpoint {
+    fn new(config: PostgresCdcInputConfig, consumer: Box<dyn InputConsumer>) -> AnyResult<Self> {
+        if !config.table.contains('.') {
+            return Err(AnyError::msg(format!(
+                "Table name '{}' must be schema-qualified, e.g., 'public.{}'",
+                config.table, config.table
+            )));
+        }
+
+        let restored = match &config.state_file {
+            Some(state_file) => Self::read_state(Path::new(state_file))?,
+            None => None,
+        };
+
+        let inner = Arc::new(PostgresCdcInputInner {
+            config,
+            status: AtomicU32::new(PipelineState::Paused as u32),
+            skip_bytes: AtomicU64::new(0),
+            restored,
+            completed_step: Mutex::new(None),
+        });
+
+        let parker = Parker::new();
+        let unparker = parker.unparker().clone();
+        let worker = PostgresCdcWorker::new(inner.clone(), consumer);
+        let _worker = spawn(move || worker.run(parker));
+
+        Ok(Self { inner, unparker })
+    }
+
+    /// Read the last position of the replication slot from the state file.
+    ///
+    /// Each line of the state file contains the number of bytes received by
+    /// the endpoint up to a position of the slot, followed by a space and the
+    /// LSN of the position.
+    fn read_state(path: &Path) -> AnyResult<Option<SlotPosition>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let state = read_to_string(path).map_err(|e| {
+            AnyError::msg(format!(
+                "Failed to read state file '{}': {e}",
+                path.display()
+            ))
+        })?;
+
+        let mut position = None;
+        for line in state.lines().filter(|line| !line.is_empty()) {
+            let parsed = line.split_once(' ').and_then(|(offset, lsn)| {
+                Some(SlotPosition {
+                    offset: offset.parse().ok()?,
+                    lsn: parse_lsn(lsn).ok()?,
+                })
+            });
+            position = Some(parsed.ok_or_else(|| {
+                AnyError::msg(format!(
+                    "Invalid line in state file '{}': '{line}'",
+                    path.display()
+                ))
+            })?);
+        }
+
+        Ok(position)
+    }
+}
+
+impl InputEndpoint for PostgresCdcInputEndpoint {
+    fn pause(&self) -> AnyResult<()> {
+        // Notify worker thread via the status flag.  The worker may
+        // send another buffer downstream before the flag takes effect.
+        self.inner
+            .status
+            .store(PipelineState::Paused as u32, Ordering::Release);
+        Ok(())
+    }
+
+    fn start(&self) -> AnyResult<()> {
+        self.inner
+            .status
+            .store(PipelineState::Running as u32, Ordering::Release);
+
+        // Wake up the worker if it's paused.
+        self.unparker.unpark();
+        Ok(())
+    }
+
+    fn disconnect(&self) {
+        self.inner
+            .status
+            .store(PipelineState::Terminated as u32, Ordering::Release);
+
+        // Wake up the worker if it's paused.
+        self.unparker.unpark();
+    }
+
+    fn completed_step(&self, step: Step) {
+        *self.inner.completed_step.lock().unwrap() = Some(step);
+
+        // Wake up the worker to advance the replication slot.
+        self.unparker.unpark();
+    }
+
+    /// Changes before the position recorded in the state file are not
+    /// received again, so the offset is relative to this position.  Fails if
+    /// the checkpoint precedes this position or if no position is recorded,
+    /// in which case the endpoint takes a new snapshot.
+    fn seek(&self, offset: u64, _position: Option<&[u8]>) -> AnyResult<()> {
+        let restored_offset = match self.inner.restored {
+            Some(position) => position.offset,
+            None if offset == 0 => 0,
+            None => {
+                return Err(AnyError::msg(
+                    "cannot resume from a checkpoint without a replication slot position recorded in the state file",
+                ))
+            }
+        };
+        let skip = offset.checked_sub(restored_offset).ok_or_else(|| {
+            AnyError::msg(
+                "cannot resume from a checkpoint taken before the replication slot position recorded in the state file",
+            )
+        })?;
+        self.inner.skip_bytes.store(skip, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl Drop for PostgresCdcInputEndpoint {
+    fn drop(&mut self) {
+        self.disconnect();
+    }
+}
+
+/// Worker thread of a Postgres CDC input endpoint.
+struct PostgresCdcWorker {
+    inner: Arc<PostgresCdcInputInner>,
+    consumer: Box<dyn InputConsumer>,
+
+    /// Table name, resolved and quoted by the server.
+    table: String,
+
+    /// Replication slot whose snapshot hasn't been sent to the consumer yet,
+    /// along with the connection that created it, which must be kept open
+    /// for the snapshot to remain valid.
+    pending_snapshot: Option<(ReplicationConnection, ReplicationSlot)>,
+
+    /// Transactions whose commit record ends at or before this LSN have been
+    /// sent to the consumer.
+    read_lsn: u64,
+
+    /// Chunks sent to the consumer, but not yet confirmed.
+    pending: VecDeque<PendingChunk>,
+
+    /// Number of bytes received by the endpoint, including data consumed
+    /// before the position recorded in the state file.
+    offset: u64,
+}
+
+impl PostgresCdcWorker {
+    fn new(inner: Arc<PostgresCdcInputInner>, consumer: Box<dyn InputConsumer>) -> Self {
+        let (read_lsn, offset) = match inner.restored {
+            Some(position) => (position.lsn, position.offset),
+            None => (0, 0),
+        };
+
+        Self {
+            inner,
+            consumer,
+            table: String::new(),
+            pending_snapshot: None,
+            read_lsn,
+            pending: VecDeque::new(),
+            offset,
+        }
+    }
+
+    fn run(mut self, parker: Parker) {
+        let poll_interval = Duration::from_millis(self.inner.config.poll_interval_ms);
+
+        // Connect from the worker thread: the synchronous client cannot be
+        // created from within an async runtime.
+        let mut client = match self.connect() {
+            Ok(client) => client,
+            Err(e) => {
+                self.consumer.error(true, e);
+                return;
+            }
+        };
+
+        loop {
+            if let Err(e) = self.confirm(&mut client) {
+                self.consumer.error(false, e);
+            }
+
+            match PipelineState::from_u32(self.inner.status.load(Ordering::Acquire)) {
+                Some(PipelineState::Paused) => parker.park_timeout(poll_interval),
+                Some(PipelineState::Running) => {
+                    let result = if let Some(snapshot) = self.pending_snapshot.take() {
+                        self.snapshot(&mut client, snapshot).map(|_| true)
+                    } else {
+                        self.poll(&mut client)
+                    };
+                    match result {
+                        Ok(true) => {}
+                        // No new transactions -- wait for the next poll.
+                        Ok(false) => parker.park_timeout(poll_interval),
+                        Err(e) => {
+                            self.consumer.error(true, e);
+                            return;
+                        }
+                    }
+                }
+                Some(PipelineState::Terminated) => return,
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    /// Connect to the server and prepare the replication slot.
+    fn connect(&mut self) -> AnyResult<Client> {
+        let config = &self.inner.config;
+
+        let mut client = Client::connect(&config.uri, NoTls)
+            .map_err(|e| AnyError::msg(format!("Failed to connect to Postgres: {e}")))?;
+
+        let table = client.query_one(
+            "SELECT oid::regclass::text, relreplident::text FROM pg_class WHERE oid = $1::text::regclass",
+            &[&config.table],
+        )?;
+        self.table = table.get(0);
+        if table.get::<_, &str>(1) != "f" {
+            return Err(AnyError::msg(format!(
+                "Table '{}' must be configured with 'REPLICA IDENTITY FULL'",
+                config.table
+            )));
+        }
+
+        let slot_exists = client
+            .query_opt(
+                "SELECT 1 FROM pg_replication_slots WHERE slot_name = $1",
+                &[&config.slot],
+            )?
+            .is_some();
+
+        match (self.inner.restored, slot_exists) {
+            (Some(_), true) => {}
+            (Some(_), false) => {
+                return Err(AnyError::msg(format!(
+                    "Replication slot '{}' recorded in the state file does not exist",
+                    config.slot
+                )))
+            }
+            (None, _) => {
+                if slot_exists {
+                    client.execute("SELECT pg_drop_replication_slot($1)", &[&config.slot])?;
+                }
+                let mut connection = ReplicationConnection::connect(&config.uri).map_err(|e| {
+                    AnyError::msg(format!("Failed to open replication connection: {e}"))
+                })?;
+                let slot = connection.create_slot(&config.slot, OUTPUT_PLUGIN)?;
+                self.pending_snapshot = Some((connection, slot));
+            }
+        }
+
+        Ok(client)
+    }
+
+    /// Send the contents of the table in the snapshot exported by the
+    /// replication slot to the consumer.
+    ///
+    /// The snapshot contains exactly the transactions that committed before
+    /// the consistent point of the slot; all later transactions are decoded
+    /// from the slot.
+    fn snapshot(
+        &mut self,
+        client: &mut Client,
+        (connection, slot): (ReplicationConnection, ReplicationSlot),
+    ) -> AnyResult<()> {
+        let mut transaction = client
+            .build_transaction()
+            .isolation_level(IsolationLevel::RepeatableRead)
+            .read_only(true)
+            .start()?;
+        transaction.batch_execute(&format!(
+            "SET TRANSACTION SNAPSHOT '{}'",
+            slot.snapshot_name
+        ))?;
+
+        // The snapshot has been imported and no longer depends on the
+        // replication connection.
+        drop(connection);
+
+        let query = format!("SELECT row_to_json(t)::text FROM {} t", self.table);
+        let mut rows = transaction.query_raw(query.as_str(), empty::<&dyn ToSql>())?;
+
+        let mut buffer = Vec::new();
+        let mut step = None;
+        while let Some(row) = rows.next()? {
+            buffer.extend_from_slice(b"{\"data\":");
+            buffer.extend_from_slice(row.get::<_, &str>(0).as_bytes());
+            buffer.extend_from_slice(b",\"weight\":1}\n");
+            if buffer.len() >= BUFFER_SIZE {
+                step = step.max(self.push(&buffer));
+                buffer.clear();
+            }
+        }
+        drop(rows);
+        transaction.commit()?;
+
+        step = step.max(self.push(&buffer));
+        self.chunk_end(step, slot.consistent_point);
+
+        Ok(())
+    }
+
+    /// Send transactions committed since the last poll to the consumer.
+    ///
+    /// Changes are peeked rather than consumed from the slot, so that they
+    /// are decoded again if the endpoint is restarted before they have been
+    /// processed by the circuit.  Since peeking starts from the last position
+    /// confirmed to the slot, transactions sent by a previous poll are
+    /// skipped based on the LSN of their commit record, which `wal2json`
+    /// reports at the start of the transaction.  Returns `false` if there are
+    /// no new transactions.
+    fn poll(&mut self, client: &mut Client) -> AnyResult<bool> {
+        let config = &self.inner.config;
+        let max_changes = i32::try_from(config.max_poll_changes).unwrap_or(i32::MAX);
+        let mut rows = client.query_raw(
+            "SELECT data FROM pg_logical_slot_peek_changes($1, NULL, $2, 'format-version', '2', 'include-lsn', '1', 'add-tables', $3)",
+            [&config.slot as &dyn ToSql, &max_changes, &config.table],
+        )?;
+
+        let mut buffer = Vec::new();
+        let mut new_transactions = false;
+
+        // End LSN of the current transaction, or `None` if the transaction
+        // has already been sent and must be skipped.
+        let mut transaction_lsn = None;
+        let mut step = None;
+
+        while let Some(row) = rows.next()? {
+            let change: JsonValue = serde_json::from_str(row.get(0))?;
+            let action = change.get("action").and_then(JsonValue::as_str);
+            match (action, transaction_lsn) {
+                (Some("B"), _) => {
+                    let lsn = change
+                        .get("nextlsn")
+                        .and_then(JsonValue::as_str)
+                        .ok_or_else(|| {
+                            AnyError::msg(format!(
+                                "change '{change}' is missing the 'nextlsn' field"
+                            ))
+                        })?;
+                    let lsn = parse_lsn(lsn)?;
+                    transaction_lsn = if lsn > self.read_lsn { Some(lsn) } else { None };
+                    step = None;
+                }
+                (_, None) => {}
+                (Some("I"), _) => Self::encode_change(&mut buffer, &change, "columns", 1)?,
+                (Some("U"), _) => {
+                    Self::encode_change(&mut buffer, &change, "identity", -1)?;
+                    Self::encode_change(&mut buffer, &change, "columns", 1)?;
+                }
+                (Some("D"), _) => Self::encode_change(&mut buffer, &change, "identity", -1)?,
+                (Some("T"), _) => self.consumer.error(
+                    false,
+                    AnyError::msg(format!(
+                        "Ignoring truncation of table '{}'",
+                        self.inner.config.table
+                    )),
+                ),
+                (Some("C"), Some(lsn)) => {
+                    step = step.max(self.push(&buffer));
+                    buffer.clear();
+                    self.chunk_end(step, lsn);
+                    transaction_lsn = None;
+                    new_transactions = true;
+                }
+                _ => {}
+            }
+
+            // Don't accumulate large transactions in memory.
+            if buffer.len() >= BUFFER_SIZE {
+                step = step.max(self.push(&buffer));
+                buffer.clear();
+            }
+        }
+
+        Ok(new_transactions)
+    }
+
+    /// Encode the row stored in the `field` field of a change decoded by
+    /// `wal2json` as an update with weight `weight`.
+    fn encode_change(
+        buffer: &mut Vec<u8>,
+        change: &JsonValue,
+        field: &str,
+        weight: i64,
+    ) -> AnyResult<()> {
+        let columns = change
+            .get(field)
+            .and_then(JsonValue::as_array)
+            .ok_or_else(|| {
+                AnyError::msg(format!(
+                    "change '{change}' is missing the '{field}' field; make sure that the table is configured with 'REPLICA IDENTITY FULL'"
+                ))
+            })?;
+
+        let mut row = JsonMap::new();
+        for column in columns {
+            let name = column
+                .get("name")
+                .and_then(JsonValue::as_str)
+                .ok_or_else(|| AnyError::msg(format!("invalid column '{column}'")))?;
+            let value = column.get("value").cloned().unwrap_or(JsonValue::Null);
+            row.insert(name.to_string(), value);
+        }
+
+        serde_json::to_writer(&mut *buffer, &json!({"data": row, "weight": weight}))?;
+        buffer.push(b'\n');
+        Ok(())
+    }
+
+    /// Push `data` to the consumer, skipping data consumed before the
+    /// checkpoint the pipeline was restored from.
+    fn push(&mut self, data: &[u8]) -> Option<Step> {
+        let skip = self
+            .inner
+            .skip_bytes
+            .load(Ordering::Acquire)
+            .min(data.len() as u64);
+        if skip > 0 {
+            self.inner.skip_bytes.fetch_sub(skip, Ordering::AcqRel);
+        }
+        self.offset += data.len() as u64;
+
+        let data = &data[skip as usize..];
+        if data.is_empty() {
+            None
+        } else {
+            self.consumer.input(data)
+        }
+    }
+
+    /// Record the end of a snapshot or transaction that committed at `lsn`.
+    fn chunk_end(&mut self, step: Option<Step>, lsn: u64) {
+        self.read_lsn = lsn;
+        self.pending.push_back(PendingChunk {
+            step,
+            position: SlotPosition {
+                offset: self.offset,
+                lsn,
+            },
+        });
+    }
+
+    /// Advance the replication slot past chunks whose contents have been
+    /// fully processed by the circuit and record its new position in the
+    /// state file.
+    fn confirm(&mut self, client: &mut Client) -> AnyResult<()> {
+        let completed_step = *self.inner.completed_step.lock().unwrap();
+        let is_completed = |chunk: &PendingChunk| match chunk.step {
+            None => true,
+            Some(step) => completed_step.map_or(false, |completed| step <= completed),
+        };
+
+        let mut position = None;
+        while self.pending.front().map_or(false, is_completed) {
+            position = Some(self.pending.pop_front().unwrap().position);
+        }
+        let position = match position {
+            Some(position) => position,
+            None => return Ok(()),
+        };
+
+        let config = &self.inner.config;
+        client.execute(
+            "SELECT pg_replication_slot_advance($1, $2::text::pg_lsn)",
+            &[&config.slot, &format_lsn(position.lsn)],
+        )?;
+
+        if let Some(state_file) = &config.state_file {
+            let mut state = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(state_file)
+                .map_err(|e| {
+                    AnyError::msg(format!("Failed to open state file '{state_file}': {e}"))
+                })?;
+            writeln!(state, "{} {}", position.offset, format_lsn(position.lsn))?;
+            state.sync_data()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_lsn, parse_lsn};
+    use crate::test::{mock_input_pipeline, wait};
+    use postgres::{Client, NoTls};
+    use serde::{Deserialize, Serialize};
+    use std::{env, fs::read_to_string, path::Path};
+    use tempfile::tempdir;
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+    struct TestStruct {
+        s: String,
+        i: i64,
+    }
+
+    impl TestStruct {
+        fn new(s: &str, i: i64) -> Self {
+            Self {
+                s: s.to_string(),
+                i,
+            }
+        }
+    }
+
+    const TABLE: &str = "public.test_postgres_cdc";
+    const SLOT: &str = "test_postgres_cdc";
+
+    /// Connection string of the test server, built from the `PGHOST`,
+    /// `PGPORT`, `PGUSER` and `PGPASSWORD` environment variables.
+    fn uri() -> String {
+        let var = |name, default: &str| env::var(name).unwrap_or_else(|_| default.to_string());
+        format!(
+            "host={} port={} user={} password={}",
+            var("PGHOST", "localhost"),
+            var("PGPORT", "5432"),
+            var("PGUSER", "postgres"),
+            var("PGPASSWORD", "postgres")
+        )
+    }
+
+    fn config(state_file: &Path) -> String {
+        format!(
+            r#"
+stream: test_input
+transport:
+    name: postgres_cdc
+    config:
+        uri: "{}"
+        table: {TABLE}
+        slot: {SLOT}
+        poll_interval_ms: 10
+        state_file: {:?}
+format:
+    name: json
+    config:
+        update_format: weighted
+"#,
+            uri(),
+            state_file.to_str().unwrap()
+        )
+    }
+
+    fn slot_active(client: &mut Client) -> bool {
+        client
+            .query_opt(
+                "SELECT 1 FROM pg_replication_slots WHERE slot_name = $1 AND active",
+                &[&SLOT],
+            )
+            .unwrap()
+            .is_some()
+    }
+
+    #[test]
+    fn test_lsn() {
+        assert_eq!(parse_lsn("16/B374D848").unwrap(), 0x16_B374_D848);
+        assert_eq!(format_lsn(0x16_B374_D848), "16/B374D848");
+        assert!(parse_lsn("16B374D848").is_err());
+    }
+
+    /// Requires a Postgres server with `wal_level = logical` and the
+    /// `wal2json` plugin; run with `cargo test postgres_cdc -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_postgres_cdc() {
+        let data = [
+            TestStruct::new("foo", 0),
+            TestStruct::new("bar", 1),
+            TestStruct::new("baz", 2),
+        ];
+        let mut client = Client::connect(&uri(), NoTls).unwrap();
+        client
+            .batch_execute(&format!(
+                "DROP TABLE IF EXISTS {TABLE};
+                 CREATE TABLE {TABLE} (s TEXT, i BIGINT);
+                 ALTER TABLE {TABLE} REPLICA IDENTITY FULL;
+                 INSERT INTO {TABLE} VALUES ('foo', 0);"
+            ))
+            .unwrap();
+
+        let temp_dir = tempdir().unwrap();
+        let state_file = temp_dir.path().join("state");
+        let config_str = config(&state_file);
+
+        // The endpoint starts with a snapshot of the table.
+        let (endpoint, _consumer, zset) =
+            mock_input_pipeline::<TestStruct>(serde_yaml::from_str(&config_str).unwrap());
+        endpoint.start().unwrap();
+        wait(|| zset.state().flushed.len() == 1, None);
+        assert_eq!(zset.state().flushed, vec![(data[0].clone(), true)]);
+
+        // Changes are mapped to weighted updates.
+        client
+            .batch_execute(&format!(
+                "INSERT INTO {TABLE} VALUES ('bar', 1);
+                 UPDATE {TABLE} SET i = 2, s = 'baz' WHERE i = 0;
+                 DELETE FROM {TABLE} WHERE i = 1;"
+            ))
+            .unwrap();
+        wait(|| zset.state().flushed.len() == 5, None);
+        assert_eq!(
+            zset.state().flushed[1..],
+            [
+                (data[1].clone(), true),
+                (data[0].clone(), false),
+                (data[2].clone(), true),
+                (data[1].clone(), false),
+            ]
+        );
+
+        // The slot position is recorded once the changes have been processed.
+        assert!(!state_file.exists());
+        endpoint.completed_step(0);
+        wait(
+            || read_to_string(&state_file).map_or(false, |s| s.lines().count() == 1),
+            None,
+        );
+
+        endpoint.disconnect();
+        drop(endpoint);
+        wait(|| !slot_active(&mut client), None);
+
+        // After a restart, the endpoint resumes from the recorded position.
+        client
+            .batch_execute(&format!("INSERT INTO {TABLE} VALUES ('foo', 0);"))
+            .unwrap();
+        let (endpoint, _consumer, zset) =
+            mock_input_pipeline::<TestStruct>(serde_yaml::from_str(&config_str).unwrap());
+        endpoint.start().unwrap();
+        wait(|| zset.state().flushed.len() == 1, None);
+        assert_eq!(zset.state().flushed, vec![(data[0].clone(), true)]);
+
+        endpoint.disconnect();
+        drop(endpoint);
+        wait(|| !slot_active(&mut client), None);
+        client
+            .batch_execute(&format!(
+                "SELECT pg_drop_replication_slot('{SLOT}'); DROP TABLE {TABLE};"
+            ))
+            .unwrap();
+    }
+}
@@ -0,0 +1,262 @@
+//! Minimal client for the Postgres replication protocol.
+//!
+//! Creating a logical replication slot together with a snapshot of the
+//! database that is consistent with the point where the slot starts decoding
+//! changes requires issuing `CREATE_REPLICATION_SLOT` over a replication
+//! connection, which the `postgres` crate does not support.  This module
+//! implements just enough of the protocol to authenticate and run this
+//! command.
+
+use super::parse_lsn;
+use anyhow::{Error as AnyError, Result as AnyResult};
+use bytes::BytesMut;
+use postgres::{config::Host, fallible_iterator::FallibleIterator, Config};
+use postgres_protocol::{
+    authentication::{
+        md5_hash,
+        sasl::{ChannelBinding, ScramSha256, SCRAM_SHA_256},
+    },
+    message::{
+        backend::{ErrorResponseBody, Message},
+        frontend,
+    },
+};
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+/// Default port of the Postgres server.
+const DEFAULT_PORT: u16 = 5432;
+
+trait Stream: Read + Write + Send {}
+
+impl<T> Stream for T where T: Read + Write + Send {}
+
+/// Replication slot created by [`ReplicationConnection::create_slot`].
+pub(super) struct ReplicationSlot {
+    /// LSN at which the slot becomes consistent: all transactions that
+    /// commit after this point are decoded from the slot, all transactions
+    /// that committed before it are visible in the exported snapshot.
+    pub(super) consistent_point: u64,
+
+    /// Name of the exported snapshot, to be imported with
+    /// `SET TRANSACTION SNAPSHOT`.
+    ///
+    /// The snapshot remains valid until the connection that created the slot
+    /// executes another command or is closed.
+    pub(super) snapshot_name: String,
+}
+
+/// A connection to the server in the logical replication mode.
+pub(super) struct ReplicationConnection {
+    stream: Box<dyn Stream>,
+    read_buf: BytesMut,
+    write_buf: BytesMut,
+}
+
+impl ReplicationConnection {
+    /// Connect to the database specified by connection string `uri`.
+    ///
+    /// Only supports unencrypted connections with `trust`, `password`, `md5`
+    /// or `scram-sha-256` authentication.
+    pub(super) fn connect(uri: &str) -> AnyResult<Self> {
+        let config: Config = uri.parse()?;
+        let user = config
+            .get_user()
+            .ok_or_else(|| AnyError::msg("user missing from the connection string"))?;
+        let port = config.get_ports().first().copied().unwrap_or(DEFAULT_PORT);
+
+        let stream: Box<dyn Stream> = match config.get_hosts().first() {
+            Some(Host::Tcp(host)) => Box::new(TcpStream::connect((host.as_str(), port))?),
+            #[cfg(unix)]
+            Some(Host::Unix(dir)) => {
+                Box::new(UnixStream::connect(dir.join(format!(".s.PGSQL.{port}")))?)
+            }
+            None => return Err(AnyError::msg("host missing from the connection string")),
+        };
+
+        let mut connection = Self {
+            stream,
+            read_buf: BytesMut::new(),
+            write_buf: BytesMut::new(),
+        };
+
+        let mut params = vec![("user", user), ("replication", "database")];
+        if let Some(dbname) = config.get_dbname() {
+            params.push(("database", dbname));
+        }
+        frontend::startup_message(params, &mut connection.write_buf)?;
+        connection.send()?;
+
+        connection.authenticate(user, config.get_password())?;
+        Ok(connection)
+    }
+
+    /// Run the authentication exchange and wait for the server to become
+    /// ready for queries.
+    fn authenticate(&mut self, user: &str, password: Option<&[u8]>) -> AnyResult<()> {
+        let password =
+            || password.ok_or_else(|| AnyError::msg("password missing from the connection string"));
+        let mut scram = None;
+
+        loop {
+            match self.receive()? {
+                Message::AuthenticationOk
+                | Message::ParameterStatus(_)
+                | Message::BackendKeyData(_)
+                | Message::NoticeResponse(_) => {}
+                Message::AuthenticationCleartextPassword => {
+                    frontend::password_message(password()?, &mut self.write_buf)?;
+                    self.send()?;
+                }
+                Message::AuthenticationMd5Password(body) => {
+                    let hash = md5_hash(user.as_bytes(), password()?, body.salt());
+                    frontend::password_message(hash.as_bytes(), &mut self.write_buf)?;
+                    self.send()?;
+                }
+                Message::AuthenticationSasl(body) => {
+                    if !body.mechanisms().any(|m| Ok(m == SCRAM_SHA_256))? {
+                        return Err(AnyError::msg(
+                            "server does not support SCRAM-SHA-256 authentication",
+                        ));
+                    }
+                    let sasl = ScramSha256::new(password()?, ChannelBinding::unsupported());
+                    frontend::sasl_initial_response(
+                        SCRAM_SHA_256,
+                        sasl.message(),
+                        &mut self.write_buf,
+                    )?;
+                    self.send()?;
+                    scram = Some(sasl);
+                }
+                Message::AuthenticationSaslContinue(body) => {
+                    let sasl = scram
+                        .as_mut()
+                        .ok_or_else(|| AnyError::msg("unexpected SASL message"))?;
+                    sasl.update(body.data())?;
+                    frontend::sasl_response(sasl.message(), &mut self.write_buf)?;
+                    self.send()?;
+                }
+                Message::AuthenticationSaslFinal(body) => {
+                    scram
+                        .as_mut()
+                        .ok_or_else(|| AnyError::msg("unexpected SASL message"))?
+                        .finish(body.data())?;
+                }
+                Message::ReadyForQuery(_) => return Ok(()),
+                Message::ErrorResponse(body) => return Err(server_error(&body)),
+                _ => return Err(AnyError::msg("unsupported authentication method")),
+            }
+        }
+    }
+
+    /// Create logical replication slot `slot` that decodes changes using
+    /// `plugin` and export a snapshot consistent with the start of the slot.
+    pub(super) fn create_slot(&mut self, slot: &str, plugin: &str) -> AnyResult<ReplicationSlot> {
+        let rows = self.simple_query(&format!(
+            "CREATE_REPLICATION_SLOT {} LOGICAL {plugin} EXPORT_SNAPSHOT",
+            quote_ident(slot)
+        ))?;
+
+        // The result consists of a single row with columns `slot_name`,
+        // `consistent_point`, `snapshot_name`, and `output_plugin`.
+        if let [row] = rows.as_slice() {
+            if let (Some(Some(consistent_point)), Some(Some(snapshot_name))) =
+                (row.get(1), row.get(2))
+            {
+                return Ok(ReplicationSlot {
+                    consistent_point: parse_lsn(consistent_point)?,
+                    snapshot_name: snapshot_name.clone(),
+                });
+            }
+        }
+
+        Err(AnyError::msg(
+            "unexpected response to CREATE_REPLICATION_SLOT",
+        ))
+    }
+
+    /// Execute `query` using the simple query protocol and return the rows
+    /// it produced.
+    fn simple_query(&mut self, query: &str) -> AnyResult<Vec<Vec<Option<String>>>> {
+        frontend::query(query, &mut self.write_buf)?;
+        self.send()?;
+
+        let mut rows = Vec::new();
+        let mut error = None;
+        loop {
+            match self.receive()? {
+                Message::DataRow(body) => {
+                    let row = body
+                        .ranges()
+                        .map(|range| {
+                            Ok(range.map(|range| {
+                                String::from_utf8_lossy(&body.buffer()[range]).into_owned()
+                            }))
+                        })
+                        .collect()?;
+                    rows.push(row);
+                }
+                Message::ErrorResponse(body) => error = Some(server_error(&body)),
+                // Wait for the server to become ready for the next query even
+                // if the query failed.
+                Message::ReadyForQuery(_) => break,
+                _ => {}
+            }
+        }
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(rows),
+        }
+    }
+
+    fn send(&mut self) -> AnyResult<()> {
+        self.stream.write_all(&self.write_buf)?;
+        self.stream.flush()?;
+        self.write_buf.clear();
+        Ok(())
+    }
+
+    fn receive(&mut self) -> AnyResult<Message> {
+        loop {
+            if let Some(message) = Message::parse(&mut self.read_buf)? {
+                return Ok(message);
+            }
+
+            let mut buf = [0; 4096];
+            let len = self.stream.read(&mut buf)?;
+            if len == 0 {
+                return Err(AnyError::msg("connection closed by the server"));
+            }
+            self.read_buf.extend_from_slice(&buf[..len]);
+        }
+    }
+}
+
+impl Drop for ReplicationConnection {
+    fn drop(&mut self) {
+        frontend::terminate(&mut self.write_buf);
+        let _ = self.send();
+    }
+}
+
+/// Quote `ident` for use as an identifier in a replication command.
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+fn server_error(body: &ErrorResponseBody) -> AnyError {
+    let message = body
+        .fields()
+        .find(|field| Ok(field.type_() == b'M'))
+        .ok()
+        .flatten()
+        .map(|field| field.value().to_string())
+        .unwrap_or_else(|| "unknown error".to_string());
+    AnyError::msg(format!("Postgres error: {message}"))
+}
@@ -29,7 +29,7 @@ use dbsp::{
     circuit::{
         metadata::{OperatorLocation, OperatorMeta},
         operator_traits::{BinaryOperator, Operator},
-        Scope,
+        Checkpoint, Scope,
     },
     operator::FilterMap,
     time::AntichainRef,
@@ -439,6 +439,9 @@ struct HashedKVBatch<K, V, R, O = usize> {
     values: ColumnLayer<V, R>,
 }
 
+// Checkpoints of circuits that contain this batch type are not supported.
+impl<K, V, R, O> Checkpoint for HashedKVBatch<K, V, R, O> {}
+
 impl<K, V, R, O> HashedKVBatch<K, V, R, O> {
     fn probe(&self) -> HashedKVBatchProbe<'_, K, V, R, O> {
         HashedKVBatchProbe::new(self)
@@ -0,0 +1,259 @@
+//! Checkpoints of the circuit state.
+//!
+//! A checkpoint contains the serialized state of all operators that carry
+//! state across clock cycles of the root circuit (see
+//! [`Operator::checkpoint`]).  Restoring a checkpoint into a freshly
+//! constructed instance of the same circuit allows the computation to resume
+//! from the clock cycle where the checkpoint was taken.
+//!
+//! Values stored by operators are serialized using the [`Checkpoint`] trait.
+
+use crate::{
+    circuit::{operator_traits::Operator, GlobalNodeId},
+    Error as DBSPError,
+};
+use bincode::{
+    config::{standard, Configuration},
+    de::Decoder,
+    enc::Encoder,
+    error::{DecodeError, EncodeError},
+    Decode, Encode,
+};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt::{Display, Error as FmtError, Formatter},
+};
+
+/// `bincode` configuration used to serialize operator state.
+const BINCODE_CONFIG: Configuration = standard();
+
+/// Checkpoint errors.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to serialize the state of an operator.
+    Encode(EncodeError),
+    /// Failed to deserialize the state of an operator.
+    Decode(DecodeError),
+    /// The state of node `node_id` was written by a different operator, i.e.,
+    /// the checkpoint was taken from a different circuit.
+    OperatorMismatch {
+        node_id: GlobalNodeId,
+        expected: String,
+        found: Cow<'static, str>,
+    },
+    /// The checkpoint was written by a circuit with a different number of
+    /// workers.
+    WorkerMismatch { expected: usize, found: usize },
+    /// The operator does not support saving its state to or restoring it
+    /// from a checkpoint.
+    RestoreNotSupported { operator: Cow<'static, str> },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        match self {
+            Self::Encode(error) => write!(f, "error serializing operator state: {error}"),
+            Self::Decode(error) => write!(f, "error deserializing operator state: {error}"),
+            Self::OperatorMismatch {
+                node_id,
+                expected,
+                found,
+            } => write!(
+                f,
+                "checkpointed state of node '{node_id}' belongs to operator '{expected}', but the node contains operator '{found}'"
+            ),
+            Self::WorkerMismatch { expected, found } => write!(
+                f,
+                "checkpoint was written by a circuit with {expected} workers, but the circuit has {found} workers"
+            ),
+            Self::RestoreNotSupported { operator } => {
+                write!(f, "operator '{operator}' does not support checkpoints")
+            }
+        }
+    }
+}
+
+impl From<EncodeError> for Error {
+    fn from(error: EncodeError) -> Self {
+        Self::Encode(error)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+/// Values that can be stored in a checkpoint.
+///
+/// Implemented for all types that implement `bincode`'s [`Encode`] and
+/// [`Decode`] traits.  Other types, e.g., custom batch types that have no
+/// serialized representation, can implement this trait with the default
+/// methods, which fail.  Checkpointing a circuit that stores such values
+/// returns an error instead of silently losing them.
+pub trait Checkpoint: Sized {
+    /// Serialize `self`.
+    fn encode_checkpoint<E: Encoder>(&self, _encoder: &mut E) -> Result<(), EncodeError> {
+        Err(EncodeError::Other("type does not support checkpoints"))
+    }
+
+    /// Deserialize a value serialized with
+    /// [`encode_checkpoint`](`Self::encode_checkpoint`).
+    fn decode_checkpoint<D: Decoder>(_decoder: &mut D) -> Result<Self, DecodeError> {
+        Err(DecodeError::Other("type does not support checkpoints"))
+    }
+}
+
+impl<T> Checkpoint for T
+where
+    T: Encode + Decode,
+{
+    fn encode_checkpoint<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.encode(encoder)
+    }
+
+    fn decode_checkpoint<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        T::decode(decoder)
+    }
+}
+
+/// Serializes a reference to a [`Checkpoint`] value as part of a larger
+/// [`Encode`] value, e.g., a tuple passed to [`encode_state`].
+pub(crate) struct EncodeCheckpoint<'a, T>(pub(crate) &'a T);
+
+impl<T> Encode for EncodeCheckpoint<'_, T>
+where
+    T: Checkpoint,
+{
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.0.encode_checkpoint(encoder)
+    }
+}
+
+/// Deserializes a [`Checkpoint`] value as part of a larger [`Decode`] value,
+/// e.g., a tuple returned by [`decode_state`].
+pub(crate) struct DecodeCheckpoint<T>(pub(crate) T);
+
+impl<T> Decode for DecodeCheckpoint<T>
+where
+    T: Checkpoint,
+{
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        T::decode_checkpoint(decoder).map(Self)
+    }
+}
+
+/// Serializes a slice of [`Checkpoint`] values as part of a larger [`Encode`]
+/// value.
+pub(crate) struct EncodeCheckpointSlice<'a, T>(pub(crate) &'a [T]);
+
+impl<T> Encode for EncodeCheckpointSlice<'_, T>
+where
+    T: Checkpoint,
+{
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.0.len().encode(encoder)?;
+        for value in self.0.iter() {
+            value.encode_checkpoint(encoder)?;
+        }
+        Ok(())
+    }
+}
+
+/// Deserializes a vector of [`Checkpoint`] values serialized with
+/// [`EncodeCheckpointSlice`].
+pub(crate) struct DecodeCheckpointVec<T>(pub(crate) Vec<T>);
+
+impl<T> Decode for DecodeCheckpointVec<T>
+where
+    T: Checkpoint,
+{
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let len = usize::decode(decoder)?;
+        let mut values = Vec::new();
+        for _ in 0..len {
+            values.push(T::decode_checkpoint(decoder)?);
+        }
+        Ok(Self(values))
+    }
+}
+
+/// Serialize `value`, e.g., the state of an operator.
+pub(crate) fn encode_state<T>(value: &T) -> Result<Vec<u8>, DBSPError>
+where
+    T: Encode,
+{
+    let mut buffer = Vec::new();
+    bincode::encode_into_std_write(value, &mut buffer, BINCODE_CONFIG).map_err(Error::from)?;
+    Ok(buffer)
+}
+
+/// Deserialize a value serialized with [`encode_state`].
+pub(crate) fn decode_state<T>(state: &[u8]) -> Result<T, DBSPError>
+where
+    T: Decode,
+{
+    let (value, len) = bincode::decode_from_slice(state, BINCODE_CONFIG).map_err(Error::from)?;
+    if len != state.len() {
+        return Err(Error::Decode(DecodeError::OtherString(format!(
+            "{} trailing bytes after the end of serialized state",
+            state.len() - len
+        )))
+        .into());
+    }
+    Ok(value)
+}
+
+/// Serialized state of the operators in a circuit and its subcircuits.
+#[derive(Default, Encode, Decode)]
+pub struct CircuitState {
+    // Operator name and serialized state indexed by global node id.
+    nodes: BTreeMap<String, (String, Vec<u8>)>,
+}
+
+impl CircuitState {
+    /// Add the state of `operator` located at node `node_id` to `self`.
+    pub(crate) fn checkpoint_operator<Op>(
+        &mut self,
+        node_id: &GlobalNodeId,
+        operator: &Op,
+    ) -> Result<(), DBSPError>
+    where
+        Op: Operator,
+    {
+        if let Some(state) = operator.checkpoint()? {
+            self.nodes
+                .insert(node_id.to_string(), (operator.name().into_owned(), state));
+        }
+        Ok(())
+    }
+
+    /// Restore the state of `operator` located at node `node_id` from `self`.
+    ///
+    /// Leaves the operator in its initial state if `self` doesn't contain
+    /// any state for the node.
+    pub(crate) fn restore_operator<Op>(
+        &self,
+        node_id: &GlobalNodeId,
+        operator: &mut Op,
+    ) -> Result<(), DBSPError>
+    where
+        Op: Operator,
+    {
+        if let Some((name, state)) = self.nodes.get(&node_id.to_string()) {
+            let found = operator.name();
+            if name != &found {
+                return Err(Error::OperatorMismatch {
+                    node_id: node_id.clone(),
+                    expected: name.clone(),
+                    found,
+                }
+                .into());
+            }
+            operator.restore(state)?;
+        }
+        Ok(())
+    }
+}
@@ -29,6 +29,7 @@
 use crate::{
     circuit::{
         cache::{CircuitCache, CircuitStoreMarker},
+        checkpoint::{decode_state, encode_state, CircuitState},
         metadata::OperatorMeta,
         operator_traits::{
             BinaryOperator, Data, ImportOperator, NaryOperator, QuaternaryOperator, SinkOperator,
@@ -43,7 +44,7 @@ use crate::{
     circuit_cache_key,
     operator::communication::Exchange,
     time::{Timestamp, UnitTimestamp},
-    Runtime,
+    Error as DBSPError, Runtime,
 };
 use std::{
     borrow::Cow,
@@ -333,6 +334,16 @@ pub trait Node {
 
     fn fixedpoint(&self, scope: Scope) -> bool;
 
+    /// Add the state of the operator to `state` (see
+    /// [`Operator::checkpoint`](super::operator_traits::Operator::checkpoint)).
+    /// A subcircuit adds the state of all of its nodes.
+    fn checkpoint(&self, state: &mut CircuitState) -> Result<(), DBSPError>;
+
+    /// Restore the state of the operator from `state` (see
+    /// [`Operator::restore`](super::operator_traits::Operator::restore)).
+    /// A subcircuit restores the state of all of its nodes.
+    fn restore(&mut self, state: &CircuitState) -> Result<(), DBSPError>;
+
     fn map_nodes_recursive(&self, _f: &mut dyn FnMut(&dyn Node)) {}
 }
 
@@ -1608,6 +1619,23 @@ where
         Ok(res)
     }
 
+    /// Add the state of all nodes in `self` and its children to `state`.
+    pub(crate) fn checkpoint_nodes(&self, state: &mut CircuitState) -> Result<(), DBSPError> {
+        for node in self.inner().nodes.iter() {
+            node.checkpoint(state)?;
+        }
+        Ok(())
+    }
+
+    /// Restore the state of all nodes in `self` and its children from
+    /// `state`.
+    pub(crate) fn restore_nodes(&self, state: &CircuitState) -> Result<(), DBSPError> {
+        for node in self.inner_mut().nodes.iter_mut() {
+            node.restore(state)?;
+        }
+        Ok(())
+    }
+
     /// Recursively apply `f` to all nodes in `self` and its children.
     pub(crate) fn map_nodes_recursive(&self, f: &mut dyn FnMut(&dyn Node)) {
         for node in self.inner().nodes.iter() {
@@ -2579,6 +2607,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self, state: &mut CircuitState) -> Result<(), DBSPError> {
+        state.checkpoint_operator(&self.id, &self.operator)
+    }
+
+    fn restore(&mut self, state: &CircuitState) -> Result<(), DBSPError> {
+        state.restore_operator(&self.id, &mut self.operator)
+    }
 }
 
 struct SourceNode<C, O, Op> {
@@ -2655,6 +2691,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self, state: &mut CircuitState) -> Result<(), DBSPError> {
+        state.checkpoint_operator(&self.id, &self.operator)
+    }
+
+    fn restore(&mut self, state: &CircuitState) -> Result<(), DBSPError> {
+        state.restore_operator(&self.id, &mut self.operator)
+    }
 }
 
 struct UnaryNode<C, I, O, Op> {
@@ -2737,6 +2781,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self, state: &mut CircuitState) -> Result<(), DBSPError> {
+        state.checkpoint_operator(&self.id, &self.operator)
+    }
+
+    fn restore(&mut self, state: &CircuitState) -> Result<(), DBSPError> {
+        state.restore_operator(&self.id, &mut self.operator)
+    }
 }
 
 struct SinkNode<C, I, Op> {
@@ -2812,6 +2864,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self, state: &mut CircuitState) -> Result<(), DBSPError> {
+        state.checkpoint_operator(&self.id, &self.operator)
+    }
+
+    fn restore(&mut self, state: &CircuitState) -> Result<(), DBSPError> {
+        state.restore_operator(&self.id, &mut self.operator)
+    }
 }
 
 struct BinaryNode<C, I1, I2, O, Op> {
@@ -2927,6 +2987,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self, state: &mut CircuitState) -> Result<(), DBSPError> {
+        state.checkpoint_operator(&self.id, &self.operator)
+    }
+
+    fn restore(&mut self, state: &CircuitState) -> Result<(), DBSPError> {
+        state.restore_operator(&self.id, &mut self.operator)
+    }
 }
 
 struct TernaryNode<C, I1, I2, I3, O, Op> {
@@ -3046,6 +3114,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self, state: &mut CircuitState) -> Result<(), DBSPError> {
+        state.checkpoint_operator(&self.id, &self.operator)
+    }
+
+    fn restore(&mut self, state: &CircuitState) -> Result<(), DBSPError> {
+        state.restore_operator(&self.id, &mut self.operator)
+    }
 }
 
 struct QuaternaryNode<C, I1, I2, I3, I4, O, Op> {
@@ -3183,6 +3259,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self, state: &mut CircuitState) -> Result<(), DBSPError> {
+        state.checkpoint_operator(&self.id, &self.operator)
+    }
+
+    fn restore(&mut self, state: &CircuitState) -> Result<(), DBSPError> {
+        state.restore_operator(&self.id, &mut self.operator)
+    }
 }
 
 struct NaryNode<C, I, O, Op>
@@ -3305,6 +3389,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self, state: &mut CircuitState) -> Result<(), DBSPError> {
+        state.checkpoint_operator(&self.id, &self.operator)
+    }
+
+    fn restore(&mut self, state: &CircuitState) -> Result<(), DBSPError> {
+        state.restore_operator(&self.id, &mut self.operator)
+    }
 }
 
 // The output half of a feedback node.  We implement a feedback node using a
@@ -3402,6 +3494,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         unsafe { (*self.operator.get()).fixedpoint(scope) }
     }
+
+    fn checkpoint(&self, state: &mut CircuitState) -> Result<(), DBSPError> {
+        state.checkpoint_operator(&self.id, unsafe { &*self.operator.get() })
+    }
+
+    fn restore(&mut self, state: &CircuitState) -> Result<(), DBSPError> {
+        state.restore_operator(&self.id, unsafe { &mut *self.operator.get() })
+    }
 }
 
 /// The input half of a feedback node
@@ -3478,6 +3578,16 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         unsafe { (*self.operator.get()).fixedpoint(scope) }
     }
+
+    // The operator is shared with `FeedbackOutputNode`, which checkpoints and
+    // restores its state.
+    fn checkpoint(&self, _state: &mut CircuitState) -> Result<(), DBSPError> {
+        Ok(())
+    }
+
+    fn restore(&mut self, _state: &CircuitState) -> Result<(), DBSPError> {
+        Ok(())
+    }
 }
 
 /// Input connector of a feedback operator.
@@ -3618,6 +3728,14 @@ where
         self.circuit.inner().fixedpoint(scope + 1)
     }
 
+    fn checkpoint(&self, state: &mut CircuitState) -> Result<(), DBSPError> {
+        self.circuit.checkpoint_nodes(state)
+    }
+
+    fn restore(&mut self, state: &CircuitState) -> Result<(), DBSPError> {
+        self.circuit.restore_nodes(state)
+    }
+
     fn map_nodes_recursive(&self, f: &mut dyn FnMut(&dyn Node)) {
         self.circuit.map_nodes_recursive(f);
     }
@@ -3662,6 +3780,25 @@ impl CircuitHandle {
         self.executor.run(&self.circuit)
     }
 
+    /// Serialize the state of all operators in the circuit.
+    ///
+    /// Must be invoked between clock cycles.  See
+    /// [`DBSPHandle::checkpoint`](`crate::DBSPHandle::checkpoint`).
+    pub fn checkpoint(&self) -> Result<Vec<u8>, DBSPError> {
+        let mut state = CircuitState::default();
+        self.circuit.checkpoint_nodes(&mut state)?;
+        encode_state(&state)
+    }
+
+    /// Restore the state of all operators in the circuit from `state` returned
+    /// by [`Self::checkpoint`].
+    ///
+    /// Must be invoked before the first clock cycle.
+    pub fn restore(&self, state: &[u8]) -> Result<(), DBSPError> {
+        let state: CircuitState = decode_state(state)?;
+        self.circuit.restore_nodes(&state)
+    }
+
     /// Attach a scheduler event handler to the circuit.
     ///
     /// This method is identical to
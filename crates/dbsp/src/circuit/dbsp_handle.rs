@@ -1,12 +1,17 @@
 use crate::{
-    circuit::runtime::RuntimeHandle, profile::Profiler, Error as DBSPError, RootCircuit, Runtime,
-    RuntimeError, SchedulerError,
+    circuit::{
+        checkpoint::{decode_state, encode_state},
+        runtime::RuntimeHandle,
+    },
+    profile::Profiler,
+    CheckpointError, Error as DBSPError, RootCircuit, Runtime, RuntimeError,
 };
 use crossbeam::channel::{bounded, Receiver, Sender, TryRecvError};
 use std::{
     fs,
     fs::create_dir_all,
     path::{Path, PathBuf},
+    sync::Arc,
     thread::Result as ThreadResult,
     time::Instant,
 };
@@ -86,7 +91,10 @@ impl Runtime {
                 match command_receiver.try_recv() {
                     Ok(Command::Step) => {
                         //moregc = true;
-                        let status = circuit.step().map(|_| Response::Unit);
+                        let status = circuit
+                            .step()
+                            .map(|_| Response::Unit)
+                            .map_err(DBSPError::from);
                         // Send response.
                         if status_sender.send(status).is_err() {
                             return;
@@ -107,6 +115,20 @@ impl Runtime {
                             return;
                         }
                     }
+                    Ok(Command::Checkpoint) => {
+                        let status = circuit.checkpoint().map(Response::Checkpoint);
+                        if status_sender.send(status).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(Command::Restore(states)) => {
+                        let status = circuit
+                            .restore(&states[worker_index])
+                            .map(|_| Response::Unit);
+                        if status_sender.send(status).is_err() {
+                            return;
+                        }
+                    }
                     // Nothing to do: do some housekeeping and relinquish the CPU if there's none
                     // left.
                     Err(TryRecvError::Empty) => {
@@ -163,11 +185,15 @@ enum Command {
     Step,
     EnableProfiler,
     DumpProfile,
+    Checkpoint,
+    // Serialized circuit state of each worker.
+    Restore(Arc<Vec<Vec<u8>>>),
 }
 
 enum Response {
     Unit,
     Profile(String),
+    Checkpoint(Vec<u8>),
 }
 
 /// A handle to control the execution of a circuit in a multithreaded runtime.
@@ -180,14 +206,14 @@ pub struct DBSPHandle {
     command_senders: Vec<Sender<Command>>,
     // Channels used to receive command completion status from
     // workers.
-    status_receivers: Vec<Receiver<Result<Response, SchedulerError>>>,
+    status_receivers: Vec<Receiver<Result<Response, DBSPError>>>,
 }
 
 impl DBSPHandle {
     fn new(
         runtime: RuntimeHandle,
         command_senders: Vec<Sender<Command>>,
-        status_receivers: Vec<Receiver<Result<Response, SchedulerError>>>,
+        status_receivers: Vec<Receiver<Result<Response, DBSPError>>>,
     ) -> Self {
         Self {
             start_time: Instant::now(),
@@ -229,7 +255,7 @@ impl DBSPHandle {
                 }
                 Ok(Err(e)) => {
                     let _ = self.kill_inner();
-                    return Err(DBSPError::Scheduler(e));
+                    return Err(e);
                 }
                 Ok(Ok(resp)) => handler(resp),
             }
@@ -285,6 +311,54 @@ impl DBSPHandle {
         Ok(dir_path)
     }
 
+    /// Write a checkpoint of the circuit state to `path`.
+    ///
+    /// The checkpoint contains the state of all operators that carry state
+    /// across clock cycles, such as integrals and traces (see
+    /// [`Operator::checkpoint`](`crate::circuit::operator_traits::Operator::checkpoint`)).
+    /// Use [`Self::restore`] to load the checkpoint into a new instance of the
+    /// same circuit running with the same number of workers in order to
+    /// resume the computation after the last clock cycle completed before the
+    /// checkpoint.
+    ///
+    /// The checkpoint is written to a temporary file, which is then renamed
+    /// to `path`, so `path` never contains a partially written checkpoint.
+    pub fn checkpoint<P: AsRef<Path>>(&mut self, path: P) -> Result<(), DBSPError> {
+        let mut states = Vec::with_capacity(self.num_workers());
+
+        self.broadcast_command(Command::Checkpoint, |resp| {
+            if let Response::Checkpoint(state) = resp {
+                states.push(state);
+            }
+        })?;
+
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+
+        fs::write(&tmp_path, encode_state(&states)?)?;
+        fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+
+    /// Restore the circuit state from a checkpoint written by
+    /// [`Self::checkpoint`].
+    ///
+    /// Must be invoked before the first clock cycle of the circuit.
+    pub fn restore<P: AsRef<Path>>(&mut self, path: P) -> Result<(), DBSPError> {
+        let states: Vec<Vec<u8>> = decode_state(&fs::read(path)?)?;
+        if states.len() != self.num_workers() {
+            return Err(CheckpointError::WorkerMismatch {
+                expected: states.len(),
+                found: self.num_workers(),
+            }
+            .into());
+        }
+
+        self.broadcast_command(Command::Restore(Arc::new(states)), |_| {})
+    }
+
     /// Terminate the execution of the circuit, exiting all worker threads.
     ///
     /// If one or more of the worker threads panics, returns the argument the
@@ -312,7 +386,11 @@ impl Drop for DBSPHandle {
 
 #[cfg(test)]
 mod tests {
-    use crate::{operator::Generator, Circuit, Error as DBSPError, Runtime, RuntimeError};
+    use crate::{
+        operator::{CollectionHandle, FilterMap, Generator, OutputHandle},
+        CheckpointError, Circuit, DBSPHandle, Error as DBSPError, OrdZSet, Runtime, RuntimeError,
+    };
+    use tempfile::TempDir;
 
     // Panic during initialization in worker thread.
     #[test]
@@ -419,4 +497,120 @@ mod tests {
 
         handle.step().unwrap();
     }
+
+    type TestHandles = (
+        CollectionHandle<u64, isize>,
+        OutputHandle<OrdZSet<u64, isize>>,
+        OutputHandle<OrdZSet<u64, isize>>,
+    );
+
+    // Circuit with both `Z1` (`integrate`) and trace (`distinct`) state.
+    fn stateful_circuit(nworkers: usize) -> (DBSPHandle, TestHandles) {
+        Runtime::init_circuit(nworkers, |circuit| {
+            let (input, input_handle) = circuit.add_input_zset::<u64, isize>();
+            let integral = input.integrate().output();
+            let distinct = input.map(|x| x % 5).distinct().output();
+            (input_handle, integral, distinct)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_checkpoint_restore1() {
+        test_checkpoint_restore(1);
+    }
+
+    #[test]
+    fn test_checkpoint_restore4() {
+        test_checkpoint_restore(4);
+    }
+
+    fn test_checkpoint_restore(nworkers: usize) {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("checkpoint");
+
+        // Reference run without a checkpoint.
+        let (mut reference, (reference_input, reference_integral, reference_distinct)) =
+            stateful_circuit(nworkers);
+        let (mut handle, (input, _, _)) = stateful_circuit(nworkers);
+
+        for step in 0..5u64 {
+            for x in step * 10..step * 10 + 10 {
+                reference_input.push(x, 1);
+                input.push(x, 1);
+            }
+            reference.step().unwrap();
+            handle.step().unwrap();
+        }
+
+        handle.checkpoint(&path).unwrap();
+        handle.kill().unwrap();
+
+        // Resume the computation in a new instance of the circuit.
+        let (mut restored, (restored_input, restored_integral, restored_distinct)) =
+            stateful_circuit(nworkers);
+        restored.restore(&path).unwrap();
+
+        for step in 5..10u64 {
+            for x in step * 10 - 20..step * 10 {
+                reference_input.push(x, -1);
+                restored_input.push(x, -1);
+            }
+            reference.step().unwrap();
+            restored.step().unwrap();
+
+            assert_eq!(
+                reference_integral.consolidate(),
+                restored_integral.consolidate()
+            );
+            assert_eq!(
+                reference_distinct.consolidate(),
+                restored_distinct.consolidate()
+            );
+        }
+    }
+
+    #[test]
+    fn test_restore_worker_mismatch() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("checkpoint");
+
+        let (mut handle, _) = stateful_circuit(2);
+        handle.step().unwrap();
+        handle.checkpoint(&path).unwrap();
+
+        let (mut restored, _) = stateful_circuit(4);
+        let err = restored.restore(&path).unwrap_err();
+
+        assert!(matches!(
+            err,
+            DBSPError::Checkpoint(CheckpointError::WorkerMismatch {
+                expected: 2,
+                found: 4
+            })
+        ));
+    }
+
+    // Operators that don't implement `Operator::checkpoint` fail the checkpoint
+    // instead of losing their state.
+    #[test]
+    fn test_checkpoint_unsupported() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("checkpoint");
+
+        let (mut handle, _) = Runtime::init_circuit(2, |circuit| {
+            let mut n = 0usize;
+            circuit.add_source(Generator::new(move || {
+                n += 1;
+                n
+            }));
+        })
+        .unwrap();
+        handle.step().unwrap();
+
+        assert!(matches!(
+            handle.checkpoint(&path).unwrap_err(),
+            DBSPError::Checkpoint(CheckpointError::RestoreNotSupported { .. })
+        ));
+    }
 }
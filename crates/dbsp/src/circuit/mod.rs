@@ -6,6 +6,7 @@
 //! streams and emitting a single value to the output stream.
 
 mod activations;
+pub(crate) mod checkpoint;
 mod dbsp_handle;
 
 pub(crate) mod runtime;
@@ -19,6 +20,7 @@ pub mod schedule;
 pub mod trace;
 
 pub use activations::{Activations, Activator};
+pub use checkpoint::{Checkpoint, CircuitState, Error as CheckpointError};
 pub use circuit_builder::{
     ChildCircuit, Circuit, CircuitHandle, ExportId, ExportStream, FeedbackConnector, GlobalNodeId,
     NodeId, OwnershipPreference, RootCircuit, Scope, Stream, WithClock,
@@ -3,9 +3,12 @@
 //! Operators are the building blocks of DBSP circuits.  An operator
 //! consumes one or more input streams and produces an output stream.
 
-use crate::circuit::{
-    metadata::{OperatorLocation, OperatorMeta},
-    OwnershipPreference, Scope,
+use crate::{
+    circuit::{
+        metadata::{OperatorLocation, OperatorMeta},
+        CheckpointError, OwnershipPreference, Scope,
+    },
+    Error,
 };
 use std::borrow::Cow;
 
@@ -194,6 +197,39 @@ pub trait Operator: 'static {
     /// of the fixed point computation, but not as part of an integrator circuit
     /// ([`Stream::integrate`](`crate::circuit::Stream::integrate`)).
     fn fixedpoint(&self, scope: Scope) -> bool;
+
+    /// Serialize the state of the operator that persists across clock cycles
+    /// of the root circuit.
+    ///
+    /// This method is invoked between clock cycles of the root circuit to
+    /// write a checkpoint of the circuit state (see
+    /// [`DBSPHandle::checkpoint`](`crate::DBSPHandle::checkpoint`)).
+    /// Operators that carry state from one clock cycle to the next, e.g.,
+    /// [`Z1`](`crate::operator::Z1`), return the serialized state, which is
+    /// later passed to [`restore`](`Self::restore`).  Operators without such
+    /// state return `None`.
+    ///
+    /// The default implementation fails, so that checkpointing a circuit
+    /// with an operator that doesn't implement this method returns an error
+    /// instead of silently dropping the state of the operator.
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Err(CheckpointError::RestoreNotSupported {
+            operator: self.name(),
+        }
+        .into())
+    }
+
+    /// Restore the state of the operator from `state` returned by
+    /// [`checkpoint`](`Self::checkpoint`).
+    ///
+    /// This method is invoked on a freshly constructed circuit before its
+    /// first clock cycle.
+    fn restore(&mut self, _state: &[u8]) -> Result<(), Error> {
+        Err(CheckpointError::RestoreNotSupported {
+            operator: self.name(),
+        }
+        .into())
+    }
 }
 
 /// A source operator that injects data from the outside world or from the
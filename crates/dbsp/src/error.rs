@@ -1,4 +1,4 @@
-use crate::{RuntimeError, SchedulerError};
+use crate::{CheckpointError, RuntimeError, SchedulerError};
 use std::{
     fmt::{Display, Error as FmtError, Formatter},
     io::Error as IOError,
@@ -8,6 +8,7 @@ use std::{
 pub enum Error {
     Scheduler(SchedulerError),
     Runtime(RuntimeError),
+    Checkpoint(CheckpointError),
     IO(IOError),
     Custom(String),
 }
@@ -21,6 +22,9 @@ impl Display for Error {
             Self::Runtime(error) => {
                 write!(f, "runtime error: '{error}'")
             }
+            Self::Checkpoint(error) => {
+                write!(f, "checkpoint error: '{error}'")
+            }
             Self::IO(error) => {
                 write!(f, "IO error: '{error}'")
             }
@@ -47,6 +51,12 @@ impl From<RuntimeError> for Error {
     }
 }
 
+impl From<CheckpointError> for Error {
+    fn from(error: CheckpointError) -> Self {
+        Self::Checkpoint(error)
+    }
+}
+
 impl From<String> for Error {
     fn from(error: String) -> Self {
         Self::Custom(error)
@@ -22,7 +22,7 @@ use crate::{
         cursor::{Cursor, CursorGroup},
         Batch, BatchReader, Builder, Spine,
     },
-    DBData, DBTimestamp, DBWeight, OrdIndexedZSet, OrdZSet,
+    DBData, DBTimestamp, DBWeight, Error, OrdIndexedZSet, OrdZSet,
 };
 
 // Some standard aggregators.
@@ -350,6 +350,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<Z, A, O> UnaryOperator<Z, O> for Aggregate<Z, A, O>
@@ -587,6 +591,10 @@ where
                 .keys()
                 .all(|ts| !ts.less_equal(&epoch_end))
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<Z, IT, A, Clk> BinaryOperator<Z, IT, Vec<(Z::Key, Option<A::Output>)>>
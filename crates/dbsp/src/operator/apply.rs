@@ -5,6 +5,7 @@ use crate::circuit::{
     operator_traits::{Data, Operator, UnaryOperator},
     Circuit, OwnershipPreference, Scope, Stream,
 };
+use crate::Error;
 use std::{borrow::Cow, panic::Location};
 
 impl<C, T1> Stream<C, T1>
@@ -124,6 +125,10 @@ where
         // parameterize the operator with custom fixed point check.
         unimplemented!();
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T1, T2, F> UnaryOperator<T1, T2> for Apply<F>
@@ -172,6 +177,10 @@ where
         // parameterize the operator with custom fixed point check.
         unimplemented!();
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T1, T2, F> UnaryOperator<T1, T2> for ApplyOwned<F>
@@ -236,6 +245,10 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         (self.fixpoint)(scope)
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<O, B, F, T1, T2> UnaryOperator<T1, T2> for ApplyCore<O, B, F>
@@ -5,6 +5,7 @@ use crate::circuit::{
     operator_traits::{BinaryOperator, Operator},
     Circuit, OwnershipPreference, Scope, Stream,
 };
+use crate::Error;
 use std::{borrow::Cow, panic::Location};
 
 impl<C, T1> Stream<C, T1>
@@ -73,6 +74,10 @@ where
         // parameterize the operator with custom fixed point check.
         unimplemented!();
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T1, T2, T3, F> BinaryOperator<T1, T2, T3> for Apply2<F>
@@ -117,6 +122,10 @@ where
         // parameterize the operator with custom fixed point check.
         unimplemented!();
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T1, T2, T3, F> BinaryOperator<T1, T2, T3> for Apply2Owned<F>
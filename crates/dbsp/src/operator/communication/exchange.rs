@@ -11,7 +11,7 @@ use crate::{
         operator_traits::{Operator, SinkOperator, SourceOperator},
         OwnershipPreference, Runtime, Scope,
     },
-    circuit_cache_key,
+    circuit_cache_key, Error,
 };
 use crossbeam_utils::CachePadded;
 use once_cell::sync::OnceCell;
@@ -441,6 +441,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<D, T, L> SinkOperator<D> for ExchangeSender<D, T, L>
@@ -540,6 +544,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<D, T, L> SourceOperator<D> for ExchangeReceiver<T, L>
@@ -6,7 +6,7 @@ use crate::{
     },
     circuit_cache_key,
     trace::{spine_fueled::Spine, Batch, Trace},
-    Circuit, Runtime, Stream,
+    Circuit, Error, Runtime, Stream,
 };
 use arc_swap::ArcSwap;
 use crossbeam::atomic::AtomicConsume;
@@ -266,6 +266,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T> SinkOperator<T> for GatherProducer<T>
@@ -325,6 +329,10 @@ impl<T: 'static> Operator for GatherConsumer<T> {
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T> SourceOperator<Spine<T>> for GatherConsumer<T>
@@ -375,6 +383,10 @@ impl<T: 'static> Operator for EmptyGatherConsumer<T> {
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T> SourceOperator<Spine<T>> for EmptyGatherConsumer<T>
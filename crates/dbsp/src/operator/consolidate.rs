@@ -9,6 +9,7 @@ use crate::{
     },
     circuit_cache_key,
     trace::{Batch, Trace},
+    Error,
 };
 
 circuit_cache_key!(ConsolidateId<C, D>(GlobalNodeId => Stream<C, D>));
@@ -73,6 +74,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T> UnaryOperator<T, T::Batch> for Consolidate<T>
@@ -6,6 +6,7 @@ use crate::{
         operator_traits::{Data, ImportOperator, Operator},
         Circuit, OwnershipPreference, Scope, Stream,
     },
+    Error,
 };
 use std::borrow::Cow;
 
@@ -114,6 +115,10 @@ where
             true
         }
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<D> ImportOperator<D, D> for Delta0<D>
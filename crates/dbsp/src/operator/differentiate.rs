@@ -2,7 +2,7 @@
 
 use crate::{
     algebra::GroupValue,
-    circuit::{Circuit, GlobalNodeId, Stream},
+    circuit::{Checkpoint, Circuit, GlobalNodeId, Stream},
     circuit_cache_key,
     operator::Minus,
     NumEntries,
@@ -15,7 +15,7 @@ circuit_cache_key!(NestedDifferentiateId<C, D>(GlobalNodeId => Stream<C, D>));
 impl<C, D> Stream<C, D>
 where
     C: Circuit + 'static,
-    D: SizeOf + NumEntries + GroupValue,
+    D: SizeOf + NumEntries + GroupValue + Checkpoint,
 {
     /// Stream differentiation.
     ///
@@ -9,7 +9,7 @@ use crate::{
     },
     circuit_cache_key,
     trace::{ord::OrdValSpine, Batch, BatchReader, Builder, Cursor as TraceCursor, Trace},
-    DBTimestamp, OrdIndexedZSet, Timestamp,
+    DBTimestamp, Error, OrdIndexedZSet, Timestamp,
 };
 use size_of::SizeOf;
 use std::{
@@ -134,6 +134,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<Z> UnaryOperator<Z, Z> for Distinct<Z>
@@ -185,6 +189,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<Z, I> BinaryOperator<Z, I, Z> for DistinctIncrementalTotal<Z, I>
@@ -547,6 +555,10 @@ where
                 .keys()
                 .all(|ts| !ts.less_equal(&epoch_end))
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<Z, T, Clk> BinaryOperator<Z, T, Z> for DistinctIncremental<Z, T, Clk>
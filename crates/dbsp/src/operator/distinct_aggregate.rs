@@ -0,0 +1,434 @@
+//! Aggregation over distinct values.
+
+use crate::{
+    algebra::{
+        AddAssignByRef, AddByRef, DefaultSemigroup, HasOne, HasZero, IndexedZSet, MonoidValue,
+        NegByRef, ZRingValue,
+    },
+    circuit::{
+        operator_traits::{BinaryOperator, Operator},
+        Scope, Stream,
+    },
+    operator::{Aggregator, Fold},
+    trace::{Batch, BatchReader, Cursor},
+    Circuit, DBData, Error, OrdIndexedZSet, OrdZSet, RootCircuit,
+};
+use std::{borrow::Cow, cmp::Ordering, marker::PhantomData};
+
+impl<B> Stream<RootCircuit, B>
+where
+    B: IndexedZSet + Send,
+    B::R: ZRingValue,
+{
+    /// Aggregate distinct values associated with each key in an indexed
+    /// Z-set.
+    ///
+    /// Applies `aggregator` to the set of values with positive weights
+    /// associated with each key, where each value has weight `1`, i.e., it is
+    /// equivalent to `self.distinct().aggregate(aggregator)`.  This is the
+    /// semantics of SQL aggregates with the `DISTINCT` modifier, e.g.,
+    /// `COUNT(DISTINCT x)`.
+    ///
+    /// This is an incremental operator: it transforms a stream of changes to
+    /// the input collection into a stream of changes to the aggregate of each
+    /// key.  Unlike the `distinct().aggregate()` chain, it maintains a single
+    /// trace of the input collection.  A key is only re-aggregated when the
+    /// current input batch adds a new distinct value to it or removes an
+    /// existing one.
+    #[allow(clippy::type_complexity)]
+    pub fn distinct_aggregate<A>(
+        &self,
+        aggregator: A,
+    ) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, A::Output, B::R>>
+    where
+        A: Aggregator<B::Val, (), B::R>,
+    {
+        self.distinct_aggregate_generic(aggregator)
+    }
+
+    /// Like [`Self::distinct_aggregate`], but can return any batch type.
+    pub fn distinct_aggregate_generic<A, O>(&self, aggregator: A) -> Stream<RootCircuit, O>
+    where
+        A: Aggregator<B::Val, (), B::R>,
+        O: IndexedZSet<Key = B::Key, Val = A::Output, R = B::R>,
+    {
+        let circuit = self.circuit();
+        let stream = self.shard();
+
+        circuit.region("distinct_aggregate", || {
+            circuit
+                .add_binary_operator(
+                    DistinctAggregate::new(aggregator),
+                    &stream,
+                    &stream.integrate_trace().delay_trace(),
+                )
+                .mark_sharded()
+        })
+    }
+
+    /// Count distinct values associated with each key in an indexed Z-set.
+    ///
+    /// Outputs the number of values with positive weights associated with
+    /// each key, i.e., `COUNT(DISTINCT x)` in SQL.  Keys without such
+    /// values are not included in the output.
+    #[allow(clippy::type_complexity)]
+    pub fn distinct_count(&self) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, B::R, B::R>> {
+        self.distinct_aggregate(<Fold<_, DefaultSemigroup<_>, _, _>>::new(
+            B::R::zero(),
+            |count: &mut B::R, _val: &B::Val, weight: B::R| count.add_assign_by_ref(&weight),
+        ))
+    }
+
+    /// Sum distinct values associated with each key in an indexed Z-set.
+    ///
+    /// Outputs the sum of `f(value)` over values with positive weights
+    /// associated with each key, where each value is counted once, i.e.,
+    /// `SUM(DISTINCT x)` in SQL.  Keys without such values are not included
+    /// in the output.
+    pub fn distinct_sum<F, A>(&self, f: F) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, A, B::R>>
+    where
+        F: Fn(&B::Val) -> A + Clone + 'static,
+        A: DBData + MonoidValue,
+    {
+        self.distinct_aggregate(<Fold<_, DefaultSemigroup<_>, _, _>>::new(
+            A::zero(),
+            move |sum: &mut A, val: &B::Val, _weight: B::R| sum.add_assign_by_ref(&f(val)),
+        ))
+    }
+}
+
+/// Incremental distinct aggregation operator that works in the root scope.
+///
+/// This is a binary operator with the following inputs:
+/// * `delta` - stream of changes to the input indexed Z-set.
+/// * `delayed_integral` - the integral of the input stream up to the
+///   previous clock cycle.
+///
+/// For each key in `delta`, the operator first checks whether `delta` changes
+/// the set of values with positive weights associated with the key.  If so,
+/// it merges the values of the key in `delta` and `delayed_integral` to
+/// compute the old and the new distinct sets, aggregates both, and outputs a
+/// retraction of the old aggregate and an insertion of the new one.
+struct DistinctAggregate<Z, I, A, O> {
+    aggregator: A,
+    _type: PhantomData<(Z, I, O)>,
+}
+
+impl<Z, I, A, O> DistinctAggregate<Z, I, A, O> {
+    fn new(aggregator: A) -> Self {
+        Self {
+            aggregator,
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<Z, I, A, O> Operator for DistinctAggregate<Z, I, A, O>
+where
+    Z: 'static,
+    I: 'static,
+    A: 'static,
+    O: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("DistinctAggregate")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+}
+
+impl<Z, I, A, O> DistinctAggregate<Z, I, A, O>
+where
+    Z: IndexedZSet,
+    Z::R: ZRingValue,
+    I: BatchReader<Key = Z::Key, Val = Z::Val, Time = (), R = Z::R>,
+    A: Aggregator<Z::Val, (), Z::R>,
+{
+    /// Returns `true` if the current key of `delta_cursor` adds or removes
+    /// a distinct value.
+    ///
+    /// `integral_cursor` points to the same key in the delayed integral or is
+    /// `None` if the key does not occur there.
+    fn distinct_changed<DC, IC>(delta_cursor: &mut DC, mut integral_cursor: Option<&mut IC>) -> bool
+    where
+        DC: Cursor<Z::Key, Z::Val, (), Z::R>,
+        IC: Cursor<Z::Key, Z::Val, (), Z::R>,
+    {
+        while delta_cursor.val_valid() {
+            let mut old_weight = Z::R::zero();
+            if let Some(integral_cursor) = integral_cursor.as_deref_mut() {
+                integral_cursor.seek_val(delta_cursor.val());
+                if integral_cursor.val_valid() && integral_cursor.val() == delta_cursor.val() {
+                    old_weight = integral_cursor.weight();
+                }
+            }
+            let new_weight = old_weight.add_by_ref(&delta_cursor.weight());
+
+            if old_weight.le0() != new_weight.le0() {
+                return true;
+            }
+            delta_cursor.step_val();
+        }
+
+        false
+    }
+
+    /// Compute the old and the new aggregates of the current key of
+    /// `delta_cursor`.
+    ///
+    /// `integral_cursor` points to the same key in the delayed integral or is
+    /// `None` if the key does not occur there.
+    fn eval_key<DC, IC>(
+        &self,
+        delta_cursor: &mut DC,
+        mut integral_cursor: Option<&mut IC>,
+    ) -> (Option<A::Output>, Option<A::Output>)
+    where
+        DC: Cursor<Z::Key, Z::Val, (), Z::R>,
+        IC: Cursor<Z::Key, Z::Val, (), Z::R>,
+    {
+        delta_cursor.rewind_vals();
+        if let Some(integral_cursor) = integral_cursor.as_deref_mut() {
+            integral_cursor.rewind_vals();
+        }
+
+        let mut old_vals = Vec::new();
+        let mut new_vals = Vec::new();
+
+        loop {
+            let delta_valid = delta_cursor.val_valid();
+            let integral_valid = integral_cursor
+                .as_ref()
+                .map_or(false, |cursor| cursor.val_valid());
+
+            // `Less` - next value only occurs in delta, `Greater` - only in the
+            // integral, `Equal` - in both.
+            let ordering = match (delta_valid, integral_valid) {
+                (false, false) => break,
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                (true, true) => delta_cursor
+                    .val()
+                    .cmp(integral_cursor.as_ref().unwrap().val()),
+            };
+
+            let (val, delta_weight) = if ordering != Ordering::Greater {
+                (delta_cursor.val().clone(), delta_cursor.weight())
+            } else {
+                let integral_cursor = integral_cursor.as_deref_mut().unwrap();
+                (integral_cursor.val().clone(), Z::R::zero())
+            };
+            let old_weight = if ordering != Ordering::Less {
+                integral_cursor.as_deref_mut().unwrap().weight()
+            } else {
+                Z::R::zero()
+            };
+            let new_weight = old_weight.add_by_ref(&delta_weight);
+
+            if !old_weight.le0() {
+                old_vals.push((val.clone(), Z::R::one()));
+            }
+            if !new_weight.le0() {
+                new_vals.push((val, Z::R::one()));
+            }
+
+            if ordering != Ordering::Greater {
+                delta_cursor.step_val();
+            }
+            if ordering != Ordering::Less {
+                integral_cursor.as_deref_mut().unwrap().step_val();
+            }
+        }
+
+        (self.aggregate(old_vals), self.aggregate(new_vals))
+    }
+
+    fn aggregate(&self, vals: Vec<(Z::Val, Z::R)>) -> Option<A::Output> {
+        let vals = <OrdZSet<Z::Val, Z::R>>::from_keys((), vals);
+        self.aggregator.aggregate_and_finalize(&mut vals.cursor())
+    }
+}
+
+impl<Z, I, A, O> BinaryOperator<Z, I, O> for DistinctAggregate<Z, I, A, O>
+where
+    Z: IndexedZSet,
+    Z::R: ZRingValue,
+    I: BatchReader<Key = Z::Key, Val = Z::Val, Time = (), R = Z::R>,
+    A: Aggregator<Z::Val, (), Z::R>,
+    O: IndexedZSet<Key = Z::Key, Val = A::Output, R = Z::R>,
+{
+    fn eval(&mut self, delta: &Z, delayed_integral: &I) -> O {
+        let mut tuples = Vec::new();
+        let mut delta_cursor = delta.cursor();
+        let mut integral_cursor = delayed_integral.cursor();
+
+        while delta_cursor.key_valid() {
+            integral_cursor.seek_key(delta_cursor.key());
+            let in_integral =
+                integral_cursor.key_valid() && integral_cursor.key() == delta_cursor.key();
+
+            if Self::distinct_changed(
+                &mut delta_cursor,
+                in_integral.then_some(&mut integral_cursor),
+            ) {
+                let (old, new) = self.eval_key(
+                    &mut delta_cursor,
+                    in_integral.then_some(&mut integral_cursor),
+                );
+
+                if old != new {
+                    if let Some(old) = old {
+                        tuples.push((
+                            O::item_from(delta_cursor.key().clone(), old),
+                            Z::R::one().neg_by_ref(),
+                        ));
+                    }
+                    if let Some(new) = new {
+                        tuples.push((O::item_from(delta_cursor.key().clone(), new), Z::R::one()));
+                    }
+                }
+            }
+
+            delta_cursor.step_key();
+        }
+
+        O::from_tuples((), tuples)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        algebra::DefaultSemigroup,
+        indexed_zset,
+        operator::{CollectionHandle, Fold, Max},
+        DBSPHandle, Runtime,
+    };
+    use proptest::{collection, prelude::*};
+
+    #[test]
+    fn distinct_aggregate_test() {
+        let (mut circuit, (mut input, count, sum, max)) = Runtime::init_circuit(1, |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<usize, isize, isize>();
+
+            let count = input.distinct_count().output();
+            let sum = input.distinct_sum(|val| *val).output();
+            let max = input.distinct_aggregate(Max).output();
+
+            (input_handle, count, sum, max)
+        })
+        .unwrap();
+
+        // Duplicate values are only aggregated once.
+        input.append(&mut vec![
+            (1, (5, 1)),
+            (1, (3, 2)),
+            (1, (8, 1)),
+            (2, (4, 3)),
+        ]);
+        circuit.step().unwrap();
+        assert_eq!(
+            count.consolidate(),
+            indexed_zset! { 1 => { 3 => 1 }, 2 => { 1 => 1 } }
+        );
+        assert_eq!(
+            sum.consolidate(),
+            indexed_zset! { 1 => { 16 => 1 }, 2 => { 4 => 1 } }
+        );
+        assert_eq!(
+            max.consolidate(),
+            indexed_zset! { 1 => { 8 => 1 }, 2 => { 4 => 1 } }
+        );
+
+        // Changing the weights of existing values does not affect the output.
+        input.append(&mut vec![(1, (3, -1)), (1, (5, 1))]);
+        circuit.step().unwrap();
+        assert_eq!(count.consolidate(), indexed_zset! {});
+        assert_eq!(sum.consolidate(), indexed_zset! {});
+        assert_eq!(max.consolidate(), indexed_zset! {});
+
+        input.append(&mut vec![(1, (8, -1)), (1, (10, 1)), (2, (4, -3))]);
+        circuit.step().unwrap();
+        assert_eq!(count.consolidate(), indexed_zset! { 2 => { 1 => -1 } });
+        assert_eq!(
+            sum.consolidate(),
+            indexed_zset! { 1 => { 16 => -1, 18 => 1 }, 2 => { 4 => -1 } }
+        );
+        assert_eq!(
+            max.consolidate(),
+            indexed_zset! { 1 => { 8 => -1, 10 => 1 }, 2 => { 4 => -1 } }
+        );
+
+        circuit.kill().unwrap();
+    }
+
+    type InputHandle = CollectionHandle<usize, (isize, isize)>;
+
+    fn distinct_aggregate_test_circuit(workers: usize) -> (DBSPHandle, InputHandle) {
+        Runtime::init_circuit(workers, move |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<usize, isize, isize>();
+
+            let count = <Fold<_, DefaultSemigroup<_>, _, _>>::new(
+                0isize,
+                |count: &mut isize, _val: &isize, weight: isize| *count += weight,
+            );
+            let sum = <Fold<_, DefaultSemigroup<_>, _, _>>::new(
+                0isize,
+                |sum: &mut isize, val: &isize, weight: isize| *sum += val * weight,
+            );
+
+            let expected_count = input.distinct().aggregate(count).gather(0).integrate();
+            let expected_sum = input.distinct().aggregate(sum).gather(0).integrate();
+            let expected_max = input.distinct().aggregate(Max).gather(0).integrate();
+
+            let count = input.distinct_count().gather(0).integrate();
+            let sum = input.distinct_sum(|val| *val).gather(0).integrate();
+            let max = input.distinct_aggregate(Max).gather(0).integrate();
+
+            expected_count.apply2(&count, |expected, actual| assert_eq!(expected, actual));
+            expected_sum.apply2(&sum, |expected, actual| assert_eq!(expected, actual));
+            expected_max.apply2(&max, |expected, actual| assert_eq!(expected, actual));
+
+            input_handle
+        })
+        .unwrap()
+    }
+
+    type InputTuple = (usize, (isize, isize));
+    type InputBatch = Vec<InputTuple>;
+
+    fn input_trace(
+        max_key: usize,
+        max_val: isize,
+        max_batch_size: usize,
+        max_batches: usize,
+    ) -> impl Strategy<Value = Vec<InputBatch>> {
+        collection::vec(
+            collection::vec(
+                (0..max_key, (-max_val..max_val, -1..=1isize)),
+                0..max_batch_size,
+            ),
+            0..max_batches,
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_distinct_aggregate(trace in input_trace(5, 10, 30, 20)) {
+            let (mut circuit, mut input) = distinct_aggregate_test_circuit(4);
+
+            for mut batch in trace {
+                input.append(&mut batch);
+                circuit.step().unwrap();
+            }
+
+            circuit.kill().unwrap();
+        }
+    }
+}
@@ -6,7 +6,7 @@ use crate::{
         Circuit, OwnershipPreference, Scope, Stream,
     },
     trace::{Batch, BatchReader, Builder, Consumer, Cursor, ValueConsumer},
-    DBData, DBWeight, OrdIndexedZSet, OrdZSet,
+    DBData, DBWeight, Error, OrdIndexedZSet, OrdZSet,
 };
 use std::{
     any::TypeId,
@@ -351,6 +351,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<CI, CO, F> UnaryOperator<CI, CO> for FilterKeys<CI, CO, F>
@@ -461,6 +465,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<CI, CO, F> UnaryOperator<CI, CO> for FilterVals<CI, CO, F>
@@ -552,6 +560,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<CI, CO, F> UnaryOperator<CI, CO> for Map<CI, CO, F>
@@ -608,6 +620,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<CI, CO, FB, FO> UnaryOperator<CI, CO> for MapKeys<CI, CO, FB, FO>
@@ -688,6 +704,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<CI, CO, F, I> UnaryOperator<CI, CO> for FlatMap<CI, CO, F, I>
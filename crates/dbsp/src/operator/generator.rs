@@ -5,6 +5,7 @@ use crate::circuit::{
     operator_traits::{Data, Operator, SourceOperator},
     Scope,
 };
+use crate::Error;
 use std::{borrow::Cow, marker::PhantomData};
 
 /// A source operator that yields an infinite output stream
@@ -90,6 +91,10 @@ where
         // can inform the circuit that it's reached a fixedpoint?
         false
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T> SourceOperator<T> for GeneratorNested<T>
@@ -9,7 +9,7 @@ use crate::{
     trace::{
         cursor::Cursor, ord::OrdIndexedZSet, Batch, BatchReader, Builder, Consumer, ValueConsumer,
     },
-    DBData,
+    DBData, Error,
 };
 use std::{borrow::Cow, marker::PhantomData};
 
@@ -118,6 +118,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<CI, CO> UnaryOperator<CI, CO> for Index<CI, CO>
@@ -202,6 +206,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<CI, CO, F> UnaryOperator<CI, CO> for IndexWith<CI, CO, F>
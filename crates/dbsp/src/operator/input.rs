@@ -6,7 +6,7 @@ use crate::{
     },
     default_hash,
     trace::Batch,
-    Circuit, DBData, DBWeight, OrdIndexedZSet, OrdZSet, Runtime, Stream,
+    Circuit, DBData, DBWeight, Error, OrdIndexedZSet, OrdZSet, Runtime, Stream,
 };
 use std::{
     borrow::Cow,
@@ -930,6 +930,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         false
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<IT, OT, F> SourceOperator<OT> for Input<IT, OT, F>
@@ -5,6 +5,7 @@ use crate::circuit::{
     operator_traits::{Operator, UnaryOperator},
     Circuit, Scope, Stream,
 };
+use crate::Error;
 use std::{borrow::Cow, marker::PhantomData};
 
 impl<C, D> Stream<C, D>
@@ -78,6 +79,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T, F> UnaryOperator<T, T> for Inspect<T, F>
@@ -21,7 +21,7 @@ use crate::{
         Circuit, Scope, Stream,
     },
     trace::{cursor::Cursor, Batch, BatchReader},
-    DBData, OrdIndexedZSet, OrdZSet,
+    DBData, Error, OrdIndexedZSet, OrdZSet,
 };
 use std::{borrow::Cow, marker::PhantomData};
 
@@ -138,6 +138,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<RF, JF, It, I1, I2, O> BinaryOperator<I1, I2, O> for StreamJoinRange<RF, JF, It, I1, I2, O>
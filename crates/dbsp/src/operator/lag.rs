@@ -0,0 +1,538 @@
+//! Lag, lead and row number operators.
+
+use crate::{
+    algebra::{AddByRef, HasOne, HasZero, IndexedZSet, NegByRef, ZRingValue},
+    circuit::{
+        operator_traits::{BinaryOperator, Operator},
+        Scope, Stream,
+    },
+    trace::{Batch, BatchReader, Cursor},
+    Circuit, DBData, Error, OrdIndexedZSet, RootCircuit,
+};
+use std::{borrow::Cow, cmp::Ordering, iter::repeat, marker::PhantomData};
+
+impl<B> Stream<RootCircuit, B>
+where
+    B: IndexedZSet + Send,
+    B::R: ZRingValue,
+{
+    /// Lag operator matching the behavior of
+    /// `LAG(expr, offset, default) OVER (PARTITION BY key ORDER BY val)` in
+    /// SQL.
+    ///
+    /// Treats each key in the input indexed Z-set as a partition and orders
+    /// values within the partition by their `Ord` implementation.  For each
+    /// value `val`, outputs tuple `(val, project(prev))`, where `prev` is the
+    /// value `offset` rows before `val` in the partition, or `(val, default)`
+    /// if there is no such row.  A value with weight `w > 0` is treated as `w`
+    /// identical rows; values with non-positive weights are ignored.  Each
+    /// output tuple has weight `1` per row that produced it.
+    ///
+    /// This is an incremental operator: it transforms a stream of changes to
+    /// the input collection into a stream of changes to the output collection,
+    /// which only contains rows whose lagged value has changed.  Only
+    /// partitions modified by the current input batch are re-evaluated; the
+    /// cost of re-evaluating a partition is proportional to the number of rows
+    /// in it.
+    #[allow(clippy::type_complexity)]
+    pub fn lag<OV, PF>(
+        &self,
+        offset: usize,
+        project: PF,
+        default: OV,
+    ) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, (B::Val, OV), B::R>>
+    where
+        OV: DBData,
+        PF: Fn(&B::Val) -> OV + 'static,
+    {
+        self.partitioned_row_function("Lag", move |rows: &[&B::Val], row| {
+            if row >= offset {
+                project(rows[row - offset])
+            } else {
+                default.clone()
+            }
+        })
+    }
+
+    /// Lead operator matching the behavior of
+    /// `LEAD(expr, offset, default) OVER (PARTITION BY key ORDER BY val)` in
+    /// SQL.
+    ///
+    /// Like [`Self::lag`], but picks the value `offset` rows after each value
+    /// in the partition.
+    #[allow(clippy::type_complexity)]
+    pub fn lead<OV, PF>(
+        &self,
+        offset: usize,
+        project: PF,
+        default: OV,
+    ) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, (B::Val, OV), B::R>>
+    where
+        OV: DBData,
+        PF: Fn(&B::Val) -> OV + 'static,
+    {
+        self.partitioned_row_function("Lead", move |rows: &[&B::Val], row| {
+            if rows.len() - row > offset {
+                project(rows[row + offset])
+            } else {
+                default.clone()
+            }
+        })
+    }
+
+    /// Row number operator matching the behavior of
+    /// `ROW_NUMBER() OVER (PARTITION BY key ORDER BY val)` in SQL.
+    ///
+    /// For each value in a partition, outputs tuple `(val, n)`, where `n` is
+    /// the 1-based position of the value in the partition ordered by the
+    /// `Ord` implementation of the value type.  A value with weight `w > 0`
+    /// is treated as `w` identical rows, which are assigned consecutive row
+    /// numbers.  Values with non-positive weights are ignored.
+    ///
+    /// Like [`Self::lag`], this operator is incremental and only outputs
+    /// rows whose row numbers have changed.  Note that inserting or deleting
+    /// a row changes the row numbers of all subsequent rows in the partition.
+    #[allow(clippy::type_complexity)]
+    pub fn row_number(&self) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, (B::Val, u64), B::R>> {
+        self.partitioned_row_function("RowNumber", |_rows: &[&B::Val], row| row as u64 + 1)
+    }
+
+    /// Evaluate `func` for each row of all partitions modified by the current
+    /// input batch.
+    ///
+    /// `func` takes all rows of the partition in ascending order and the
+    /// index of the current row.
+    #[allow(clippy::type_complexity)]
+    fn partitioned_row_function<OV, F>(
+        &self,
+        name: &'static str,
+        func: F,
+    ) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, (B::Val, OV), B::R>>
+    where
+        OV: DBData,
+        F: Fn(&[&B::Val], usize) -> OV + 'static,
+    {
+        let circuit = self.circuit();
+        let stream = self.shard();
+
+        circuit.region(name, || {
+            circuit
+                .add_binary_operator(
+                    RowFunction::new(name, func),
+                    &stream,
+                    &stream.integrate_trace().delay_trace(),
+                )
+                .mark_sharded()
+        })
+    }
+}
+
+/// Convert a positive weight into the number of rows it represents.
+///
+/// `R` only supports ring operations, so we compute the count by repeated
+/// doubling followed by greedy subtraction of powers of two, which takes
+/// `O(log(weight))` steps.
+fn row_count<R>(weight: &R) -> usize
+where
+    R: ZRingValue,
+{
+    if weight.le0() {
+        return 0;
+    }
+
+    // Powers of two that do not exceed `weight`, paired with their values as
+    // `usize`.  Compare `weight - power` with `power` instead of computing
+    // `2 * power` first to avoid overflow.
+    let mut powers = vec![(R::one(), 1usize)];
+    loop {
+        let (power, count) = powers.last().unwrap();
+        let rest = weight.add_by_ref(&power.neg_by_ref());
+        if !rest.add_by_ref(&power.neg_by_ref()).ge0() {
+            break;
+        }
+        let next = (power.add_by_ref(power), count * 2);
+        powers.push(next);
+    }
+
+    let mut remainder = weight.clone();
+    let mut count = 0;
+    for (power, power_count) in powers.iter().rev() {
+        let rest = remainder.add_by_ref(&power.neg_by_ref());
+        if rest.ge0() {
+            remainder = rest;
+            count += power_count;
+        }
+    }
+
+    count
+}
+
+/// Incremental operator that evaluates a function over the rows of each
+/// partition in the root scope.
+///
+/// This is a binary operator with the following inputs:
+/// * `delta` - stream of changes to the input indexed Z-set.
+/// * `delayed_integral` - the integral of the input stream up to the
+///   previous clock cycle.
+///
+/// For each key in `delta`, the operator evaluates `func` over the contents
+/// of the partition before and after applying `delta`, and outputs the
+/// difference between the two results.
+struct RowFunction<Z, I, OV, F> {
+    name: &'static str,
+    func: F,
+    _type: PhantomData<(Z, I, OV)>,
+}
+
+impl<Z, I, OV, F> RowFunction<Z, I, OV, F> {
+    fn new(name: &'static str, func: F) -> Self {
+        Self {
+            name,
+            func,
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<Z, I, OV, F> Operator for RowFunction<Z, I, OV, F>
+where
+    Z: 'static,
+    I: 'static,
+    OV: 'static,
+    F: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from(self.name)
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+}
+
+impl<Z, I, OV, F> RowFunction<Z, I, OV, F>
+where
+    Z: IndexedZSet,
+    Z::R: ZRingValue,
+    I: BatchReader<Key = Z::Key, Val = Z::Val, Time = (), R = Z::R>,
+    OV: DBData,
+    F: Fn(&[&Z::Val], usize) -> OV + 'static,
+{
+    /// Collect the values of the current key of `delta_cursor` before and
+    /// after applying `delta` along with the number of rows they represent.
+    ///
+    /// `integral_cursor` points to the same key in the delayed integral or is
+    /// `None` if the key does not occur there.
+    #[allow(clippy::type_complexity)]
+    fn partition_runs<DC, IC>(
+        delta_cursor: &mut DC,
+        mut integral_cursor: Option<&mut IC>,
+    ) -> (Vec<(Z::Val, usize)>, Vec<(Z::Val, usize)>)
+    where
+        DC: Cursor<Z::Key, Z::Val, (), Z::R>,
+        IC: Cursor<Z::Key, Z::Val, (), Z::R>,
+    {
+        let mut old_runs = Vec::new();
+        let mut new_runs = Vec::new();
+
+        loop {
+            let delta_valid = delta_cursor.val_valid();
+            let integral_valid = integral_cursor
+                .as_ref()
+                .map_or(false, |cursor| cursor.val_valid());
+
+            // `Less` - next value only occurs in delta, `Greater` - only in
+            // the integral, `Equal` - in both.
+            let ordering = match (delta_valid, integral_valid) {
+                (false, false) => break,
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                (true, true) => delta_cursor
+                    .val()
+                    .cmp(integral_cursor.as_ref().unwrap().val()),
+            };
+
+            let (val, delta_weight) = if ordering != Ordering::Greater {
+                (delta_cursor.val().clone(), delta_cursor.weight())
+            } else {
+                (
+                    integral_cursor.as_ref().unwrap().val().clone(),
+                    Z::R::zero(),
+                )
+            };
+            let old_weight = if ordering != Ordering::Less {
+                integral_cursor.as_deref_mut().unwrap().weight()
+            } else {
+                Z::R::zero()
+            };
+            let new_weight = old_weight.add_by_ref(&delta_weight);
+
+            if !old_weight.le0() {
+                old_runs.push((val.clone(), row_count(&old_weight)));
+            }
+            if !new_weight.le0() {
+                new_runs.push((val, row_count(&new_weight)));
+            }
+
+            if ordering != Ordering::Greater {
+                delta_cursor.step_val();
+            }
+            if ordering != Ordering::Less {
+                integral_cursor.as_deref_mut().unwrap().step_val();
+            }
+        }
+
+        (old_runs, new_runs)
+    }
+
+    /// Evaluate `func` for all rows in `runs` and append results with the
+    /// specified weight to `tuples`.
+    #[allow(clippy::type_complexity)]
+    fn eval_partition(
+        &self,
+        key: &Z::Key,
+        runs: &[(Z::Val, usize)],
+        weight: &Z::R,
+        tuples: &mut Vec<((Z::Key, (Z::Val, OV)), Z::R)>,
+    ) {
+        let rows: Vec<&Z::Val> = runs
+            .iter()
+            .flat_map(|(val, count)| repeat(val).take(*count))
+            .collect();
+
+        for (row, val) in rows.iter().enumerate() {
+            tuples.push((
+                (key.clone(), ((*val).clone(), (self.func)(&rows, row))),
+                weight.clone(),
+            ));
+        }
+    }
+}
+
+impl<Z, I, OV, F> BinaryOperator<Z, I, OrdIndexedZSet<Z::Key, (Z::Val, OV), Z::R>>
+    for RowFunction<Z, I, OV, F>
+where
+    Z: IndexedZSet,
+    Z::R: ZRingValue,
+    I: BatchReader<Key = Z::Key, Val = Z::Val, Time = (), R = Z::R>,
+    OV: DBData,
+    F: Fn(&[&Z::Val], usize) -> OV + 'static,
+{
+    fn eval(
+        &mut self,
+        delta: &Z,
+        delayed_integral: &I,
+    ) -> OrdIndexedZSet<Z::Key, (Z::Val, OV), Z::R> {
+        let plus_one = Z::R::one();
+        let minus_one = plus_one.neg_by_ref();
+
+        let mut tuples = Vec::new();
+        let mut delta_cursor = delta.cursor();
+        let mut integral_cursor = delayed_integral.cursor();
+
+        while delta_cursor.key_valid() {
+            integral_cursor.seek_key(delta_cursor.key());
+
+            let (old_runs, new_runs) =
+                if integral_cursor.key_valid() && integral_cursor.key() == delta_cursor.key() {
+                    Self::partition_runs(&mut delta_cursor, Some(&mut integral_cursor))
+                } else {
+                    Self::partition_runs::<_, I::Cursor<'_>>(&mut delta_cursor, None)
+                };
+
+            // Rows whose output did not change cancel out when the batch is
+            // consolidated.
+            self.eval_partition(delta_cursor.key(), &old_runs, &minus_one, &mut tuples);
+            self.eval_partition(delta_cursor.key(), &new_runs, &plus_one, &mut tuples);
+
+            delta_cursor.step_key();
+        }
+
+        OrdIndexedZSet::from_tuples((), tuples)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::row_count;
+    use crate::{
+        indexed_zset,
+        operator::CollectionHandle,
+        trace::{Batch, BatchReader, Cursor},
+        DBData, DBSPHandle, OrdIndexedZSet, Runtime,
+    };
+    use proptest::{collection, prelude::*};
+
+    type TestIndexedZSet = OrdIndexedZSet<usize, isize, isize>;
+
+    /// Non-incremental reference implementation of row functions.
+    fn row_function_slow<OV, F>(
+        batch: &TestIndexedZSet,
+        func: F,
+    ) -> OrdIndexedZSet<usize, (isize, OV), isize>
+    where
+        OV: DBData,
+        F: Fn(&[isize], usize) -> OV,
+    {
+        let mut tuples = Vec::new();
+        let mut cursor = batch.cursor();
+
+        while cursor.key_valid() {
+            let mut rows = Vec::new();
+            while cursor.val_valid() {
+                for _ in 0..cursor.weight() {
+                    rows.push(*cursor.val());
+                }
+                cursor.step_val();
+            }
+            for (row, val) in rows.iter().enumerate() {
+                tuples.push(((*cursor.key(), (*val, func(&rows, row))), 1));
+            }
+            cursor.step_key();
+        }
+
+        OrdIndexedZSet::from_tuples((), tuples)
+    }
+
+    #[test]
+    fn row_count_test() {
+        assert_eq!(row_count(&0isize), 0);
+        assert_eq!(row_count(&-5isize), 0);
+        assert_eq!(row_count(&1isize), 1);
+        assert_eq!(row_count(&6isize), 6);
+        assert_eq!(row_count(&1024i64), 1024);
+        assert_eq!(row_count(&1_000_003i32), 1_000_003);
+        assert_eq!(row_count(&isize::MAX), isize::MAX as usize);
+    }
+
+    #[test]
+    fn lag_test() {
+        let (mut circuit, (mut input, lag, lead, row_number)) =
+            Runtime::init_circuit(1, |circuit| {
+                let (input, input_handle) = circuit.add_input_indexed_zset::<usize, isize, isize>();
+
+                let lag = input.lag(1, |v| *v, -1).output();
+                let lead = input.lead(1, |v| *v, -1).output();
+                let row_number = input.row_number().output();
+
+                (input_handle, lag, lead, row_number)
+            })
+            .unwrap();
+
+        input.append(&mut vec![(1, (1, 1)), (1, (3, 1)), (1, (5, 1))]);
+        circuit.step().unwrap();
+        assert_eq!(
+            lag.consolidate(),
+            indexed_zset! { 1 => { (1, -1) => 1, (3, 1) => 1, (5, 3) => 1 } }
+        );
+        assert_eq!(
+            lead.consolidate(),
+            indexed_zset! { 1 => { (1, 3) => 1, (3, 5) => 1, (5, -1) => 1 } }
+        );
+        assert_eq!(
+            row_number.consolidate(),
+            indexed_zset! { 1 => { (1, 1) => 1, (3, 2) => 1, (5, 3) => 1 } }
+        );
+
+        // Only rows whose neighbors or row numbers changed are updated.
+        input.append(&mut vec![(1, (2, 1))]);
+        circuit.step().unwrap();
+        assert_eq!(
+            lag.consolidate(),
+            indexed_zset! { 1 => { (2, 1) => 1, (3, 1) => -1, (3, 2) => 1 } }
+        );
+        assert_eq!(
+            lead.consolidate(),
+            indexed_zset! { 1 => { (1, 2) => 1, (1, 3) => -1, (2, 3) => 1 } }
+        );
+        assert_eq!(
+            row_number.consolidate(),
+            indexed_zset! { 1 => { (2, 2) => 1, (3, 2) => -1, (3, 3) => 1, (5, 3) => -1, (5, 4) => 1 } }
+        );
+
+        // A value with weight 2 counts as two rows.
+        input.append(&mut vec![(1, (5, 1))]);
+        circuit.step().unwrap();
+        assert_eq!(lag.consolidate(), indexed_zset! { 1 => { (5, 5) => 1 } });
+        assert_eq!(lead.consolidate(), indexed_zset! { 1 => { (5, 5) => 1 } });
+        assert_eq!(
+            row_number.consolidate(),
+            indexed_zset! { 1 => { (5, 5) => 1 } }
+        );
+
+        circuit.kill().unwrap();
+    }
+
+    type InputHandle = CollectionHandle<usize, (isize, isize)>;
+
+    fn lag_test_circuit(workers: usize, offset: usize) -> (DBSPHandle, InputHandle) {
+        Runtime::init_circuit(workers, move |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<usize, isize, isize>();
+
+            let expected_lag = input.gather(0).integrate().apply(move |batch| {
+                row_function_slow(batch, |rows, row| {
+                    if row >= offset {
+                        Some(rows[row - offset])
+                    } else {
+                        None
+                    }
+                })
+            });
+            let expected_lead = input.gather(0).integrate().apply(move |batch| {
+                row_function_slow(batch, |rows, row| rows.get(row + offset).cloned())
+            });
+            let expected_row_number = input
+                .gather(0)
+                .integrate()
+                .apply(|batch| row_function_slow(batch, |_rows, row| row as u64 + 1));
+
+            let lag = input.lag(offset, |v| Some(*v), None).gather(0).integrate();
+            let lead = input.lead(offset, |v| Some(*v), None).gather(0).integrate();
+            let row_number = input.row_number().gather(0).integrate();
+
+            expected_lag.apply2(&lag, |expected, actual| assert_eq!(expected, actual));
+            expected_lead.apply2(&lead, |expected, actual| assert_eq!(expected, actual));
+            expected_row_number
+                .apply2(&row_number, |expected, actual| assert_eq!(expected, actual));
+
+            input_handle
+        })
+        .unwrap()
+    }
+
+    type InputTuple = (usize, (isize, isize));
+    type InputBatch = Vec<InputTuple>;
+
+    fn input_trace(
+        max_key: usize,
+        max_val: isize,
+        max_batch_size: usize,
+        max_batches: usize,
+    ) -> impl Strategy<Value = Vec<InputBatch>> {
+        collection::vec(
+            collection::vec(
+                (0..max_key, (-max_val..max_val, -1..=2isize)),
+                0..max_batch_size,
+            ),
+            0..max_batches,
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_lag(trace in input_trace(5, 20, 30, 20), offset in 0..4usize) {
+            let (mut circuit, mut input) = lag_test_circuit(4, offset);
+
+            for mut batch in trace {
+                input.append(&mut batch);
+                circuit.step().unwrap();
+            }
+
+            circuit.kill().unwrap();
+        }
+    }
+}
@@ -16,6 +16,7 @@ mod csv;
 mod delta0;
 mod differentiate;
 mod distinct;
+mod distinct_aggregate;
 mod filter_map;
 mod generator;
 mod index;
@@ -23,6 +24,7 @@ mod input;
 mod integrate;
 mod join;
 mod join_range;
+mod lag;
 mod neg;
 mod output;
 mod plus;
@@ -30,6 +32,7 @@ mod semijoin;
 mod stream_fold;
 mod sum;
 pub mod time_series;
+mod topk;
 mod trace;
 mod z1;
 
@@ -52,4 +55,5 @@ pub use neg::UnaryMinus;
 pub use output::OutputHandle;
 pub use plus::{Minus, Plus};
 pub use sum::Sum;
+pub use topk::{CmpFunc, WithCustomOrd};
 pub use z1::{DelayedFeedback, DelayedNestedFeedback, Z1Nested, Z1};
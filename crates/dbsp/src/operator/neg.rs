@@ -6,6 +6,7 @@ use crate::{
         operator_traits::{Operator, UnaryOperator},
         Circuit, OwnershipPreference, Scope, Stream,
     },
+    Error,
 };
 use std::{borrow::Cow, marker::PhantomData, ops::Neg};
 
@@ -48,6 +49,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T> Default for UnaryMinus<T> {
@@ -5,7 +5,7 @@ use crate::{
         LocalStoreMarker, OwnershipPreference, RootCircuit, Scope,
     },
     trace::{Batch, Spine, Trace},
-    Circuit, Runtime, Stream,
+    Circuit, Error, Runtime, Stream,
 };
 use std::{
     borrow::Cow,
@@ -259,6 +259,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T> SinkOperator<T> for Output<T>
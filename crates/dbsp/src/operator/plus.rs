@@ -6,6 +6,7 @@ use crate::{
         operator_traits::{BinaryOperator, Operator},
         Circuit, OwnershipPreference, Scope, Stream,
     },
+    Error,
 };
 use std::{
     borrow::Cow,
@@ -118,6 +119,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<D> BinaryOperator<D, D, D> for Plus<D>
@@ -175,6 +180,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 // TODO: Add `subtract` operation to `GroupValue`, which
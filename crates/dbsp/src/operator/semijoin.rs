@@ -7,7 +7,7 @@ use crate::{
     circuit::{GlobalNodeId, OwnershipPreference},
     circuit_cache_key,
     trace::{Batch, BatchReader, Builder, Consumer, Cursor, ValueConsumer},
-    Circuit, Stream,
+    Circuit, Error, Stream,
 };
 use std::{
     borrow::Cow,
@@ -88,6 +88,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<Pairs, Keys, Out> BinaryOperator<Pairs, Keys, Out> for SemiJoinStream<Pairs, Keys, Out>
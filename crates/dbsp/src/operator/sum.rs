@@ -6,7 +6,7 @@ use crate::{
         operator_traits::{NaryOperator, Operator},
         Circuit, OwnershipPreference, Scope, Stream,
     },
-    NumEntries,
+    Error, NumEntries,
 };
 use std::{
     borrow::Cow,
@@ -71,6 +71,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<D> NaryOperator<D, D> for Sum<D>
@@ -0,0 +1,659 @@
+use crate::{
+    algebra::{NegByRef, ZRingValue},
+    circuit::{
+        operator_traits::{Operator, QuaternaryOperator, TernaryOperator},
+        Scope,
+    },
+    operator::time_series::PartitionedIndexedZSet,
+    trace::{Batch, BatchReader, Builder, Cursor},
+    Circuit, DBData, Error, OrdZSet, RootCircuit, Stream,
+};
+use std::{borrow::Cow, cmp::Ordering, marker::PhantomData};
+
+impl<B> Stream<RootCircuit, B> {
+    /// As-of join of two partitioned time series.
+    ///
+    /// Matches each record `(ts, v1)` in partition `pk` of `self` with the
+    /// greatest record `(ts2, v2)` with `ts2 <= ts` and positive weight in
+    /// the same partition of `other`, i.e., the latest known value in `other`
+    /// at time `ts`, and outputs `join_func(pk, (ts, v1), Some((ts2, v2)))`.
+    /// Records that do not have a match in `other` are output as
+    /// `join_func(pk, (ts, v1), None)`.  Output records inherit the weights
+    /// of records in `self`.
+    ///
+    /// This operator is incremental: when a new or retracted record arrives
+    /// in `other`, it retracts matches of records in `self` that are no longer
+    /// valid and emits new matches in their place.
+    ///
+    /// This operator maintains the complete contents of both input collections.
+    /// Use [`Self::asof_join_with_watermark`] to bound its memory footprint.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - time series data partitioned by partition key and indexed by
+    ///   time within each partition.
+    /// * `other` - time series to look up values in, partitioned and indexed
+    ///   the same way as `self`.
+    /// * `join_func` - function that computes an output record from a record
+    ///   in `self` and its match in `other`.
+    pub fn asof_join<TS, V1, V2, B2, F, O>(
+        &self,
+        other: &Stream<RootCircuit, B2>,
+        join_func: F,
+    ) -> Stream<RootCircuit, OrdZSet<O, B::R>>
+    where
+        B: PartitionedIndexedZSet<TS, V1>,
+        B::R: ZRingValue,
+        B2: PartitionedIndexedZSet<TS, V2, Key = B::Key, R = B::R>,
+        TS: DBData,
+        V1: DBData,
+        V2: DBData,
+        O: DBData,
+        F: Fn(&B::Key, &(TS, V1), Option<&(TS, V2)>) -> O + 'static,
+    {
+        self.circuit().region("asof_join", || {
+            let left = self.shard();
+            let right = other.shard();
+
+            self.circuit()
+                .add_quaternary_operator(
+                    AsofJoin::new(join_func),
+                    &left,
+                    &right,
+                    &left.integrate_trace().delay_trace(),
+                    &right.integrate_trace().delay_trace(),
+                )
+                .mark_sharded()
+        })
+    }
+
+    /// Similar to [`Self::asof_join`], but uses `watermark` to bound its
+    /// memory footprint.
+    ///
+    /// The `watermark` stream bounds the out-of-ordedness of both input
+    /// streams by providing a monotonically growing lower bound on timestamps
+    /// that can appear in them, e.g., computed by the
+    /// [`watermark_monotonic`](`Stream::watermark_monotonic`) operator.  The
+    /// operator does not expect inputs with timestamps smaller than the current
+    /// watermark.
+    ///
+    /// Records in `self` older than the watermark can no longer be affected
+    /// by new inputs and are discarded.  In `other`, records older than the
+    /// watermark are discarded as soon as they are superseded by a newer
+    /// record with timestamp `<=` the watermark.  Garbage collection is
+    /// performed lazily for partitions that receive new inputs.
+    pub fn asof_join_with_watermark<TS, V1, V2, B2, F, O>(
+        &self,
+        other: &Stream<RootCircuit, B2>,
+        watermark: &Stream<RootCircuit, TS>,
+        join_func: F,
+    ) -> Stream<RootCircuit, OrdZSet<O, B::R>>
+    where
+        B: PartitionedIndexedZSet<TS, V1>,
+        B::R: ZRingValue,
+        B2: PartitionedIndexedZSet<TS, V2, Key = B::Key, R = B::R>,
+        TS: DBData,
+        V1: DBData,
+        V2: DBData,
+        O: DBData,
+        F: Fn(&B::Key, &(TS, V1), Option<&(TS, V2)>) -> O + 'static,
+    {
+        let circuit = self.circuit();
+
+        circuit.region("asof_join_with_watermark", || {
+            let left = self.shard();
+            let right = other.shard();
+
+            let left_trace = left.integrate_trace_with_gc(watermark, AsofGc::<TS, V1>::new(false));
+            let right_trace = right.integrate_trace_with_gc(watermark, AsofGc::<TS, V2>::new(true));
+
+            circuit
+                .add_quaternary_operator(
+                    AsofJoin::new(join_func),
+                    &left,
+                    &right,
+                    &left_trace.delay_trace(),
+                    &right_trace.delay_trace(),
+                )
+                .mark_sharded()
+        })
+    }
+}
+
+/// Move `cursor` to the greatest value of the current key with timestamp
+/// `<= ts`.  Leaves the cursor invalid if there is no such value.
+fn seek_ts_reverse<K, TS, V, R, C>(cursor: &mut C, ts: &TS)
+where
+    C: Cursor<K, (TS, V), (), R>,
+    TS: Ord,
+{
+    cursor.fast_forward_vals();
+    cursor.seek_val_with_reverse(|(t, _)| t <= ts);
+}
+
+/// Move `cursor` to `key`; returns `false` if `key` is not in the batch.
+pub(super) fn seek_key_exact<K, V, R, C>(cursor: &mut C, key: &K) -> bool
+where
+    C: Cursor<K, V, (), R>,
+    K: Ord,
+{
+    cursor.seek_key(key);
+    cursor.key_valid() && cursor.key() == key
+}
+
+/// Find the greatest value with timestamp `<= ts` whose combined weight in
+/// the current keys of `cursor1` and `cursor2` is positive.
+///
+/// `None` cursors represent empty collections.
+fn find_match<K, TS, V, R, C1, C2>(
+    mut cursor1: Option<&mut C1>,
+    mut cursor2: Option<&mut C2>,
+    ts: &TS,
+) -> Option<(TS, V)>
+where
+    C1: Cursor<K, (TS, V), (), R>,
+    C2: Cursor<K, (TS, V), (), R>,
+    TS: Ord + Clone,
+    V: Ord + Clone,
+    R: ZRingValue,
+{
+    if let Some(cursor1) = cursor1.as_deref_mut() {
+        seek_ts_reverse(cursor1, ts);
+    }
+    if let Some(cursor2) = cursor2.as_deref_mut() {
+        seek_ts_reverse(cursor2, ts);
+    }
+
+    loop {
+        let valid1 = cursor1.as_ref().map_or(false, |cursor| cursor.val_valid());
+        let valid2 = cursor2.as_ref().map_or(false, |cursor| cursor.val_valid());
+
+        // Scan values in descending order.  `Greater` - next value only
+        // occurs in `cursor1`, `Less` - only in `cursor2`, `Equal` - in both.
+        let ordering = match (valid1, valid2) {
+            (false, false) => return None,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (true, true) => cursor1
+                .as_ref()
+                .unwrap()
+                .val()
+                .cmp(cursor2.as_ref().unwrap().val()),
+        };
+
+        let mut weight = R::zero();
+        if ordering != Ordering::Less {
+            weight = weight.add_by_ref(&cursor1.as_deref_mut().unwrap().weight());
+        }
+        if ordering != Ordering::Greater {
+            weight = weight.add_by_ref(&cursor2.as_deref_mut().unwrap().weight());
+        }
+
+        if !weight.le0() {
+            return if ordering != Ordering::Less {
+                Some(cursor1.as_ref().unwrap().val().clone())
+            } else {
+                Some(cursor2.as_ref().unwrap().val().clone())
+            };
+        }
+
+        if ordering != Ordering::Less {
+            cursor1.as_deref_mut().unwrap().step_val_reverse();
+        }
+        if ordering != Ordering::Greater {
+            cursor2.as_deref_mut().unwrap().step_val_reverse();
+        }
+    }
+}
+
+/// Quaternary operator that implements the internals of `asof_join`.
+///
+/// * Input stream 1: updates to the left time series.
+/// * Input stream 2: updates to the right time series.
+/// * Input stream 3: trace of the left time series up to the previous clock
+///   cycle.
+/// * Input stream 4: trace of the right time series up to the previous clock
+///   cycle.
+///
+/// The output is computed as the sum of:
+/// * matches of new left records against the updated right collection, and
+/// * changes to the matches of existing left records caused by right updates.
+///   Given updates to a right partition with timestamps in `[first..last]`,
+///   only left records with timestamps in `[first..next)`, where `next` is the
+///   first timestamp after `last` in the right partition, can be affected.
+struct AsofJoin<TS, V1, V2, F, O> {
+    join_func: F,
+    phantom: PhantomData<(TS, V1, V2, O)>,
+}
+
+impl<TS, V1, V2, F, O> AsofJoin<TS, V1, V2, F, O> {
+    fn new(join_func: F) -> Self {
+        Self {
+            join_func,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<TS, V1, V2, F, O> Operator for AsofJoin<TS, V1, V2, F, O>
+where
+    TS: 'static,
+    V1: 'static,
+    V2: 'static,
+    F: 'static,
+    O: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("AsofJoin")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+}
+
+impl<TS, V1, V2, F, O, B1, B2, T1, T2> QuaternaryOperator<B1, B2, T1, T2, OrdZSet<O, B1::R>>
+    for AsofJoin<TS, V1, V2, F, O>
+where
+    TS: DBData,
+    V1: DBData,
+    V2: DBData,
+    O: DBData,
+    F: Fn(&B1::Key, &(TS, V1), Option<&(TS, V2)>) -> O + 'static,
+    B1: PartitionedIndexedZSet<TS, V1>,
+    B1::R: ZRingValue,
+    B2: PartitionedIndexedZSet<TS, V2, Key = B1::Key, R = B1::R>,
+    T1: BatchReader<Key = B1::Key, Val = (TS, V1), Time = (), R = B1::R> + Clone,
+    T2: BatchReader<Key = B1::Key, Val = (TS, V2), Time = (), R = B1::R> + Clone,
+{
+    fn eval<'a>(
+        &mut self,
+        left_delta: Cow<'a, B1>,
+        right_delta: Cow<'a, B2>,
+        left_trace: Cow<'a, T1>,
+        right_trace: Cow<'a, T2>,
+    ) -> OrdZSet<O, B1::R> {
+        let mut tuples = Vec::new();
+        let mut left_delta_cursor = left_delta.cursor();
+        let mut left_trace_cursor = left_trace.cursor();
+        let mut right_trace_cursor = right_trace.cursor();
+
+        // Match new left records against the updated right collection.
+        let mut right_delta_cursor = right_delta.cursor();
+        while left_delta_cursor.key_valid() {
+            let key = left_delta_cursor.key();
+            let in_trace = seek_key_exact(&mut right_trace_cursor, key);
+            let in_delta = seek_key_exact(&mut right_delta_cursor, key);
+
+            while left_delta_cursor.val_valid() {
+                let weight = left_delta_cursor.weight();
+                let matched = find_match(
+                    in_trace.then_some(&mut right_trace_cursor),
+                    in_delta.then_some(&mut right_delta_cursor),
+                    &left_delta_cursor.val().0,
+                );
+                tuples.push((
+                    (self.join_func)(
+                        left_delta_cursor.key(),
+                        left_delta_cursor.val(),
+                        matched.as_ref(),
+                    ),
+                    weight,
+                ));
+                left_delta_cursor.step_val();
+            }
+
+            left_delta_cursor.step_key();
+        }
+
+        // Update matches of existing left records affected by right updates.
+        let mut right_delta_cursor = right_delta.cursor();
+        right_trace_cursor.rewind_keys();
+        while right_delta_cursor.key_valid() {
+            let key = right_delta_cursor.key().clone();
+            if !seek_key_exact(&mut left_trace_cursor, &key) {
+                right_delta_cursor.step_key();
+                continue;
+            }
+            let in_trace = seek_key_exact(&mut right_trace_cursor, &key);
+
+            let first = right_delta_cursor.val().0.clone();
+            right_delta_cursor.fast_forward_vals();
+            let last = right_delta_cursor.val().0.clone();
+
+            // The first timestamp after `last` with a positive weight.  Right
+            // records with this timestamp are not modified by `right_delta`,
+            // so left records with timestamps `>= next` keep their matches.
+            let mut next = None;
+            if in_trace {
+                right_trace_cursor.seek_val_with(|(ts, _)| ts > &last);
+                while right_trace_cursor.val_valid() {
+                    if !right_trace_cursor.weight().le0() {
+                        next = Some(right_trace_cursor.val().0.clone());
+                        break;
+                    }
+                    right_trace_cursor.step_val();
+                }
+            }
+
+            left_trace_cursor.seek_val_with(|(ts, _)| ts >= &first);
+            while left_trace_cursor.val_valid()
+                && next
+                    .as_ref()
+                    .map_or(true, |next| &left_trace_cursor.val().0 < next)
+            {
+                let ts = &left_trace_cursor.val().0;
+                let old_match = find_match::<_, _, _, _, _, B2::Cursor<'_>>(
+                    in_trace.then_some(&mut right_trace_cursor),
+                    None,
+                    ts,
+                );
+                let new_match = find_match(
+                    in_trace.then_some(&mut right_trace_cursor),
+                    Some(&mut right_delta_cursor),
+                    ts,
+                );
+
+                if old_match != new_match {
+                    let weight = left_trace_cursor.weight();
+                    tuples.push((
+                        (self.join_func)(&key, left_trace_cursor.val(), old_match.as_ref()),
+                        weight.neg_by_ref(),
+                    ));
+                    tuples.push((
+                        (self.join_func)(&key, left_trace_cursor.val(), new_match.as_ref()),
+                        weight,
+                    ));
+                }
+                left_trace_cursor.step_val();
+            }
+
+            right_delta_cursor.step_key();
+        }
+
+        OrdZSet::from_keys((), tuples)
+    }
+}
+
+/// Ternary operator that computes retractions of records that are no longer
+/// needed by `asof_join_with_watermark`.
+///
+/// * Input stream 1: updates to a time series.  Used to identify partitions to
+///   garbage collect.
+/// * Input stream 2: trace containing the accumulated time series data.
+/// * Input stream 3: watermark.
+///
+/// Retracts all records with timestamps `< watermark` in each partition in
+/// input stream 1, except (when `retain_latest` is `true`) the latest record
+/// with timestamp `<= watermark`.
+struct AsofGc<TS, V> {
+    retain_latest: bool,
+    phantom: PhantomData<(TS, V)>,
+}
+
+impl<TS, V> AsofGc<TS, V> {
+    fn new(retain_latest: bool) -> Self {
+        Self {
+            retain_latest,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<TS, V> Operator for AsofGc<TS, V>
+where
+    TS: 'static,
+    V: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("AsofGc")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+}
+
+impl<TS, V, B, T> TernaryOperator<B, T, TS, B> for AsofGc<TS, V>
+where
+    TS: DBData,
+    V: DBData,
+    B: PartitionedIndexedZSet<TS, V>,
+    B::R: ZRingValue,
+    T: BatchReader<Key = B::Key, Val = (TS, V), Time = (), R = B::R> + Clone,
+{
+    fn eval<'a>(&mut self, delta: Cow<'a, B>, trace: Cow<'a, T>, watermark: Cow<'a, TS>) -> B {
+        let watermark = watermark.as_ref();
+        let mut builder = B::Builder::new_builder(());
+        let mut delta_cursor = delta.cursor();
+        let mut trace_cursor = trace.cursor();
+
+        while delta_cursor.key_valid() {
+            if seek_key_exact(&mut trace_cursor, delta_cursor.key()) {
+                let latest = if self.retain_latest {
+                    find_match::<_, _, _, _, _, T::Cursor<'_>>(
+                        Some(&mut trace_cursor),
+                        None,
+                        watermark,
+                    )
+                } else {
+                    None
+                };
+
+                trace_cursor.rewind_vals();
+                while trace_cursor.val_valid() && &trace_cursor.val().0 < watermark {
+                    if latest.as_ref() != Some(trace_cursor.val()) {
+                        let weight = trace_cursor.weight();
+                        builder.push((
+                            B::item_from(trace_cursor.key().clone(), trace_cursor.val().clone()),
+                            weight.neg_by_ref(),
+                        ));
+                    }
+                    trace_cursor.step_val();
+                }
+            }
+
+            delta_cursor.step_key();
+        }
+
+        builder.done()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        operator::{CollectionHandle, FilterMap},
+        trace::{Batch, BatchReader, Cursor},
+        zset, DBSPHandle, OrdIndexedZSet, OrdZSet, Runtime,
+    };
+    use proptest::{collection, prelude::*};
+
+    type TimeSeries = OrdIndexedZSet<u64, (u64, i64), isize>;
+    type Output = (u64, u64, i64, Option<(u64, i64)>);
+
+    fn join_func(pk: &u64, (ts, v): &(u64, i64), matched: Option<&(u64, i64)>) -> Output {
+        (*pk, *ts, *v, matched.cloned())
+    }
+
+    /// Non-incremental reference implementation of `asof_join`.
+    fn asof_join_slow(left: &TimeSeries, right: &TimeSeries) -> OrdZSet<Output, isize> {
+        let mut tuples = Vec::new();
+        let mut left_cursor = left.cursor();
+        let mut right_cursor = right.cursor();
+
+        while left_cursor.key_valid() {
+            let mut right_vals = Vec::new();
+            right_cursor.seek_key(left_cursor.key());
+            if right_cursor.key_valid() && right_cursor.key() == left_cursor.key() {
+                while right_cursor.val_valid() {
+                    if right_cursor.weight() > 0 {
+                        right_vals.push(*right_cursor.val());
+                    }
+                    right_cursor.step_val();
+                }
+            }
+
+            while left_cursor.val_valid() {
+                let val = *left_cursor.val();
+                let matched = right_vals.iter().rev().find(|(ts, _)| *ts <= val.0);
+                tuples.push((
+                    join_func(left_cursor.key(), &val, matched),
+                    left_cursor.weight(),
+                ));
+                left_cursor.step_val();
+            }
+            left_cursor.step_key();
+        }
+
+        OrdZSet::from_keys((), tuples)
+    }
+
+    #[test]
+    fn asof_join_test() {
+        let (mut circuit, (mut left, mut right, output)) = Runtime::init_circuit(1, |circuit| {
+            let (left, left_handle) = circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+            let (right, right_handle) = circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            let output = left.asof_join(&right, join_func).output();
+
+            (left_handle, right_handle, output)
+        })
+        .unwrap();
+
+        right.append(&mut vec![(1, ((10, 100), 1)), (1, ((20, 200), 1))]);
+        left.append(&mut vec![
+            (1, ((5, 3), 1)),
+            (1, ((15, 1), 1)),
+            (1, ((25, 2), 1)),
+            (2, ((15, 4), 1)),
+        ]);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            zset! {
+                (1, 5, 3, None) => 1,
+                (1, 15, 1, Some((10, 100))) => 1,
+                (1, 25, 2, Some((20, 200))) => 1,
+                (2, 15, 4, None) => 1,
+            }
+        );
+
+        // A late right record replaces the match of the left record at time 15.
+        right.append(&mut vec![(1, ((12, 120), 1))]);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            zset! {
+                (1, 15, 1, Some((10, 100))) => -1,
+                (1, 15, 1, Some((12, 120))) => 1,
+            }
+        );
+
+        // Deleting a right record re-matches the left record at time 25 with
+        // the previous right record.
+        right.append(&mut vec![(1, ((20, 200), -1))]);
+        left.append(&mut vec![(1, ((30, 5), 1))]);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            zset! {
+                (1, 25, 2, Some((20, 200))) => -1,
+                (1, 25, 2, Some((12, 120))) => 1,
+                (1, 30, 5, Some((12, 120))) => 1,
+            }
+        );
+
+        circuit.kill().unwrap();
+    }
+
+    type InputHandle = CollectionHandle<u64, ((u64, i64), isize)>;
+
+    fn asof_join_test_circuit(lateness: u64) -> (DBSPHandle, (InputHandle, InputHandle)) {
+        Runtime::init_circuit(4, move |circuit| {
+            let (left, left_handle) = circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+            let (right, right_handle) = circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            let watermark = left
+                .plus(&right)
+                .map_index(|(_pk, (ts, _v))| (*ts, ()))
+                .watermark_monotonic(move |ts| ts.saturating_sub(lateness));
+
+            let expected = left
+                .gather(0)
+                .integrate()
+                .apply2(&right.gather(0).integrate(), asof_join_slow);
+
+            let output = left.asof_join(&right, join_func).gather(0).integrate();
+            let output_watermark = left
+                .asof_join_with_watermark(&right, &watermark, join_func)
+                .gather(0)
+                .integrate();
+
+            expected.apply2(&output, |expected, actual| assert_eq!(expected, actual));
+            expected.apply2(&output_watermark, |expected, actual| {
+                assert_eq!(expected, actual)
+            });
+
+            (left_handle, right_handle)
+        })
+        .unwrap()
+    }
+
+    type InputTuple = (u64, ((u64, i64), isize));
+    type InputBatch = Vec<InputTuple>;
+
+    fn input_batch(
+        partitions: u64,
+        window: (u64, u64),
+        max_batch_size: usize,
+    ) -> impl Strategy<Value = InputBatch> {
+        collection::vec(
+            (0..partitions, ((window.0..window.1, 0..5i64), -1..=1isize)),
+            0..max_batch_size,
+        )
+    }
+
+    /// Batches whose timestamps grow monotonically, so that no input is older
+    /// than `window_size` relative to the latest timestamp seen so far.
+    fn input_trace_quasi_monotone(
+        partitions: u64,
+        window_size: u64,
+        window_step: u64,
+        max_batch_size: usize,
+        batches: usize,
+    ) -> impl Strategy<Value = Vec<(InputBatch, InputBatch)>> {
+        (0..batches)
+            .map(|i| {
+                let window = (i as u64 * window_step, i as u64 * window_step + window_size);
+                (
+                    input_batch(partitions, window, max_batch_size),
+                    input_batch(partitions, window, max_batch_size),
+                )
+            })
+            .collect::<Vec<_>>()
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_asof_join(trace in input_trace_quasi_monotone(5, 100, 20, 20, 30)) {
+            let (mut circuit, (mut left, mut right)) = asof_join_test_circuit(100);
+
+            for (mut left_batch, mut right_batch) in trace {
+                left.append(&mut left_batch);
+                right.append(&mut right_batch);
+                circuit.step().unwrap();
+            }
+
+            circuit.kill().unwrap();
+        }
+    }
+}
@@ -1,9 +1,11 @@
+mod asof_join;
 mod partitioned;
 mod radix_tree;
 mod range;
 mod rolling_aggregate;
 mod watermark;
 mod window;
+mod window_aggregate;
 
 pub use partitioned::{
     OrdPartitionedIndexedZSet, PartitionCursor, PartitionedBatch, PartitionedBatchReader,
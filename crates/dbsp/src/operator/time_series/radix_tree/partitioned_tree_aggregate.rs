@@ -14,7 +14,7 @@ use crate::{
         Aggregator,
     },
     trace::{Builder, Cursor, Spine},
-    Circuit, DBData, DBWeight, OrdIndexedZSet, RootCircuit, Stream,
+    Circuit, DBData, DBWeight, Error, OrdIndexedZSet, RootCircuit, Stream,
 };
 use num::PrimInt;
 use size_of::SizeOf;
@@ -364,6 +364,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<TS, V, Z, IT, OT, Agg, O> TernaryOperator<Z, IT, OT, O>
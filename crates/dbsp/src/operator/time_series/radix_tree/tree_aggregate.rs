@@ -11,7 +11,7 @@ use crate::{
         Aggregator,
     },
     trace::{Batch, BatchReader, Builder, Spine},
-    Circuit, NumEntries, OrdIndexedZSet, Stream,
+    Circuit, Error, NumEntries, OrdIndexedZSet, Stream,
 };
 use num::PrimInt;
 use size_of::SizeOf;
@@ -186,6 +186,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<Z, IT, OT, Agg, O> TernaryOperator<Z, IT, OT, O> for RadixTreeAggregate<Z, IT, OT, Agg, O>
@@ -17,7 +17,7 @@ use crate::{
         Aggregator, FilterMap,
     },
     trace::{Builder, Cursor, Spine},
-    Circuit, DBData, DBWeight, RootCircuit, Stream,
+    Circuit, DBData, DBWeight, Error, RootCircuit, Stream,
 };
 use num::{Bounded, PrimInt};
 use std::{borrow::Cow, marker::PhantomData, ops::Neg};
@@ -469,6 +469,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<TS, V, Agg, B, T, RT, OT, O> QuaternaryOperator<B, T, RT, OT, O>
@@ -1,6 +1,8 @@
 use crate::{
-    operator::communication::new_exchange_operators,
-    trace::{cursor::Cursor, BatchReader},
+    algebra::IndexedZSet,
+    circuit::{operator_traits::TernaryOperator, Checkpoint},
+    operator::{communication::new_exchange_operators, DelayedFeedback},
+    trace::{cursor::Cursor, BatchReader, Spine},
     Circuit, NumEntries, RootCircuit, Runtime, Stream,
 };
 use size_of::SizeOf;
@@ -33,7 +35,7 @@ where
     pub fn watermark_monotonic<W, TS>(&self, watermark_func: W) -> Stream<RootCircuit, TS>
     where
         W: Fn(&B::Key) -> TS + 'static,
-        TS: Ord + Clone + Default + SizeOf + NumEntries + Send + 'static,
+        TS: Ord + Clone + Default + SizeOf + NumEntries + Checkpoint + Send + 'static,
     {
         let local_watermark = self.stream_fold(TS::default(), move |old_watermark, batch| {
             let mut cursor = batch.cursor();
@@ -74,6 +76,57 @@ where
     }
 }
 
+impl<B> Stream<RootCircuit, B>
+where
+    B: IndexedZSet + Send,
+{
+    /// Integrate a sharded stream into a trace, using `watermark` to
+    /// garbage collect the trace.
+    ///
+    /// At each clock cycle, the `gc` operator is evaluated over the current
+    /// input batch, the current contents of the trace, and the watermark,
+    /// and outputs retractions of records that are no longer needed.  The
+    /// retractions are added to the trace at the next clock cycle.  Operators
+    /// that consume the trace must therefore only rely on records that `gc`
+    /// does not retract.
+    ///
+    /// ```text
+    ///                ┌────┐    ┌───────────────┐   trace
+    /// self ──┬──────►│plus├───►│integrate_trace├─────┬──────►
+    ///        │       └────┘    └───────────────┘     │
+    ///        │          ▲                            ▼
+    ///        │          │     ┌────┐  retractions  ┌──┐
+    ///        │          └─────┤Z^-1│◄──────────────┤gc│◄──── watermark
+    ///        │                └────┘               └──┘
+    ///        │                                       ▲
+    ///        └───────────────────────────────────────┘
+    /// ```
+    ///
+    /// `self` must be sharded.
+    pub(super) fn integrate_trace_with_gc<TS, Op>(
+        &self,
+        watermark: &Stream<RootCircuit, TS>,
+        gc: Op,
+    ) -> Stream<RootCircuit, Spine<B>>
+    where
+        TS: Clone + 'static,
+        Op: TernaryOperator<B, Spine<B>, TS, B>,
+    {
+        let circuit = self.circuit();
+
+        let feedback = <DelayedFeedback<RootCircuit, B>>::new(circuit);
+        feedback.stream().mark_sharded();
+
+        let trace = self.plus(feedback.stream()).integrate_trace();
+        let retractions = circuit
+            .add_ternary_operator(gc, self, &trace, watermark)
+            .mark_sharded();
+        feedback.connect(&retractions);
+
+        trace
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::Runtime;
@@ -3,11 +3,13 @@
 use crate::{
     algebra::{IndexedZSet, NegByRef},
     circuit::{
+        checkpoint::{decode_state, encode_state},
         operator_traits::{Operator, TernaryOperator},
         Circuit, OwnershipPreference, Scope, Stream,
     },
     operator::trace::TraceBound,
     trace::{cursor::Cursor, BatchReader, Spine},
+    Error,
 };
 use std::{borrow::Cow, cmp::max, marker::PhantomData};
 
@@ -125,6 +127,15 @@ where
         // Do we have meaningful examples of using windows inside nested scopes?
         panic!("'Window' operator used in fixedpoint iteration")
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        encode_state(&self.window).map(Some)
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.window = decode_state(state)?;
+        Ok(())
+    }
 }
 
 impl<B> TernaryOperator<Spine<B>, B, (B::Key, B::Key), B> for Window<B>
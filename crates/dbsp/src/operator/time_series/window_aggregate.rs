@@ -0,0 +1,1109 @@
+//! Window aggregation operators.
+
+use crate::{
+    algebra::{HasOne, NegByRef, ZRingValue},
+    circuit::{
+        operator_traits::{BinaryOperator, Operator, TernaryOperator},
+        Scope,
+    },
+    operator::{
+        time_series::{
+            asof_join::seek_key_exact, OrdPartitionedIndexedZSet, PartitionedIndexedZSet,
+        },
+        Aggregator,
+    },
+    trace::{consolidation::consolidate, Batch, BatchReader, Builder, Cursor},
+    Circuit, DBData, DBWeight, Error, OrdZSet, RootCircuit, Stream,
+};
+use num::PrimInt;
+use std::{borrow::Cow, marker::PhantomData};
+
+/// Stream of window aggregates partitioned by partition key and indexed by
+/// window start time.  Values are `(window_end, aggregate)` pairs.
+pub type OrdPartitionedWindowStream<PK, TS, A, R> =
+    Stream<RootCircuit, OrdPartitionedIndexedZSet<PK, TS, (TS, A), R>>;
+
+impl<B> Stream<RootCircuit, B> {
+    /// Tumbling window aggregate of a partitioned time series.
+    ///
+    /// Splits the time axis into non-overlapping windows
+    /// `[k * size, (k + 1) * size)` and computes an aggregate over the
+    /// values in each window within each partition.  Equivalent to
+    /// [`Self::hopping_window_aggregate`] with `hop = size`.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - time series data partitioned by partition key and indexed by
+    ///   time within each partition.
+    /// * `watermark` - monotonically growing lower bound on timestamps in the
+    ///   input stream.
+    /// * `size` - window size.
+    /// * `aggregator` - aggregator used to summarize values within each
+    ///   window.
+    pub fn tumbling_window_aggregate<TS, V, Agg>(
+        &self,
+        watermark: &Stream<RootCircuit, TS>,
+        size: TS,
+        aggregator: Agg,
+    ) -> OrdPartitionedWindowStream<B::Key, TS, Agg::Output, B::R>
+    where
+        B: PartitionedIndexedZSet<TS, V>,
+        B::R: ZRingValue,
+        Agg: Aggregator<V, (), B::R>,
+        TS: DBData + PrimInt,
+        V: DBData,
+    {
+        self.hopping_window_aggregate(watermark, size, size, aggregator)
+    }
+
+    /// Hopping window aggregate of a partitioned time series.
+    ///
+    /// Computes an aggregate over the values in windows
+    /// `[k * hop, k * hop + size)` within each partition.  Windows overlap
+    /// when `hop < size`, in which case each input record contributes to
+    /// several windows.  Outputs one record per non-empty window, indexed
+    /// by partition key and window start time, whose value is the
+    /// `(window_end, aggregate)` pair.
+    ///
+    /// This operator is incremental: when a new or retracted record arrives,
+    /// it retracts previously computed aggregates of all windows the record
+    /// belongs to and emits updated aggregates in their place.
+    ///
+    /// The `watermark` stream bounds the out-of-ordedness of the input
+    /// data by providing a monotonically growing lower bound on
+    /// timestamps that can appear in the input stream, e.g., computed by the
+    /// [`watermark_monotonic`](`Stream::watermark_monotonic`) operator.  The
+    /// operator does not expect inputs with timestamps smaller than the current
+    /// watermark.  Windows that end before the watermark are closed: their
+    /// aggregates can no longer change, and input records that only belong to
+    /// closed windows are discarded.  Garbage collection is performed lazily
+    /// for partitions that receive new inputs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` or `hop` is not positive.
+    pub fn hopping_window_aggregate<TS, V, Agg>(
+        &self,
+        watermark: &Stream<RootCircuit, TS>,
+        size: TS,
+        hop: TS,
+        aggregator: Agg,
+    ) -> OrdPartitionedWindowStream<B::Key, TS, Agg::Output, B::R>
+    where
+        B: PartitionedIndexedZSet<TS, V>,
+        B::R: ZRingValue,
+        Agg: Aggregator<V, (), B::R>,
+        TS: DBData + PrimInt,
+        V: DBData,
+    {
+        assert!(size > TS::zero(), "window size must be positive");
+        assert!(hop > TS::zero(), "window hop must be positive");
+
+        let circuit = self.circuit();
+
+        circuit.region("hopping_window_aggregate", || {
+            let stream = self.shard();
+            let trace =
+                stream.integrate_trace_with_gc(watermark, HoppingWindowGc::<TS, V>::new(size));
+
+            circuit
+                .add_binary_operator(
+                    HoppingWindowAggregate::new(size, hop, aggregator),
+                    &stream,
+                    &trace.delay_trace(),
+                )
+                .mark_sharded()
+        })
+    }
+
+    /// Session window aggregate of a partitioned time series.
+    ///
+    /// Groups records within each partition into sessions, i.e., maximal
+    /// sequences of records where each record is less than `gap` time units
+    /// apart from the previous one, and computes an aggregate over the values
+    /// in each session.  A session that starts at time `start` and whose last
+    /// record has timestamp `last` covers the `[start, last + gap)` window.
+    /// Outputs one record per session, indexed by partition key and session
+    /// start time, whose value is the `(session_end, aggregate)` pair.
+    ///
+    /// This operator is incremental: a new record can extend a session or
+    /// merge two adjacent sessions, while a retraction can shrink or split
+    /// a session.  In all these cases the operator retracts aggregates of
+    /// the old sessions and emits aggregates of the new ones.
+    ///
+    /// The `watermark` stream bounds the out-of-ordedness of the input
+    /// data by providing a monotonically growing lower bound on
+    /// timestamps that can appear in the input stream.  The operator does not
+    /// expect inputs with timestamps smaller than the current watermark.
+    /// Sessions that end before the watermark are closed: they can no longer
+    /// be extended, and their records are discarded.  Garbage collection is
+    /// performed lazily for partitions that receive new inputs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `gap` is not positive.
+    pub fn session_window_aggregate<TS, V, Agg>(
+        &self,
+        watermark: &Stream<RootCircuit, TS>,
+        gap: TS,
+        aggregator: Agg,
+    ) -> OrdPartitionedWindowStream<B::Key, TS, Agg::Output, B::R>
+    where
+        B: PartitionedIndexedZSet<TS, V>,
+        B::R: ZRingValue,
+        Agg: Aggregator<V, (), B::R>,
+        TS: DBData + PrimInt,
+        V: DBData,
+    {
+        assert!(gap > TS::zero(), "session gap must be positive");
+
+        let circuit = self.circuit();
+
+        circuit.region("session_window_aggregate", || {
+            let stream = self.shard();
+            let trace =
+                stream.integrate_trace_with_gc(watermark, SessionWindowGc::<TS, V>::new(gap));
+
+            circuit
+                .add_ternary_operator(
+                    SessionWindowAggregate::new(gap, aggregator),
+                    &stream,
+                    &trace.delay_trace(),
+                    watermark,
+                )
+                .mark_sharded()
+        })
+    }
+}
+
+/// Start times of all hopping windows that contain `ts`, in ascending order.
+fn window_starts<TS>(ts: TS, size: TS, hop: TS) -> Vec<TS>
+where
+    TS: PrimInt,
+{
+    let mut rem = ts % hop;
+    if rem < TS::zero() {
+        rem = rem + hop;
+    }
+
+    let mut starts = Vec::new();
+    let mut start = Some(ts - rem);
+    while let Some(s) = start {
+        if ts - s >= size {
+            break;
+        }
+        starts.push(s);
+        start = s.checked_sub(&hop);
+    }
+
+    starts.reverse();
+    starts
+}
+
+/// Aggregate weighted values with `aggregator`.
+fn aggregate_values<V, R, Agg>(aggregator: &Agg, values: Vec<(V, R)>) -> Option<Agg::Output>
+where
+    V: DBData,
+    R: DBWeight,
+    Agg: Aggregator<V, (), R>,
+{
+    let values = <OrdZSet<V, R>>::from_keys((), values);
+    aggregator.aggregate_and_finalize(&mut values.cursor())
+}
+
+/// Values with timestamps in `[from, to)` under the current key of `cursor`.
+fn range_values<K, TS, V, R, C>(cursor: &mut C, from: &TS, to: &TS) -> Vec<((TS, V), R)>
+where
+    C: Cursor<K, (TS, V), (), R>,
+    TS: DBData,
+    V: DBData,
+    R: DBWeight,
+{
+    let mut values = Vec::new();
+
+    cursor.seek_val_with(|(ts, _)| ts >= from);
+    while cursor.val_valid() && &cursor.val().0 < to {
+        values.push((cursor.val().clone(), cursor.weight()));
+        cursor.step_val();
+    }
+
+    values
+}
+
+/// All values under the current key of `cursor`.
+fn partition_values<K, TS, V, R, C>(cursor: &mut C) -> Vec<((TS, V), R)>
+where
+    C: Cursor<K, (TS, V), (), R>,
+    TS: DBData,
+    V: DBData,
+    R: DBWeight,
+{
+    let mut values = Vec::new();
+
+    while cursor.val_valid() {
+        values.push((cursor.val().clone(), cursor.weight()));
+        cursor.step_val();
+    }
+
+    values
+}
+
+/// Apply `updates` to `values`.
+fn apply_updates<T, R>(values: &[(T, R)], updates: Vec<(T, R)>) -> Vec<(T, R)>
+where
+    T: DBData,
+    R: DBWeight,
+{
+    let mut result = values.to_vec();
+    result.extend(updates);
+    consolidate(&mut result);
+    result
+}
+
+/// Aggregate values with timestamps in `[from, to)` in `values` sorted by
+/// timestamp.
+fn aggregate_window<TS, V, R, Agg>(
+    values: &[((TS, V), R)],
+    from: &TS,
+    to: &TS,
+    aggregator: &Agg,
+) -> Option<Agg::Output>
+where
+    TS: Ord,
+    V: DBData,
+    R: DBWeight,
+    Agg: Aggregator<V, (), R>,
+{
+    let lower = values.partition_point(|((ts, _), _)| ts < from);
+    let upper = values.partition_point(|((ts, _), _)| ts < to);
+
+    aggregate_values(
+        aggregator,
+        values[lower..upper]
+            .iter()
+            .map(|((_, v), w)| (v.clone(), w.clone()))
+            .collect(),
+    )
+}
+
+/// Split `values` sorted by timestamp into sessions.
+///
+/// Returns a `(start, end, values)` tuple for each session.  Only values
+/// with positive weights are taken into account.
+#[allow(clippy::type_complexity)]
+fn sessions<TS, V, R>(values: &[((TS, V), R)], gap: TS) -> Vec<(TS, TS, Vec<(V, R)>)>
+where
+    TS: PrimInt,
+    V: Clone,
+    R: ZRingValue,
+{
+    let mut sessions: Vec<(TS, TS, Vec<(V, R)>)> = Vec::new();
+
+    for ((ts, v), weight) in values {
+        if weight.le0() {
+            continue;
+        }
+        let end = ts.saturating_add(gap);
+
+        match sessions.last_mut() {
+            Some(session) if ts < &session.1 => {
+                session.1 = end;
+                session.2.push((v.clone(), weight.clone()));
+            }
+            _ => sessions.push((*ts, end, vec![(v.clone(), weight.clone())])),
+        }
+    }
+
+    sessions
+}
+
+/// Binary operator that implements the internals of
+/// `hopping_window_aggregate`.
+///
+/// * Input stream 1: updates to the time series.
+/// * Input stream 2: trace of the time series up to the previous clock cycle.
+///
+/// For each window that contains at least one updated record, computes the
+/// old aggregate from input stream 2 and the new aggregate from input stream 2
+/// with updates in input stream 1 applied, and outputs the difference.
+struct HoppingWindowAggregate<TS, V, Agg> {
+    size: TS,
+    hop: TS,
+    aggregator: Agg,
+    phantom: PhantomData<V>,
+}
+
+impl<TS, V, Agg> HoppingWindowAggregate<TS, V, Agg> {
+    fn new(size: TS, hop: TS, aggregator: Agg) -> Self {
+        Self {
+            size,
+            hop,
+            aggregator,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<TS, V, Agg> Operator for HoppingWindowAggregate<TS, V, Agg>
+where
+    TS: 'static,
+    V: 'static,
+    Agg: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("HoppingWindowAggregate")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+}
+
+impl<TS, V, Agg, B, T>
+    BinaryOperator<B, T, OrdPartitionedIndexedZSet<B::Key, TS, (TS, Agg::Output), B::R>>
+    for HoppingWindowAggregate<TS, V, Agg>
+where
+    TS: DBData + PrimInt,
+    V: DBData,
+    Agg: Aggregator<V, (), B::R>,
+    B: PartitionedIndexedZSet<TS, V>,
+    B::R: ZRingValue,
+    T: BatchReader<Key = B::Key, Val = (TS, V), Time = (), R = B::R>,
+{
+    fn eval(
+        &mut self,
+        delta: &B,
+        delayed_trace: &T,
+    ) -> OrdPartitionedIndexedZSet<B::Key, TS, (TS, Agg::Output), B::R> {
+        let mut tuples = Vec::new();
+        let mut delta_cursor = delta.cursor();
+        let mut delayed_trace_cursor = delayed_trace.cursor();
+
+        while delta_cursor.key_valid() {
+            // Windows affected by updates to the current partition.
+            let mut starts = Vec::new();
+            let mut updates = Vec::new();
+            while delta_cursor.val_valid() {
+                starts.extend(window_starts(delta_cursor.val().0, self.size, self.hop));
+                updates.push((delta_cursor.val().clone(), delta_cursor.weight()));
+                delta_cursor.step_val();
+            }
+            starts.sort();
+            starts.dedup();
+
+            let key = delta_cursor.key();
+            if let (Some(first), Some(last)) = (starts.first(), starts.last()) {
+                let old_values = if seek_key_exact(&mut delayed_trace_cursor, key) {
+                    range_values(
+                        &mut delayed_trace_cursor,
+                        first,
+                        &last.saturating_add(self.size),
+                    )
+                } else {
+                    Vec::new()
+                };
+                let new_values = apply_updates(&old_values, updates);
+
+                for start in starts.iter() {
+                    let end = start.saturating_add(self.size);
+                    let old = aggregate_window(&old_values, start, &end, &self.aggregator);
+                    let new = aggregate_window(&new_values, start, &end, &self.aggregator);
+
+                    if old != new {
+                        if let Some(old) = old {
+                            tuples.push((
+                                (key.clone(), (*start, (end, old))),
+                                B::R::one().neg_by_ref(),
+                            ));
+                        }
+                        if let Some(new) = new {
+                            tuples.push(((key.clone(), (*start, (end, new))), B::R::one()));
+                        }
+                    }
+                }
+            }
+
+            delta_cursor.step_key();
+        }
+
+        OrdPartitionedIndexedZSet::from_tuples((), tuples)
+    }
+}
+
+/// Ternary operator that computes retractions of records that are no longer
+/// needed by `hopping_window_aggregate`.
+///
+/// * Input stream 1: updates to a time series.  Used to identify partitions to
+///   garbage collect.
+/// * Input stream 2: trace containing the accumulated time series data.
+/// * Input stream 3: watermark.
+///
+/// Retracts all records with timestamps `ts` such that `ts + size <=
+/// watermark` in each partition in input stream 1.  All windows that contain
+/// such records are closed.
+struct HoppingWindowGc<TS, V> {
+    size: TS,
+    phantom: PhantomData<V>,
+}
+
+impl<TS, V> HoppingWindowGc<TS, V> {
+    fn new(size: TS) -> Self {
+        Self {
+            size,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<TS, V> Operator for HoppingWindowGc<TS, V>
+where
+    TS: 'static,
+    V: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("HoppingWindowGc")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+}
+
+impl<TS, V, B, T> TernaryOperator<B, T, TS, B> for HoppingWindowGc<TS, V>
+where
+    TS: DBData + PrimInt,
+    V: DBData,
+    B: PartitionedIndexedZSet<TS, V>,
+    B::R: ZRingValue,
+    T: BatchReader<Key = B::Key, Val = (TS, V), Time = (), R = B::R> + Clone,
+{
+    fn eval<'a>(&mut self, delta: Cow<'a, B>, trace: Cow<'a, T>, watermark: Cow<'a, TS>) -> B {
+        let watermark = *watermark.as_ref();
+        let mut builder = B::Builder::new_builder(());
+        let mut delta_cursor = delta.cursor();
+        let mut trace_cursor = trace.cursor();
+
+        while delta_cursor.key_valid() {
+            if seek_key_exact(&mut trace_cursor, delta_cursor.key()) {
+                while trace_cursor.val_valid()
+                    && trace_cursor.val().0.saturating_add(self.size) <= watermark
+                {
+                    let weight = trace_cursor.weight();
+                    builder.push((
+                        B::item_from(trace_cursor.key().clone(), trace_cursor.val().clone()),
+                        weight.neg_by_ref(),
+                    ));
+                    trace_cursor.step_val();
+                }
+            }
+
+            delta_cursor.step_key();
+        }
+
+        builder.done()
+    }
+}
+
+/// Ternary operator that implements the internals of
+/// `session_window_aggregate`.
+///
+/// * Input stream 1: updates to the time series.
+/// * Input stream 2: trace of the time series up to the previous clock cycle.
+/// * Input stream 3: watermark.
+///
+/// For each partition in input stream 1, retracts aggregates of old sessions
+/// computed from input stream 2 and emits aggregates of new sessions computed
+/// from input stream 2 with updates in input stream 1 applied.  Closed
+/// sessions, which end before the watermark, are skipped.
+struct SessionWindowAggregate<TS, V, Agg> {
+    gap: TS,
+    aggregator: Agg,
+    phantom: PhantomData<V>,
+}
+
+impl<TS, V, Agg> SessionWindowAggregate<TS, V, Agg> {
+    fn new(gap: TS, aggregator: Agg) -> Self {
+        Self {
+            gap,
+            aggregator,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Aggregate open sessions in `values` and push them to `tuples` with
+    /// weight `weight`.
+    #[allow(clippy::type_complexity)]
+    fn push_sessions<K, R>(
+        &self,
+        key: &K,
+        values: &[((TS, V), R)],
+        watermark: &TS,
+        weight: &R,
+        tuples: &mut Vec<((K, (TS, (TS, Agg::Output))), R)>,
+    ) where
+        TS: PrimInt,
+        V: DBData,
+        K: Clone,
+        R: DBWeight + ZRingValue,
+        Agg: Aggregator<V, (), R>,
+    {
+        for (start, end, values) in sessions(values, self.gap) {
+            if &end <= watermark {
+                continue;
+            }
+            if let Some(agg) = aggregate_values(&self.aggregator, values) {
+                tuples.push(((key.clone(), (start, (end, agg))), weight.clone()));
+            }
+        }
+    }
+}
+
+impl<TS, V, Agg> Operator for SessionWindowAggregate<TS, V, Agg>
+where
+    TS: 'static,
+    V: 'static,
+    Agg: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("SessionWindowAggregate")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+}
+
+impl<TS, V, Agg, B, T>
+    TernaryOperator<B, T, TS, OrdPartitionedIndexedZSet<B::Key, TS, (TS, Agg::Output), B::R>>
+    for SessionWindowAggregate<TS, V, Agg>
+where
+    TS: DBData + PrimInt,
+    V: DBData,
+    Agg: Aggregator<V, (), B::R>,
+    B: PartitionedIndexedZSet<TS, V>,
+    B::R: ZRingValue,
+    T: BatchReader<Key = B::Key, Val = (TS, V), Time = (), R = B::R> + Clone,
+{
+    fn eval<'a>(
+        &mut self,
+        delta: Cow<'a, B>,
+        delayed_trace: Cow<'a, T>,
+        watermark: Cow<'a, TS>,
+    ) -> OrdPartitionedIndexedZSet<B::Key, TS, (TS, Agg::Output), B::R> {
+        let watermark = watermark.as_ref();
+        let one = B::R::one();
+        let minus_one = one.neg_by_ref();
+
+        let mut tuples = Vec::new();
+        let mut delta_cursor = delta.cursor();
+        let mut delayed_trace_cursor = delayed_trace.cursor();
+
+        while delta_cursor.key_valid() {
+            let mut updates = Vec::new();
+            while delta_cursor.val_valid() {
+                updates.push((delta_cursor.val().clone(), delta_cursor.weight()));
+                delta_cursor.step_val();
+            }
+
+            let key = delta_cursor.key();
+            let old_values = if seek_key_exact(&mut delayed_trace_cursor, key) {
+                partition_values(&mut delayed_trace_cursor)
+            } else {
+                Vec::new()
+            };
+            let new_values = apply_updates(&old_values, updates);
+
+            self.push_sessions(key, &old_values, watermark, &minus_one, &mut tuples);
+            self.push_sessions(key, &new_values, watermark, &one, &mut tuples);
+
+            delta_cursor.step_key();
+        }
+
+        // Sessions that did not change cancel out.
+        OrdPartitionedIndexedZSet::from_tuples((), tuples)
+    }
+}
+
+/// Ternary operator that computes retractions of records that are no longer
+/// needed by `session_window_aggregate`.
+///
+/// * Input stream 1: updates to a time series.  Used to identify partitions to
+///   garbage collect.
+/// * Input stream 2: trace containing the accumulated time series data.
+/// * Input stream 3: watermark.
+///
+/// Retracts all records that belong to closed sessions, i.e., sessions that
+/// end before the watermark, in each partition in input stream 1.  Closed
+/// sessions form a prefix of each partition, so this amounts to retracting
+/// all records with timestamps smaller than the end of the last closed
+/// session.
+struct SessionWindowGc<TS, V> {
+    gap: TS,
+    phantom: PhantomData<V>,
+}
+
+impl<TS, V> SessionWindowGc<TS, V> {
+    fn new(gap: TS) -> Self {
+        Self {
+            gap,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<TS, V> Operator for SessionWindowGc<TS, V>
+where
+    TS: 'static,
+    V: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("SessionWindowGc")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+}
+
+impl<TS, V, B, T> TernaryOperator<B, T, TS, B> for SessionWindowGc<TS, V>
+where
+    TS: DBData + PrimInt,
+    V: DBData,
+    B: PartitionedIndexedZSet<TS, V>,
+    B::R: ZRingValue,
+    T: BatchReader<Key = B::Key, Val = (TS, V), Time = (), R = B::R> + Clone,
+{
+    fn eval<'a>(&mut self, delta: Cow<'a, B>, trace: Cow<'a, T>, watermark: Cow<'a, TS>) -> B {
+        let watermark = *watermark.as_ref();
+        let mut builder = B::Builder::new_builder(());
+        let mut delta_cursor = delta.cursor();
+        let mut trace_cursor = trace.cursor();
+
+        while delta_cursor.key_valid() {
+            if seek_key_exact(&mut trace_cursor, delta_cursor.key()) {
+                // Find the end of the last closed session.
+                let mut closed_end = None;
+                let mut session_end: Option<TS> = None;
+                while trace_cursor.val_valid() {
+                    if !trace_cursor.weight().le0() {
+                        let ts = trace_cursor.val().0;
+                        if let Some(end) = session_end {
+                            if ts >= end {
+                                if end > watermark {
+                                    break;
+                                }
+                                closed_end = Some(end);
+                            }
+                        }
+                        session_end = Some(ts.saturating_add(self.gap));
+                    }
+                    trace_cursor.step_val();
+                }
+                if !trace_cursor.val_valid() {
+                    if let Some(end) = session_end {
+                        if end <= watermark {
+                            closed_end = Some(end);
+                        }
+                    }
+                }
+
+                if let Some(closed_end) = closed_end {
+                    trace_cursor.rewind_vals();
+                    while trace_cursor.val_valid() && trace_cursor.val().0 < closed_end {
+                        let weight = trace_cursor.weight();
+                        builder.push((
+                            B::item_from(trace_cursor.key().clone(), trace_cursor.val().clone()),
+                            weight.neg_by_ref(),
+                        ));
+                        trace_cursor.step_val();
+                    }
+                }
+            }
+
+            delta_cursor.step_key();
+        }
+
+        builder.done()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::window_starts;
+    use crate::{
+        algebra::DefaultSemigroup,
+        indexed_zset,
+        operator::{Aggregator, CollectionHandle, FilterMap, Fold},
+        trace::{Batch, BatchReader, Cursor},
+        DBSPHandle, OrdIndexedZSet, OrdZSet, RootCircuit, Runtime, Stream,
+    };
+    use proptest::{collection, prelude::*};
+    use std::collections::BTreeMap;
+
+    type DataBatch = OrdIndexedZSet<u64, (u64, i64), isize>;
+    type OutputBatch = OrdIndexedZSet<u64, (u64, (u64, i64)), isize>;
+
+    fn sum() -> impl Aggregator<i64, (), isize, Output = i64> {
+        <Fold<_, DefaultSemigroup<_>, _, _>>::new(0i64, |agg: &mut i64, val: &i64, w: isize| {
+            *agg += val * (w as i64)
+        })
+    }
+
+    fn sum_values(values: Vec<(i64, isize)>) -> Option<i64> {
+        sum().aggregate_and_finalize(&mut <OrdZSet<i64, isize>>::from_keys((), values).cursor())
+    }
+
+    /// Non-incremental reference implementation of `hopping_window_aggregate`.
+    fn hopping_window_aggregate_slow(batch: &DataBatch, size: u64, hop: u64) -> OutputBatch {
+        let mut windows = BTreeMap::<(u64, u64), Vec<(i64, isize)>>::new();
+        let mut cursor = batch.cursor();
+
+        while cursor.key_valid() {
+            while cursor.val_valid() {
+                let (ts, v) = *cursor.val();
+                for start in (0..=ts).step_by(hop as usize) {
+                    if ts - start < size {
+                        windows
+                            .entry((*cursor.key(), start))
+                            .or_default()
+                            .push((v, cursor.weight()));
+                    }
+                }
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+
+        let tuples = windows
+            .into_iter()
+            .filter_map(|((pk, start), values)| {
+                sum_values(values).map(|agg| ((pk, (start, (start + size, agg))), 1))
+            })
+            .collect();
+        OutputBatch::from_tuples((), tuples)
+    }
+
+    /// Non-incremental reference implementation of `session_window_aggregate`.
+    fn session_window_aggregate_slow(batch: &DataBatch, gap: u64) -> OutputBatch {
+        let mut tuples = Vec::new();
+        let mut cursor = batch.cursor();
+
+        while cursor.key_valid() {
+            let mut sessions: Vec<(u64, u64, Vec<(i64, isize)>)> = Vec::new();
+            while cursor.val_valid() {
+                let (ts, v) = *cursor.val();
+                let weight = cursor.weight();
+                if weight > 0 {
+                    match sessions.last_mut() {
+                        Some((_, end, values)) if ts < *end => {
+                            *end = ts + gap;
+                            values.push((v, weight));
+                        }
+                        _ => sessions.push((ts, ts + gap, vec![(v, weight)])),
+                    }
+                }
+                cursor.step_val();
+            }
+
+            for (start, end, values) in sessions {
+                let agg = sum_values(values).unwrap();
+                tuples.push(((*cursor.key(), (start, (end, agg))), 1));
+            }
+            cursor.step_key();
+        }
+
+        OutputBatch::from_tuples((), tuples)
+    }
+
+    #[test]
+    fn window_starts_test() {
+        assert_eq!(window_starts(12u64, 10, 10), vec![10]);
+        assert_eq!(window_starts(12u64, 10, 5), vec![5, 10]);
+        assert_eq!(window_starts(3u64, 10, 5), vec![0]);
+        assert_eq!(window_starts(7u64, 5, 10), vec![]);
+        assert_eq!(window_starts(-3i64, 10, 5), vec![-10, -5]);
+    }
+
+    #[test]
+    fn tumbling_window_test() {
+        let (mut circuit, (mut input, watermark, output)) = Runtime::init_circuit(1, |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+            let (watermark, watermark_handle) = circuit.add_input_stream::<u64>();
+
+            let output = input
+                .tumbling_window_aggregate(&watermark, 10, sum())
+                .output();
+
+            (input_handle, watermark_handle, output)
+        })
+        .unwrap();
+
+        input.append(&mut vec![
+            (1, ((1, 10), 1)),
+            (1, ((5, 20), 1)),
+            (1, ((12, 5), 1)),
+            (2, ((3, 1), 1)),
+        ]);
+        watermark.set_for_all(0);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! {
+                1 => {(0, (10, 30)) => 1, (10, (20, 5)) => 1},
+                2 => {(0, (10, 1)) => 1},
+            }
+        );
+
+        input.append(&mut vec![(1, ((15, 5), 1))]);
+        watermark.set_for_all(10);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! { 1 => {(10, (20, 5)) => -1, (10, (20, 10)) => 1} }
+        );
+
+        input.append(&mut vec![(1, ((25, 1), 1))]);
+        watermark.set_for_all(20);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! { 1 => {(20, (30, 1)) => 1} }
+        );
+
+        circuit.kill().unwrap();
+    }
+
+    #[test]
+    fn hopping_window_test() {
+        let (mut circuit, (mut input, watermark, output)) = Runtime::init_circuit(1, |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+            let (watermark, watermark_handle) = circuit.add_input_stream::<u64>();
+
+            let output = input
+                .hopping_window_aggregate(&watermark, 10, 5, sum())
+                .output();
+
+            (input_handle, watermark_handle, output)
+        })
+        .unwrap();
+
+        input.append(&mut vec![
+            (1, ((1, 10), 1)),
+            (1, ((5, 20), 1)),
+            (1, ((12, 5), 1)),
+            (2, ((3, 1), 1)),
+        ]);
+        watermark.set_for_all(0);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! {
+                1 => {(0, (10, 30)) => 1, (5, (15, 25)) => 1, (10, (20, 5)) => 1},
+                2 => {(0, (10, 1)) => 1},
+            }
+        );
+
+        // Retracting a record updates all windows it belongs to.
+        input.append(&mut vec![(1, ((5, 20), -1))]);
+        watermark.set_for_all(5);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! {
+                1 => {
+                    (0, (10, 30)) => -1,
+                    (0, (10, 10)) => 1,
+                    (5, (15, 25)) => -1,
+                    (5, (15, 5)) => 1
+                }
+            }
+        );
+
+        circuit.kill().unwrap();
+    }
+
+    #[test]
+    fn session_window_test() {
+        let (mut circuit, (mut input, watermark, output)) = Runtime::init_circuit(1, |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+            let (watermark, watermark_handle) = circuit.add_input_stream::<u64>();
+
+            let output = input
+                .session_window_aggregate(&watermark, 5, sum())
+                .output();
+
+            (input_handle, watermark_handle, output)
+        })
+        .unwrap();
+
+        input.append(&mut vec![
+            (1, ((1, 10), 1)),
+            (1, ((5, 20), 1)),
+            (1, ((12, 5), 1)),
+            (2, ((3, 1), 1)),
+        ]);
+        watermark.set_for_all(0);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! {
+                1 => {(1, (10, 30)) => 1, (12, (17, 5)) => 1},
+                2 => {(3, (8, 1)) => 1},
+            }
+        );
+
+        // A new record merges two sessions.
+        input.append(&mut vec![(1, ((8, 1), 1))]);
+        watermark.set_for_all(0);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! {
+                1 => {(1, (10, 30)) => -1, (12, (17, 5)) => -1, (1, (17, 36)) => 1}
+            }
+        );
+
+        // The watermark closes the first session.
+        input.append(&mut vec![(1, ((30, 2), 1))]);
+        watermark.set_for_all(20);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! { 1 => {(30, (35, 2)) => 1} }
+        );
+
+        input.append(&mut vec![(1, ((32, 1), 1))]);
+        watermark.set_for_all(20);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! { 1 => {(30, (35, 2)) => -1, (30, (37, 3)) => 1} }
+        );
+
+        circuit.kill().unwrap();
+    }
+
+    type InputHandle = CollectionHandle<u64, ((u64, i64), isize)>;
+
+    fn window_test_circuit<F>(lateness: u64, window_func: F) -> (DBSPHandle, InputHandle)
+    where
+        F: Fn(
+                &Stream<RootCircuit, DataBatch>,
+                &Stream<RootCircuit, u64>,
+            ) -> (
+                Stream<RootCircuit, OutputBatch>,
+                Stream<RootCircuit, OutputBatch>,
+            ) + Clone
+            + Send
+            + 'static,
+    {
+        Runtime::init_circuit(4, move |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            let watermark = input
+                .map_index(|(_pk, (ts, _v))| (*ts, ()))
+                .watermark_monotonic(move |ts| ts.saturating_sub(lateness));
+
+            let (output, expected) = window_func(&input, &watermark);
+            let output = output.gather(0).integrate();
+
+            expected.apply2(&output, |expected, actual| assert_eq!(expected, actual));
+
+            input_handle
+        })
+        .unwrap()
+    }
+
+    type InputBatch = Vec<(u64, ((u64, i64), isize))>;
+
+    /// Batches whose timestamps grow monotonically, so that no input is older
+    /// than `window_size` relative to the latest timestamp seen so far.
+    fn input_trace_quasi_monotone(
+        partitions: u64,
+        window_size: u64,
+        window_step: u64,
+        max_batch_size: usize,
+        batches: usize,
+    ) -> impl Strategy<Value = Vec<InputBatch>> {
+        (0..batches)
+            .map(|i| {
+                let window = (i as u64 * window_step, i as u64 * window_step + window_size);
+                collection::vec(
+                    (0..partitions, ((window.0..window.1, 0..5i64), -1..=1isize)),
+                    0..max_batch_size,
+                )
+            })
+            .collect::<Vec<_>>()
+    }
+
+    fn run_test(circuit: (DBSPHandle, InputHandle), trace: Vec<InputBatch>) {
+        let (mut circuit, mut input) = circuit;
+
+        for mut batch in trace {
+            input.append(&mut batch);
+            circuit.step().unwrap();
+        }
+
+        circuit.kill().unwrap();
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_tumbling_window(trace in input_trace_quasi_monotone(5, 100, 20, 20, 30)) {
+            let circuit = window_test_circuit(100, |input, watermark| {
+                (
+                    input.tumbling_window_aggregate(watermark, 25, sum()),
+                    input
+                        .gather(0)
+                        .integrate()
+                        .apply(|batch| hopping_window_aggregate_slow(batch, 25, 25)),
+                )
+            });
+            run_test(circuit, trace);
+        }
+
+        #[test]
+        fn proptest_hopping_window(trace in input_trace_quasi_monotone(5, 100, 20, 20, 30)) {
+            let circuit = window_test_circuit(100, |input, watermark| {
+                (
+                    input.hopping_window_aggregate(watermark, 30, 10, sum()),
+                    input
+                        .gather(0)
+                        .integrate()
+                        .apply(|batch| hopping_window_aggregate_slow(batch, 30, 10)),
+                )
+            });
+            run_test(circuit, trace);
+        }
+
+        #[test]
+        fn proptest_session_window(trace in input_trace_quasi_monotone(5, 100, 20, 20, 30)) {
+            let circuit = window_test_circuit(100, |input, watermark| {
+                (
+                    input.session_window_aggregate(watermark, 15, sum()),
+                    input
+                        .gather(0)
+                        .integrate()
+                        .apply(|batch| session_window_aggregate_slow(batch, 15)),
+                )
+            });
+            run_test(circuit, trace);
+        }
+    }
+}
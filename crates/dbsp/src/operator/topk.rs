@@ -0,0 +1,541 @@
+//! Top-K operators.
+
+use crate::{
+    algebra::{AddByRef, HasZero, IndexedZSet, NegByRef, ZRingValue},
+    circuit::{
+        operator_traits::{BinaryOperator, Operator},
+        Scope, Stream,
+    },
+    operator::FilterMap,
+    trace::{BatchReader, Builder, Cursor},
+    Circuit, DBData, DBWeight, Error, OrdIndexedZSet, RootCircuit,
+};
+use size_of::{Context, SizeOf};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    fmt::{self, Debug},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+/// A comparison function used to order values in
+/// [`Stream::topk_custom_order`].
+///
+/// The function must define a total order that is consistent with `Eq`,
+/// i.e., it returns `Ordering::Equal` only for equal values.
+pub trait CmpFunc<T>: Send + 'static {
+    fn cmp(left: &T, right: &T) -> Ordering;
+}
+
+/// A wrapper around `T` that orders values using comparison function `F`
+/// rather than `T`'s `Ord` implementation.
+pub struct WithCustomOrd<T, F> {
+    pub val: T,
+    _cmp: PhantomData<F>,
+}
+
+impl<T, F> WithCustomOrd<T, F> {
+    pub fn new(val: T) -> Self {
+        Self {
+            val,
+            _cmp: PhantomData,
+        }
+    }
+}
+
+impl<T: Clone, F> Clone for WithCustomOrd<T, F> {
+    fn clone(&self) -> Self {
+        Self::new(self.val.clone())
+    }
+}
+
+impl<T: Debug, F> Debug for WithCustomOrd<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.val.fmt(f)
+    }
+}
+
+impl<T: Hash, F> Hash for WithCustomOrd<T, F> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.val.hash(state);
+    }
+}
+
+impl<T, F: CmpFunc<T>> PartialEq for WithCustomOrd<T, F> {
+    fn eq(&self, other: &Self) -> bool {
+        F::cmp(&self.val, &other.val) == Ordering::Equal
+    }
+}
+
+impl<T, F: CmpFunc<T>> Eq for WithCustomOrd<T, F> {}
+
+impl<T, F: CmpFunc<T>> PartialOrd for WithCustomOrd<T, F> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T, F: CmpFunc<T>> Ord for WithCustomOrd<T, F> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        F::cmp(&self.val, &other.val)
+    }
+}
+
+impl<T: SizeOf, F> SizeOf for WithCustomOrd<T, F> {
+    fn size_of_children(&self, context: &mut Context) {
+        self.val.size_of_children(context);
+    }
+}
+
+impl<T, F> bincode::Encode for WithCustomOrd<T, F>
+where
+    T: bincode::Encode,
+{
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> core::result::Result<(), bincode::error::EncodeError> {
+        bincode::Encode::encode(&self.val, encoder)
+    }
+}
+
+impl<T, F> bincode::Decode for WithCustomOrd<T, F>
+where
+    T: bincode::Decode,
+{
+    fn decode<D: bincode::de::Decoder>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        let val: T = bincode::Decode::decode(decoder)?;
+        Ok(Self::new(val))
+    }
+}
+
+impl<B> Stream<RootCircuit, B>
+where
+    B: IndexedZSet + Send,
+    B::R: ZRingValue,
+{
+    /// Pick `k` smallest values in each group.
+    ///
+    /// For each key in the input stream, removes all but `k` smallest values
+    /// with positive weights.  Values are compared using the `Ord`
+    /// implementation of the value type; the output retains the weights of
+    /// the selected values.
+    ///
+    /// This is an incremental operator: it transforms a stream of changes to
+    /// the input collection into a stream of changes to the top-K set of each
+    /// group.  Only groups modified by the current input batch are
+    /// re-evaluated, by scanning their values in the integrated trace of the
+    /// input until `k` values have been found.
+    pub fn topk_asc(&self, k: usize) -> Self {
+        self.topk(k, true)
+    }
+
+    /// Pick `k` largest values in each group.
+    ///
+    /// Like [`Self::topk_asc`], but keeps the `k` largest values of each
+    /// group.
+    pub fn topk_desc(&self, k: usize) -> Self {
+        self.topk(k, false)
+    }
+
+    fn topk(&self, k: usize, ascending: bool) -> Self {
+        let circuit = self.circuit();
+        let stream = self.shard();
+
+        circuit.region("topk", || {
+            circuit
+                .add_binary_operator(
+                    TopK::new(k, ascending),
+                    &stream,
+                    &stream.integrate_trace().delay_trace(),
+                )
+                .mark_sharded()
+        })
+    }
+}
+
+impl<K, V, R> Stream<RootCircuit, OrdIndexedZSet<K, V, R>>
+where
+    K: DBData,
+    V: DBData,
+    R: DBWeight + ZRingValue,
+{
+    /// Pick `k` smallest values in each group according to comparison
+    /// function `F`.
+    ///
+    /// Like [`Self::topk_asc`], but orders values using `F` instead of their
+    /// `Ord` implementation.  Values are wrapped in [`WithCustomOrd`], so that
+    /// the integrated trace of the input is sorted in the custom order and
+    /// only needs to be scanned up to the `k`-th value of each group.
+    pub fn topk_custom_order<F>(&self, k: usize) -> Self
+    where
+        F: CmpFunc<V>,
+    {
+        self.map_index(|(key, val)| (key.clone(), <WithCustomOrd<V, F>>::new(val.clone())))
+            .topk_asc(k)
+            .map_index(|(key, val)| (key.clone(), val.val.clone()))
+    }
+}
+
+/// Incremental top-K operator that works in the root scope.
+///
+/// This is a binary operator with the following inputs:
+/// * `delta` - stream of changes to the input indexed Z-set.
+/// * `delayed_integral` - the integral of the input stream up to the
+///   previous clock cycle.
+///
+/// For each key in `delta`, the operator merges the values of the key in
+/// `delta` and `delayed_integral` in ascending (or descending) order, and
+/// tracks the first `k` values with positive weights before and after
+/// applying `delta`.  The output contains the retractions of values that
+/// left the top-K set (or changed their weight) and insertions of values
+/// that entered it.
+struct TopK<Z, I> {
+    k: usize,
+    ascending: bool,
+    _type: PhantomData<(Z, I)>,
+}
+
+impl<Z, I> TopK<Z, I> {
+    fn new(k: usize, ascending: bool) -> Self {
+        Self {
+            k,
+            ascending,
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<Z, I> Operator for TopK<Z, I>
+where
+    Z: 'static,
+    I: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("TopK")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+}
+
+impl<Z, I> TopK<Z, I>
+where
+    Z: IndexedZSet,
+    Z::R: ZRingValue,
+    I: BatchReader<Key = Z::Key, Val = Z::Val, Time = (), R = Z::R>,
+{
+    /// Move `cursor` to the first value of the current key in the scan order.
+    fn first_val<C>(&self, cursor: &mut C)
+    where
+        C: Cursor<Z::Key, Z::Val, (), Z::R>,
+    {
+        if self.ascending {
+            cursor.rewind_vals();
+        } else {
+            cursor.fast_forward_vals();
+        }
+    }
+
+    /// Move `cursor` to the next value of the current key in the scan order.
+    fn next_val<C>(&self, cursor: &mut C)
+    where
+        C: Cursor<Z::Key, Z::Val, (), Z::R>,
+    {
+        if self.ascending {
+            cursor.step_val();
+        } else {
+            cursor.step_val_reverse();
+        }
+    }
+
+    /// Compute changes to the top-K set of the current key of `delta_cursor`.
+    ///
+    /// `integral_cursor` points to the same key in the delayed integral or is
+    /// `None` if the key does not occur there.  Changes are appended to
+    /// `updates` in the scan order.
+    fn eval_key<DC, IC>(
+        &self,
+        delta_cursor: &mut DC,
+        mut integral_cursor: Option<&mut IC>,
+        updates: &mut Vec<(Z::Val, Z::R)>,
+    ) where
+        DC: Cursor<Z::Key, Z::Val, (), Z::R>,
+        IC: Cursor<Z::Key, Z::Val, (), Z::R>,
+    {
+        self.first_val(delta_cursor);
+        if let Some(integral_cursor) = integral_cursor.as_deref_mut() {
+            self.first_val(integral_cursor);
+        }
+
+        // Number of values with positive weights before and after applying
+        // `delta`.
+        let mut old_count = 0;
+        let mut new_count = 0;
+
+        while old_count < self.k || new_count < self.k {
+            let delta_valid = delta_cursor.val_valid();
+            let integral_valid = integral_cursor
+                .as_ref()
+                .map_or(false, |cursor| cursor.val_valid());
+
+            // Pick the cursor that points to the next value in the scan order
+            // (`Less` - delta, `Greater` - integral, `Equal` - both).
+            let ordering = match (delta_valid, integral_valid) {
+                (false, false) => break,
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                (true, true) => {
+                    let ordering = delta_cursor
+                        .val()
+                        .cmp(integral_cursor.as_ref().unwrap().val());
+                    if self.ascending {
+                        ordering
+                    } else {
+                        ordering.reverse()
+                    }
+                }
+            };
+
+            let (val, delta_weight) = if ordering != Ordering::Greater {
+                (delta_cursor.val().clone(), delta_cursor.weight())
+            } else {
+                let integral_cursor = integral_cursor.as_deref_mut().unwrap();
+                (integral_cursor.val().clone(), Z::R::zero())
+            };
+            let old_weight = if ordering != Ordering::Less {
+                integral_cursor.as_deref_mut().unwrap().weight()
+            } else {
+                Z::R::zero()
+            };
+            let new_weight = old_weight.add_by_ref(&delta_weight);
+
+            let mut update = Z::R::zero();
+            if old_weight.ge0() && !old_weight.is_zero() {
+                if old_count < self.k {
+                    update = old_weight.neg_by_ref();
+                }
+                old_count += 1;
+            }
+            if new_weight.ge0() && !new_weight.is_zero() {
+                if new_count < self.k {
+                    update = update.add_by_ref(&new_weight);
+                }
+                new_count += 1;
+            }
+            if !update.is_zero() {
+                updates.push((val, update));
+            }
+
+            if ordering != Ordering::Greater {
+                self.next_val(delta_cursor);
+            }
+            if ordering != Ordering::Less {
+                self.next_val(integral_cursor.as_deref_mut().unwrap());
+            }
+        }
+    }
+}
+
+impl<Z, I> BinaryOperator<Z, I, Z> for TopK<Z, I>
+where
+    Z: IndexedZSet,
+    Z::R: ZRingValue,
+    I: BatchReader<Key = Z::Key, Val = Z::Val, Time = (), R = Z::R>,
+{
+    fn eval(&mut self, delta: &Z, delayed_integral: &I) -> Z {
+        let mut builder = Z::Builder::with_capacity((), delta.len());
+        let mut delta_cursor = delta.cursor();
+        let mut integral_cursor = delayed_integral.cursor();
+        let mut updates = Vec::new();
+
+        while delta_cursor.key_valid() {
+            integral_cursor.seek_key(delta_cursor.key());
+
+            if integral_cursor.key_valid() && integral_cursor.key() == delta_cursor.key() {
+                self.eval_key(&mut delta_cursor, Some(&mut integral_cursor), &mut updates);
+            } else {
+                self.eval_key::<_, I::Cursor<'_>>(&mut delta_cursor, None, &mut updates);
+            }
+
+            // The builder expects values in ascending order.
+            if !self.ascending {
+                updates.reverse();
+            }
+            for (val, weight) in updates.drain(..) {
+                builder.push((Z::item_from(delta_cursor.key().clone(), val), weight));
+            }
+
+            delta_cursor.step_key();
+        }
+
+        builder.done()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CmpFunc;
+    use crate::{
+        indexed_zset,
+        operator::CollectionHandle,
+        trace::{Batch, BatchReader, Cursor},
+        DBSPHandle, OrdIndexedZSet, Runtime,
+    };
+    use proptest::{collection, prelude::*};
+    use std::cmp::Ordering;
+
+    type TestIndexedZSet = OrdIndexedZSet<usize, isize, isize>;
+
+    /// Orders integers in descending order.
+    struct Reverse;
+
+    impl CmpFunc<isize> for Reverse {
+        fn cmp(left: &isize, right: &isize) -> Ordering {
+            right.cmp(left)
+        }
+    }
+
+    /// Non-incremental reference implementation of top-K.
+    fn topk_slow(batch: &TestIndexedZSet, k: usize, ascending: bool) -> TestIndexedZSet {
+        let mut tuples = Vec::new();
+        let mut cursor = batch.cursor();
+
+        while cursor.key_valid() {
+            let mut vals = Vec::new();
+            while cursor.val_valid() {
+                let weight = cursor.weight();
+                if weight > 0 {
+                    vals.push(((*cursor.key(), *cursor.val()), weight));
+                }
+                cursor.step_val();
+            }
+            if !ascending {
+                vals.reverse();
+            }
+            tuples.extend(vals.into_iter().take(k));
+            cursor.step_key();
+        }
+
+        OrdIndexedZSet::from_tuples((), tuples)
+    }
+
+    #[test]
+    fn topk_test() {
+        let (mut circuit, (mut input, asc, desc, custom)) = Runtime::init_circuit(1, |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<usize, isize, isize>();
+
+            let asc = input.topk_asc(2).output();
+            let desc = input.topk_desc(2).output();
+            let custom = input.topk_custom_order::<Reverse>(2).output();
+
+            (input_handle, asc, desc, custom)
+        })
+        .unwrap();
+
+        input.append(&mut vec![(1, (5, 1)), (1, (3, 1)), (1, (8, 1))]);
+        circuit.step().unwrap();
+        assert_eq!(asc.consolidate(), indexed_zset! { 1 => { 3 => 1, 5 => 1 } });
+        assert_eq!(
+            desc.consolidate(),
+            indexed_zset! { 1 => { 5 => 1, 8 => 1 } }
+        );
+        assert_eq!(
+            custom.consolidate(),
+            indexed_zset! { 1 => { 5 => 1, 8 => 1 } }
+        );
+
+        input.append(&mut vec![(1, (3, -1)), (1, (10, 1)), (2, (1, 2))]);
+        circuit.step().unwrap();
+        assert_eq!(
+            asc.consolidate(),
+            indexed_zset! { 1 => { 3 => -1, 8 => 1 }, 2 => { 1 => 2 } }
+        );
+        assert_eq!(
+            desc.consolidate(),
+            indexed_zset! { 1 => { 5 => -1, 10 => 1 }, 2 => { 1 => 2 } }
+        );
+        assert_eq!(
+            custom.consolidate(),
+            indexed_zset! { 1 => { 5 => -1, 10 => 1 }, 2 => { 1 => 2 } }
+        );
+
+        // Changing the weight of a value in the top-K set updates its weight
+        // in the output; changes outside the top-K set are not visible.
+        input.append(&mut vec![(1, (5, 1))]);
+        circuit.step().unwrap();
+        assert_eq!(asc.consolidate(), indexed_zset! { 1 => { 5 => 1 } });
+        assert_eq!(desc.consolidate(), indexed_zset! {});
+        assert_eq!(custom.consolidate(), indexed_zset! {});
+
+        circuit.kill().unwrap();
+    }
+
+    type InputHandle = CollectionHandle<usize, (isize, isize)>;
+
+    fn topk_test_circuit(workers: usize, k: usize) -> (DBSPHandle, InputHandle) {
+        Runtime::init_circuit(workers, move |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<usize, isize, isize>();
+
+            let expected_asc = input
+                .gather(0)
+                .integrate()
+                .apply(move |batch| topk_slow(batch, k, true));
+            let expected_desc = input
+                .gather(0)
+                .integrate()
+                .apply(move |batch| topk_slow(batch, k, false));
+
+            let asc = input.topk_asc(k).gather(0).integrate();
+            let desc = input.topk_desc(k).gather(0).integrate();
+            let custom = input.topk_custom_order::<Reverse>(k).gather(0).integrate();
+
+            expected_asc.apply2(&asc, |expected, actual| assert_eq!(expected, actual));
+            expected_desc.apply2(&desc, |expected, actual| assert_eq!(expected, actual));
+            expected_desc.apply2(&custom, |expected, actual| assert_eq!(expected, actual));
+
+            input_handle
+        })
+        .unwrap()
+    }
+
+    type InputTuple = (usize, (isize, isize));
+    type InputBatch = Vec<InputTuple>;
+
+    fn input_trace(
+        max_key: usize,
+        max_val: isize,
+        max_batch_size: usize,
+        max_batches: usize,
+    ) -> impl Strategy<Value = Vec<InputBatch>> {
+        collection::vec(
+            collection::vec(
+                (0..max_key, (-max_val..max_val, -1..=1isize)),
+                0..max_batch_size,
+            ),
+            0..max_batches,
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_topk(trace in input_trace(5, 20, 30, 20), k in 1..5usize) {
+            let (mut circuit, mut input) = topk_test_circuit(4, k);
+
+            for mut batch in trace {
+                input.append(&mut batch);
+                circuit.step().unwrap();
+            }
+
+            circuit.kill().unwrap();
+        }
+    }
+}
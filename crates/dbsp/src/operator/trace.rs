@@ -1,5 +1,6 @@
 use crate::{
     circuit::{
+        checkpoint::{decode_state, encode_state, DecodeCheckpoint, EncodeCheckpoint},
         metadata::{MetaItem, OperatorMeta},
         operator_traits::{BinaryOperator, Operator, StrictOperator, StrictUnaryOperator},
         Circuit, ExportId, ExportStream, GlobalNodeId, OwnershipPreference, Scope, Stream,
@@ -7,7 +8,7 @@ use crate::{
     },
     circuit_cache_key,
     trace::{cursor::Cursor, Batch, BatchReader, Builder, Spine, Trace},
-    DBData, Timestamp,
+    DBData, Error, Timestamp,
 };
 use size_of::SizeOf;
 use std::{borrow::Cow, cell::RefCell, marker::PhantomData, ops::DerefMut, rc::Rc};
@@ -358,6 +359,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T> BinaryOperator<T, T::Batch, T> for UntimedTraceAppend<T>
@@ -420,6 +425,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T, B, Clk> BinaryOperator<T, B, T> for TraceAppend<T, B, Clk>
@@ -546,6 +555,30 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         !self.dirty[scope as usize]
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        encode_state(&(
+            &self.time,
+            self.trace.as_ref().map(EncodeCheckpoint),
+            &self.dirty,
+            &self.effective_key_bound,
+            &self.effective_val_bound,
+        ))
+        .map(Some)
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        let trace: Option<DecodeCheckpoint<T>>;
+        (
+            self.time,
+            trace,
+            self.dirty,
+            self.effective_key_bound,
+            self.effective_val_bound,
+        ) = decode_state(state)?;
+        self.trace = trace.map(|DecodeCheckpoint(trace)| trace);
+        Ok(())
+    }
 }
 
 impl<T> StrictOperator<T> for Z1Trace<T>
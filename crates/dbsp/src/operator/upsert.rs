@@ -9,7 +9,7 @@ use crate::{
         consolidation::consolidate, cursor::Cursor, Batch, BatchReader, Builder, Spine, Trace,
     },
     utils::VecExt,
-    Circuit, DBData, DBTimestamp, Stream, Timestamp,
+    Circuit, DBData, DBTimestamp, Error, Stream, Timestamp,
 };
 use std::{borrow::Cow, marker::PhantomData, ops::Neg};
 
@@ -150,6 +150,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
 }
 
 impl<T, B> BinaryOperator<T, Vec<(T::Key, Option<T::Val>)>, B> for Upsert<T, B>
@@ -20,7 +20,11 @@ use std::{
 /// Two antichains are equal if the contain the same set of elements, even if in
 /// different orders. This can make equality testing quadratic, though linear in
 /// the common case that the sequences are identical.
-#[derive(Default, SizeOf)]
+#[derive(Default, SizeOf, bincode::Decode, bincode::Encode)]
+#[bincode(
+    decode_bounds = "T: bincode::Decode + 'static",
+    encode_bounds = "T: bincode::Encode + 'static"
+)]
 pub struct Antichain<T> {
     // TODO: We can specialize containers based on the inner type, meaning we could give ourselves
     //       a more favorable memory footprint for things like `Antichain<()>`
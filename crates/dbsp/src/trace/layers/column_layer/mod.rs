@@ -27,7 +27,11 @@ use std::{
 };
 
 /// A layer of unordered values
-#[derive(Debug, Clone, Eq, PartialEq, SizeOf)]
+#[derive(Debug, Clone, Eq, PartialEq, SizeOf, bincode::Decode, bincode::Encode)]
+#[bincode(
+    decode_bounds = "K: bincode::Decode + 'static, R: bincode::Decode + 'static",
+    encode_bounds = "K: bincode::Encode + 'static, R: bincode::Encode + 'static"
+)]
 pub struct ColumnLayer<K, R> {
     // Invariant: keys.len == diffs.len
     pub(super) keys: Vec<K>,
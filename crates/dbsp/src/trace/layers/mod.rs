@@ -20,6 +20,7 @@ mod test;
 pub use advance::{advance, advance_erased, advance_raw, retreat};
 
 use crate::algebra::HasZero;
+use bincode::{Decode, Encode};
 use size_of::SizeOf;
 use std::{
     fmt::Debug,
@@ -244,6 +245,8 @@ pub trait OrdOffset:
     + TryInto<usize>
     + HasZero
     + SizeOf
+    + Encode
+    + Decode
     + Sized
     + 'static
 {
@@ -262,6 +265,8 @@ where
         + TryInto<usize>
         + HasZero
         + SizeOf
+        + Encode
+        + Decode
         + Sized
         + 'static,
     <O as TryInto<usize>>::Error: Debug,
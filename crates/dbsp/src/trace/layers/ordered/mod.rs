@@ -28,7 +28,11 @@ use textwrap::indent;
 /// In this representation, the values for `keys[i]` are found at `vals[offs[i]
 /// .. offs[i+1]]`.
 // False positive from clippy
-#[derive(Debug, SizeOf, PartialEq, Eq, Clone)]
+#[derive(Debug, SizeOf, PartialEq, Eq, Clone, bincode::Decode, bincode::Encode)]
+#[bincode(
+    decode_bounds = "K: bincode::Decode + 'static, L: bincode::Decode + 'static, O: bincode::Decode + 'static",
+    encode_bounds = "K: bincode::Encode + 'static, L: bincode::Encode + 'static, O: bincode::Encode + 'static"
+)]
 pub struct OrderedLayer<K, L, O = usize> {
     /// The keys of the layer.
     pub(crate) keys: Vec<K>,
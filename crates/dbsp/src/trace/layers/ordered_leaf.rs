@@ -13,7 +13,11 @@ use std::{
 };
 
 /// A layer of unordered values.
-#[derive(Debug, SizeOf, Eq, PartialEq, Clone)]
+#[derive(Debug, SizeOf, Eq, PartialEq, Clone, bincode::Decode, bincode::Encode)]
+#[bincode(
+    decode_bounds = "K: bincode::Decode + 'static, R: bincode::Decode + 'static",
+    encode_bounds = "K: bincode::Encode + 'static, R: bincode::Encode + 'static"
+)]
 pub struct OrderedLeaf<K, R> {
     /// Unordered values.
     pub vals: Vec<(K, R)>,
@@ -29,11 +29,10 @@ mod test_batch;
 
 use crate::{
     algebra::{HasZero, MonoidValue},
-    circuit::Activator,
+    circuit::{Activator, Checkpoint},
     time::{AntichainRef, Timestamp},
     NumEntries,
 };
-#[cfg(feature = "persistence")]
 use bincode::{Decode, Encode};
 use size_of::SizeOf;
 use std::{fmt::Debug, hash::Hash};
@@ -45,24 +44,20 @@ use std::{fmt::Debug, hash::Hash};
 /// must be generic over any relational data, it is sufficient to impose
 /// `DBData` as a trait bound on types.  Conversely, a trait bound of the form
 /// `B: BatchReader` implies `B::Key: DBData` and `B::Val: DBData`.
-#[cfg(feature = "persistence")]
+///
+/// The `Encode` and `Decode` bounds allow batches to be serialized as part of
+/// a checkpoint of the circuit state (see
+/// [`DBSPHandle::checkpoint`](`crate::DBSPHandle::checkpoint`)).
 pub trait DBData:
     Clone + Eq + Ord + Hash + SizeOf + Send + Debug + Decode + Encode + 'static
 {
 }
 
-#[cfg(not(feature = "persistence"))]
-pub trait DBData: Clone + Eq + Ord + Hash + SizeOf + Send + Debug + 'static {}
-
-#[cfg(feature = "persistence")]
 impl<T> DBData for T where
     T: Clone + Eq + Ord + Hash + SizeOf + Send + Debug + Decode + Encode + 'static
 {
 }
 
-#[cfg(not(feature = "persistence"))]
-impl<T> DBData for T where T: Clone + Eq + Ord + Hash + SizeOf + Send + Debug + 'static {}
-
 /// Trait for data types used as weights.
 ///
 /// A type used for weights in a batch (i.e., as `BatchReader::R`) must behave
@@ -176,7 +171,7 @@ pub trait Trace: BatchReader {
 /// useful for views derived from other sources in ways that prevent the
 /// construction of batches from the type of data in the view (for example,
 /// filtered views, or views with extended time coordinates).
-pub trait BatchReader: NumEntries + SizeOf + 'static
+pub trait BatchReader: NumEntries + SizeOf + Checkpoint + 'static
 where
     Self: Sized,
 {
@@ -27,7 +27,11 @@ use std::{
 type Layers<K, V, R, O> = OrderedLayer<K, ColumnLayer<V, R>, O>;
 
 /// An immutable collection of update tuples.
-#[derive(Debug, Clone, Eq, PartialEq, SizeOf)]
+#[derive(Debug, Clone, Eq, PartialEq, SizeOf, bincode::Decode, bincode::Encode)]
+#[bincode(
+    decode_bounds = "K: DBData, V: DBData, R: DBWeight, O: OrdOffset",
+    encode_bounds = "K: DBData, V: DBData, R: DBWeight, O: OrdOffset"
+)]
 pub struct OrdIndexedZSet<K, V, R, O = usize>
 where
     K: Ord,
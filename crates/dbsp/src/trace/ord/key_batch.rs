@@ -26,7 +26,11 @@ pub type OrdKeyBatchLayer<K, T, R, O> = OrderedLayer<K, ColumnLayer<T, R>, O>;
 
 /// An immutable collection of update tuples, from a contiguous interval of
 /// logical times.
-#[derive(Debug, Clone, SizeOf)]
+#[derive(Debug, Clone, SizeOf, bincode::Decode, bincode::Encode)]
+#[bincode(
+    decode_bounds = "K: DBData, T: DBTimestamp, R: DBWeight, O: OrdOffset",
+    encode_bounds = "K: DBData, T: DBTimestamp, R: DBWeight, O: OrdOffset"
+)]
 pub struct OrdKeyBatch<K, T, R, O = usize> {
     /// Where all the dataz is.
     pub layer: OrdKeyBatchLayer<K, T, R, O>,
@@ -24,7 +24,11 @@ pub type OrdValBatchLayer<K, V, T, R, O> =
 
 /// An immutable collection of update tuples, from a contiguous interval of
 /// logical times.
-#[derive(Debug, Clone, SizeOf)]
+#[derive(Debug, Clone, SizeOf, bincode::Decode, bincode::Encode)]
+#[bincode(
+    decode_bounds = "K: DBData, V: DBData, T: DBTimestamp, R: DBWeight, O: OrdOffset",
+    encode_bounds = "K: DBData, V: DBData, T: DBTimestamp, R: DBWeight, O: OrdOffset"
+)]
 pub struct OrdValBatch<K, V, T, R, O = usize>
 where
     K: Ord,
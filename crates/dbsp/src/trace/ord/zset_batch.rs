@@ -24,7 +24,8 @@ use std::{
 };
 
 /// An immutable collection of `(key, weight)` pairs without timing information.
-#[derive(Debug, Clone, Eq, PartialEq, SizeOf)]
+#[derive(Debug, Clone, Eq, PartialEq, SizeOf, bincode::Decode, bincode::Encode)]
+#[bincode(decode_bounds = "K: DBData, R: DBWeight", encode_bounds = "K: DBData, R: DBWeight")]
 pub struct OrdZSet<K, R> {
     #[doc(hidden)]
     pub layer: ColumnLayer<K, R>,
@@ -711,3 +711,73 @@ where
             .expect("Could not write batch to db");
     }
 }
+
+/// Serializes the contents of the trace as a sequence of keys with their
+/// values, times and weights, e.g., to write a checkpoint of the circuit state.
+impl<B> Encode for PersistentTrace<B>
+where
+    B: Batch,
+{
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> core::result::Result<(), bincode::error::EncodeError> {
+        use crate::trace::cursor::CursorDebug;
+
+        let mut entries: Vec<(B::Key, Values<B::Val, B::Time, B::R>)> = Vec::new();
+        let mut cursor = self.cursor();
+        while cursor.key_valid() {
+            entries.push((cursor.key().clone(), cursor.val_to_vec()));
+            cursor.step_key();
+        }
+
+        bincode::Encode::encode(&entries, encoder)?;
+        bincode::Encode::encode(&self.lower, encoder)?;
+        bincode::Encode::encode(&self.upper, encoder)?;
+        bincode::Encode::encode(&self.lower_key_bound, encoder)?;
+        bincode::Encode::encode(&self.lower_val_bound, encoder)?;
+        bincode::Encode::encode(&self.dirty, encoder)?;
+        Ok(())
+    }
+}
+
+/// Restores a trace serialized by the [`Encode`] implementation into a new
+/// column family.
+impl<B> Decode for PersistentTrace<B>
+where
+    B: Batch + Clone + 'static,
+    B::Time: DBTimestamp,
+{
+    fn decode<D: bincode::de::Decoder>(
+        decoder: &mut D,
+    ) -> core::result::Result<Self, bincode::error::DecodeError> {
+        let entries: Vec<(B::Key, Values<B::Val, B::Time, B::R>)> =
+            bincode::Decode::decode(decoder)?;
+
+        let mut trace = <Self as Trace>::new(None);
+        trace.lower = bincode::Decode::decode(decoder)?;
+        trace.upper = bincode::Decode::decode(decoder)?;
+        trace.lower_key_bound = bincode::Decode::decode(decoder)?;
+        trace.lower_val_bound = bincode::Decode::decode(decoder)?;
+        trace.dirty = bincode::Decode::decode(decoder)?;
+
+        let mut tmp_key = ReusableEncodeBuffer::default();
+        let mut tmp_val = ReusableEncodeBuffer::default();
+
+        let mut sstable = WriteBatch::default();
+        for (key, vals) in entries {
+            let encoded_key = tmp_key.encode(&key).expect("Can't encode `key`");
+            trace.approximate_len += vals.len();
+            let encoded_vals = tmp_val
+                .encode(&MergeOp::Insert(vals))
+                .expect("Can't encode `vals`");
+            sstable.merge_cf(&trace.cf, encoded_key, encoded_vals);
+        }
+
+        ROCKS_DB_INSTANCE
+            .write(sstable)
+            .expect("Could not write batch to db");
+
+        Ok(trace)
+    }
+}
@@ -88,6 +88,12 @@ use crate::{
     },
     NumEntries,
 };
+use bincode::{
+    de::Decoder,
+    enc::Encoder,
+    error::{DecodeError, EncodeError},
+    Decode, Encode,
+};
 use size_of::SizeOf;
 use std::{
     cmp::max,
@@ -523,6 +529,48 @@ where
     }
 }
 
+/// Serializes the trace as the sequence of batches it contains, e.g., to write
+/// a checkpoint of the circuit state.  The state of in-progress merges is not
+/// preserved: the decoded trace merges its batches from scratch.  Fails if
+/// the batch type does not support checkpoints (see
+/// [`Checkpoint`](`crate::circuit::Checkpoint`)).
+impl<B> Encode for Spine<B>
+where
+    B: Batch,
+{
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.lower_key_bound.encode(encoder)?;
+        self.lower_val_bound.encode(encoder)?;
+
+        let num_batches = self.fold_batches(0usize, |n, _| n + 1);
+        num_batches.encode(encoder)?;
+        self.try_fold_batches((), |(), batch| batch.encode_checkpoint(encoder))?;
+
+        self.dirty.encode(encoder)
+    }
+}
+
+impl<B> Decode for Spine<B>
+where
+    B: Batch,
+    B::Key: Ord,
+    B::Val: Ord,
+{
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let mut spine = <Self as Trace>::new(None);
+        spine.lower_key_bound = Decode::decode(decoder)?;
+        spine.lower_val_bound = Decode::decode(decoder)?;
+
+        let num_batches = usize::decode(decoder)?;
+        for _ in 0..num_batches {
+            spine.insert(B::decode_checkpoint(decoder)?);
+        }
+
+        spine.dirty = Decode::decode(decoder)?;
+        Ok(spine)
+    }
+}
+
 impl<B> Default for Spine<B>
 where
     B: Batch,
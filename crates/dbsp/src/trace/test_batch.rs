@@ -241,7 +241,15 @@ where
 }
 
 /// Inefficient but simple batch implementation as a B-tree map.
-#[derive(Clone, Debug, PartialEq, Eq, SizeOf)]
+#[derive(Clone, Debug, PartialEq, Eq, SizeOf, bincode::Decode, bincode::Encode)]
+#[bincode(
+    decode_bounds = "K: DBData, V: DBData, T: DBTimestamp, R: DBWeight",
+    encode_bounds = "K: DBData, V: DBData, T: DBTimestamp, R: DBWeight",
+    borrow_decode_bounds = "K: DBData + bincode::BorrowDecode<'__de>,
+                            V: DBData + bincode::BorrowDecode<'__de>,
+                            T: DBTimestamp + bincode::BorrowDecode<'__de>,
+                            R: DBWeight + bincode::BorrowDecode<'__de>"
+)]
 pub struct TestBatch<K, V, T, R> {
     data: BTreeMap<(K, V, T), R>,
     lower_key_bound: Option<K>,